@@ -1,10 +1,9 @@
 use chrono::offset::{TimeZone, Utc};
-use data_encoding::HEXLOWER;
 use serde_derive::Deserialize;
 use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
-use tuf::crypto::{Ed25519PrivateKey, HashAlgorithm, KeyType, PrivateKey, SignatureScheme};
+use tuf::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
 use tuf::metadata::{
     MetadataPath, MetadataVersion, Role, SnapshotMetadataBuilder, TargetPath,
     TargetsMetadataBuilder, TimestampMetadataBuilder,
@@ -16,39 +15,27 @@ use walkdir::WalkDir;
 mod pretty;
 pub use pretty::JsonPretty;
 
-// These structs and functions are necessary to parse keys.json, which contains the keys
-// used by go-tuf to generate the equivalent metadata. We use the same keys to facilitate
-// compatibility testing.
+// keys.json contains the keys used by go-tuf to generate the equivalent metadata, in the
+// securesystemslib key format. We use the same keys to facilitate compatibility testing.
 
-#[derive(Clone, Deserialize)]
-struct KeyValue {
-    #[serde(rename = "public")]
-    _public: String,
-    private: String,
-}
-
-#[derive(Clone, Deserialize)]
-struct TestKeyPair {
-    #[serde(rename = "keytype")]
-    _keytype: KeyType,
-    #[serde(rename = "scheme")]
-    _scheme: SignatureScheme,
-    keyval: KeyValue,
+#[derive(Deserialize)]
+struct TestKeys {
+    root: Vec<Vec<serde_json::Value>>,
+    targets: Vec<Vec<serde_json::Value>>,
+    snapshot: Vec<Vec<serde_json::Value>>,
+    timestamp: Vec<Vec<serde_json::Value>>,
 }
 
-impl TestKeyPair {
-    fn to_private_key(&self) -> Ed25519PrivateKey {
-        let priv_bytes = HEXLOWER.decode(self.keyval.private.as_bytes()).unwrap();
-        Ed25519PrivateKey::from_ed25519(&priv_bytes[..]).unwrap()
-    }
-}
+fn to_private_key(key: &serde_json::Value) -> Ed25519PrivateKey {
+    // go-tuf publishes these keys without their `keyid_hash_algorithms`, so their key IDs are
+    // calculated without them.
+    let mut key = key.clone();
+    key.as_object_mut()
+        .expect("key must be an object")
+        .remove("keyid_hash_algorithms");
 
-#[derive(Deserialize)]
-struct TestKeys {
-    root: Vec<Vec<TestKeyPair>>,
-    targets: Vec<Vec<TestKeyPair>>,
-    snapshot: Vec<Vec<TestKeyPair>>,
-    timestamp: Vec<Vec<TestKeyPair>>,
+    Ed25519PrivateKey::from_securesystemslib_json(key.to_string().as_bytes())
+        .expect("failed to parse key")
 }
 
 fn init_json_keys(path: &Path) -> TestKeys {
@@ -61,10 +48,10 @@ type RoleKeys = HashMap<&'static str, Ed25519PrivateKey>;
 
 fn init_role_keys(json_keys: &TestKeys) -> RoleKeys {
     let mut keys = HashMap::new();
-    keys.insert("root", json_keys.root[0][0].to_private_key());
-    keys.insert("snapshot", json_keys.snapshot[0][0].to_private_key());
-    keys.insert("targets", json_keys.targets[0][0].to_private_key());
-    keys.insert("timestamp", json_keys.timestamp[0][0].to_private_key());
+    keys.insert("root", to_private_key(&json_keys.root[0][0]));
+    keys.insert("snapshot", to_private_key(&json_keys.snapshot[0][0]));
+    keys.insert("targets", to_private_key(&json_keys.targets[0][0]));
+    keys.insert("timestamp", to_private_key(&json_keys.timestamp[0][0]));
     keys
 }
 
@@ -264,7 +251,6 @@ pub async fn generate_repos(
     add_target(&mut repo, &keys, 0, consistent_snapshot).await;
 
     // Queue up a series of key rotations
-    let mut i: u8 = 1;
     let rotations = [
         Some(Role::Root),
         Some(Role::Targets),
@@ -272,7 +258,7 @@ pub async fn generate_repos(
         Some(Role::Timestamp),
         None,
     ];
-    for r in rotations.iter() {
+    for (i, r) in (1u8..).zip(rotations.iter()) {
         // Initialize new repo and copy the files from the previous step.
         let dir_i = Path::new(dir).join(i.to_string());
         let mut repo = FileSystemRepositoryBuilder::new(dir_i)
@@ -282,17 +268,17 @@ pub async fn generate_repos(
         copy_repo(dir, i);
 
         let root_signer = match r {
-            Some(Role::Root) => keys.insert("root", json_keys.root[1][0].to_private_key()),
+            Some(Role::Root) => keys.insert("root", to_private_key(&json_keys.root[1][0])),
             Some(Role::Targets) => {
-                keys.insert("targets", json_keys.targets[1][0].to_private_key());
+                keys.insert("targets", to_private_key(&json_keys.targets[1][0]));
                 None
             }
             Some(Role::Snapshot) => {
-                keys.insert("snapshot", json_keys.snapshot[1][0].to_private_key());
+                keys.insert("snapshot", to_private_key(&json_keys.snapshot[1][0]));
                 None
             }
             Some(Role::Timestamp) => {
-                keys.insert("timestamp", json_keys.timestamp[1][0].to_private_key());
+                keys.insert("timestamp", to_private_key(&json_keys.timestamp[1][0]));
                 None
             }
             None => None,
@@ -306,7 +292,6 @@ pub async fn generate_repos(
         )
         .await;
         add_target(&mut repo, &keys, i, consistent_snapshot).await;
-        i += 1;
     }
     Ok(())
 }
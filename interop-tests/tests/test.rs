@@ -39,15 +39,16 @@ use futures_executor::block_on;
 use futures_util::io::AsyncReadExt;
 use interop_tests::JsonPretty;
 use pretty_assertions::assert_eq;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use tuf::client::{Client, Config};
-use tuf::crypto::PublicKey;
+use tuf::crypto::{KeyId, KeyIdPolicy, KeyIdScheme, PublicKey};
 use tuf::metadata::{MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata, TargetPath};
 use tuf::pouf::{Pouf, Pouf1};
 use tuf::repository::{
     EphemeralRepository, FileSystemRepository, FileSystemRepositoryBuilder, RepositoryProvider,
 };
+use tuf::verify::Verifier;
 
 #[test]
 fn fuchsia_go_tuf_consistent_snapshot_false() {
@@ -85,6 +86,80 @@ fn fuchsia_go_tuf_transition_m4_consistent_snapshot_true() {
     );
 }
 
+// go-tuf lists each key under both its canonical key ID and its TUF 0.9 key ID, and signs with
+// the key under both IDs.
+#[test]
+fn fuchsia_go_tuf_legacy_key_ids() {
+    block_on(async {
+        let dir = Path::new("tests")
+            .join("fuchsia-go-tuf-5527fe")
+            .join("consistent-snapshot-false")
+            .join("0");
+        let raw_root = fetch_root::<Pouf1>(&dir).await;
+        let root = raw_root.parse_untrusted().unwrap().assume_valid().unwrap();
+
+        // The keys listed under their TUF 0.9 key IDs are kept as aliases.
+        assert_eq!(root.keys().len(), 4);
+        assert_eq!(root.key_id_aliases().len(), 4);
+        assert_eq!(
+            root.key_id_aliases()
+                .keys()
+                .cloned()
+                .collect::<HashSet<_>>(),
+            root.keys()
+                .values()
+                .map(|key| KeyIdScheme::Legacy.key_id(key).unwrap())
+                .collect::<HashSet<_>>()
+        );
+        for key in root.key_id_aliases().values() {
+            assert!(root.keys().contains_key(key.key_id()));
+        }
+
+        let legacy_root_keys = root
+            .role_keys_by_id(root.root())
+            .filter(|(key_id, key)| *key_id != key.key_id())
+            .collect::<Vec<(&KeyId, &PublicKey)>>();
+        assert_eq!(legacy_root_keys.len(), 1);
+
+        // Keys listed only under their TUF 0.9 key IDs are only trusted with a policy that
+        // accepts them.
+        let role = MetadataPath::root();
+        assert_matches!(
+            Verifier::new().verify_signatures_with_key_ids(
+                &role,
+                &raw_root,
+                1,
+                legacy_root_keys.clone()
+            ),
+            Err(tuf::Error::MetadataMissingSignatures { .. })
+        );
+
+        let verifier =
+            Verifier::new().key_id_policy(KeyIdPolicy::new().accept(KeyIdScheme::Legacy));
+        verifier
+            .verify_signatures_with_key_ids(&role, &raw_root, 1, legacy_root_keys)
+            .unwrap();
+
+        // The signatures under both IDs are by one key, so they only count once.
+        verifier
+            .verify_signatures_with_key_ids(&role, &raw_root, 1, root.role_keys_by_id(root.root()))
+            .unwrap();
+        assert_matches!(
+            verifier.verify_signatures_with_key_ids(
+                &role,
+                &raw_root,
+                2,
+                root.role_keys_by_id(root.root())
+            ),
+            Err(tuf::Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 1,
+                threshold: 2,
+                ..
+            })
+        );
+    })
+}
+
 // Tests to catch changes to the way we generate metadata.
 #[test]
 fn rust_tuf_identity_consistent_snapshot_false() {
@@ -207,6 +282,21 @@ where
 
 /// Extract the initial key ids from the first step.
 async fn extract_keys<D>(dir: &Path) -> Vec<PublicKey>
+where
+    D: Pouf,
+{
+    let metadata = fetch_root::<D>(dir)
+        .await
+        .parse_untrusted()
+        .unwrap()
+        .assume_valid()
+        .unwrap();
+
+    metadata.root_keys().cloned().collect()
+}
+
+/// Fetch the first version of the root metadata in a step.
+async fn fetch_root<D>(dir: &Path) -> RawSignedMetadata<D, RootMetadata>
 where
     D: Pouf,
 {
@@ -220,13 +310,7 @@ where
         .await
         .unwrap();
     reader.read_to_end(&mut buf).await.unwrap();
-    let metadata = RawSignedMetadata::<D, RootMetadata>::new(buf)
-        .parse_untrusted()
-        .unwrap()
-        .assume_valid()
-        .unwrap();
-
-    metadata.root_keys().cloned().collect()
+    RawSignedMetadata::new(buf)
 }
 
 fn init_remote<D>(dir: &Path) -> FileSystemRepository<D>
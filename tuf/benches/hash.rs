@@ -0,0 +1,69 @@
+//! Benchmarks for hashing large targets with several hash algorithms.
+
+use std::io::Write as _;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures_executor::block_on;
+use futures_util::io::AllowStdIo;
+use tuf::crypto::{
+    calculate_hashes_from_file, calculate_hashes_from_reader, calculate_hashes_from_slice,
+    HashAlgorithm,
+};
+
+/// The size of the target being hashed.
+const TARGET_SIZE: usize = 64 * 1024 * 1024;
+
+const HASH_ALGS: &[HashAlgorithm] = &[HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+
+fn target() -> Vec<u8> {
+    (0..TARGET_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+fn bench_hash(c: &mut Criterion) {
+    let target = target();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("target");
+    std::fs::File::create(&path)
+        .unwrap()
+        .write_all(&target)
+        .unwrap();
+
+    let mut group = c.benchmark_group("hash");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(TARGET_SIZE as u64));
+
+    // Hash with one algorithm after another on the current thread, for comparison.
+    group.bench_function(BenchmarkId::new("serial", "slice"), |b| {
+        b.iter(|| {
+            HASH_ALGS
+                .iter()
+                .map(|alg| calculate_hashes_from_slice(&target, std::slice::from_ref(alg)).unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("parallel", "slice"), |b| {
+        b.iter(|| calculate_hashes_from_slice(&target, HASH_ALGS).unwrap())
+    });
+
+    group.bench_function(BenchmarkId::new("parallel", "reader"), |b| {
+        b.iter(|| block_on(calculate_hashes_from_reader(&target[..], HASH_ALGS)).unwrap())
+    });
+
+    group.bench_function(BenchmarkId::new("parallel", "file_reader"), |b| {
+        b.iter(|| {
+            let file = AllowStdIo::new(std::fs::File::open(&path).unwrap());
+            block_on(calculate_hashes_from_reader(file, HASH_ALGS)).unwrap()
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("parallel", "file_mmap"), |b| {
+        b.iter(|| calculate_hashes_from_file(&path, HASH_ALGS).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_hash);
+criterion_main!(benches);
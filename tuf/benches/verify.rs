@@ -0,0 +1,226 @@
+//! Benchmarks for metadata signature verification.
+//!
+//! Run with `cargo bench -p tuf --features batch-verify` to include batch verification.
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tuf::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+use tuf::database::Database;
+use tuf::metadata::{
+    Delegation, Delegations, MetadataDescription, MetadataPath, RawSignedMetadata,
+    RawSignedMetadataSet, RawSignedMetadataSetBuilder, RootMetadataBuilder, SignedMetadata,
+    SnapshotMetadataBuilder, TargetDescription, TargetPath, TargetsMetadata,
+    TargetsMetadataBuilder, TimestampMetadataBuilder,
+};
+use tuf::pouf::Pouf1;
+use tuf::verify::{SignatureCache, Verifier};
+
+const KEYS: &[&[u8]] = &[
+    include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+    include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+    include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+    include_bytes!("../tests/ed25519/ed25519-4.pk8.der"),
+    include_bytes!("../tests/ed25519/ed25519-5.pk8.der"),
+    include_bytes!("../tests/ed25519/ed25519-6.pk8.der"),
+];
+
+/// The number of targets in each targets metadata.
+const TARGETS: usize = 1000;
+
+/// The number of roles delegated to by the top-level targets role.
+const DELEGATIONS: usize = 64;
+
+fn keys() -> Vec<Ed25519PrivateKey> {
+    KEYS.iter()
+        .map(|key| Ed25519PrivateKey::from_pkcs8(key).unwrap())
+        .collect()
+}
+
+/// Constructs a [Verifier] for a benchmark.
+type MakeVerifier = Box<dyn Fn() -> Verifier>;
+
+/// The verifiers to compare, named for the benchmark.
+fn verifiers() -> Vec<(&'static str, MakeVerifier)> {
+    let cache = Arc::new(SignatureCache::new(4096));
+
+    vec![
+        ("individual", Box::new(Verifier::new)),
+        (
+            "cached",
+            Box::new(move || Verifier::new().cache(Arc::clone(&cache))),
+        ),
+        #[cfg(feature = "batch-verify")]
+        ("batch", Box::new(|| Verifier::new().batch(true))),
+    ]
+}
+
+fn sign<M>(
+    mut metadata: SignedMetadata<Pouf1, M>,
+    keys: &[Ed25519PrivateKey],
+) -> RawSignedMetadata<Pouf1, M>
+where
+    M: tuf::metadata::Metadata,
+{
+    for key in keys {
+        metadata.add_signature(key).unwrap();
+    }
+    metadata.to_raw().unwrap()
+}
+
+fn targets(keys: &[Ed25519PrivateKey], prefix: &str) -> RawSignedMetadata<Pouf1, TargetsMetadata> {
+    let mut builder = TargetsMetadataBuilder::new();
+    for i in 0..TARGETS {
+        let path = TargetPath::new(format!("{}/target-{}", prefix, i)).unwrap();
+        let description =
+            TargetDescription::from_slice(path.as_str().as_bytes(), &[HashAlgorithm::Sha256])
+                .unwrap();
+        builder = builder.insert_target_description(path, description);
+    }
+
+    let metadata = builder.signed::<Pouf1>(&keys[0]).unwrap();
+    sign(metadata, &keys[1..])
+}
+
+fn bench_verify_signatures(c: &mut Criterion) {
+    let keys = keys();
+    let mut group = c.benchmark_group("verify_signatures");
+
+    for signatures in [1, 3, 6] {
+        let raw_targets = targets(&keys[..signatures], "targets");
+        let public_keys = keys[..signatures]
+            .iter()
+            .map(|key| key.public())
+            .collect::<Vec<_>>();
+
+        for (name, verifier) in verifiers() {
+            let verifier = verifier();
+            group.bench_with_input(
+                BenchmarkId::new(name, signatures),
+                &raw_targets,
+                |b, raw_targets| {
+                    b.iter(|| {
+                        verifier
+                            .verify_signatures(
+                                &MetadataPath::targets(),
+                                raw_targets,
+                                signatures as u32,
+                                public_keys.iter().copied(),
+                            )
+                            .unwrap()
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+/// A repository whose top-level targets delegates to [DELEGATIONS] roles, each signed by a
+/// threshold of three keys.
+struct DelegatedRepository {
+    metadata: RawSignedMetadataSet<Pouf1>,
+    delegations: Vec<(MetadataPath, RawSignedMetadata<Pouf1, TargetsMetadata>)>,
+}
+
+fn delegated_repository() -> DelegatedRepository {
+    let keys = keys();
+    let (top_level_key, delegated_keys) = (&keys[0], &keys[3..]);
+    let expires = Utc::now() + Duration::days(1);
+
+    let root = RootMetadataBuilder::new()
+        .expires(expires)
+        .root_key(top_level_key.public().clone())
+        .targets_key(top_level_key.public().clone())
+        .snapshot_key(top_level_key.public().clone())
+        .timestamp_key(top_level_key.public().clone())
+        .signed::<Pouf1>(top_level_key)
+        .unwrap();
+
+    let mut delegations_builder = Delegations::builder();
+    for key in delegated_keys {
+        delegations_builder = delegations_builder.key(key.public().clone());
+    }
+
+    let mut delegations = vec![];
+    let mut snapshot = SnapshotMetadataBuilder::new().expires(expires);
+    for i in 0..DELEGATIONS {
+        let role = MetadataPath::new(format!("delegation-{}", i)).unwrap();
+
+        let mut delegation = Delegation::builder(role.clone())
+            .threshold(delegated_keys.len() as u32)
+            .delegate_path(TargetPath::new(format!("delegation-{}/", i)).unwrap());
+        for key in delegated_keys {
+            delegation = delegation.key(key.public());
+        }
+        delegations_builder = delegations_builder.role(delegation.build().unwrap());
+
+        let raw = targets(delegated_keys, &role.to_string());
+        snapshot = snapshot.insert_metadata_description(
+            role.clone(),
+            MetadataDescription::from_slice(raw.as_bytes(), 1, &[HashAlgorithm::Sha256]).unwrap(),
+        );
+        delegations.push((role, raw));
+    }
+
+    let targets = TargetsMetadataBuilder::new()
+        .expires(expires)
+        .delegations(delegations_builder.build().unwrap())
+        .signed::<Pouf1>(top_level_key)
+        .unwrap();
+    let snapshot = snapshot
+        .insert_metadata(&targets, &[HashAlgorithm::Sha256])
+        .unwrap()
+        .signed::<Pouf1>(top_level_key)
+        .unwrap();
+    let timestamp = TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+        .unwrap()
+        .expires(expires)
+        .signed::<Pouf1>(top_level_key)
+        .unwrap();
+
+    let metadata = RawSignedMetadataSetBuilder::new()
+        .root(root.to_raw().unwrap())
+        .targets(targets.to_raw().unwrap())
+        .snapshot(snapshot.to_raw().unwrap())
+        .timestamp(timestamp.to_raw().unwrap())
+        .build();
+
+    DelegatedRepository {
+        metadata,
+        delegations,
+    }
+}
+
+fn bench_update_delegated_targets(c: &mut Criterion) {
+    let repo = delegated_repository();
+    let mut group = c.benchmark_group("update_delegated_targets");
+    group.sample_size(10);
+
+    for (name, verifier) in verifiers() {
+        let verifier = verifier();
+        group.bench_function(BenchmarkId::new(name, DELEGATIONS), |b| {
+            b.iter(|| {
+                let mut db = Database::from_trusted_metadata(&repo.metadata).unwrap();
+                db.set_verifier(verifier.clone());
+
+                for (role, raw) in &repo.delegations {
+                    db.update_delegated_targets(&MetadataPath::targets(), role, raw)
+                        .unwrap();
+                }
+                db
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_verify_signatures,
+    bench_update_delegated_targets
+);
+criterion_main!(benches);
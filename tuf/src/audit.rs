@@ -0,0 +1,1441 @@
+//! Repository consistency audits
+//!
+//! A [Client](crate::client::Client) only verifies the metadata and targets it needs for an
+//! update. The [Auditor] instead checks everything in a repository, and reports every problem it
+//! finds as a [Finding] rather than stopping at the first one.
+
+use {
+    crate::{
+        client::Config,
+        clock::{Clock, FixedClock, SystemClock},
+        crypto::{self, HashAlgorithm, KeyId},
+        database::Database,
+        error::{Error, Result},
+        metadata::{
+            Metadata, MetadataDescription, MetadataPath, MetadataVersion, RawSignedMetadata,
+            RootMetadata, SnapshotMetadata, TargetDescription, TargetPath, TargetsMetadata,
+            TimestampMetadata,
+        },
+        pouf::Pouf,
+        repository::RepositoryProvider,
+        util::{role_metadata, version_for},
+    },
+    chrono::{DateTime, Duration, Utc},
+    futures_util::io::AsyncReadExt,
+    std::{
+        collections::{HashMap, HashSet, VecDeque},
+        fmt::{self, Display},
+        marker::PhantomData,
+        sync::Arc,
+    },
+};
+
+const DEFAULT_WARNING_HORIZON: Duration = Duration::days(7);
+
+/// Checks the consistency of a whole repository.
+///
+/// The audit starts from version 1 of the root metadata in the repository, and checks that:
+///
+/// * Every root metadata version verifies against the previous version, and `root.json` is the
+///   latest version.
+/// * The timestamp, snapshot, targets, and every reachable delegated targets role verify, and
+///   match the versions, lengths, and hashes listed by their parents.
+/// * The snapshot lists every delegated role, and only roles that are delegated.
+/// * Every target exists, with the length and hashes listed in its targets metadata.
+/// * No metadata has expired, or expires within the warning horizon.
+/// * Every role's threshold can be met by the keys listed for it, and every listed key is used.
+/// * Every delegation can be reached by clients.
+///
+/// Expired metadata is still verified, so an audit of an expired repository also reports any
+/// other problems it has.
+///
+/// ```
+/// # use {
+/// #     futures_executor::block_on,
+/// #     tuf::{
+/// #         audit::Auditor,
+/// #         crypto::Ed25519PrivateKey,
+/// #         pouf::Pouf1,
+/// #         repo_builder::RepoBuilder,
+/// #         repository::EphemeralRepository,
+/// #     },
+/// # };
+/// #
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+/// # ).unwrap();
+/// #
+/// # block_on(async {
+/// let repo = EphemeralRepository::<Pouf1>::new();
+/// RepoBuilder::create(&repo)
+///     .trusted_root_keys(&[&key])
+///     .trusted_targets_keys(&[&key])
+///     .trusted_snapshot_keys(&[&key])
+///     .trusted_timestamp_keys(&[&key])
+///     .commit()
+///     .await
+///     .unwrap();
+///
+/// let report = Auditor::new(&repo).verify().await.unwrap();
+/// assert!(!report.has_errors());
+/// # });
+/// ```
+pub struct Auditor<D, R>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    repo: R,
+    clock: Arc<dyn Clock>,
+    warning_horizon: Duration,
+    config: Config,
+    _pouf: PhantomData<D>,
+}
+
+impl<D, R> Auditor<D, R>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    /// Create an auditor for the repository `repo`.
+    pub fn new(repo: R) -> Self {
+        Self {
+            repo,
+            clock: Arc::new(SystemClock),
+            warning_horizon: DEFAULT_WARNING_HORIZON,
+            config: Config::default(),
+            _pouf: PhantomData,
+        }
+    }
+
+    /// Check expiration against the time reported by `clock`, rather than the system time.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Warn about metadata which expires within `warning_horizon`. Defaults to 7 days.
+    pub fn warning_horizon(mut self, warning_horizon: Duration) -> Self {
+        self.warning_horizon = warning_horizon;
+        self
+    }
+
+    /// Audit the repository as seen by clients using `config`. Metadata longer than the maximum
+    /// lengths of the config is reported as [Finding::MetadataTooLarge], and delegations nested
+    /// deeper than its maximum delegation depth are unreachable. The clock of the config is not
+    /// used. Defaults to [Config::default].
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Audit the repository.
+    ///
+    /// Problems with the repository are returned in the [AuditReport]. An `Err` is only returned
+    /// if the repository could not be read.
+    pub async fn verify(&self) -> Result<AuditReport> {
+        let mut audit = Audit {
+            now: self.clock.now()?,
+            report: AuditReport::default(),
+        };
+
+        let mut database = match self.verify_root_chain(&mut audit).await? {
+            Some(database) => database,
+            None => return Ok(audit.report),
+        };
+
+        if self.verify_top_level(&mut audit, &mut database).await? {
+            let reachable = self.verify_delegations(&mut audit, &mut database).await?;
+
+            if let Some(snapshot) = database.trusted_snapshot() {
+                let mut orphaned = snapshot
+                    .meta()
+                    .keys()
+                    .filter(|role| **role != MetadataPath::targets() && !reachable.contains(*role))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                orphaned.sort();
+                for role in orphaned {
+                    audit.push(Finding::OrphanedMetadata { role });
+                }
+            }
+
+            self.verify_targets(&mut audit, &database).await?;
+        }
+
+        Ok(audit.report)
+    }
+
+    /// Verify every root metadata version, starting from version 1. Returns a database trusting
+    /// the latest root that verified.
+    async fn verify_root_chain(&self, audit: &mut Audit) -> Result<Option<Database<D>>> {
+        let root_path = MetadataPath::root();
+
+        let raw_root = match self
+            .fetch_metadata::<RootMetadata>(audit, &root_path, MetadataVersion::Number(1))
+            .await?
+        {
+            Some(raw_root) => raw_root,
+            None => return Ok(None),
+        };
+        let mut database = match Database::from_trusted_root(&raw_root) {
+            Ok(database) => database,
+            Err(err) => {
+                audit.push_error(&root_path, MetadataVersion::Number(1), err);
+                return Ok(None);
+            }
+        };
+        // Expiration is reported by the audit itself, so the database is given a clock that is
+        // before any metadata could have expired.
+        database.set_clock(Arc::new(FixedClock::new(DateTime::<Utc>::MIN_UTC)));
+        let mut latest = raw_root.as_bytes().to_vec();
+
+        for version in 2.. {
+            let version = MetadataVersion::Number(version);
+            let raw_root = match self.fetch_raw(audit, &root_path, version).await? {
+                Fetched::Found(raw_root) => raw_root,
+                Fetched::Missing | Fetched::TooLarge => break,
+            };
+
+            if let Err(err) = database.update_root(&raw_root) {
+                audit.push_error(&root_path, version, err);
+                break;
+            }
+            latest = raw_root.as_bytes().to_vec();
+        }
+
+        let root = database.trusted_root();
+        match self
+            .fetch_metadata::<RootMetadata>(audit, &root_path, MetadataVersion::None)
+            .await?
+        {
+            Some(raw_root) if raw_root.as_bytes() != &latest[..] => {
+                audit.push(Finding::RootMismatch {
+                    version: root.version(),
+                });
+            }
+            _ => {}
+        }
+
+        self.check_expiration(audit, &root_path, root.expires());
+
+        let roles = [
+            (
+                MetadataPath::root(),
+                root.root().threshold(),
+                root.root().key_ids(),
+            ),
+            (
+                MetadataPath::snapshot(),
+                root.snapshot().threshold(),
+                root.snapshot().key_ids(),
+            ),
+            (
+                MetadataPath::targets(),
+                root.targets().threshold(),
+                root.targets().key_ids(),
+            ),
+            (
+                MetadataPath::timestamp(),
+                root.timestamp().threshold(),
+                root.timestamp().key_ids(),
+            ),
+        ];
+        for (role, threshold, key_ids) in &roles {
+            check_threshold(audit, role, *threshold, key_ids, root.keys());
+        }
+        check_unused_keys(
+            audit,
+            &root_path,
+            root.keys(),
+            roles.iter().map(|(_, _, key_ids)| *key_ids),
+        );
+
+        Ok(Some(database))
+    }
+
+    /// Verify the timestamp, snapshot, and targets metadata. Returns whether they all verified.
+    async fn verify_top_level(
+        &self,
+        audit: &mut Audit,
+        database: &mut Database<D>,
+    ) -> Result<bool> {
+        let consistent_snapshot = database.trusted_root().consistent_snapshot();
+
+        let timestamp_path = MetadataPath::timestamp();
+        let raw_timestamp = match self
+            .fetch_metadata::<TimestampMetadata>(audit, &timestamp_path, MetadataVersion::None)
+            .await?
+        {
+            Some(raw_timestamp) => raw_timestamp,
+            None => return Ok(false),
+        };
+        let timestamp = match database.update_timestamp(&raw_timestamp) {
+            Ok(timestamp) => timestamp.cloned(),
+            Err(err) => {
+                audit.push_error(&timestamp_path, MetadataVersion::None, err);
+                return Ok(false);
+            }
+        };
+        let snapshot_description = match timestamp {
+            Some(timestamp) => {
+                self.check_expiration(audit, &timestamp_path, timestamp.expires());
+                timestamp.snapshot().clone()
+            }
+            None => return Ok(false),
+        };
+
+        let snapshot_path = MetadataPath::snapshot();
+        let raw_snapshot = match self
+            .fetch_described_metadata::<SnapshotMetadata>(
+                audit,
+                &snapshot_path,
+                &snapshot_description,
+                consistent_snapshot,
+            )
+            .await?
+        {
+            Some(raw_snapshot) => raw_snapshot,
+            None => return Ok(false),
+        };
+        if let Err(err) = database.update_snapshot(&raw_snapshot) {
+            audit.push_error(
+                &snapshot_path,
+                version_for(&snapshot_description, consistent_snapshot),
+                err,
+            );
+            return Ok(false);
+        }
+        let targets_description = match database.trusted_snapshot() {
+            Some(snapshot) => {
+                self.check_expiration(audit, &snapshot_path, snapshot.expires());
+                snapshot.meta().get(&MetadataPath::targets()).cloned()
+            }
+            None => return Ok(false),
+        };
+
+        let targets_path = MetadataPath::targets();
+        let targets_description = match targets_description {
+            Some(targets_description) => targets_description,
+            None => {
+                audit.push(Finding::NotInSnapshot { role: targets_path });
+                return Ok(false);
+            }
+        };
+        let raw_targets = match self
+            .fetch_described_metadata::<TargetsMetadata>(
+                audit,
+                &targets_path,
+                &targets_description,
+                consistent_snapshot,
+            )
+            .await?
+        {
+            Some(raw_targets) => raw_targets,
+            None => return Ok(false),
+        };
+        if let Err(err) = database.update_targets(&raw_targets) {
+            audit.push_error(
+                &targets_path,
+                version_for(&targets_description, consistent_snapshot),
+                err,
+            );
+            return Ok(false);
+        }
+        match database.trusted_targets() {
+            Some(targets) => {
+                self.check_expiration(audit, &targets_path, targets.expires());
+                check_delegation_keys(audit, &targets_path, targets);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Verify every delegated targets role reachable from the targets role. Returns the roles
+    /// that are reachable.
+    async fn verify_delegations(
+        &self,
+        audit: &mut Audit,
+        database: &mut Database<D>,
+    ) -> Result<HashSet<MetadataPath>> {
+        let consistent_snapshot = database.trusted_root().consistent_snapshot();
+
+        let mut reachable = HashSet::new();
+        let mut queue = VecDeque::from([(MetadataPath::targets(), vec![])]);
+
+        while let Some((parent, chain)) = queue.pop_front() {
+            let delegations = match role_metadata(database, &parent) {
+                Some(metadata) => metadata.delegations().clone(),
+                None => continue,
+            };
+
+            for delegation in delegations.roles() {
+                let role = delegation.name();
+
+                // The top-level targets role may delegate any path, but delegated roles may only
+                // delegate paths they were delegated.
+                let depth = chain.len() as u32 + 1;
+                if depth > self.config.max_delegation_depth()
+                    || (!chain.is_empty()
+                        && !delegation
+                            .paths()
+                            .iter()
+                            .any(|path| path.matches_chain(&chain)))
+                {
+                    audit.push(Finding::UnreachableDelegation {
+                        parent: parent.clone(),
+                        role: role.clone(),
+                    });
+                    continue;
+                }
+
+                if !reachable.insert(role.clone()) {
+                    continue;
+                }
+
+                let description = match database
+                    .trusted_snapshot()
+                    .and_then(|snapshot| snapshot.meta().get(role))
+                {
+                    Some(description) => description.clone(),
+                    None => {
+                        audit.push(Finding::NotInSnapshot { role: role.clone() });
+                        continue;
+                    }
+                };
+
+                let raw = match self
+                    .fetch_described_metadata::<TargetsMetadata>(
+                        audit,
+                        role,
+                        &description,
+                        consistent_snapshot,
+                    )
+                    .await?
+                {
+                    Some(raw) => raw,
+                    None => continue,
+                };
+                if let Err(err) = database.update_delegated_targets(&parent, role, &raw) {
+                    audit.push_error(role, version_for(&description, consistent_snapshot), err);
+                    continue;
+                }
+
+                if let Some(metadata) = database.trusted_delegations().get(role) {
+                    self.check_expiration(audit, role, metadata.expires());
+                    check_delegation_keys(audit, role, metadata);
+                }
+
+                let mut chain = chain.clone();
+                chain.push(delegation.paths().clone());
+                queue.push_back((role.clone(), chain));
+            }
+        }
+
+        Ok(reachable)
+    }
+
+    /// Check every target listed by the verified targets metadata.
+    async fn verify_targets(&self, audit: &mut Audit, database: &Database<D>) -> Result<()> {
+        let consistent_snapshot = database.trusted_root().consistent_snapshot();
+
+        let mut targets = vec![];
+        for metadata in database
+            .trusted_targets()
+            .into_iter()
+            .chain(database.trusted_delegations().values())
+        {
+            for (target_path, description) in metadata.targets() {
+                targets.push((target_path.clone(), description.clone()));
+            }
+        }
+
+        // Roles may describe the same target, so sort to check them in a stable order.
+        targets.sort_by(|a, b| a.0.cmp(&b.0));
+        targets.dedup();
+
+        for (target_path, description) in &targets {
+            self.verify_target(audit, consistent_snapshot, target_path, description)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn verify_target(
+        &self,
+        audit: &mut Audit,
+        consistent_snapshot: bool,
+        target_path: &TargetPath,
+        description: &TargetDescription,
+    ) -> Result<()> {
+        let hashes = crypto::retain_supported_hashes(description.hashes());
+        if hashes.is_empty() {
+            audit.push(Finding::NoSupportedHashAlgorithm {
+                path: target_path.clone(),
+            });
+            return Ok(());
+        }
+
+        let paths = if consistent_snapshot {
+            hashes
+                .iter()
+                .map(|(_, hash)| target_path.with_hash_prefix(hash))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![target_path.clone()]
+        };
+
+        let algorithms = hashes
+            .iter()
+            .map(|(algorithm, _)| (*algorithm).clone())
+            .collect::<Vec<_>>();
+
+        for path in paths {
+            let read = match self.repo.fetch_target(&path).await {
+                Ok(read) => read,
+                Err(Error::TargetNotFound(_)) => {
+                    audit.push(Finding::MissingTarget { path });
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let (length, actual) = crypto::calculate_hashes_from_reader(read, &algorithms).await?;
+
+            if length != description.length() {
+                audit.push(Finding::TargetLengthMismatch {
+                    path: path.clone(),
+                    expected: description.length(),
+                    actual: length,
+                });
+            }
+            for (algorithm, expected) in &hashes {
+                if actual.get(*algorithm) != Some(expected) {
+                    audit.push(Finding::TargetHashMismatch {
+                        path: path.clone(),
+                        algorithm: (*algorithm).clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetch the metadata described by `description`, and check its length and hashes.
+    async fn fetch_described_metadata<M: Metadata>(
+        &self,
+        audit: &mut Audit,
+        path: &MetadataPath,
+        description: &MetadataDescription<M>,
+        consistent_snapshot: bool,
+    ) -> Result<Option<RawSignedMetadata<D, M>>> {
+        let raw = match self
+            .fetch_metadata(audit, path, version_for(description, consistent_snapshot))
+            .await?
+        {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+
+        let bytes = raw.as_bytes();
+        if let Some(length) = description.length() {
+            if bytes.len() != length {
+                audit.push(Finding::MetadataLengthMismatch {
+                    role: path.clone(),
+                    expected: length,
+                    actual: bytes.len(),
+                });
+            }
+        }
+        let hashes = crypto::retain_supported_hashes(description.hashes());
+        for algorithm in mismatched_hashes(bytes, &hashes)? {
+            audit.push(Finding::MetadataHashMismatch {
+                role: path.clone(),
+                algorithm,
+            });
+        }
+
+        Ok(Some(raw))
+    }
+
+    /// Fetch metadata, reporting it as missing if it doesn't exist.
+    async fn fetch_metadata<M: Metadata>(
+        &self,
+        audit: &mut Audit,
+        path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> Result<Option<RawSignedMetadata<D, M>>> {
+        match self.fetch_raw(audit, path, version).await? {
+            Fetched::Found(raw) => Ok(Some(raw)),
+            Fetched::Missing => {
+                audit.push(Finding::MissingMetadata {
+                    path: path.clone(),
+                    version,
+                });
+                Ok(None)
+            }
+            Fetched::TooLarge => Ok(None),
+        }
+    }
+
+    /// Fetch metadata, reading no more than the maximum length the config allows for its role.
+    /// Metadata that is too large is reported, but a missing metadata is left to the caller.
+    async fn fetch_raw<M: Metadata>(
+        &self,
+        audit: &mut Audit,
+        path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> Result<Fetched<RawSignedMetadata<D, M>>> {
+        let read = match self.repo.fetch_metadata(path, version).await {
+            Ok(read) => read,
+            Err(Error::MetadataNotFound { .. }) => return Ok(Fetched::Missing),
+            Err(err) => return Err(err),
+        };
+
+        // Read one byte past the maximum, to tell if the metadata is too large.
+        let max_length = self.max_length(path);
+        let mut bytes = vec![];
+        read.take(max_length.map_or(u64::MAX, |max_length| max_length as u64 + 1))
+            .read_to_end(&mut bytes)
+            .await?;
+        if let Some(max_length) = max_length {
+            if bytes.len() > max_length {
+                audit.push(Finding::MetadataTooLarge {
+                    path: path.clone(),
+                    version,
+                    max_length,
+                });
+                return Ok(Fetched::TooLarge);
+            }
+        }
+
+        Ok(Fetched::Found(RawSignedMetadata::new(bytes)))
+    }
+
+    /// The maximum length of the metadata for `role` allowed by the config.
+    fn max_length(&self, role: &MetadataPath) -> Option<usize> {
+        if *role == MetadataPath::root() {
+            *self.config.max_root_length()
+        } else if *role == MetadataPath::timestamp() {
+            *self.config.max_timestamp_length()
+        } else if *role == MetadataPath::snapshot() {
+            *self.config.max_snapshot_length()
+        } else {
+            *self.config.max_targets_length()
+        }
+    }
+
+    fn check_expiration(&self, audit: &mut Audit, role: &MetadataPath, expires: &DateTime<Utc>) {
+        if *expires <= audit.now {
+            audit.push(Finding::Expired {
+                role: role.clone(),
+                expires: *expires,
+            });
+        } else if audit
+            .now
+            .checked_add_signed(self.warning_horizon)
+            .map_or(true, |horizon| *expires <= horizon)
+        {
+            audit.push(Finding::ExpiresSoon {
+                role: role.clone(),
+                expires: *expires,
+            });
+        }
+    }
+}
+
+/// The result of fetching metadata.
+enum Fetched<T> {
+    Found(T),
+    Missing,
+    TooLarge,
+}
+
+struct Audit {
+    now: DateTime<Utc>,
+    report: AuditReport,
+}
+
+impl Audit {
+    fn push(&mut self, finding: Finding) {
+        self.report.findings.push(finding);
+    }
+
+    fn push_error(&mut self, path: &MetadataPath, version: MetadataVersion, err: Error) {
+        self.push(match err {
+            Error::WrongMetadataVersion {
+                parent_role,
+                child_role,
+                expected_version,
+                new_version,
+            } => Finding::VersionMismatch {
+                parent: parent_role,
+                role: child_role,
+                expected: expected_version,
+                actual: new_version,
+            },
+            err => Finding::InvalidMetadata {
+                path: path.clone(),
+                version,
+                error: err.to_string(),
+            },
+        });
+    }
+}
+
+fn check_threshold(
+    audit: &mut Audit,
+    role: &MetadataPath,
+    threshold: u32,
+    key_ids: &HashSet<KeyId>,
+    keys: &HashMap<KeyId, crypto::PublicKey>,
+) {
+    let available = key_ids
+        .iter()
+        .filter(|key_id| keys.contains_key(key_id))
+        .count();
+    if threshold as usize > available {
+        audit.push(Finding::UnachievableThreshold {
+            role: role.clone(),
+            threshold,
+            keys: available,
+        });
+    }
+}
+
+fn check_unused_keys<'a>(
+    audit: &mut Audit,
+    role: &MetadataPath,
+    keys: &HashMap<KeyId, crypto::PublicKey>,
+    used: impl Iterator<Item = &'a HashSet<KeyId>>,
+) {
+    let used = used.flatten().collect::<HashSet<_>>();
+    let mut unused = keys
+        .keys()
+        .filter(|key_id| !used.contains(key_id))
+        .cloned()
+        .collect::<Vec<_>>();
+    unused.sort();
+    for key_id in unused {
+        audit.push(Finding::UnusedKey {
+            role: role.clone(),
+            key_id,
+        });
+    }
+}
+
+/// Check the thresholds and keys of the delegations made by `role`.
+fn check_delegation_keys(audit: &mut Audit, role: &MetadataPath, metadata: &TargetsMetadata) {
+    let delegations = metadata.delegations();
+    for delegation in delegations.roles() {
+        check_threshold(
+            audit,
+            delegation.name(),
+            delegation.threshold(),
+            delegation.key_ids(),
+            delegations.keys(),
+        );
+    }
+    check_unused_keys(
+        audit,
+        role,
+        delegations.keys(),
+        delegations
+            .roles()
+            .iter()
+            .map(|delegation| delegation.key_ids()),
+    );
+}
+
+/// Returns the algorithms in `hashes` whose hash of `bytes` doesn't match.
+fn mismatched_hashes(
+    bytes: &[u8],
+    hashes: &[(&'static HashAlgorithm, crypto::HashValue)],
+) -> Result<Vec<HashAlgorithm>> {
+    let mut mismatched = vec![];
+    for (algorithm, expected) in hashes {
+        let actual = crypto::calculate_hashes_from_slice(bytes, &[(*algorithm).clone()])?;
+        if actual.get(*algorithm) != Some(expected) {
+            mismatched.push((*algorithm).clone());
+        }
+    }
+    Ok(mismatched)
+}
+
+/// The result of an [Auditor::verify].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    findings: Vec<Finding>,
+}
+
+impl AuditReport {
+    /// Every finding, in the order they were found.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// The findings with [Severity::Error].
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity() == Severity::Error)
+    }
+
+    /// The findings with [Severity::Warning].
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity() == Severity::Warning)
+    }
+
+    /// Whether any finding is an error.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Whether nothing was found.
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// How serious a [Finding] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The repository works, but may stop working, or contains something unnecessary.
+    Warning,
+    /// Clients will fail to update from the repository, or fail to fetch some targets.
+    Error,
+}
+
+/// A problem found by an [Auditor].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The metadata does not exist.
+    MissingMetadata {
+        /// The role of the metadata.
+        path: MetadataPath,
+        /// The version of the metadata.
+        version: MetadataVersion,
+    },
+
+    /// The metadata failed to parse or verify.
+    InvalidMetadata {
+        /// The role of the metadata.
+        path: MetadataPath,
+        /// The version of the metadata.
+        version: MetadataVersion,
+        /// Why verification failed.
+        error: String,
+    },
+
+    /// The metadata is longer than the maximum length clients download for its role.
+    MetadataTooLarge {
+        /// The role of the metadata.
+        path: MetadataPath,
+        /// The version of the metadata.
+        version: MetadataVersion,
+        /// The maximum length of the metadata.
+        max_length: usize,
+    },
+
+    /// The unversioned root metadata is not the latest root metadata version.
+    RootMismatch {
+        /// The latest root metadata version.
+        version: u32,
+    },
+
+    /// The role is not listed in the snapshot metadata.
+    NotInSnapshot {
+        /// The role.
+        role: MetadataPath,
+    },
+
+    /// The role is listed in the snapshot metadata, but no reachable role delegates to it.
+    OrphanedMetadata {
+        /// The role.
+        role: MetadataPath,
+    },
+
+    /// The metadata's version is not the version listed by its parent.
+    VersionMismatch {
+        /// The role listing the metadata.
+        parent: MetadataPath,
+        /// The role of the metadata.
+        role: MetadataPath,
+        /// The version listed by the parent.
+        expected: u32,
+        /// The version of the metadata.
+        actual: u32,
+    },
+
+    /// The metadata's length is not the length listed by its parent.
+    MetadataLengthMismatch {
+        /// The role of the metadata.
+        role: MetadataPath,
+        /// The length listed by the parent.
+        expected: usize,
+        /// The length of the metadata.
+        actual: usize,
+    },
+
+    /// The metadata's hash is not the hash listed by its parent.
+    MetadataHashMismatch {
+        /// The role of the metadata.
+        role: MetadataPath,
+        /// The algorithm of the mismatched hash.
+        algorithm: HashAlgorithm,
+    },
+
+    /// The target does not exist.
+    MissingTarget {
+        /// The path of the target, including any hash prefix.
+        path: TargetPath,
+    },
+
+    /// The target's length is not the length listed in its targets metadata.
+    TargetLengthMismatch {
+        /// The path of the target, including any hash prefix.
+        path: TargetPath,
+        /// The length listed in the targets metadata.
+        expected: u64,
+        /// The length of the target.
+        actual: u64,
+    },
+
+    /// The target's hash is not the hash listed in its targets metadata.
+    TargetHashMismatch {
+        /// The path of the target, including any hash prefix.
+        path: TargetPath,
+        /// The algorithm of the mismatched hash.
+        algorithm: HashAlgorithm,
+    },
+
+    /// None of the target's hashes use a supported algorithm, so clients can't verify it.
+    NoSupportedHashAlgorithm {
+        /// The path of the target.
+        path: TargetPath,
+    },
+
+    /// The metadata has expired.
+    Expired {
+        /// The role of the metadata.
+        role: MetadataPath,
+        /// When the metadata expired.
+        expires: DateTime<Utc>,
+    },
+
+    /// The metadata expires within the warning horizon.
+    ExpiresSoon {
+        /// The role of the metadata.
+        role: MetadataPath,
+        /// When the metadata expires.
+        expires: DateTime<Utc>,
+    },
+
+    /// The role's threshold is higher than the number of keys listed for it.
+    UnachievableThreshold {
+        /// The role.
+        role: MetadataPath,
+        /// The threshold of the role.
+        threshold: u32,
+        /// The number of keys listed for the role.
+        keys: usize,
+    },
+
+    /// The key is listed by the metadata, but not used by any role it defines.
+    UnusedKey {
+        /// The role of the metadata listing the key.
+        role: MetadataPath,
+        /// The unused key.
+        key_id: KeyId,
+    },
+
+    /// Clients can't reach the delegation, since none of its paths were delegated to its parent,
+    /// or it's nested deeper than the maximum delegation depth.
+    UnreachableDelegation {
+        /// The role making the delegation.
+        parent: MetadataPath,
+        /// The delegated role.
+        role: MetadataPath,
+    },
+}
+
+impl Finding {
+    /// How serious the finding is.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Finding::OrphanedMetadata { .. }
+            | Finding::ExpiresSoon { .. }
+            | Finding::UnusedKey { .. }
+            | Finding::UnreachableDelegation { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::MissingMetadata { path, version } => {
+                write!(f, "metadata {} version {} is missing", path, version)
+            }
+            Finding::InvalidMetadata {
+                path,
+                version,
+                error,
+            } => write!(
+                f,
+                "metadata {} version {} is invalid: {}",
+                path, version, error
+            ),
+            Finding::MetadataTooLarge {
+                path,
+                version,
+                max_length,
+            } => write!(
+                f,
+                "metadata {} version {} is larger than {} bytes",
+                path, version, max_length
+            ),
+            Finding::RootMismatch { version } => {
+                write!(f, "unversioned root is not root version {}", version)
+            }
+            Finding::NotInSnapshot { role } => write!(f, "role {} is not in the snapshot", role),
+            Finding::OrphanedMetadata { role } => {
+                write!(f, "role {} is in the snapshot, but is not delegated", role)
+            }
+            Finding::VersionMismatch {
+                parent,
+                role,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} lists {} version {}, but found version {}",
+                parent, role, expected, actual
+            ),
+            Finding::MetadataLengthMismatch {
+                role,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "metadata {} should be {} bytes, but is {} bytes",
+                role, expected, actual
+            ),
+            Finding::MetadataHashMismatch { role, algorithm } => {
+                write!(f, "metadata {} has the wrong {:?} hash", role, algorithm)
+            }
+            Finding::MissingTarget { path } => write!(f, "target {} is missing", path),
+            Finding::TargetLengthMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "target {} should be {} bytes, but is {} bytes",
+                path, expected, actual
+            ),
+            Finding::TargetHashMismatch { path, algorithm } => {
+                write!(f, "target {} has the wrong {:?} hash", path, algorithm)
+            }
+            Finding::NoSupportedHashAlgorithm { path } => {
+                write!(f, "target {} has no supported hash algorithm", path)
+            }
+            Finding::Expired { role, expires } => {
+                write!(f, "metadata {} expired at {}", role, expires)
+            }
+            Finding::ExpiresSoon { role, expires } => {
+                write!(f, "metadata {} expires at {}", role, expires)
+            }
+            Finding::UnachievableThreshold {
+                role,
+                threshold,
+                keys,
+            } => write!(
+                f,
+                "role {} has threshold {}, but only {} keys",
+                role, threshold, keys
+            ),
+            Finding::UnusedKey { role, key_id } => {
+                write!(f, "key {} in {} is not used by any role", key_id, role)
+            }
+            Finding::UnreachableDelegation { parent, role } => {
+                write!(f, "delegation from {} to {} is unreachable", parent, role)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::clock::FixedClock,
+        crate::crypto::{Ed25519PrivateKey, PrivateKey as _},
+        crate::metadata::{Delegation, TargetsMetadataBuilder},
+        crate::pouf::Pouf1,
+        crate::repo_builder::RepoBuilder,
+        crate::repository::{EphemeralRepository, RepositoryStorage},
+        chrono::TimeZone as _,
+        futures_executor::block_on,
+        futures_util::io::Cursor,
+        lazy_static::lazy_static,
+        pretty_assertions::assert_eq,
+    };
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+            ];
+            keys.iter()
+                .map(|key| Ed25519PrivateKey::from_pkcs8(key).unwrap())
+                .collect()
+        };
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn auditor<R: RepositoryProvider<Pouf1>>(repo: R) -> Auditor<Pouf1, R> {
+        Auditor::new(repo)
+            .clock(Arc::new(FixedClock::new(now())))
+            .warning_horizon(Duration::hours(1))
+    }
+
+    async fn publish(repo: &EphemeralRepository<Pouf1>, consistent_snapshot: bool) {
+        RepoBuilder::create(repo)
+            .current_time(now())
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|builder| builder.consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .add_target(TargetPath::new("a").unwrap(), Cursor::new(b"a1"))
+            .await
+            .unwrap()
+            .stage_targets()
+            .unwrap()
+            .snapshot_includes_length(true)
+            .snapshot_includes_hashes(&[HashAlgorithm::Sha256])
+            .stage_snapshot()
+            .unwrap()
+            .timestamp_includes_length(true)
+            .timestamp_includes_hashes(&[HashAlgorithm::Sha256])
+            .commit()
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn audit_of_consistent_repository_finds_nothing() {
+        for consistent_snapshot in [true, false] {
+            block_on(async {
+                let repo = EphemeralRepository::<Pouf1>::new();
+                publish(&repo, consistent_snapshot).await;
+
+                assert_eq!(
+                    auditor(&repo).verify().await.unwrap(),
+                    AuditReport::default()
+                );
+            })
+        }
+    }
+
+    #[test]
+    fn audit_reports_expiration() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            publish(&repo, false).await;
+
+            // The timestamp expires after a day, and the snapshot after a week.
+            let report = auditor(&repo)
+                .warning_horizon(Duration::days(7))
+                .clock(Arc::new(FixedClock::new(now() + Duration::days(1))))
+                .verify()
+                .await
+                .unwrap();
+            assert_eq!(
+                report.findings(),
+                &[
+                    Finding::Expired {
+                        role: MetadataPath::timestamp(),
+                        expires: now() + Duration::days(1),
+                    },
+                    Finding::ExpiresSoon {
+                        role: MetadataPath::snapshot(),
+                        expires: now() + Duration::days(7),
+                    },
+                ]
+            );
+            assert!(report.has_errors());
+            assert_eq!(report.warnings().count(), 1);
+        })
+    }
+
+    #[test]
+    fn audit_reports_metadata_larger_than_config_allows() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            publish(&repo, false).await;
+
+            let config = Config::build()
+                .max_timestamp_length(Some(10))
+                .finish()
+                .unwrap();
+            let report = auditor(&repo).config(config).verify().await.unwrap();
+            assert_eq!(
+                report.findings(),
+                &[Finding::MetadataTooLarge {
+                    path: MetadataPath::timestamp(),
+                    version: MetadataVersion::None,
+                    max_length: 10,
+                }]
+            );
+            assert!(report.has_errors());
+        })
+    }
+
+    #[test]
+    fn audit_reports_broken_metadata_and_targets() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            publish(&repo, true).await;
+
+            let metadata = repo.metadata();
+            let root = &metadata[&(MetadataPath::root(), MetadataVersion::Number(1))];
+            let targets = &metadata[&(MetadataPath::targets(), MetadataVersion::Number(1))];
+
+            // Publish root version 2, but leave `root.json` at version 1.
+            let database =
+                Database::from_trusted_root(&RawSignedMetadata::new(root.to_vec())).unwrap();
+            RepoBuilder::from_database(&repo, &database)
+                .current_time(now())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .skip_targets()
+                .skip_snapshot()
+                .skip_timestamp()
+                .commit()
+                .await
+                .unwrap();
+            repo.store_metadata(&MetadataPath::root(), MetadataVersion::None, &mut &root[..])
+                .await
+                .unwrap();
+
+            // Modify the targets metadata and a target.
+            let mut modified_targets = targets.to_vec();
+            modified_targets.push(b'\n');
+            repo.store_metadata(
+                &MetadataPath::targets(),
+                MetadataVersion::Number(1),
+                &mut &modified_targets[..],
+            )
+            .await
+            .unwrap();
+
+            let target_path = repo.list_targets().await.unwrap().remove(0);
+            repo.store_target(&target_path, &mut &b"a2 "[..])
+                .await
+                .unwrap();
+
+            let report = auditor(&repo).verify().await.unwrap();
+            assert_eq!(
+                report.findings(),
+                &[
+                    Finding::RootMismatch { version: 2 },
+                    Finding::MetadataLengthMismatch {
+                        role: MetadataPath::targets(),
+                        expected: targets.len(),
+                        actual: targets.len() + 1,
+                    },
+                    Finding::MetadataHashMismatch {
+                        role: MetadataPath::targets(),
+                        algorithm: HashAlgorithm::Sha256,
+                    },
+                    Finding::TargetLengthMismatch {
+                        path: target_path.clone(),
+                        expected: 2,
+                        actual: 3,
+                    },
+                    Finding::TargetHashMismatch {
+                        path: target_path.clone(),
+                        algorithm: HashAlgorithm::Sha256,
+                    },
+                ]
+            );
+
+            repo.remove_target(&target_path).await.unwrap();
+            repo.remove_metadata(&MetadataPath::timestamp(), MetadataVersion::None)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                auditor(&repo).verify().await.unwrap().findings(),
+                &[
+                    Finding::RootMismatch { version: 2 },
+                    Finding::MissingMetadata {
+                        path: MetadataPath::timestamp(),
+                        version: MetadataVersion::None,
+                    },
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn audit_reports_broken_delegations() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+            let nested_path = MetadataPath::new("nested").unwrap();
+            let unlisted_path = MetadataPath::new("unlisted").unwrap();
+            let unsigned_path = MetadataPath::new("unsigned").unwrap();
+            let orphan_path = MetadataPath::new("orphan").unwrap();
+
+            // The nested delegation's path is not delegated to `delegation`.
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .expires(now() + Duration::days(30))
+                .insert_target_from_slice(
+                    TargetPath::new("foo/a").unwrap(),
+                    b"a1",
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap()
+                .delegations(
+                    crate::metadata::Delegations::builder()
+                        .key(KEYS[1].public().clone())
+                        .role(
+                            Delegation::builder(nested_path.clone())
+                                .key(KEYS[1].public())
+                                .delegate_path(TargetPath::new("bar/").unwrap())
+                                .build()
+                                .unwrap(),
+                        )
+                        .build()
+                        .unwrap(),
+                )
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            repo.store_metadata(
+                &delegation_path,
+                MetadataVersion::None,
+                &mut raw_delegation.as_bytes(),
+            )
+            .await
+            .unwrap();
+            repo.store_target(&TargetPath::new("foo/a").unwrap(), &mut &b"a1"[..])
+                .await
+                .unwrap();
+
+            let description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            RepoBuilder::create(&repo)
+                .current_time(now())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|builder| builder.consistent_snapshot(false))
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_key(KEYS[2].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .add_delegation_role(
+                    Delegation::builder(unlisted_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("baz/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .add_delegation_role(
+                    Delegation::builder(unsigned_path.clone())
+                        .key_id(KEYS[0].public().key_id().clone())
+                        .delegate_path(TargetPath::new("qux/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder
+                        .insert_metadata_description(delegation_path.clone(), description.clone())
+                        .insert_metadata_description(orphan_path.clone(), description.clone())
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let report = auditor(&repo).verify().await.unwrap();
+            assert_eq!(
+                report.findings(),
+                &[
+                    Finding::UnachievableThreshold {
+                        role: unsigned_path.clone(),
+                        threshold: 1,
+                        keys: 0,
+                    },
+                    Finding::UnusedKey {
+                        role: MetadataPath::targets(),
+                        key_id: KEYS[2].public().key_id().clone(),
+                    },
+                    Finding::NotInSnapshot {
+                        role: unlisted_path.clone(),
+                    },
+                    Finding::NotInSnapshot {
+                        role: unsigned_path.clone(),
+                    },
+                    Finding::UnreachableDelegation {
+                        parent: delegation_path.clone(),
+                        role: nested_path.clone(),
+                    },
+                    Finding::OrphanedMetadata {
+                        role: orphan_path.clone(),
+                    },
+                ]
+            );
+            assert_eq!(
+                report
+                    .findings()
+                    .iter()
+                    .map(|finding| finding.severity())
+                    .collect::<Vec<_>>(),
+                vec![
+                    Severity::Error,
+                    Severity::Warning,
+                    Severity::Error,
+                    Severity::Error,
+                    Severity::Warning,
+                    Severity::Warning,
+                ]
+            );
+        })
+    }
+}
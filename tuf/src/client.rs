@@ -49,20 +49,26 @@
 
 use chrono::{offset::Utc, DateTime};
 use futures_io::AsyncRead;
-use log::{error, warn};
+use log::{debug, error, warn};
+use std::collections::{BTreeMap, BTreeSet};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
+use crate::clock::{Clock, SystemClock};
 use crate::crypto::{self, HashAlgorithm, HashValue, PublicKey};
-use crate::database::Database;
+use crate::database::{Database, ExpirationGracePolicy, Freshness};
 use crate::error::{Error, Result};
 use crate::metadata::{
     Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata, SnapshotMetadata,
     TargetDescription, TargetPath, TargetsMetadata,
 };
 use crate::pouf::Pouf;
-use crate::repository::{Repository, RepositoryProvider, RepositoryStorage};
-use crate::verify::Verified;
+use crate::repository::{
+    resolve_listed_metadata, Repository, RepositoryProvider, RepositoryStorage,
+};
+use crate::revocation::RevocationList;
+use crate::verify::{Verified, Verifier};
 
 /// A client that interacts with TUF repositories.
 #[derive(Debug)]
@@ -152,7 +158,7 @@ where
             .fetch_metadata(&root_path, root_version, config.max_root_length, vec![])
             .await?;
 
-        let tuf = Database::from_trusted_root(&raw_root)?;
+        let tuf = Database::from_trusted_root_with_verifier(&raw_root, config.database_verifier())?;
 
         Self::new(config, tuf, local, remote).await
     }
@@ -213,7 +219,8 @@ where
         remote: R,
     ) -> Result<Self> {
         let (local, remote) = (Repository::new(local), Repository::new(remote));
-        let tuf = Database::from_trusted_root(trusted_root)?;
+        let tuf =
+            Database::from_trusted_root_with_verifier(trusted_root, config.database_verifier())?;
 
         Self::new(config, tuf, local, remote).await
     }
@@ -301,8 +308,12 @@ where
         )
         .await?;
 
-        let tuf =
-            Database::from_root_with_trusted_keys(&raw_root, root_threshold, trusted_root_keys)?;
+        let tuf = Database::from_root_with_trusted_keys_and_verifier(
+            &raw_root,
+            root_threshold,
+            trusted_root_keys,
+            config.database_verifier(),
+        )?;
 
         // FIXME(#253) verify the trusted root version matches the provided version.
         let root_version = MetadataVersion::Number(tuf.trusted_root().version());
@@ -332,7 +343,13 @@ where
     }
 
     /// Create a new TUF client. It will trust and update the TUF database.
-    pub fn from_database(config: Config, tuf: Database<D>, local: L, remote: R) -> Self {
+    ///
+    /// The database will use the [Clock], [ExpirationGracePolicy] and [Verifier] from the
+    /// `config`.
+    pub fn from_database(config: Config, mut tuf: Database<D>, local: L, remote: R) -> Self {
+        tuf.set_clock(Arc::clone(&config.clock));
+        tuf.set_expiration_grace_policy(config.expiration_grace_policy.clone());
+        tuf.set_verifier(config.verifier.clone());
         Self {
             config,
             tuf,
@@ -368,36 +385,28 @@ where
         local: Repository<L, D>,
         remote: Repository<R, D>,
     ) -> Result<Self> {
-        let start_time = Utc::now();
+        tuf.set_clock(Arc::clone(&config.clock));
+        tuf.set_expiration_grace_policy(config.expiration_grace_policy.clone());
+        tuf.set_verifier(config.verifier.clone());
 
+        // The local metadata was already trusted, so it may be used within the grace period.
+        tuf.set_reloading(true);
         let res = async {
-            let _r =
-                Self::update_root_with_repos(&start_time, &config, &mut tuf, None, &local).await?;
-            let _ts =
-                Self::update_timestamp_with_repos(&start_time, &config, &mut tuf, None, &local)
-                    .await?;
-            let _sn = Self::update_snapshot_with_repos(
-                &start_time,
-                &config,
-                &mut tuf,
-                None,
-                &local,
-                false,
-            )
-            .await?;
-            let _ta = Self::update_targets_with_repos(
-                &start_time,
-                &config,
-                &mut tuf,
-                None,
-                &local,
-                false,
-            )
-            .await?;
+            let _r = Self::update_root_with_repos(&config, &mut tuf, None, &local).await?;
+            let _ts = Self::update_timestamp_with_repos(&config, &mut tuf, None, &local).await?;
+            let sn =
+                Self::update_snapshot_with_repos(&config, &mut tuf, None, &local, false).await?;
+            if sn {
+                let _rv =
+                    Self::update_revocations_with_repos(&config, &mut tuf, None, &local).await?;
+            }
+            let _ta =
+                Self::update_targets_with_repos(&config, &mut tuf, None, &local, false).await?;
 
             Ok(())
         }
         .await;
+        tuf.set_reloading(false);
 
         match res {
             Ok(()) | Err(Error::MetadataNotFound { .. }) => {}
@@ -418,22 +427,80 @@ where
     ///
     /// Returns `true` if an update occurred and `false` otherwise.
     pub async fn update(&mut self) -> Result<bool> {
-        self.update_with_start_time(&Utc::now()).await
+        let res = self.update_all().await;
+
+        // The database may have dropped trust in some roles even if the update failed part way
+        // through, so always prune the local repository.
+        let pruned = self.prune_local_metadata().await;
+        let updated = res?;
+        pruned?;
+
+        Ok(updated)
     }
 
-    /// Update TUF metadata from the remote repository, using the specified time to determine if
-    /// the metadata is expired.
-    ///
-    /// Returns `true` if an update occurred and `false` otherwise.
-    ///
-    /// **WARNING**: Using an older time opens up users to a freeze attack.
-    pub async fn update_with_start_time(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
-        let r = self.update_root(start_time).await?;
-        let ts = self.update_timestamp(start_time).await?;
-        let sn = self.update_snapshot(start_time).await?;
-        let ta = self.update_targets(start_time).await?;
+    async fn update_all(&mut self) -> Result<bool> {
+        let r = self.update_root_from_remote().await?;
+        let ts = self.update_timestamp().await?;
+        let sn = self.update_snapshot().await?;
+        // Repositories publish revocations along with a new snapshot.
+        let rv = sn && self.update_revocations().await?;
+        let ta = self.update_targets().await?;
+
+        Ok(r || ts || sn || rv || ta)
+    }
+
+    /// Remove the unversioned metadata from the local repository for every role the [Database] no
+    /// longer trusts, such as the timestamp and snapshot after a root key rotation, or a
+    /// delegated role that was removed from the snapshot. Otherwise the stale metadata would be
+    /// reloaded by [Client::with_trusted_local].
+    async fn prune_local_metadata(&mut self) -> Result<()> {
+        let consistent_snapshot = self.tuf.trusted_root().consistent_snapshot();
+        for (meta_path, version) in self.local.list_metadata().await? {
+            let (meta_path, version) =
+                resolve_listed_metadata(meta_path, version, consistent_snapshot);
+            if version != MetadataVersion::None || self.retains_local_metadata(&meta_path) {
+                continue;
+            }
+
+            debug!(
+                "Removing untrusted metadata {} from the local repository",
+                meta_path
+            );
+            self.local.remove_metadata(&meta_path, version).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the local copy of the metadata for `meta_path` should be kept.
+    fn retains_local_metadata(&self, meta_path: &MetadataPath) -> bool {
+        if *meta_path == MetadataPath::root() || *meta_path == RevocationList::path() {
+            true
+        } else if *meta_path == MetadataPath::timestamp() {
+            self.tuf.trusted_timestamp().is_some()
+        } else if *meta_path == MetadataPath::snapshot() {
+            self.tuf.trusted_snapshot().is_some()
+        } else if *meta_path == MetadataPath::targets() {
+            self.tuf.trusted_targets().is_some()
+        } else {
+            // Delegated metadata is lazily loaded, so keep it as long as the snapshot lists it.
+            self.tuf
+                .trusted_snapshot()
+                .map(|snapshot| snapshot.meta().contains_key(meta_path))
+                .unwrap_or(false)
+        }
+    }
 
-        Ok(r || ts || sn || ta)
+    /// Update TUF metadata from the remote repository, and describe what changed.
+    ///
+    /// See [UpdateReport] for what is included in the report.
+    pub async fn update_with_report(&mut self) -> Result<UpdateReport> {
+        let previous = TrustedState::new(&self.tuf);
+        self.update().await?;
+        Ok(UpdateReport::from_states(
+            previous,
+            TrustedState::new(&self.tuf),
+        ))
     }
 
     /// Consumes the [Client] and returns the inner [Database] and other parts.
@@ -485,9 +552,17 @@ where
     /// Update TUF root metadata from the remote repository.
     ///
     /// Returns `true` if an update occurred and `false` otherwise.
-    pub async fn update_root(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+    pub async fn update_root(&mut self) -> Result<bool> {
+        let res = self.update_root_from_remote().await;
+        let pruned = self.prune_local_metadata().await;
+        let updated = res?;
+        pruned?;
+
+        Ok(updated)
+    }
+
+    async fn update_root_from_remote(&mut self) -> Result<bool> {
         Self::update_root_with_repos(
-            start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
@@ -497,7 +572,6 @@ where
     }
 
     async fn update_root_with_repos<Remote>(
-        start_time: &DateTime<Utc>,
         config: &Config,
         tuf: &mut Database<D>,
         mut local: Option<&mut Repository<L, D>>,
@@ -579,15 +653,9 @@ where
         //     attack. On the next update cycle, begin at step 5.0 and version N of the root
         //     metadata file.
 
-        // TODO: Consider moving the root metadata expiration check into `tuf::Database`, since that's
-        // where we check timestamp/snapshot/targets/delegations for expiration.
-        if tuf.trusted_root().expires() <= start_time {
+        if let Err(err) = tuf.ensure_root_unexpired() {
             error!("Root metadata expired, potential freeze attack");
-            return Err(Error::ExpiredMetadata {
-                path: MetadataPath::root(),
-                expiration: *tuf.trusted_root().expires(),
-                now: *start_time,
-            });
+            return Err(err);
         }
 
         /////////////////////////////////////////
@@ -600,9 +668,8 @@ where
     }
 
     /// Returns `true` if an update occurred and `false` otherwise.
-    async fn update_timestamp(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+    async fn update_timestamp(&mut self) -> Result<bool> {
         Self::update_timestamp_with_repos(
-            start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
@@ -612,7 +679,6 @@ where
     }
 
     async fn update_timestamp_with_repos<Remote>(
-        start_time: &DateTime<Utc>,
         config: &Config,
         tuf: &mut Database<D>,
         local: Option<&mut Repository<L, D>>,
@@ -640,10 +706,7 @@ where
             )
             .await?;
 
-        if tuf
-            .update_timestamp(start_time, &raw_signed_timestamp)?
-            .is_some()
-        {
+        if tuf.update_timestamp(&raw_signed_timestamp)?.is_some() {
             /////////////////////////////////////////
             // TUF-1.0.9 §5.2.4:
             //
@@ -667,10 +730,9 @@ where
     }
 
     /// Returns `true` if an update occurred and `false` otherwise.
-    async fn update_snapshot(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+    async fn update_snapshot(&mut self) -> Result<bool> {
         let consistent_snapshot = self.tuf.trusted_root().consistent_snapshot();
         Self::update_snapshot_with_repos(
-            start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
@@ -681,7 +743,6 @@ where
     }
 
     async fn update_snapshot_with_repos<Remote>(
-        start_time: &DateTime<Utc>,
         config: &Config,
         tuf: &mut Database<D>,
         local: Option<&mut Repository<L, D>>,
@@ -732,7 +793,7 @@ where
 
         // https://theupdateframework.github.io/specification/v1.0.26/#update-snapshot 5.5.3 through
         // 5.5.6 are checked in [Database].
-        if tuf.update_snapshot(start_time, &raw_signed_snapshot)? {
+        if tuf.update_snapshot(&raw_signed_snapshot)? {
             // https://theupdateframework.github.io/specification/v1.0.26/#update-snapshot 5.5.7:
             //
             // Persist snapshot metadata. The client MUST write the file to non-volatile storage as
@@ -749,11 +810,69 @@ where
         }
     }
 
+    /// Apply the key revocations published by the remote repository, so revoked keys no longer
+    /// count towards the thresholds of the targets and delegated targets metadata. This is only
+    /// done after updating the snapshot metadata.
+    ///
+    /// Returns `true` if a key was newly revoked and `false` otherwise.
+    async fn update_revocations(&mut self) -> Result<bool> {
+        Self::update_revocations_with_repos(
+            &self.config,
+            &mut self.tuf,
+            Some(&mut self.local),
+            &self.remote,
+        )
+        .await
+    }
+
+    async fn update_revocations_with_repos<Remote>(
+        config: &Config,
+        tuf: &mut Database<D>,
+        local: Option<&mut Repository<L, D>>,
+        remote: &Repository<Remote, D>,
+    ) -> Result<bool>
+    where
+        Remote: RepositoryProvider<D>,
+    {
+        // The revocations aren't listed in the snapshot, so their length is bounded like the
+        // snapshot's. Repositories only publish them once a key has been revoked.
+        let raw_revocations = match remote.fetch_revocations(config.max_snapshot_length).await {
+            Ok(raw_revocations) => raw_revocations,
+            Err(Error::MetadataNotFound { .. }) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        let revocations = RevocationList::from_slice::<D>(&raw_revocations)?;
+        let revoked = tuf.update_revocations(&revocations)?;
+
+        // Nothing signs the list, so a mirror could serve an older or empty one. Only ever add the
+        // revocations the database kept to the local copy, rather than storing what was fetched.
+        if let Some(local) = local {
+            let mut stored = match local.fetch_revocations(None).await {
+                Ok(raw) => RevocationList::from_slice::<D>(&raw).unwrap_or_else(|err| {
+                    warn!("Replacing unreadable local revocations: {}", err);
+                    RevocationList::new()
+                }),
+                Err(Error::MetadataNotFound { .. }) => RevocationList::new(),
+                Err(err) => return Err(err),
+            };
+
+            let mut changed = false;
+            for revocation in tuf.published_revocations().revocations() {
+                changed |= stored.insert(revocation.clone());
+            }
+            if changed {
+                local.store_revocations(&stored.to_bytes::<D>()?).await?;
+            }
+        }
+
+        Ok(revoked > 0)
+    }
+
     /// Returns `true` if an update occurred and `false` otherwise.
-    async fn update_targets(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+    async fn update_targets(&mut self) -> Result<bool> {
         let consistent_snapshot = self.tuf.trusted_root().consistent_snapshot();
         Self::update_targets_with_repos(
-            start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
@@ -764,7 +883,6 @@ where
     }
 
     async fn update_targets_with_repos<Remote>(
-        start_time: &DateTime<Utc>,
         config: &Config,
         tuf: &mut Database<D>,
         local: Option<&mut Repository<L, D>>,
@@ -818,7 +936,7 @@ where
             .fetch_metadata(&targets_path, version, targets_length, target_hashes)
             .await?;
 
-        if tuf.update_targets(start_time, &raw_signed_targets)? {
+        if tuf.update_targets(&raw_signed_targets)? {
             /////////////////////////////////////////
             // TUF-1.0.9 §5.4.4:
             //
@@ -846,22 +964,7 @@ where
         &mut self,
         target: &TargetPath,
     ) -> Result<impl AsyncRead + Send + Unpin + '_> {
-        self.fetch_target_with_start_time(target, &Utc::now()).await
-    }
-
-    /// Fetch a target from the remote repo.
-    ///
-    /// It is **critical** that none of the bytes written to the `write` are used until this future
-    /// returns `Ok`, as the hash of the target is not verified until all bytes are read from the
-    /// repository.
-    pub async fn fetch_target_with_start_time(
-        &mut self,
-        target: &TargetPath,
-        start_time: &DateTime<Utc>,
-    ) -> Result<impl AsyncRead + Send + Unpin + '_> {
-        let target_description = self
-            .fetch_target_description_with_start_time(target, start_time)
-            .await?;
+        let target_description = self.fetch_target_description(target).await?;
 
         // TODO: Check the local repository to see if it already has the target.
         self.remote
@@ -879,23 +982,7 @@ where
     /// returns `Ok`, as the hash of the target is not verified until all bytes are read from the
     /// repository.
     pub async fn fetch_target_to_local(&mut self, target: &TargetPath) -> Result<()> {
-        self.fetch_target_to_local_with_start_time(target, &Utc::now())
-            .await
-    }
-
-    /// Fetch a target from the remote repo and write it to the local repo.
-    ///
-    /// It is **critical** that none of the bytes written to the `write` are used until this future
-    /// returns `Ok`, as the hash of the target is not verified until all bytes are read from the
-    /// repository.
-    pub async fn fetch_target_to_local_with_start_time(
-        &mut self,
-        target: &TargetPath,
-        start_time: &DateTime<Utc>,
-    ) -> Result<()> {
-        let target_description = self
-            .fetch_target_description_with_start_time(target, start_time)
-            .await?;
+        let target_description = self.fetch_target_description(target).await?;
 
         // Since the async read we fetch from the remote repository has internal
         // lifetimes, we need to break up client into sub-objects so that rust
@@ -918,19 +1005,81 @@ where
     }
 
     /// Fetch a target description from the remote repo and return it.
+    ///
+    /// This never returns a description verified with expired metadata, even if the
+    /// [ExpirationGracePolicy] allows it. Use [Client::fetch_target_description_with_freshness]
+    /// to look up targets using stale metadata.
     pub async fn fetch_target_description(
         &mut self,
         target: &TargetPath,
     ) -> Result<TargetDescription> {
-        self.fetch_target_description_with_start_time(target, &Utc::now())
+        let start_time = self.tuf.clock().now()?;
+        let (description, freshness) = self
+            .lookup_target_description_with_freshness(&start_time, target)
+            .await?;
+        freshness.ensure_fresh(&start_time)?;
+        Ok(description)
+    }
+
+    /// Fetch a target description from the remote repo and return it, along with whether any of
+    /// the metadata used to verify it has expired.
+    ///
+    /// Expired metadata is only used if it is still within the grace period allowed by the
+    /// configured [ExpirationGracePolicy], in which case the description is marked as
+    /// [Freshness::Stale].
+    pub async fn fetch_target_description_with_freshness(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<(TargetDescription, Freshness)> {
+        let start_time = self.tuf.clock().now()?;
+        self.lookup_target_description_with_freshness(&start_time, target)
             .await
     }
 
-    /// Fetch a target description from the remote repo and return it.
-    pub async fn fetch_target_description_with_start_time(
+    /// Search the delegation graph for `target` and report how the search proceeded.
+    ///
+    /// Unlike [Client::fetch_target_description], this does not fail when the target cannot be
+    /// found. Instead the returned [TargetLookupTrace] lists every role that was considered, why
+    /// each delegation was followed or skipped, and which role, if any, provided the description.
+    /// An `Error` is only returned if the trusted snapshot or top-level targets metadata is
+    /// missing, since there is no delegation graph to walk without them.
+    pub async fn explain_target_description(
         &mut self,
         target: &TargetPath,
+    ) -> Result<TargetLookupTrace> {
+        let mut trace = TargetLookupTrace::new(target.clone());
+
+        if let Err(err) = self
+            .lookup_target_description_with_trace(target, &mut trace)
+            .await
+        {
+            // If we failed before even reaching the top-level targets role, there is nothing to
+            // explain.
+            if trace.steps.is_empty() {
+                return Err(err);
+            }
+        }
+
+        Ok(trace)
+    }
+
+    async fn lookup_target_description_with_freshness(
+        &mut self,
         start_time: &DateTime<Utc>,
+        target: &TargetPath,
+    ) -> Result<(TargetDescription, Freshness)> {
+        let mut trace = TargetLookupTrace::new(target.clone());
+        let description = self
+            .lookup_target_description_with_trace(target, &mut trace)
+            .await?;
+        let freshness = self.tuf.freshness(start_time, &trace.delegation_chain())?;
+        Ok((description, freshness))
+    }
+
+    async fn lookup_target_description_with_trace(
+        &mut self,
+        target: &TargetPath,
+        trace: &mut TargetLookupTrace,
     ) -> Result<TargetDescription> {
         let snapshot = self
             .tuf
@@ -950,7 +1099,7 @@ where
         //     validated, end the search and report that the target cannot be found.
 
         let (_, target_description) = self
-            .lookup_target_description(start_time, false, 0, target, &snapshot, None)
+            .lookup_target_description(false, 0, target, &snapshot, None, trace)
             .await;
 
         target_description
@@ -958,24 +1107,13 @@ where
 
     async fn lookup_target_description(
         &mut self,
-        start_time: &DateTime<Utc>,
         default_terminate: bool,
         current_depth: u32,
         target: &TargetPath,
         snapshot: &SnapshotMetadata,
         targets: Option<(&Verified<TargetsMetadata>, MetadataPath)>,
+        trace: &mut TargetLookupTrace,
     ) -> (bool, Result<TargetDescription>) {
-        if current_depth > self.config.max_delegation_depth {
-            warn!(
-                "Walking the delegation graph would have exceeded the configured max depth: {}",
-                self.config.max_delegation_depth
-            );
-            return (
-                default_terminate,
-                Err(Error::TargetNotFound(target.clone())),
-            );
-        }
-
         // these clones are dumb, but we need immutable values and not references for update
         // tuf in the loop below
         let (targets, targets_role) = match targets {
@@ -994,12 +1132,56 @@ where
             },
         };
 
+        // The top-level targets role is always searched. Delegated roles are recorded by their
+        // parent below, since that's where the decision to follow them is made.
+        if current_depth == 0 {
+            trace.push(
+                targets_role.clone(),
+                None,
+                current_depth,
+                false,
+                LookupDecision::Followed,
+            );
+        }
+
         if let Some(t) = targets.targets().get(target) {
+            trace.resolved_by = Some(targets_role);
+            trace.description = Some(t.clone());
             return (default_terminate, Ok(t.clone()));
         }
 
         for delegation in targets.delegations().roles() {
+            let record = |trace: &mut TargetLookupTrace, decision: LookupDecision| {
+                // Skipping a terminating delegation ends the search.
+                if delegation.terminating() && decision != LookupDecision::Followed {
+                    trace
+                        .terminated_by
+                        .get_or_insert_with(|| delegation.name().clone());
+                }
+                trace.push(
+                    delegation.name().clone(),
+                    Some(targets_role.clone()),
+                    current_depth + 1,
+                    delegation.terminating(),
+                    decision,
+                );
+            };
+
             if !delegation.paths().iter().any(|p| target.is_child(p)) {
+                record(trace, LookupDecision::PathMismatch);
+                if delegation.terminating() {
+                    return (true, Err(Error::TargetNotFound(target.clone())));
+                } else {
+                    continue;
+                }
+            }
+
+            if current_depth + 1 > self.config.max_delegation_depth {
+                warn!(
+                    "Walking the delegation graph would have exceeded the configured max depth: {}",
+                    self.config.max_delegation_depth
+                );
+                record(trace, LookupDecision::DepthLimitExceeded);
                 if delegation.terminating() {
                     return (true, Err(Error::TargetNotFound(target.clone())));
                 } else {
@@ -1010,9 +1192,11 @@ where
             let role_meta = match snapshot.meta().get(delegation.name()) {
                 Some(m) => m,
                 None if delegation.terminating() => {
+                    record(trace, LookupDecision::MissingFromSnapshot);
                     return (true, Err(Error::TargetNotFound(target.clone())));
                 }
                 None => {
+                    record(trace, LookupDecision::MissingFromSnapshot);
                     continue;
                 }
             };
@@ -1052,6 +1236,7 @@ where
                 Ok(m) => m,
                 Err(e) => {
                     warn!("Failed to fetch metadata {:?}: {:?}", delegation.name(), e);
+                    record(trace, LookupDecision::FetchFailed(e.to_string()));
                     if delegation.terminating() {
                         return (true, Err(e));
                     } else {
@@ -1061,12 +1246,13 @@ where
             };
 
             match self.tuf.update_delegated_targets(
-                start_time,
                 &targets_role,
                 delegation.name(),
                 &raw_signed_meta,
             ) {
                 Ok(_) => {
+                    record(trace, LookupDecision::Followed);
+
                     /////////////////////////////////////////
                     // TUF-1.0.9 §5.4.4:
                     //
@@ -1096,23 +1282,32 @@ where
                         .clone();
                     let f: Pin<Box<dyn Future<Output = _>>> =
                         Box::pin(self.lookup_target_description(
-                            start_time,
                             delegation.terminating(),
                             current_depth + 1,
                             target,
                             snapshot,
                             Some((&meta, delegation.name().clone())),
+                            trace,
                         ));
                     let (term, res) = f.await;
 
-                    if term && res.is_err() {
-                        return (true, res);
+                    if res.is_ok() {
+                        return (term, res);
                     }
 
-                    // TODO end recursion early
+                    if term {
+                        trace
+                            .terminated_by
+                            .get_or_insert_with(|| delegation.name().clone());
+                        return (true, res);
+                    }
+                }
+                Err(e) => {
+                    record(trace, LookupDecision::VerificationFailed(e.to_string()));
+                    if delegation.terminating() {
+                        return (true, Err(e));
+                    }
                 }
-                Err(_) if !delegation.terminating() => continue,
-                Err(e) => return (true, Err(e)),
             };
         }
 
@@ -1123,103 +1318,494 @@ where
     }
 }
 
-/// Deconstructed parts of a [Client].
+/// A record of how a [Client] searched the delegation graph for a target.
 ///
-/// This allows taking apart a [Client] in order to reclaim the [Database],
-/// local, and remote repositories.
-#[non_exhaustive]
-#[derive(Debug)]
-pub struct Parts<D, L, R>
-where
-    D: Pouf,
-    L: RepositoryProvider<D> + RepositoryStorage<D>,
-    R: RepositoryProvider<D>,
-{
-    /// The client configuration.
-    pub config: Config,
+/// See [Client::explain_target_description].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetLookupTrace {
+    target: TargetPath,
+    steps: Vec<TargetLookupStep>,
+    resolved_by: Option<MetadataPath>,
+    terminated_by: Option<MetadataPath>,
+    description: Option<TargetDescription>,
+}
 
-    /// The Tuf database, which is updated by the [Client].
-    pub database: Database<D>,
+impl TargetLookupTrace {
+    fn new(target: TargetPath) -> Self {
+        Self {
+            target,
+            steps: vec![],
+            resolved_by: None,
+            terminated_by: None,
+            description: None,
+        }
+    }
 
-    /// The local repository, which is used to initialize the database, and
-    /// is updated by the [Client].
-    pub local: L,
+    fn push(
+        &mut self,
+        role: MetadataPath,
+        parent: Option<MetadataPath>,
+        depth: u32,
+        terminating: bool,
+        decision: LookupDecision,
+    ) {
+        self.steps.push(TargetLookupStep {
+            role,
+            parent,
+            depth,
+            terminating,
+            decision,
+        });
+    }
 
-    /// The remote repository, which is used by the client to update the database.
-    pub remote: R,
-}
+    /// The delegated roles, in order, that lead from the top-level targets role to the role that
+    /// provided the description.
+    fn delegation_chain(&self) -> Vec<MetadataPath> {
+        let mut chain = vec![];
+        let mut role = self.resolved_by.as_ref();
 
-/// Helper function that first tries to fetch the metadata from the local store, and if it doesn't
-/// exist or does and fails to parse, try fetching it from the remote store.
-async fn fetch_metadata_from_local_or_else_remote<'a, D, L, R, M>(
-    path: &'a MetadataPath,
-    version: MetadataVersion,
-    max_length: Option<usize>,
-    hashes: Vec<(&'static HashAlgorithm, HashValue)>,
-    local: &'a Repository<L, D>,
-    remote: &'a Repository<R, D>,
-) -> Result<(bool, RawSignedMetadata<D, M>)>
-where
-    D: Pouf,
-    L: RepositoryProvider<D> + RepositoryStorage<D>,
-    R: RepositoryProvider<D>,
-    M: Metadata + 'static,
-{
-    match local
-        .fetch_metadata(path, version, max_length, hashes.clone())
-        .await
-    {
-        Ok(raw_meta) => Ok((false, raw_meta)),
-        Err(Error::MetadataNotFound { .. }) => {
-            let raw_meta = remote
-                .fetch_metadata(path, version, max_length, hashes)
-                .await?;
-            Ok((true, raw_meta))
+        while let Some(current) = role {
+            let step = self
+                .steps
+                .iter()
+                .find(|step| &step.role == current && step.decision == LookupDecision::Followed);
+
+            match step {
+                Some(step) if step.parent.is_some() => {
+                    chain.push(current.clone());
+                    role = step.parent.as_ref();
+                }
+                _ => break,
+            }
         }
-        Err(err) => Err(err),
+
+        chain.reverse();
+        chain
+    }
+
+    /// The target that was looked up.
+    pub fn target(&self) -> &TargetPath {
+        &self.target
+    }
+
+    /// The roles that were considered, in the order the search visited them.
+    pub fn steps(&self) -> &[TargetLookupStep] {
+        &self.steps
+    }
+
+    /// The role that provided the target description, if the target was found.
+    pub fn resolved_by(&self) -> Option<&MetadataPath> {
+        self.resolved_by.as_ref()
+    }
+
+    /// The terminating delegation that ended the search, if any.
+    pub fn terminated_by(&self) -> Option<&MetadataPath> {
+        self.terminated_by.as_ref()
+    }
+
+    /// The target description, if the target was found.
+    pub fn description(&self) -> Option<&TargetDescription> {
+        self.description.as_ref()
     }
 }
 
-/// Configuration for a TUF `Client`.
-///
-/// # Defaults
-///
-/// The following values are considered reasonably safe defaults, however these values may change
-/// as this crate moves out of beta. If you are concered about them changing, you should use the
-/// `ConfigBuilder` and set your own values.
-///
-/// ```
-/// # use tuf::client::{Config};
-/// let config = Config::default();
-/// assert_eq!(config.max_root_length(), &Some(500 * 1024));
-/// assert_eq!(config.max_timestamp_length(), &Some(16 * 1024));
-/// assert_eq!(config.max_snapshot_length(), &Some(2000000));
-/// assert_eq!(config.max_targets_length(), &Some(5000000));
-/// assert_eq!(config.max_delegation_depth(), 8);
-/// ```
+/// A single role considered while searching for a target.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Config {
-    max_root_length: Option<usize>,
-    max_timestamp_length: Option<usize>,
-    max_snapshot_length: Option<usize>,
-    max_targets_length: Option<usize>,
-    max_delegation_depth: u32,
+pub struct TargetLookupStep {
+    role: MetadataPath,
+    parent: Option<MetadataPath>,
+    depth: u32,
+    terminating: bool,
+    decision: LookupDecision,
 }
 
-impl Config {
-    /// Initialize a `ConfigBuilder` with the default values.
-    pub fn build() -> ConfigBuilder {
-        ConfigBuilder::default()
+impl TargetLookupStep {
+    /// The role that was considered.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
     }
 
-    /// Return the optional maximum root metadata length.
-    pub fn max_root_length(&self) -> &Option<usize> {
-        &self.max_root_length
+    /// The role that delegated to this role, or `None` for the top-level targets role.
+    pub fn parent(&self) -> Option<&MetadataPath> {
+        self.parent.as_ref()
     }
 
-    /// Return the optional maximum timestamp metadata size.
-    pub fn max_timestamp_length(&self) -> &Option<usize> {
-        &self.max_timestamp_length
+    /// How many delegations away from the top-level targets role this role is.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Whether the delegation to this role is terminating.
+    pub fn terminating(&self) -> bool {
+        self.terminating
+    }
+
+    /// Whether the role was followed, and if not, why it was skipped.
+    pub fn decision(&self) -> &LookupDecision {
+        &self.decision
+    }
+}
+
+/// Why a role was followed or skipped while searching for a target.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupDecision {
+    /// The role's metadata was trusted and searched for the target.
+    Followed,
+
+    /// The delegation's paths do not include the target.
+    PathMismatch,
+
+    /// Following the delegation would exceed [Config::max_delegation_depth].
+    DepthLimitExceeded,
+
+    /// The delegated role is not listed in the trusted snapshot metadata.
+    MissingFromSnapshot,
+
+    /// The delegated role's metadata could not be fetched from the remote repository.
+    FetchFailed(String),
+
+    /// The delegated role's metadata could not be verified, such as when it is not signed by a
+    /// threshold of the delegated keys.
+    VerificationFailed(String),
+}
+
+/// A description of how the trusted metadata changed between two [Database] states, such as
+/// before and after a [Client] update.
+///
+/// Targets and delegated roles are compared across the top-level targets role and every delegated
+/// targets role the databases trust. A [Client] update only fetches the top-level targets
+/// metadata, and delegated targets metadata is fetched on demand when looking up a target, so
+/// targets described by delegated roles are only included once their metadata is trusted. A
+/// target described by several roles is compared by the description in the top-level targets
+/// role, or else in the first delegated role by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdateReport {
+    root: VersionChange,
+    timestamp: VersionChange,
+    snapshot: VersionChange,
+    targets: VersionChange,
+    added_delegated_roles: BTreeSet<MetadataPath>,
+    removed_delegated_roles: BTreeSet<MetadataPath>,
+    added_targets: BTreeMap<TargetPath, TargetDescription>,
+    removed_targets: BTreeMap<TargetPath, TargetDescription>,
+    modified_targets: BTreeMap<TargetPath, TargetChange>,
+}
+
+impl UpdateReport {
+    /// Compare the trusted metadata in the `previous` and `current` databases.
+    pub fn new<D: Pouf>(previous: &Database<D>, current: &Database<D>) -> Self {
+        Self::from_states(TrustedState::new(previous), TrustedState::new(current))
+    }
+
+    fn from_states(previous: TrustedState, current: TrustedState) -> Self {
+        let mut added_targets = BTreeMap::new();
+        let mut modified_targets = BTreeMap::new();
+        let mut removed_targets = previous.targets;
+        for (path, current) in current.targets {
+            match removed_targets.remove(&path) {
+                None => {
+                    added_targets.insert(path, current);
+                }
+                Some(previous) if previous != current => {
+                    modified_targets.insert(path, TargetChange { previous, current });
+                }
+                Some(_) => {}
+            }
+        }
+
+        UpdateReport {
+            root: VersionChange::new(Some(previous.root_version), Some(current.root_version)),
+            timestamp: VersionChange::new(previous.timestamp_version, current.timestamp_version),
+            snapshot: VersionChange::new(previous.snapshot_version, current.snapshot_version),
+            targets: VersionChange::new(previous.targets_version, current.targets_version),
+            added_delegated_roles: current
+                .delegated_roles
+                .difference(&previous.delegated_roles)
+                .cloned()
+                .collect(),
+            removed_delegated_roles: previous
+                .delegated_roles
+                .difference(&current.delegated_roles)
+                .cloned()
+                .collect(),
+            added_targets,
+            removed_targets,
+            modified_targets,
+        }
+    }
+
+    /// Returns `true` if any of the trusted metadata changed.
+    pub fn has_changes(&self) -> bool {
+        self.root.is_changed()
+            || self.timestamp.is_changed()
+            || self.snapshot.is_changed()
+            || self.targets.is_changed()
+            || !self.added_delegated_roles.is_empty()
+            || !self.removed_delegated_roles.is_empty()
+            || !self.added_targets.is_empty()
+            || !self.removed_targets.is_empty()
+            || !self.modified_targets.is_empty()
+    }
+
+    /// How the trusted root version changed.
+    pub fn root(&self) -> &VersionChange {
+        &self.root
+    }
+
+    /// How the trusted timestamp version changed.
+    pub fn timestamp(&self) -> &VersionChange {
+        &self.timestamp
+    }
+
+    /// How the trusted snapshot version changed.
+    pub fn snapshot(&self) -> &VersionChange {
+        &self.snapshot
+    }
+
+    /// How the trusted top-level targets version changed.
+    pub fn targets(&self) -> &VersionChange {
+        &self.targets
+    }
+
+    /// Roles that are now delegated to by the trusted targets roles.
+    pub fn added_delegated_roles(&self) -> &BTreeSet<MetadataPath> {
+        &self.added_delegated_roles
+    }
+
+    /// Roles that are no longer delegated to by the trusted targets roles.
+    pub fn removed_delegated_roles(&self) -> &BTreeSet<MetadataPath> {
+        &self.removed_delegated_roles
+    }
+
+    /// Targets that are now described by the trusted targets roles.
+    pub fn added_targets(&self) -> &BTreeMap<TargetPath, TargetDescription> {
+        &self.added_targets
+    }
+
+    /// Targets that are no longer described by the trusted targets roles, along with their previous
+    /// description.
+    pub fn removed_targets(&self) -> &BTreeMap<TargetPath, TargetDescription> {
+        &self.removed_targets
+    }
+
+    /// Targets whose description changed.
+    pub fn modified_targets(&self) -> &BTreeMap<TargetPath, TargetChange> {
+        &self.modified_targets
+    }
+}
+
+/// The versions, delegated roles and targets of a [Database] that an [UpdateReport] compares.
+struct TrustedState {
+    root_version: u32,
+    timestamp_version: Option<u32>,
+    snapshot_version: Option<u32>,
+    targets_version: Option<u32>,
+    delegated_roles: BTreeSet<MetadataPath>,
+    targets: BTreeMap<TargetPath, TargetDescription>,
+}
+
+impl TrustedState {
+    fn new<D: Pouf>(tuf: &Database<D>) -> Self {
+        let delegations = tuf.trusted_delegations().iter().collect::<BTreeMap<_, _>>();
+
+        let mut delegated_roles = BTreeSet::new();
+        let mut targets = BTreeMap::new();
+        for metadata in tuf
+            .trusted_targets()
+            .into_iter()
+            .chain(delegations.into_values())
+        {
+            delegated_roles.extend(
+                metadata
+                    .delegations()
+                    .roles()
+                    .iter()
+                    .map(|role| role.name().clone()),
+            );
+            for (path, description) in metadata.targets() {
+                targets
+                    .entry(path.clone())
+                    .or_insert_with(|| description.clone());
+            }
+        }
+
+        TrustedState {
+            root_version: tuf.trusted_root().version(),
+            timestamp_version: tuf.trusted_timestamp().map(|t| t.version()),
+            snapshot_version: tuf.trusted_snapshot().map(|s| s.version()),
+            targets_version: tuf.trusted_targets().map(|t| t.version()),
+            delegated_roles,
+            targets,
+        }
+    }
+}
+
+/// The trusted version of a role before and after an update. A version is `None` if there was no
+/// trusted metadata for the role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VersionChange {
+    previous: Option<u32>,
+    current: Option<u32>,
+}
+
+impl VersionChange {
+    fn new(previous: Option<u32>, current: Option<u32>) -> Self {
+        VersionChange { previous, current }
+    }
+
+    /// The version before the update.
+    pub fn previous(&self) -> Option<u32> {
+        self.previous
+    }
+
+    /// The version after the update.
+    pub fn current(&self) -> Option<u32> {
+        self.current
+    }
+
+    /// Returns `true` if the version changed.
+    pub fn is_changed(&self) -> bool {
+        self.previous != self.current
+    }
+}
+
+/// The description of a target before and after an update.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetChange {
+    previous: TargetDescription,
+    current: TargetDescription,
+}
+
+impl TargetChange {
+    /// The description before the update.
+    pub fn previous(&self) -> &TargetDescription {
+        &self.previous
+    }
+
+    /// The description after the update.
+    pub fn current(&self) -> &TargetDescription {
+        &self.current
+    }
+
+    /// Returns `true` if the target length changed.
+    pub fn length_changed(&self) -> bool {
+        self.previous.length() != self.current.length()
+    }
+
+    /// Returns `true` if the target hashes changed.
+    pub fn hashes_changed(&self) -> bool {
+        self.previous.hashes() != self.current.hashes()
+    }
+
+    /// Returns `true` if the target custom metadata changed.
+    pub fn custom_changed(&self) -> bool {
+        self.previous.custom() != self.current.custom()
+    }
+}
+
+/// Deconstructed parts of a [Client].
+///
+/// This allows taking apart a [Client] in order to reclaim the [Database],
+/// local, and remote repositories.
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct Parts<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// The client configuration.
+    pub config: Config,
+
+    /// The Tuf database, which is updated by the [Client].
+    pub database: Database<D>,
+
+    /// The local repository, which is used to initialize the database, and
+    /// is updated by the [Client].
+    pub local: L,
+
+    /// The remote repository, which is used by the client to update the database.
+    pub remote: R,
+}
+
+/// Helper function that first tries to fetch the metadata from the local store, and if it doesn't
+/// exist or does and fails to parse, try fetching it from the remote store.
+async fn fetch_metadata_from_local_or_else_remote<'a, D, L, R, M>(
+    path: &'a MetadataPath,
+    version: MetadataVersion,
+    max_length: Option<usize>,
+    hashes: Vec<(&'static HashAlgorithm, HashValue)>,
+    local: &'a Repository<L, D>,
+    remote: &'a Repository<R, D>,
+) -> Result<(bool, RawSignedMetadata<D, M>)>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+    M: Metadata + 'static,
+{
+    match local
+        .fetch_metadata(path, version, max_length, hashes.clone())
+        .await
+    {
+        Ok(raw_meta) => Ok((false, raw_meta)),
+        Err(Error::MetadataNotFound { .. }) => {
+            let raw_meta = remote
+                .fetch_metadata(path, version, max_length, hashes)
+                .await?;
+            Ok((true, raw_meta))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Configuration for a TUF `Client`.
+///
+/// # Defaults
+///
+/// The following values are considered reasonably safe defaults, however these values may change
+/// as this crate moves out of beta. If you are concered about them changing, you should use the
+/// `ConfigBuilder` and set your own values.
+///
+/// ```
+/// # use tuf::client::{Config};
+/// let config = Config::default();
+/// assert_eq!(config.max_root_length(), &Some(500 * 1024));
+/// assert_eq!(config.max_timestamp_length(), &Some(16 * 1024));
+/// assert_eq!(config.max_snapshot_length(), &Some(2000000));
+/// assert_eq!(config.max_targets_length(), &Some(5000000));
+/// assert_eq!(config.max_delegation_depth(), 8);
+/// ```
+///
+/// The [SystemClock] is used by default to determine if metadata has expired.
+#[derive(Clone, Debug)]
+pub struct Config {
+    max_root_length: Option<usize>,
+    max_timestamp_length: Option<usize>,
+    max_snapshot_length: Option<usize>,
+    max_targets_length: Option<usize>,
+    max_delegation_depth: u32,
+    clock: Arc<dyn Clock>,
+    expiration_grace_policy: ExpirationGracePolicy,
+    verifier: Verifier,
+}
+
+impl Config {
+    /// Initialize a `ConfigBuilder` with the default values.
+    pub fn build() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Return the optional maximum root metadata length.
+    pub fn max_root_length(&self) -> &Option<usize> {
+        &self.max_root_length
+    }
+
+    /// Return the optional maximum timestamp metadata size.
+    pub fn max_timestamp_length(&self) -> &Option<usize> {
+        &self.max_timestamp_length
     }
 
     /// Return the optional maximum snapshot metadata size.
@@ -1236,6 +1822,27 @@ impl Config {
     pub fn max_delegation_depth(&self) -> u32 {
         self.max_delegation_depth
     }
+
+    /// The [Clock] used to determine if metadata has expired.
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// The policy for using expired metadata.
+    pub fn expiration_grace_policy(&self) -> &ExpirationGracePolicy {
+        &self.expiration_grace_policy
+    }
+
+    /// The [Verifier] used to check metadata signatures.
+    pub fn verifier(&self) -> &Verifier {
+        &self.verifier
+    }
+
+    /// The [Verifier] for a new database, which checks keyless signing certificates against the
+    /// configured [Clock].
+    fn database_verifier(&self) -> Verifier {
+        self.verifier.clone().clock(Arc::clone(&self.clock))
+    }
 }
 
 impl Default for Config {
@@ -1246,10 +1853,29 @@ impl Default for Config {
             max_snapshot_length: Some(2000000),
             max_targets_length: Some(5000000),
             max_delegation_depth: 8,
+            clock: Arc::new(SystemClock),
+            expiration_grace_policy: ExpirationGracePolicy::default(),
+            verifier: Verifier::default(),
         }
     }
 }
 
+// Clocks can't be compared, so configs are only equal if they share the same clock.
+impl PartialEq for Config {
+    fn eq(&self, other: &Self) -> bool {
+        self.max_root_length == other.max_root_length
+            && self.max_timestamp_length == other.max_timestamp_length
+            && self.max_snapshot_length == other.max_snapshot_length
+            && self.max_targets_length == other.max_targets_length
+            && self.max_delegation_depth == other.max_delegation_depth
+            && self.expiration_grace_policy == other.expiration_grace_policy
+            && self.verifier == other.verifier
+            && Arc::as_ptr(&self.clock) as *const () == Arc::as_ptr(&other.clock) as *const ()
+    }
+}
+
+impl Eq for Config {}
+
 /// Helper for building and validating a TUF client `Config`.
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct ConfigBuilder {
@@ -1291,26 +1917,48 @@ impl ConfigBuilder {
         self.cfg.max_delegation_depth = max;
         self
     }
+
+    /// Set the [Clock] used to determine if metadata has expired.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cfg.clock = clock;
+        self
+    }
+
+    /// Set the policy for using expired metadata. By default, expired metadata is never used.
+    pub fn expiration_grace_policy(mut self, policy: ExpirationGracePolicy) -> Self {
+        self.cfg.expiration_grace_policy = policy;
+        self
+    }
+
+    /// Set the [Verifier] used to check metadata signatures, such as to cache verified signatures
+    /// between updates.
+    pub fn verifier(mut self, verifier: Verifier) -> Self {
+        self.cfg.verifier = verifier;
+        self
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::clock::{FixedClock, MonotonicClock};
     use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
     use crate::metadata::{
-        MetadataDescription, MetadataPath, MetadataVersion, RootMetadataBuilder,
-        SnapshotMetadataBuilder, TargetsMetadataBuilder, TimestampMetadataBuilder,
+        Delegation, Delegations, MetadataDescription, MetadataPath, MetadataVersion,
+        RootMetadataBuilder, SnapshotMetadataBuilder, TargetsMetadataBuilder,
+        TimestampMetadataBuilder,
     };
     use crate::pouf::Pouf1;
     use crate::repo_builder::RepoBuilder;
     use crate::repository::{
         fetch_metadata_to_string, EphemeralRepository, ErrorRepository, Track, TrackRepository,
     };
+    use crate::revocation::KeyRevocation;
     use assert_matches::assert_matches;
     use chrono::prelude::*;
     use futures_executor::block_on;
     use lazy_static::lazy_static;
-    use maplit::hashmap;
+    use maplit::{btreemap, btreeset, hashmap};
     use pretty_assertions::assert_eq;
     use serde_json::json;
     use std::collections::HashMap;
@@ -1563,6 +2211,7 @@ mod test {
                 Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
                 Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
                 Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.snapshot().unwrap()),
+                Track::FetchErr(RevocationList::path(), MetadataVersion::None),
                 Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.targets().unwrap()),
             ],
         );
@@ -1675,6 +2324,7 @@ mod test {
                         MetadataVersion::Number(2),
                         metadata2.snapshot().unwrap()
                     ),
+                    Track::FetchErr(RevocationList::path(), MetadataVersion::None),
                     Track::fetch_meta_found(
                         MetadataVersion::Number(2),
                         metadata2.targets().unwrap()
@@ -1938,6 +2588,7 @@ mod test {
                 Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
                 Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
                 Track::fetch_meta_found(snapshot_version, metadata1.snapshot().unwrap()),
+                Track::FetchErr(RevocationList::path(), MetadataVersion::None),
                 Track::fetch_meta_found(targets_version, metadata1.targets().unwrap()),
             ]
         );
@@ -2020,6 +2671,7 @@ mod test {
                 Track::FetchErr(root_path.clone(), MetadataVersion::Number(4)),
                 Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
                 Track::fetch_meta_found(snapshot_version, metadata1.snapshot().unwrap()),
+                Track::FetchErr(RevocationList::path(), MetadataVersion::None),
                 Track::fetch_meta_found(targets_version, metadata1.targets().unwrap()),
             ]
         );
@@ -2421,4 +3073,884 @@ mod test {
             assert_matches!(client.update().await, Ok(true));
         })
     }
+
+    /// Build a remote repository whose top-level targets role delegates to `delegations`. Each
+    /// `(role, key, builder)` entry in `delegated` is signed by `key` and published to the remote
+    /// repository and listed in the snapshot.
+    async fn delegated_remote(
+        delegations: Vec<Delegation>,
+        delegated: Vec<(&'static str, &Ed25519PrivateKey, TargetsMetadataBuilder)>,
+    ) -> (
+        RawSignedMetadata<Pouf1, RootMetadata>,
+        EphemeralRepository<Pouf1>,
+    ) {
+        let mut remote = EphemeralRepository::<Pouf1>::new();
+
+        let mut snapshot_meta = vec![];
+        for (role, key, builder) in delegated {
+            let path = MetadataPath::new(role).unwrap();
+            let raw = builder.signed::<Pouf1>(key).unwrap().to_raw().unwrap();
+
+            remote
+                .store_metadata(&path, MetadataVersion::Number(1), &mut raw.as_bytes())
+                .await
+                .unwrap();
+
+            let description =
+                MetadataDescription::from_slice(raw.as_bytes(), 1, &[HashAlgorithm::Sha256])
+                    .unwrap();
+            snapshot_meta.push((path, description));
+        }
+
+        let mut builder = RepoBuilder::create(&mut remote)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root()
+            .unwrap()
+            .add_delegation_key(KEYS[1].public().clone());
+        for delegation in delegations {
+            builder = builder.add_delegation_role(delegation);
+        }
+
+        let metadata = builder
+            .stage_targets()
+            .unwrap()
+            .stage_snapshot_with_builder(|mut bld| {
+                for (path, description) in snapshot_meta {
+                    bld = bld.insert_metadata_description(path, description);
+                }
+                bld
+            })
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        (metadata.root().unwrap().clone(), remote)
+    }
+
+    fn delegation(role: &'static str, terminating: bool, path: &'static str) -> Delegation {
+        Delegation::new(
+            MetadataPath::new(role).unwrap(),
+            terminating,
+            1,
+            once(KEYS[1].public().key_id().clone()).collect(),
+            once(TargetPath::new(path).unwrap()).collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn explain_target_description_records_skipped_and_followed_roles() {
+        block_on(async {
+            let target = TargetPath::new("foo/bar").unwrap();
+            let description =
+                TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+
+            let (root, remote) = delegated_remote(
+                vec![
+                    delegation("a-unrelated", false, "baz/"),
+                    delegation("b-bad-signature", false, "foo/"),
+                    delegation("c-missing", false, "foo/"),
+                    delegation("d-good", false, "foo/"),
+                ],
+                vec![
+                    ("b-bad-signature", &KEYS[2], TargetsMetadataBuilder::new()),
+                    (
+                        "d-good",
+                        &KEYS[1],
+                        TargetsMetadataBuilder::new()
+                            .insert_target_description(target.clone(), description.clone()),
+                    ),
+                ],
+            )
+            .await;
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            // The delegated description is found.
+            assert_eq!(
+                client.fetch_target_description(&target).await.unwrap(),
+                description
+            );
+
+            let trace = client.explain_target_description(&target).await.unwrap();
+            assert_eq!(trace.target(), &target);
+            assert_eq!(
+                trace.resolved_by(),
+                Some(&MetadataPath::new("d-good").unwrap())
+            );
+            assert_eq!(trace.terminated_by(), None);
+            assert_eq!(trace.description(), Some(&description));
+
+            let steps = trace
+                .steps()
+                .iter()
+                .map(|step| {
+                    (
+                        step.role().to_string(),
+                        step.parent().map(ToString::to_string),
+                        step.depth(),
+                        step.decision(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let steps = steps
+                .iter()
+                .map(|(role, parent, depth, decision)| {
+                    (role.as_str(), parent.as_deref(), *depth, *decision)
+                })
+                .collect::<Vec<_>>();
+
+            assert_matches!(
+                &steps[..],
+                [
+                    ("targets", None, 0, LookupDecision::Followed),
+                    (
+                        "a-unrelated",
+                        Some("targets"),
+                        1,
+                        LookupDecision::PathMismatch
+                    ),
+                    (
+                        "b-bad-signature",
+                        Some("targets"),
+                        1,
+                        LookupDecision::VerificationFailed(_)
+                    ),
+                    (
+                        "c-missing",
+                        Some("targets"),
+                        1,
+                        LookupDecision::MissingFromSnapshot
+                    ),
+                    ("d-good", Some("targets"), 1, LookupDecision::Followed),
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn explain_target_description_records_terminating_delegation() {
+        block_on(async {
+            let target = TargetPath::new("foo/bar").unwrap();
+            let description =
+                TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+
+            let (root, remote) = delegated_remote(
+                vec![
+                    delegation("a-terminating", true, "foo/"),
+                    delegation("d-good", false, "foo/"),
+                ],
+                vec![
+                    ("a-terminating", &KEYS[1], TargetsMetadataBuilder::new()),
+                    (
+                        "d-good",
+                        &KEYS[1],
+                        TargetsMetadataBuilder::new()
+                            .insert_target_description(target.clone(), description),
+                    ),
+                ],
+            )
+            .await;
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            assert_matches!(
+                client.fetch_target_description(&target).await,
+                Err(Error::TargetNotFound(path)) if path == target
+            );
+
+            let trace = client.explain_target_description(&target).await.unwrap();
+            assert_eq!(trace.resolved_by(), None);
+            assert_eq!(trace.description(), None);
+            assert_eq!(
+                trace.terminated_by(),
+                Some(&MetadataPath::new("a-terminating").unwrap())
+            );
+
+            // The search ends before "d-good" is considered.
+            let roles = trace
+                .steps()
+                .iter()
+                .map(|step| {
+                    (
+                        step.role().to_string(),
+                        step.terminating(),
+                        step.decision().clone(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                roles,
+                vec![
+                    ("targets".to_string(), false, LookupDecision::Followed),
+                    ("a-terminating".to_string(), true, LookupDecision::Followed),
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn explain_target_description_records_depth_limit() {
+        block_on(async {
+            let target = TargetPath::new("foo/bar").unwrap();
+            let description =
+                TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+
+            let (root, remote) = delegated_remote(
+                vec![delegation("d-good", false, "foo/")],
+                vec![(
+                    "d-good",
+                    &KEYS[1],
+                    TargetsMetadataBuilder::new()
+                        .insert_target_description(target.clone(), description),
+                )],
+            )
+            .await;
+
+            let config = Config::build().max_delegation_depth(0).finish().unwrap();
+            let mut client =
+                Client::with_trusted_root(config, &root, EphemeralRepository::new(), remote)
+                    .await
+                    .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            // The delegated role is not fetched once the depth limit is reached.
+            assert_matches!(
+                client.fetch_target_description(&target).await,
+                Err(Error::TargetNotFound(path)) if path == target
+            );
+            assert!(client.database().trusted_delegations().is_empty());
+
+            let trace = client.explain_target_description(&target).await.unwrap();
+            assert_eq!(trace.resolved_by(), None);
+            assert_eq!(
+                trace
+                    .steps()
+                    .last()
+                    .map(|step| (step.role().to_string(), step.decision())),
+                Some(("d-good".to_string(), &LookupDecision::DepthLimitExceeded))
+            );
+        })
+    }
+
+    #[test]
+    fn fetch_target_description_stops_at_first_delegated_match() {
+        block_on(async {
+            let target = TargetPath::new("foo/bar").unwrap();
+            let first = TargetDescription::from_slice(b"first", &[HashAlgorithm::Sha256]).unwrap();
+            let second =
+                TargetDescription::from_slice(b"second", &[HashAlgorithm::Sha256]).unwrap();
+
+            let (root, remote) = delegated_remote(
+                vec![
+                    delegation("a-first", false, "foo/"),
+                    delegation("b-second", false, "foo/"),
+                ],
+                vec![
+                    (
+                        "a-first",
+                        &KEYS[1],
+                        TargetsMetadataBuilder::new()
+                            .insert_target_description(target.clone(), first.clone()),
+                    ),
+                    (
+                        "b-second",
+                        &KEYS[1],
+                        TargetsMetadataBuilder::new()
+                            .insert_target_description(target.clone(), second),
+                    ),
+                ],
+            )
+            .await;
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            // The description from the first matching delegation wins, and the search ends there.
+            assert_eq!(
+                client.fetch_target_description(&target).await.unwrap(),
+                first
+            );
+            assert!(client
+                .database()
+                .trusted_delegations()
+                .get(&MetadataPath::new("b-second").unwrap())
+                .is_none());
+
+            let trace = client.explain_target_description(&target).await.unwrap();
+            assert_eq!(
+                trace.resolved_by(),
+                Some(&MetadataPath::new("a-first").unwrap())
+            );
+            assert_eq!(
+                trace
+                    .steps()
+                    .iter()
+                    .map(|step| step.role().to_string())
+                    .collect::<Vec<_>>(),
+                vec!["targets", "a-first"]
+            );
+        })
+    }
+
+    #[test]
+    fn explain_target_description_err_without_snapshot() {
+        block_on(async {
+            let (root, remote) = delegated_remote(vec![], vec![]).await;
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            assert_matches!(
+                client
+                    .explain_target_description(&TargetPath::new("foo").unwrap())
+                    .await,
+                Err(Error::MetadataNotFound { path, .. }) if path == MetadataPath::snapshot()
+            );
+        })
+    }
+
+    #[test]
+    fn update_with_report_describes_changes() {
+        block_on(async {
+            let foo = TargetPath::new("foo").unwrap();
+            let bar = TargetPath::new("bar").unwrap();
+            let baz = TargetPath::new("baz").unwrap();
+
+            let foo1 = TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+            let foo2 = TargetDescription::from_slice_with_custom(
+                b"foo",
+                &[HashAlgorithm::Sha256],
+                hashmap!("version".to_string() => json!(2)),
+            )
+            .unwrap();
+            let bar1 = TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+            let baz1 = TargetDescription::from_slice(b"baz", &[HashAlgorithm::Sha256]).unwrap();
+
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .stage_targets_with_builder(|bld| {
+                    bld.insert_target_description(foo.clone(), foo1.clone())
+                        .insert_target_description(bar.clone(), bar1.clone())
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            // The first update fetches everything but the root.
+            let report = client.update_with_report().await.unwrap();
+            assert!(report.has_changes());
+            assert_eq!(report.root(), &VersionChange::new(Some(1), Some(1)));
+            assert_eq!(report.timestamp(), &VersionChange::new(None, Some(1)));
+            assert_eq!(report.snapshot(), &VersionChange::new(None, Some(1)));
+            assert_eq!(report.targets(), &VersionChange::new(None, Some(1)));
+            assert_eq!(
+                report.added_targets(),
+                &btreemap! {
+                    foo.clone() => foo1.clone(),
+                    bar.clone() => bar1.clone(),
+                }
+            );
+            assert!(report.removed_targets().is_empty());
+            assert!(report.modified_targets().is_empty());
+            assert!(report.added_delegated_roles().is_empty());
+
+            // Nothing changes if the remote hasn't changed.
+            let report = client.update_with_report().await.unwrap();
+            assert!(!report.has_changes());
+            assert!(report.added_targets().is_empty());
+
+            // Publish new targets metadata that adds, removes, and modifies targets.
+            let db = client.database().clone();
+            RepoBuilder::from_database(client.remote_repo_mut(), &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .skip_root()
+                .inherit_from_trusted_targets(false)
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(delegation("delegated", false, "qux/"))
+                .stage_targets_with_builder(|bld| {
+                    bld.insert_target_description(foo.clone(), foo2.clone())
+                        .insert_target_description(baz.clone(), baz1.clone())
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let report = client.update_with_report().await.unwrap();
+            assert!(report.has_changes());
+            assert!(!report.root().is_changed());
+            assert_eq!(report.timestamp(), &VersionChange::new(Some(1), Some(2)));
+            assert_eq!(report.snapshot(), &VersionChange::new(Some(1), Some(2)));
+            assert_eq!(report.targets(), &VersionChange::new(Some(1), Some(2)));
+            assert_eq!(
+                report.added_delegated_roles(),
+                &btreeset! { MetadataPath::new("delegated").unwrap() }
+            );
+            assert!(report.removed_delegated_roles().is_empty());
+            assert_eq!(report.added_targets(), &btreemap! { baz => baz1 });
+            assert_eq!(report.removed_targets(), &btreemap! { bar => bar1 });
+
+            let change = &report.modified_targets()[&foo];
+            assert_eq!(change.previous(), &foo1);
+            assert_eq!(change.current(), &foo2);
+            assert!(!change.length_changed());
+            assert!(!change.hashes_changed());
+            assert!(change.custom_changed());
+        })
+    }
+
+    #[test]
+    fn update_report_includes_trusted_delegated_roles() {
+        block_on(async {
+            let target = TargetPath::new("foo/bar").unwrap();
+            let description =
+                TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+            let nested = Delegations::new(
+                hashmap! { KEYS[1].public().key_id().clone() => KEYS[1].public().clone() },
+                vec![delegation("nested", false, "foo/bar/")],
+            )
+            .unwrap();
+
+            let (root, remote) = delegated_remote(
+                vec![delegation("delegated", false, "foo/")],
+                vec![(
+                    "delegated",
+                    &KEYS[1],
+                    TargetsMetadataBuilder::new()
+                        .insert_target_description(target.clone(), description.clone())
+                        .delegations(nested),
+                )],
+            )
+            .await;
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            // The delegated role's metadata isn't trusted until a target lookup fetches it.
+            let previous = client.database().clone();
+            assert_eq!(
+                client.fetch_target_description(&target).await.unwrap(),
+                description
+            );
+
+            let report = UpdateReport::new(&previous, client.database());
+            assert!(report.has_changes());
+            assert!(!report.targets().is_changed());
+            assert_eq!(
+                report.added_delegated_roles(),
+                &btreeset! { MetadataPath::new("nested").unwrap() }
+            );
+            assert_eq!(report.added_targets(), &btreemap! { target => description });
+            assert!(report.removed_targets().is_empty());
+        })
+    }
+
+    #[test]
+    fn update_uses_configured_clock() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let now = Utc::now();
+            let fixed = Arc::new(FixedClock::new(now));
+            let clock = Arc::new(MonotonicClock::new(Arc::clone(&fixed)));
+            let config = Config::build().clock(clock.clone()).finish().unwrap();
+
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            // A reading after the metadata has expired fails the update without being trusted.
+            fixed.set(now + chrono::Duration::days(30));
+            assert_matches!(
+                client.update().await,
+                Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+            );
+            assert_eq!(clock.last_trusted_time(), None);
+
+            // Accepting the timestamp metadata records the time.
+            fixed.set(now);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(clock.last_trusted_time(), Some(now));
+
+            // The monotonic clock refuses to go back before the last trusted timestamp.
+            fixed.set(now - chrono::Duration::days(1));
+            assert_matches!(
+                client.update().await,
+                Err(Error::ClockMovedBackwards { .. })
+            );
+
+            fixed.set(now + chrono::Duration::hours(1));
+            assert_matches!(client.update().await, Ok(false));
+        })
+    }
+
+    #[test]
+    fn update_applies_published_revocations() {
+        block_on(async {
+            let remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let key_id = KEYS[1].public().key_id();
+            assert!(!client
+                .database()
+                .is_key_revoked(&MetadataPath::targets(), key_id));
+
+            let revocation = KeyRevocation::new(MetadataPath::targets(), key_id.clone())
+                .sign::<Pouf1>(&KEYS[1])
+                .unwrap();
+            RepoBuilder::from_database(&remote, client.database())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_key_revocation(revocation.clone())
+                .stage_root()
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            assert_matches!(client.update().await, Ok(true));
+            assert!(client
+                .database()
+                .is_key_revoked(&MetadataPath::targets(), key_id));
+            assert_eq!(
+                client.database().published_revocations().revocations(),
+                &[revocation]
+            );
+
+            // The revocations are kept in the local repository, and reloaded from it.
+            let local = client.into_parts().local;
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                local,
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert!(client
+                .database()
+                .is_key_revoked(&MetadataPath::targets(), key_id));
+
+            // Nothing signs the list, so an emptied one doesn't remove the local revocations.
+            remote
+                .store_metadata(
+                    &RevocationList::path(),
+                    MetadataVersion::None,
+                    &mut &*RevocationList::new().to_bytes::<Pouf1>().unwrap(),
+                )
+                .await
+                .unwrap();
+            RepoBuilder::from_database(&remote, client.database())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let local = client.into_parts().local;
+            let client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                local,
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert!(client
+                .database()
+                .is_key_revoked(&MetadataPath::targets(), key_id));
+        })
+    }
+
+    #[test]
+    fn offline_client_can_use_stale_metadata_within_grace_period() {
+        block_on(async {
+            let now = Utc::now();
+            let target = TargetPath::new("foo").unwrap();
+            let description =
+                TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .current_time(now)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .stage_targets_with_builder(|bld| {
+                    bld.insert_target_description(target.clone(), description.clone())
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let clock = Arc::new(FixedClock::new(now));
+            let config = Config::build().clock(clock.clone()).finish().unwrap();
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            let local = client.into_parts().local;
+
+            // Two days later the device restarts while offline, and the timestamp has expired.
+            clock.set(now + chrono::Duration::days(2));
+            let config = Config::build()
+                .clock(clock.clone())
+                .expiration_grace_policy(
+                    ExpirationGracePolicy::default().timestamp(chrono::Duration::days(3)),
+                )
+                .finish()
+                .unwrap();
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata.root().unwrap(),
+                local,
+                EphemeralRepository::new(),
+            )
+            .await
+            .unwrap();
+
+            assert_matches!(
+                client.fetch_target_description(&target).await,
+                Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+            );
+
+            let (stale_description, freshness) = client
+                .fetch_target_description_with_freshness(&target)
+                .await
+                .unwrap();
+            assert_eq!(stale_description, description);
+            assert_matches!(
+                freshness,
+                Freshness::Stale(expired)
+                if expired.keys().collect::<Vec<_>>() == vec![&MetadataPath::timestamp()]
+            );
+        })
+    }
+
+    #[test]
+    fn update_prunes_local_metadata_after_root_key_rotation() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata1 = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata1.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(
+                client.local_repo().list_metadata().await.unwrap(),
+                vec![
+                    (MetadataPath::snapshot(), MetadataVersion::None),
+                    (MetadataPath::targets(), MetadataVersion::None),
+                    (MetadataPath::timestamp(), MetadataVersion::None),
+                ]
+            );
+
+            // Rotate every key, but don't publish any metadata signed by the new keys.
+            let mut parts = client.into_parts();
+            RepoBuilder::create(&mut parts.remote)
+                .signing_root_keys(&[&KEYS[0]])
+                .trusted_root_keys(&[&KEYS[1]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .stage_root_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .skip_targets()
+                .skip_snapshot()
+                .skip_timestamp()
+                .commit()
+                .await
+                .unwrap();
+
+            // The update fails since the timestamp is signed by the old key, but the database no
+            // longer trusts the old timestamp, snapshot, and targets, so they are pruned.
+            let mut client = Client::from_parts(parts);
+            assert_matches!(
+                client.update().await,
+                Err(Error::MetadataMissingSignatures { .. })
+            );
+            assert_eq!(client.tuf.trusted_root().version(), 2);
+            assert_eq!(
+                client.local_repo().list_metadata().await.unwrap(),
+                vec![
+                    (MetadataPath::root(), MetadataVersion::None),
+                    (MetadataPath::root(), MetadataVersion::Number(2)),
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn update_prunes_local_delegations_missing_from_snapshot() {
+        block_on(async {
+            let target = TargetPath::new("foo/bar").unwrap();
+            let description =
+                TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+
+            let (root, remote) = delegated_remote(
+                vec![delegation("a-delegation", false, "foo/")],
+                vec![(
+                    "a-delegation",
+                    &KEYS[1],
+                    TargetsMetadataBuilder::new()
+                        .insert_target_description(target.clone(), description.clone()),
+                )],
+            )
+            .await;
+
+            let local = EphemeralRepository::<Pouf1>::new();
+            let removed = MetadataPath::new("b-removed").unwrap();
+            local
+                .store_metadata(&removed, MetadataVersion::None, &mut &b"stale"[..])
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(Config::default(), &root, local, remote)
+                .await
+                .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(
+                client.fetch_target_description(&target).await.unwrap(),
+                description
+            );
+
+            // The delegation listed in the snapshot was cached, and the stale one was pruned.
+            assert_matches!(client.update().await, Ok(false));
+            assert_eq!(
+                client.local_repo().list_metadata().await.unwrap(),
+                vec![
+                    (
+                        MetadataPath::new("a-delegation").unwrap(),
+                        MetadataVersion::None
+                    ),
+                    (MetadataPath::snapshot(), MetadataVersion::None),
+                    (MetadataPath::targets(), MetadataVersion::None),
+                    (MetadataPath::timestamp(), MetadataVersion::None),
+                ]
+            );
+        })
+    }
 }
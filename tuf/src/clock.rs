@@ -0,0 +1,224 @@
+//! Sources of time used to determine if metadata has expired.
+//!
+//! TUF relies on the client knowing the current time in order to reject expired metadata, which
+//! protects against freeze attacks. By default the system clock is used, but devices without a
+//! reliable real-time clock may want to supply a more trustworthy source of time.
+//!
+//! ```
+//! # use chrono::{offset::Utc, Duration};
+//! # use std::sync::Arc;
+//! # use tuf::clock::{Clock, FixedClock, MonotonicClock};
+//! let start = Utc::now();
+//! let fixed = Arc::new(FixedClock::new(start));
+//! let clock = MonotonicClock::new(Arc::clone(&fixed));
+//!
+//! assert_eq!(clock.now().unwrap(), start);
+//!
+//! // A database records the time whenever it accepts new timestamp metadata.
+//! clock.record_trusted_time(start);
+//!
+//! // Moving the clock back before that time is an error.
+//! fixed.set(start - Duration::seconds(1));
+//! assert!(clock.now().is_err());
+//! ```
+
+use chrono::{offset::Utc, DateTime, Duration};
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+use crate::error::{Error, Result};
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time, or an `Error` if the time cannot be trusted.
+    fn now(&self) -> Result<DateTime<Utc>>;
+
+    /// Called by a [Database](crate::database::Database) when it accepts new timestamp metadata
+    /// that had not expired at `time`, a reading of this clock. Does nothing by default.
+    fn record_trusted_time(&self, _time: DateTime<Utc>) {}
+}
+
+impl<T: Clock + ?Sized> Clock for Arc<T> {
+    fn now(&self) -> Result<DateTime<Utc>> {
+        (**self).now()
+    }
+
+    fn record_trusted_time(&self, time: DateTime<Utc>) {
+        (**self).record_trusted_time(time)
+    }
+}
+
+/// A [Clock] that uses the system time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<DateTime<Utc>> {
+        Ok(Utc::now())
+    }
+}
+
+/// A [Clock] that always reports the time it was last set to. This is mainly useful for testing.
+#[derive(Debug)]
+pub struct FixedClock {
+    now: RwLock<DateTime<Utc>>,
+}
+
+impl FixedClock {
+    /// Create a new [FixedClock] that reports `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: RwLock::new(now),
+        }
+    }
+
+    /// Change the time reported by this clock.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.write().unwrap() = now;
+    }
+
+    /// Move the time reported by this clock by `duration`, which may be negative.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.write().unwrap();
+        *now += duration;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> Result<DateTime<Utc>> {
+        Ok(*self.now.read().unwrap())
+    }
+}
+
+/// A [Clock] that refuses to let time move backwards past the last trusted timestamp.
+///
+/// The last trusted time is the latest time at which a [Database](crate::database::Database)
+/// using this clock accepted new timestamp metadata, so it can never be later than a time the
+/// timestamp role vouched for by signing metadata that expires after it. Any reading from the
+/// wrapped clock that is earlier results in [Error::ClockMovedBackwards]. Other readings are not
+/// remembered, so a wrapped clock that briefly runs ahead can't lock out correct times.
+/// Applications can persist [MonotonicClock::last_trusted_time] and restore it with
+/// [MonotonicClock::with_last_trusted_time] to keep this guarantee across restarts.
+#[derive(Debug)]
+pub struct MonotonicClock<C> {
+    inner: C,
+    last_trusted_time: RwLock<Option<DateTime<Utc>>>,
+}
+
+impl<C: Clock> MonotonicClock<C> {
+    /// Create a new [MonotonicClock] that wraps `inner`.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            last_trusted_time: RwLock::new(None),
+        }
+    }
+
+    /// Create a new [MonotonicClock] that wraps `inner`, and which will reject any time earlier
+    /// than `last_trusted_time`.
+    pub fn with_last_trusted_time(inner: C, last_trusted_time: DateTime<Utc>) -> Self {
+        Self {
+            inner,
+            last_trusted_time: RwLock::new(Some(last_trusted_time)),
+        }
+    }
+
+    /// The latest time at which timestamp metadata was accepted, if any.
+    pub fn last_trusted_time(&self) -> Option<DateTime<Utc>> {
+        *self.last_trusted_time.read().unwrap()
+    }
+}
+
+impl<C: Clock> Clock for MonotonicClock<C> {
+    fn now(&self) -> Result<DateTime<Utc>> {
+        let now = self.inner.now()?;
+
+        match self.last_trusted_time() {
+            Some(last_trusted_time) if now < last_trusted_time => Err(Error::ClockMovedBackwards {
+                last_trusted_time,
+                now,
+            }),
+            _ => Ok(now),
+        }
+    }
+
+    fn record_trusted_time(&self, time: DateTime<Utc>) {
+        let mut last_trusted_time = self.last_trusted_time.write().unwrap();
+        if last_trusted_time.map_or(true, |last| last < time) {
+            *last_trusted_time = Some(time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn fixed_clock_can_be_moved() {
+        let start = Utc::now();
+        let clock = FixedClock::new(start);
+        assert_eq!(clock.now().unwrap(), start);
+
+        clock.advance(Duration::days(1));
+        assert_eq!(clock.now().unwrap(), start + Duration::days(1));
+
+        clock.set(start);
+        assert_eq!(clock.now().unwrap(), start);
+    }
+
+    #[test]
+    fn monotonic_clock_rejects_time_moving_backwards() {
+        let start = Utc::now();
+        let fixed = Arc::new(FixedClock::new(start));
+        let clock = MonotonicClock::new(Arc::clone(&fixed));
+        assert_eq!(clock.last_trusted_time(), None);
+
+        // Readings alone aren't trusted.
+        assert_eq!(clock.now().unwrap(), start);
+        assert_eq!(clock.last_trusted_time(), None);
+
+        clock.record_trusted_time(start);
+        assert_eq!(clock.last_trusted_time(), Some(start));
+
+        // Time standing still is fine.
+        assert_eq!(clock.now().unwrap(), start);
+
+        fixed.advance(Duration::seconds(-1));
+        assert_matches!(
+            clock.now(),
+            Err(Error::ClockMovedBackwards { last_trusted_time, now })
+            if last_trusted_time == start && now == start - Duration::seconds(1)
+        );
+
+        // A rejected time doesn't change the last trusted time.
+        assert_eq!(clock.last_trusted_time(), Some(start));
+
+        fixed.advance(Duration::seconds(2));
+        assert_eq!(clock.now().unwrap(), start + Duration::seconds(1));
+        clock.record_trusted_time(start + Duration::seconds(1));
+        assert_eq!(
+            clock.last_trusted_time(),
+            Some(start + Duration::seconds(1))
+        );
+
+        // Recording an earlier time doesn't move the last trusted time back.
+        clock.record_trusted_time(start);
+        assert_eq!(
+            clock.last_trusted_time(),
+            Some(start + Duration::seconds(1))
+        );
+    }
+
+    #[test]
+    fn monotonic_clock_with_last_trusted_time() {
+        let start = Utc::now();
+        let clock = MonotonicClock::with_last_trusted_time(
+            FixedClock::new(start),
+            start + Duration::days(1),
+        );
+
+        assert_matches!(clock.now(), Err(Error::ClockMovedBackwards { .. }));
+    }
+}
@@ -0,0 +1,653 @@
+//! Keyless signing with Sigstore, as described in [TAP 18].
+//!
+//! A `sigstore-oidc` key names a signer by their OIDC identity and issuer instead of by a public
+//! key. Each signature carries a bundle holding a short-lived certificate for that identity,
+//! issued by a Sigstore certificate authority such as Fulcio, which is checked against locally
+//! configured [KeylessTrustRoots].
+//!
+//! Transparency log inclusion proofs are not checked, so the leaf certificate must be valid at the
+//! [verification time](KeylessTrustRoots::verification_time), or else at the current time of the
+//! [Verifier](crate::verify::Verifier)'s clock.
+//!
+//! Verifying certificates requires the `keyless` feature. Without it, `sigstore-oidc` keys and
+//! their bundles can still be parsed and written, but every keyless signature is rejected.
+//!
+//! [TAP 18]: https://github.com/theupdateframework/taps/blob/master/tap18.md
+
+use {
+    data_encoding::BASE64,
+    serde::{
+        de::{Deserialize, Deserializer, Error as DeserializeError},
+        ser::{Serialize, Serializer},
+    },
+    serde_derive::{Deserialize, Serialize},
+};
+
+#[cfg(feature = "keyless")]
+use {
+    super::{
+        pem,
+        x509::{Certificate, OID_CODE_SIGNING, SIGNATURE_ALGORITHMS},
+    },
+    crate::{
+        clock::Clock,
+        error::{Error, Result},
+    },
+    chrono::{DateTime, Utc},
+    rustls_pki_types::{CertificateDer, UnixTime},
+    std::time::Duration,
+    webpki::{EndEntityCert, KeyUsage},
+};
+
+/// The media type of the bundles written by [SignatureBundle::new].
+const BUNDLE_MEDIA_TYPE: &str = "application/vnd.dev.sigstore.bundle+json;version=0.1";
+
+/// The OIDC identity that must have signed with a `sigstore-oidc` key.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeylessIdentity {
+    identity: String,
+    issuer: String,
+}
+
+impl KeylessIdentity {
+    /// Create an identity. `identity` is matched against the email addresses and URIs in the
+    /// signing certificate's subject alternative names, and `issuer` against the OIDC issuer
+    /// recorded in the certificate, such as `https://accounts.google.com`.
+    pub fn new(identity: impl Into<String>, issuer: impl Into<String>) -> Self {
+        KeylessIdentity {
+            identity: identity.into(),
+            issuer: issuer.into(),
+        }
+    }
+
+    /// The signer's email address or URI.
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// The OIDC issuer that authenticated the signer.
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+}
+
+/// The certificate authorities trusted to issue certificates for keyless signers.
+///
+/// ```
+/// # use tuf::crypto::KeylessTrustRoots;
+/// let root = include_str!("../../tests/sigstore/root.pem");
+/// let roots = KeylessTrustRoots::new().root_pem(root).unwrap();
+/// ```
+#[cfg(feature = "keyless")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeylessTrustRoots {
+    roots: Vec<Certificate>,
+    verification_time: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "keyless")]
+impl KeylessTrustRoots {
+    /// Create an empty set of trust roots, which trusts no certificates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the DER encoded CA certificate `der`.
+    pub fn root_der(mut self, der: &[u8]) -> Result<Self> {
+        let root = Certificate::from_der(der)?;
+        if !root.is_ca() {
+            return Err(Error::IllegalArgument(
+                "trust roots must be CA certificates".into(),
+            ));
+        }
+        self.roots.push(root);
+        Ok(self)
+    }
+
+    /// Trust the PEM encoded CA certificate `pem`, which is labeled `CERTIFICATE`.
+    pub fn root_pem(self, pem: &str) -> Result<Self> {
+        self.root_der(&pem::decode(pem::CERTIFICATE, pem)?)
+    }
+
+    /// Check certificates are valid at `time` instead of the current time. Since Sigstore leaf
+    /// certificates only live for a few minutes, this is usually the time the metadata was signed.
+    pub fn verification_time(mut self, time: DateTime<Utc>) -> Self {
+        self.verification_time = Some(time);
+        self
+    }
+
+    /// The number of trusted certificates.
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Whether no certificates are trusted.
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+/// The subset of a [Sigstore bundle] that TUF keyless signatures carry: the signing certificate
+/// and its chain, and the signature of the canonical metadata.
+///
+/// [Sigstore bundle]: https://github.com/sigstore/protobuf-specs
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SignatureBundle {
+    media_type: String,
+    certificates: Vec<Vec<u8>>,
+    signature: Vec<u8>,
+}
+
+impl SignatureBundle {
+    /// Create a bundle from the DER encoded `certificates`, leaf first, and the leaf key's
+    /// `signature`.
+    pub fn new(certificates: Vec<Vec<u8>>, signature: Vec<u8>) -> Self {
+        SignatureBundle {
+            media_type: BUNDLE_MEDIA_TYPE.into(),
+            certificates,
+            signature,
+        }
+    }
+
+    /// The bundle's media type, which includes the bundle format version.
+    pub fn media_type(&self) -> &str {
+        &self.media_type
+    }
+
+    /// The DER encoded certificates, leaf first.
+    pub fn certificates(&self) -> &[Vec<u8>] {
+        &self.certificates
+    }
+
+    /// The signature made with the leaf certificate's key.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+mod shims {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct Bundle {
+        pub media_type: String,
+        pub verification_material: VerificationMaterial,
+        pub message_signature: MessageSignature,
+    }
+
+    /// Bundles before v0.3 hold a chain, and later ones only the leaf certificate.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct VerificationMaterial {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub x509_certificate_chain: Option<CertificateChain>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub certificate: Option<RawBytes>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct CertificateChain {
+        pub certificates: Vec<RawBytes>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct RawBytes {
+        #[serde(with = "base64")]
+        pub raw_bytes: Vec<u8>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct MessageSignature {
+        #[serde(with = "base64")]
+        pub signature: Vec<u8>,
+    }
+
+    mod base64 {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            bytes: &[u8],
+            ser: S,
+        ) -> ::std::result::Result<S::Ok, S::Error> {
+            ser.serialize_str(&BASE64.encode(bytes))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            de: D,
+        ) -> ::std::result::Result<Vec<u8>, D::Error> {
+            let string: String = Deserialize::deserialize(de)?;
+            BASE64
+                .decode(string.as_bytes())
+                .map_err(|err| DeserializeError::custom(format!("invalid base64: {}", err)))
+        }
+    }
+}
+
+impl Serialize for SignatureBundle {
+    fn serialize<S>(&self, ser: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let raw_bytes = |der: &Vec<u8>| shims::RawBytes {
+            raw_bytes: der.clone(),
+        };
+        shims::Bundle {
+            media_type: self.media_type.clone(),
+            verification_material: shims::VerificationMaterial {
+                x509_certificate_chain: Some(shims::CertificateChain {
+                    certificates: self.certificates.iter().map(raw_bytes).collect(),
+                }),
+                certificate: None,
+            },
+            message_signature: shims::MessageSignature {
+                signature: self.signature.clone(),
+            },
+        }
+        .serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureBundle {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> ::std::result::Result<Self, D::Error> {
+        let bundle: shims::Bundle = Deserialize::deserialize(de)?;
+        let material = bundle.verification_material;
+
+        let certificates = match (material.x509_certificate_chain, material.certificate) {
+            (Some(chain), None) => chain.certificates,
+            (None, Some(leaf)) => vec![leaf],
+            _ => {
+                return Err(DeserializeError::custom(
+                    "bundle must contain exactly one of a certificate chain or a certificate",
+                ))
+            }
+        };
+        if certificates.is_empty() {
+            return Err(DeserializeError::custom("bundle has no certificates"));
+        }
+
+        Ok(SignatureBundle {
+            media_type: bundle.media_type,
+            certificates: certificates.into_iter().map(|c| c.raw_bytes).collect(),
+            signature: bundle.message_signature.signature,
+        })
+    }
+}
+
+/// Verify `bundle` holds a signature of `msg` by `identity`, with a certificate that chains to one
+/// of the `roots`. Certificates are checked against the `clock` unless the `roots` have a
+/// verification time.
+#[cfg(feature = "keyless")]
+pub(crate) fn verify(
+    roots: &KeylessTrustRoots,
+    identity: &KeylessIdentity,
+    bundle: &SignatureBundle,
+    msg: &[u8],
+    clock: &dyn Clock,
+) -> Result<()> {
+    let time = match roots.verification_time {
+        Some(time) => time,
+        None => clock.now()?,
+    };
+
+    let (leaf, intermediates) = bundle
+        .certificates
+        .split_first()
+        .ok_or_else(|| Error::UntrustedCertificate("bundle has no certificates".into()))?;
+    let leaf = Certificate::from_der(leaf)?;
+
+    if !leaf.is_valid_at(&time) {
+        return Err(Error::UntrustedCertificate(format!(
+            "signing certificate is not valid at {}",
+            time
+        )));
+    }
+    if !leaf.can_sign() {
+        return Err(Error::UntrustedCertificate(
+            "signing certificate is not a code signing certificate".into(),
+        ));
+    }
+
+    verify_chain(roots, &leaf, intermediates, &time)?;
+
+    if !leaf
+        .emails()
+        .iter()
+        .chain(leaf.uris())
+        .any(|name| name == identity.identity())
+    {
+        return Err(Error::UntrustedCertificate(format!(
+            "signing certificate was not issued to {}",
+            identity.identity()
+        )));
+    }
+    if leaf.oidc_issuer() != Some(identity.issuer()) {
+        return Err(Error::UntrustedCertificate(format!(
+            "signing certificate was not issued by {}",
+            identity.issuer()
+        )));
+    }
+
+    leaf.verify_signature(msg, &bundle.signature)
+}
+
+/// Check that `leaf` is a code signing certificate issued through the DER encoded `intermediates`
+/// by one of the roots that is valid at `time`. webpki enforces the basic, name and extended key
+/// usage constraints along the way.
+#[cfg(feature = "keyless")]
+fn verify_chain(
+    roots: &KeylessTrustRoots,
+    leaf: &Certificate,
+    intermediates: &[Vec<u8>],
+    time: &DateTime<Utc>,
+) -> Result<()> {
+    let untrusted = |err: webpki::Error| {
+        Error::UntrustedCertificate(format!("certificate chain is not trusted: {}", err))
+    };
+
+    let roots = roots
+        .roots
+        .iter()
+        .filter(|root| root.is_valid_at(time))
+        .map(Certificate::as_der)
+        .collect::<Vec<_>>();
+    let anchors = roots
+        .iter()
+        .map(webpki::anchor_from_trusted_cert)
+        .collect::<::std::result::Result<Vec<_>, _>>()
+        .map_err(untrusted)?;
+    let intermediates = intermediates
+        .iter()
+        .map(|der| CertificateDer::from(der.as_slice()))
+        .collect::<Vec<_>>();
+    let time = u64::try_from(time.timestamp())
+        .map(|secs| UnixTime::since_unix_epoch(Duration::from_secs(secs)))
+        .map_err(|_| Error::UntrustedCertificate(format!("invalid verification time {}", time)))?;
+
+    let leaf = leaf.as_der();
+    EndEntityCert::try_from(&leaf)
+        .map_err(untrusted)?
+        .verify_for_usage(
+            SIGNATURE_ALGORITHMS,
+            &anchors,
+            &intermediates,
+            time,
+            KeyUsage::required(OID_CODE_SIGNING),
+            None,
+            None,
+        )
+        .map_err(untrusted)?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "keyless"))]
+mod verify_test {
+    use super::*;
+    use crate::clock::{FixedClock, SystemClock};
+    use assert_matches::assert_matches;
+    use chrono::TimeZone as _;
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+
+    const ROOT: &str = include_str!("../../tests/sigstore/root.pem");
+    const OTHER_ROOT: &str = include_str!("../../tests/sigstore/other-root.pem");
+    const INTERMEDIATE: &str = include_str!("../../tests/sigstore/intermediate.pem");
+    const LEAF: &str = include_str!("../../tests/sigstore/leaf.pem");
+    const LEAF_KEY: &[u8] = include_bytes!("../../tests/sigstore/leaf.pk8.der");
+    const LEAF_URI: &str = include_str!("../../tests/sigstore/leaf-uri.pem");
+    const LEAF_URI_KEY: &[u8] = include_bytes!("../../tests/sigstore/leaf-uri.pk8.der");
+    const UNTRUSTED_LEAF: &str = include_str!("../../tests/sigstore/untrusted-leaf.pem");
+    const UNTRUSTED_LEAF_KEY: &[u8] = include_bytes!("../../tests/sigstore/untrusted-leaf.pk8.der");
+
+    const MSG: &[u8] = b"canonical metadata";
+
+    fn der(pem: &str) -> Vec<u8> {
+        pem::decode(pem::CERTIFICATE, pem).unwrap()
+    }
+
+    fn bundle(leaf: &str, key: &[u8], msg: &[u8]) -> SignatureBundle {
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, key).unwrap();
+        let signature = key.sign(&SystemRandom::new(), msg).unwrap();
+        SignatureBundle::new(
+            vec![der(leaf), der(INTERMEDIATE)],
+            signature.as_ref().to_vec(),
+        )
+    }
+
+    fn roots() -> KeylessTrustRoots {
+        KeylessTrustRoots::new().root_pem(ROOT).unwrap()
+    }
+
+    fn signer() -> KeylessIdentity {
+        KeylessIdentity::new("signer@example.com", "https://accounts.example.com")
+    }
+
+    #[test]
+    fn verify_good_signature() {
+        verify(
+            &roots(),
+            &signer(),
+            &bundle(LEAF, LEAF_KEY, MSG),
+            MSG,
+            &SystemClock,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn verify_uri_identity_with_deprecated_issuer() {
+        let identity = KeylessIdentity::new(
+            "https://github.com/example/repo/.github/workflows/release.yml@refs/heads/main",
+            "https://token.actions.githubusercontent.com",
+        );
+        verify(
+            &roots(),
+            &identity,
+            &bundle(LEAF_URI, LEAF_URI_KEY, MSG),
+            MSG,
+            &SystemClock,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reject_bad_signature() {
+        assert_matches!(
+            verify(
+                &roots(),
+                &signer(),
+                &bundle(LEAF, LEAF_KEY, b"other metadata"),
+                MSG,
+                &SystemClock
+            ),
+            Err(Error::UntrustedCertificate(_))
+        );
+    }
+
+    #[test]
+    fn reject_wrong_identity() {
+        let identity = KeylessIdentity::new("mallory@example.com", "https://accounts.example.com");
+        assert_matches!(
+            verify(&roots(), &identity, &bundle(LEAF, LEAF_KEY, MSG), MSG, &SystemClock),
+            Err(Error::UntrustedCertificate(msg)) if msg.contains("mallory@example.com")
+        );
+    }
+
+    #[test]
+    fn reject_wrong_issuer() {
+        let identity = KeylessIdentity::new("signer@example.com", "https://evil.example.com");
+        assert_matches!(
+            verify(&roots(), &identity, &bundle(LEAF, LEAF_KEY, MSG), MSG, &SystemClock),
+            Err(Error::UntrustedCertificate(msg)) if msg.contains("https://evil.example.com")
+        );
+    }
+
+    #[test]
+    fn reject_untrusted_root() {
+        // The leaf's own CA isn't trusted.
+        let other_roots = KeylessTrustRoots::new().root_pem(OTHER_ROOT).unwrap();
+        assert_matches!(
+            verify(
+                &other_roots,
+                &signer(),
+                &bundle(LEAF, LEAF_KEY, MSG),
+                MSG,
+                &SystemClock
+            ),
+            Err(Error::UntrustedCertificate(_))
+        );
+
+        // A leaf from another CA, even when the intermediate is trusted.
+        assert_matches!(
+            verify(
+                &roots(),
+                &signer(),
+                &bundle(UNTRUSTED_LEAF, UNTRUSTED_LEAF_KEY, MSG),
+                MSG,
+                &SystemClock
+            ),
+            Err(Error::UntrustedCertificate(_))
+        );
+
+        // No trust roots at all.
+        assert_matches!(
+            verify(
+                &KeylessTrustRoots::new(),
+                &signer(),
+                &bundle(LEAF, LEAF_KEY, MSG),
+                MSG,
+                &SystemClock
+            ),
+            Err(Error::UntrustedCertificate(_))
+        );
+    }
+
+    #[test]
+    fn reject_leaf_as_root() {
+        assert_matches!(
+            KeylessTrustRoots::new().root_pem(LEAF),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn reject_expired_and_not_yet_valid_certificates() {
+        for time in [
+            Utc.with_ymd_and_hms(2023, 12, 31, 23, 59, 59).unwrap(),
+            Utc.with_ymd_and_hms(2124, 1, 1, 0, 0, 1).unwrap(),
+        ] {
+            assert_matches!(
+                verify(
+                    &roots().verification_time(time),
+                    &signer(),
+                    &bundle(LEAF, LEAF_KEY, MSG),
+                    MSG,
+                    &SystemClock
+                ),
+                Err(Error::UntrustedCertificate(msg)) if msg.contains("not valid at")
+            );
+        }
+
+        verify(
+            &roots().verification_time(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            &signer(),
+            &bundle(LEAF, LEAF_KEY, MSG),
+            MSG,
+            &SystemClock,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn verify_at_clock_time() {
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2124, 1, 1, 0, 0, 1).unwrap());
+        assert_matches!(
+            verify(&roots(), &signer(), &bundle(LEAF, LEAF_KEY, MSG), MSG, &clock),
+            Err(Error::UntrustedCertificate(msg)) if msg.contains("not valid at")
+        );
+
+        clock.set(Utc.with_ymd_and_hms(2050, 1, 1, 0, 0, 0).unwrap());
+        verify(
+            &roots(),
+            &signer(),
+            &bundle(LEAF, LEAF_KEY, MSG),
+            MSG,
+            &clock,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reject_missing_intermediate() {
+        let mut bundle = bundle(LEAF, LEAF_KEY, MSG);
+        bundle.certificates.truncate(1);
+        assert_matches!(
+            verify(&roots(), &signer(), &bundle, MSG, &SystemClock),
+            Err(Error::UntrustedCertificate(_))
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serde_bundle() {
+        let bundle = SignatureBundle::new(vec![vec![1, 2, 3], vec![4, 5]], vec![6, 7, 8]);
+        let encoded = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(
+            encoded,
+            json!({
+                "mediaType": BUNDLE_MEDIA_TYPE,
+                "verificationMaterial": {
+                    "x509CertificateChain": {
+                        "certificates": [
+                            { "rawBytes": "AQID" },
+                            { "rawBytes": "BAU=" },
+                        ],
+                    },
+                },
+                "messageSignature": { "signature": "BgcI" },
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<SignatureBundle>(encoded).unwrap(),
+            bundle
+        );
+    }
+
+    #[test]
+    fn deserialize_v03_bundle() {
+        let bundle: SignatureBundle = serde_json::from_value(json!({
+            "mediaType": "application/vnd.dev.sigstore.bundle.v0.3+json",
+            "verificationMaterial": {
+                "certificate": { "rawBytes": "AQID" },
+                "tlogEntries": [],
+            },
+            "messageSignature": {
+                "messageDigest": { "algorithm": "SHA2_256", "digest": "" },
+                "signature": "BgcI",
+            },
+        }))
+        .unwrap();
+        assert_eq!(
+            bundle.media_type(),
+            "application/vnd.dev.sigstore.bundle.v0.3+json"
+        );
+        assert_eq!(bundle.certificates(), &[vec![1, 2, 3]]);
+        assert_eq!(bundle.signature(), &[6, 7, 8]);
+
+        assert!(serde_json::from_value::<SignatureBundle>(json!({
+            "mediaType": BUNDLE_MEDIA_TYPE,
+            "verificationMaterial": {},
+            "messageSignature": { "signature": "BgcI" },
+        }))
+        .is_err());
+    }
+}
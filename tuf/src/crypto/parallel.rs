@@ -0,0 +1,393 @@
+//! Hashing with several hash algorithms at once.
+//!
+//! Large inputs are hashed by one worker thread per hash algorithm. Readers are read in large
+//! buffers which are handed to every worker, so reading the next buffer overlaps with hashing the
+//! previous ones. Small inputs, inputs hashed with a single algorithm, and everything on machines
+//! with a single CPU are hashed on the calling thread.
+
+use {
+    super::{DigestContext, HashAlgorithm, HashValue},
+    crate::error::{Error, Result},
+    futures_channel::{mpsc, oneshot},
+    futures_io::AsyncRead,
+    futures_util::{future::poll_fn, AsyncReadExt as _, StreamExt as _},
+    std::{collections::HashMap, fs::File, io, path::Path, sync::Arc, thread},
+};
+
+/// Readers are read in buffers of this size, and inputs at least this large are hashed in parallel.
+pub(crate) const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// The number of buffers queued for each worker before the reader waits for it to catch up.
+const QUEUE_DEPTH: usize = 4;
+
+fn digest_contexts(hash_algs: &[HashAlgorithm]) -> Result<Vec<(HashAlgorithm, DigestContext)>> {
+    hash_algs
+        .iter()
+        .map(|alg| Ok((alg.clone(), alg.digest_context()?)))
+        .collect()
+}
+
+fn finish(contexts: Vec<(HashAlgorithm, DigestContext)>) -> HashMap<HashAlgorithm, HashValue> {
+    contexts
+        .into_iter()
+        .map(|(alg, context)| (alg, HashValue::new(context.finish())))
+        .collect()
+}
+
+/// Whether to hash with `algs` algorithms on worker threads.
+fn use_threads(algs: usize) -> bool {
+    algs > 1 && thread::available_parallelism().map_or(false, |n| n.get() > 1)
+}
+
+fn worker_exited() -> Error {
+    Error::Opaque("hash worker thread exited unexpectedly".into())
+}
+
+/// Hash `buf` with every algorithm in `hash_algs`.
+pub(crate) fn hash_slice(
+    buf: &[u8],
+    hash_algs: &[HashAlgorithm],
+) -> Result<HashMap<HashAlgorithm, HashValue>> {
+    hash_slice_with_threads(buf, hash_algs, use_threads(hash_algs.len()))
+}
+
+fn hash_slice_with_threads(
+    buf: &[u8],
+    hash_algs: &[HashAlgorithm],
+    threads: bool,
+) -> Result<HashMap<HashAlgorithm, HashValue>> {
+    let mut contexts = digest_contexts(hash_algs)?;
+
+    if !threads || buf.len() < BUFFER_SIZE {
+        for (_, context) in &mut contexts {
+            context.update(buf);
+        }
+        return Ok(finish(contexts));
+    }
+
+    thread::scope(|scope| {
+        let handles = contexts
+            .into_iter()
+            .map(|(alg, mut context)| {
+                let handle = scope.spawn(move || {
+                    context.update(buf);
+                    context.finish()
+                });
+                (alg, handle)
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|(alg, handle)| {
+                let value = handle.join().map_err(|_| worker_exited())?;
+                Ok((alg, HashValue::new(value)))
+            })
+            .collect()
+    })
+}
+
+/// Read `read` to the end, returning its length and its hash with every algorithm in
+/// `hash_algs`.
+pub(crate) async fn hash_reader<R>(
+    read: R,
+    hash_algs: &[HashAlgorithm],
+) -> Result<(u64, HashMap<HashAlgorithm, HashValue>)>
+where
+    R: AsyncRead + Unpin,
+{
+    hash_reader_with_threads(read, hash_algs, use_threads(hash_algs.len())).await
+}
+
+async fn hash_reader_with_threads<R>(
+    mut read: R,
+    hash_algs: &[HashAlgorithm],
+    threads: bool,
+) -> Result<(u64, HashMap<HashAlgorithm, HashValue>)>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut contexts = digest_contexts(hash_algs)?;
+    let mut buf = vec![0; BUFFER_SIZE];
+    let mut size = 0;
+
+    // Only start workers if the input doesn't fit in a single buffer.
+    let read_bytes = read_full(&mut read, &mut buf).await?;
+    size += read_bytes as u64;
+
+    if !threads || read_bytes < BUFFER_SIZE {
+        let mut read_bytes = read_bytes;
+        loop {
+            for (_, context) in &mut contexts {
+                context.update(&buf[..read_bytes]);
+            }
+
+            if read_bytes < BUFFER_SIZE {
+                return Ok((size, finish(contexts)));
+            }
+
+            read_bytes = read_full(&mut read, &mut buf).await?;
+            size += read_bytes as u64;
+        }
+    }
+
+    let mut workers = contexts
+        .into_iter()
+        .map(|(alg, context)| Worker::spawn(alg, context))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut chunk = Arc::new(buf);
+    loop {
+        for worker in &mut workers {
+            worker.send(Arc::clone(&chunk)).await?;
+        }
+
+        let mut buf = vec![0; BUFFER_SIZE];
+        let read_bytes = read_full(&mut read, &mut buf).await?;
+        if read_bytes == 0 {
+            break;
+        }
+
+        size += read_bytes as u64;
+        buf.truncate(read_bytes);
+        chunk = Arc::new(buf);
+    }
+
+    let mut hashes = HashMap::with_capacity(workers.len());
+    for worker in workers {
+        let (alg, value) = worker.finish().await?;
+        hashes.insert(alg, value);
+    }
+
+    Ok((size, hashes))
+}
+
+/// Hash the file at `path` with every algorithm in `hash_algs`, returning its length and hashes.
+pub(crate) fn hash_file(
+    path: &Path,
+    hash_algs: &[HashAlgorithm],
+) -> Result<(u64, HashMap<HashAlgorithm, HashValue>)> {
+    let file = File::open(path).map_err(|err| Error::IoPath {
+        path: path.to_path_buf(),
+        err,
+    })?;
+    hash_open_file(&file, path, hash_algs)
+}
+
+/// Hash `file`, which was opened from `path`, with every algorithm in `hash_algs`, returning its
+/// length and hashes.
+///
+/// The file is memory mapped, and read through [hash_reader] from its current position if it is
+/// empty or cannot be mapped.
+pub(crate) fn hash_open_file(
+    file: &File,
+    path: &Path,
+    hash_algs: &[HashAlgorithm],
+) -> Result<(u64, HashMap<HashAlgorithm, HashValue>)> {
+    let len = file
+        .metadata()
+        .map_err(|err| Error::IoPath {
+            path: path.to_path_buf(),
+            err,
+        })?
+        .len();
+
+    if len > 0 {
+        // SAFETY: the mapping is only read while hashing. Callers of the public functions that
+        // hash files are required not to modify the file while it is hashed, since the mapped
+        // bytes would change underneath the hash, or reading them would fault if it were
+        // truncated.
+        if let Ok(map) = unsafe { memmap2::Mmap::map(file) } {
+            return Ok((map.len() as u64, hash_slice(&map, hash_algs)?));
+        }
+    }
+
+    futures_executor::block_on(hash_reader(
+        futures_util::io::AllowStdIo::new(file),
+        hash_algs,
+    ))
+}
+
+/// Fill `buf` from `read`, returning fewer bytes than `buf.len()` only at the end of the input.
+async fn read_full<R>(read: &mut R, buf: &mut [u8]) -> io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut filled = 0;
+    while filled < buf.len() {
+        match read.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// A thread hashing the buffers sent to it with a single algorithm.
+struct Worker {
+    alg: HashAlgorithm,
+    sender: mpsc::Sender<Arc<Vec<u8>>>,
+    result: oneshot::Receiver<Vec<u8>>,
+}
+
+impl Worker {
+    fn spawn(alg: HashAlgorithm, mut context: DigestContext) -> Result<Self> {
+        let (sender, mut receiver) = mpsc::channel::<Arc<Vec<u8>>>(QUEUE_DEPTH);
+        let (result_sender, result) = oneshot::channel();
+
+        thread::Builder::new()
+            .name(format!("tuf-hash-{}", alg))
+            .spawn(move || {
+                while let Some(chunk) = futures_executor::block_on(receiver.next()) {
+                    context.update(&chunk);
+                }
+                let _ = result_sender.send(context.finish());
+            })?;
+
+        Ok(Worker {
+            alg,
+            sender,
+            result,
+        })
+    }
+
+    /// Queue `chunk` for hashing, waiting if the worker is [QUEUE_DEPTH] buffers behind.
+    async fn send(&mut self, chunk: Arc<Vec<u8>>) -> Result<()> {
+        poll_fn(|cx| self.sender.poll_ready(cx))
+            .await
+            .map_err(|_| worker_exited())?;
+        self.sender.start_send(chunk).map_err(|_| worker_exited())
+    }
+
+    /// Wait for the worker to hash every queued buffer.
+    async fn finish(self) -> Result<(HashAlgorithm, HashValue)> {
+        let Worker {
+            alg,
+            sender,
+            result,
+        } = self;
+
+        // Closing the channel tells the worker there are no more buffers.
+        drop(sender);
+
+        let value = result.await.map_err(|_| worker_exited())?;
+        Ok((alg, HashValue::new(value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use std::io::Write as _;
+
+    const ALGS: &[HashAlgorithm] = &[
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Blake2b256,
+    ];
+
+    fn serial(buf: &[u8]) -> HashMap<HashAlgorithm, HashValue> {
+        ALGS.iter()
+            .map(|alg| (alg.clone(), super::super::calculate_hash(buf, alg)))
+            .collect()
+    }
+
+    /// Inputs around the sizes where hashing switches between serial and parallel.
+    fn inputs() -> Vec<Vec<u8>> {
+        [
+            0,
+            1,
+            BUFFER_SIZE - 1,
+            BUFFER_SIZE,
+            BUFFER_SIZE + 1,
+            3 * BUFFER_SIZE + 17,
+        ]
+        .iter()
+        .map(|len| (0..*len).map(|i| (i % 251) as u8).collect())
+        .collect()
+    }
+
+    #[test]
+    fn hash_slice_matches_serial() {
+        for input in inputs() {
+            for threads in [false, true] {
+                assert_eq!(
+                    hash_slice_with_threads(&input, ALGS, threads).unwrap(),
+                    serial(&input)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hash_reader_matches_serial() {
+        for input in inputs() {
+            for threads in [false, true] {
+                let (size, hashes) =
+                    block_on(hash_reader_with_threads(&input[..], ALGS, threads)).unwrap();
+                assert_eq!(size, input.len() as u64);
+                assert_eq!(hashes, serial(&input));
+            }
+        }
+    }
+
+    #[test]
+    fn hash_reader_with_short_reads() {
+        // A reader that never fills the buffer in one read.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl AsyncRead for Trickle<'_> {
+            fn poll_read(
+                mut self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+                buf: &mut [u8],
+            ) -> std::task::Poll<io::Result<usize>> {
+                let n = buf.len().min(self.0.len()).min(4093);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                std::task::Poll::Ready(Ok(n))
+            }
+        }
+
+        let input = inputs().pop().unwrap();
+        let (size, hashes) =
+            block_on(hash_reader_with_threads(Trickle(&input), ALGS, true)).unwrap();
+        assert_eq!(size, input.len() as u64);
+        assert_eq!(hashes, serial(&input));
+    }
+
+    #[test]
+    fn hash_file_matches_serial() {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, input) in inputs().into_iter().enumerate() {
+            let path = dir.path().join(i.to_string());
+            File::create(&path).unwrap().write_all(&input).unwrap();
+
+            let (size, hashes) = hash_file(&path, ALGS).unwrap();
+            assert_eq!(size, input.len() as u64);
+            assert_eq!(hashes, serial(&input));
+        }
+    }
+
+    #[test]
+    fn hash_file_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing");
+        assert_matches!(
+            hash_file(&path, ALGS),
+            Err(Error::IoPath { path: p, .. }) if p == path
+        );
+    }
+
+    #[test]
+    fn unknown_algorithm() {
+        let algs = &[HashAlgorithm::Unknown("md5".into())];
+        assert_matches!(hash_slice(b"", algs), Err(Error::IllegalArgument(_)));
+        assert_matches!(
+            block_on(hash_reader(&b""[..], algs)),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+}
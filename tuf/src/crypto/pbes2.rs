@@ -0,0 +1,435 @@
+//! Passphrase-encrypted PKCS#8 private keys, using the PBES2 scheme from [RFC 8018].
+//!
+//! An encrypted key is stored as an `EncryptedPrivateKeyInfo` structure from [RFC 5958]. Keys
+//! are derived from the passphrase with scrypt ([RFC 7914]) or PBKDF2, and encrypted with
+//! AES-GCM ([RFC 5084]) or AES-CBC. The [pkcs5] crate handles the key derivation functions and
+//! AES-CBC, but doesn't support AES-GCM, so that is implemented here.
+//!
+//! [RFC 5084]: https://tools.ietf.org/html/rfc5084
+//! [RFC 5958]: https://tools.ietf.org/html/rfc5958
+//! [RFC 7914]: https://tools.ietf.org/html/rfc7914
+//! [RFC 8018]: https://tools.ietf.org/html/rfc8018
+
+use {
+    crate::error::{Error, Result},
+    der::{
+        asn1::{AnyRef, ObjectIdentifier, OctetStringRef},
+        Decode as _, Encode as _, Sequence,
+    },
+    pkcs5::{
+        pbes2::{self, EncryptionScheme, Kdf, Pbkdf2Params, Pbkdf2Prf, ScryptParams},
+        AlgorithmIdentifierRef,
+    },
+    ring::{
+        aead, pbkdf2,
+        rand::{SecureRandom, SystemRandom},
+    },
+    std::num::NonZeroU32,
+};
+
+/// 2.16.840.1.101.3.4.1.6
+const AES_128_GCM_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.1.6");
+/// 2.16.840.1.101.3.4.1.46
+const AES_256_GCM_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.1.46");
+
+const SALT_LENGTH: usize = 16;
+const CBC_IV_LENGTH: usize = 16;
+const GCM_NONCE_LENGTH: usize = 12;
+const GCM_TAG_LENGTH: u8 = 16;
+
+/// The AES-GCM tag length used when the parameters don't list one.
+const GCM_DEFAULT_TAG_LENGTH: u8 = 12;
+
+/// The most PBKDF2 iterations allowed, since the count is read from untrusted key files.
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// The most memory scrypt may use, `128 * r * 2^log_n * p` bytes, since the parameters are read
+/// from untrusted key files. This also bounds the work scrypt does.
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+
+/// The key derivation function used to derive an encryption key from a passphrase.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pkcs8Kdf {
+    /// scrypt, with a cost of `2^log_n`, a block size of `r`, and a parallelization of `p`.
+    Scrypt {
+        /// The base 2 logarithm of the CPU/memory cost.
+        log_n: u8,
+        /// The block size.
+        r: u32,
+        /// The parallelization.
+        p: u32,
+    },
+    /// PBKDF2 with HMAC-SHA256.
+    Pbkdf2Sha256 {
+        /// The number of iterations.
+        iterations: u32,
+    },
+}
+
+/// The cipher used to encrypt a private key.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pkcs8Cipher {
+    /// AES-128 in CBC mode.
+    Aes128Cbc,
+    /// AES-256 in CBC mode.
+    Aes256Cbc,
+    /// AES-128 in GCM mode.
+    Aes128Gcm,
+    /// AES-256 in GCM mode.
+    Aes256Gcm,
+}
+
+/// How to encrypt a PKCS#8 private key with a passphrase.
+///
+/// Defaults to scrypt with a cost of 2^15, a block size of 8, and a parallelization of 1, and
+/// AES-256-GCM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pkcs8Encryption {
+    kdf: Pkcs8Kdf,
+    cipher: Pkcs8Cipher,
+}
+
+impl Pkcs8Encryption {
+    /// Encrypt with a key derived with `kdf`, using `cipher`.
+    pub fn new(kdf: Pkcs8Kdf, cipher: Pkcs8Cipher) -> Self {
+        Self { kdf, cipher }
+    }
+
+    /// The key derivation function.
+    pub fn kdf(&self) -> &Pkcs8Kdf {
+        &self.kdf
+    }
+
+    /// The cipher.
+    pub fn cipher(&self) -> Pkcs8Cipher {
+        self.cipher
+    }
+}
+
+impl Default for Pkcs8Encryption {
+    fn default() -> Self {
+        Self {
+            kdf: Pkcs8Kdf::Scrypt {
+                log_n: 15,
+                r: 8,
+                p: 1,
+            },
+            cipher: Pkcs8Cipher::Aes256Gcm,
+        }
+    }
+}
+
+/// `EncryptedPrivateKeyInfo` from [RFC 5958]. The `pkcs8` crate has this structure too, but it
+/// only allows the encryption schemes supported by [pkcs5].
+///
+/// [RFC 5958]: https://tools.ietf.org/html/rfc5958
+#[derive(Sequence)]
+struct EncryptedPrivateKeyInfo<'a> {
+    encryption_algorithm: AlgorithmIdentifierRef<'a>,
+    encrypted_data: OctetStringRef<'a>,
+}
+
+/// `PBES2-params` from [RFC 8018], allowing any encryption scheme.
+///
+/// [RFC 8018]: https://tools.ietf.org/html/rfc8018
+#[derive(Sequence)]
+struct Pbes2Params<'a> {
+    key_derivation_func: Kdf<'a>,
+    encryption_scheme: AlgorithmIdentifierRef<'a>,
+}
+
+/// `GCMParameters` from [RFC 5084].
+///
+/// [RFC 5084]: https://tools.ietf.org/html/rfc5084
+#[derive(Sequence)]
+struct GcmParams<'a> {
+    nonce: OctetStringRef<'a>,
+    #[asn1(default = "default_gcm_tag_length")]
+    icv_len: u8,
+}
+
+fn default_gcm_tag_length() -> u8 {
+    GCM_DEFAULT_TAG_LENGTH
+}
+
+/// Decrypt the DER encoded `EncryptedPrivateKeyInfo` in `der_key` with `passphrase`, returning
+/// the DER encoded PKCS#8 private key.
+pub(crate) fn decrypt(der_key: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let info = EncryptedPrivateKeyInfo::from_der(der_key).map_err(der_error)?;
+    if info.encryption_algorithm.oid != pbes2::PBES2_OID {
+        return Err(Error::Encoding(
+            "unsupported key encryption algorithm".into(),
+        ));
+    }
+    let params = parameters(&info.encryption_algorithm)?
+        .decode_as::<Pbes2Params<'_>>()
+        .map_err(der_error)?;
+    let kdf = params.key_derivation_func;
+    check_kdf(&kdf)?;
+    let ciphertext = info.encrypted_data.as_bytes();
+
+    let algorithm = match params.encryption_scheme.oid {
+        oid if oid == AES_128_GCM_OID => &aead::AES_128_GCM,
+        oid if oid == AES_256_GCM_OID => &aead::AES_256_GCM,
+        _ => {
+            let encryption =
+                EncryptionScheme::try_from(params.encryption_scheme).map_err(der_error)?;
+            return pbes2::Parameters { kdf, encryption }
+                .decrypt(passphrase, ciphertext)
+                .map_err(|err| match err {
+                    // A wrong passphrase leaves invalid padding, which pkcs5 reports as a failure
+                    // to encrypt.
+                    pkcs5::Error::DecryptFailed | pkcs5::Error::EncryptFailed => {
+                        Error::KeyDecryption
+                    }
+                    err => pkcs5_error(err),
+                });
+        }
+    };
+
+    // Only 16 byte tags are supported.
+    let gcm = parameters(&params.encryption_scheme)?
+        .decode_as::<GcmParams<'_>>()
+        .map_err(der_error)?;
+    if gcm.nonce.as_bytes().len() != GCM_NONCE_LENGTH || gcm.icv_len != GCM_TAG_LENGTH {
+        return Err(Error::Encoding("unsupported AES-GCM parameters".into()));
+    }
+
+    let key = derive_key(&kdf, passphrase, algorithm.key_len())?;
+    gcm_decrypt(algorithm, &key, gcm.nonce.as_bytes(), ciphertext)
+}
+
+/// Encrypt the DER encoded PKCS#8 private key `der_key` with `passphrase`, returning a DER
+/// encoded `EncryptedPrivateKeyInfo`.
+pub(crate) fn encrypt(
+    der_key: &[u8],
+    passphrase: &[u8],
+    encryption: &Pkcs8Encryption,
+) -> Result<Vec<u8>> {
+    let rng = SystemRandom::new();
+    let salt = random::<SALT_LENGTH>(&rng)?;
+
+    let key_length = match encryption.cipher {
+        Pkcs8Cipher::Aes128Cbc | Pkcs8Cipher::Aes128Gcm => 16,
+        Pkcs8Cipher::Aes256Cbc | Pkcs8Cipher::Aes256Gcm => 32,
+    };
+
+    let kdf = match encryption.kdf {
+        Pkcs8Kdf::Scrypt { log_n, r, p } => {
+            let params = scrypt::Params::new(log_n, r, p, key_length).map_err(|err| {
+                Error::IllegalArgument(format!("invalid scrypt parameters: {}", err))
+            })?;
+            Kdf::from(
+                ScryptParams::from_params_and_salt(params, &salt).map_err(|err| {
+                    Error::IllegalArgument(format!("invalid scrypt parameters: {}", err))
+                })?,
+            )
+        }
+        Pkcs8Kdf::Pbkdf2Sha256 { iterations } => Kdf::from(
+            Pbkdf2Params::hmac_with_sha256(iterations, &salt).map_err(|err| {
+                Error::IllegalArgument(format!("invalid PBKDF2 parameters: {}", err))
+            })?,
+        ),
+    };
+    // Never write a key that couldn't be read back.
+    check_kdf(&kdf)?;
+
+    let iv;
+    let nonce;
+    let gcm_params;
+    let (encryption_scheme, ciphertext) = match encryption.cipher {
+        Pkcs8Cipher::Aes128Cbc | Pkcs8Cipher::Aes256Cbc => {
+            iv = random::<CBC_IV_LENGTH>(&rng)?;
+            let encryption = if key_length == 16 {
+                EncryptionScheme::Aes128Cbc { iv: &iv }
+            } else {
+                EncryptionScheme::Aes256Cbc { iv: &iv }
+            };
+            let ciphertext = pbes2::Parameters {
+                kdf: kdf.clone(),
+                encryption,
+            }
+            .encrypt(passphrase, der_key)
+            .map_err(pkcs5_error)?;
+            (
+                AlgorithmIdentifierRef::try_from(encryption).map_err(der_error)?,
+                ciphertext,
+            )
+        }
+        Pkcs8Cipher::Aes128Gcm | Pkcs8Cipher::Aes256Gcm => {
+            nonce = random::<GCM_NONCE_LENGTH>(&rng)?;
+            let (oid, algorithm) = if key_length == 16 {
+                (AES_128_GCM_OID, &aead::AES_128_GCM)
+            } else {
+                (AES_256_GCM_OID, &aead::AES_256_GCM)
+            };
+            let key = derive_key(&kdf, passphrase, key_length)?;
+            let ciphertext = gcm_encrypt(algorithm, &key, &nonce, der_key)?;
+            gcm_params = GcmParams {
+                nonce: OctetStringRef::new(&nonce).map_err(der_error)?,
+                icv_len: GCM_TAG_LENGTH,
+            }
+            .to_der()
+            .map_err(der_error)?;
+            (
+                AlgorithmIdentifierRef {
+                    oid,
+                    parameters: Some(AnyRef::from_der(&gcm_params).map_err(der_error)?),
+                },
+                ciphertext,
+            )
+        }
+    };
+
+    let pbes2_params = Pbes2Params {
+        key_derivation_func: kdf,
+        encryption_scheme,
+    }
+    .to_der()
+    .map_err(der_error)?;
+
+    EncryptedPrivateKeyInfo {
+        encryption_algorithm: AlgorithmIdentifierRef {
+            oid: pbes2::PBES2_OID,
+            parameters: Some(AnyRef::from_der(&pbes2_params).map_err(der_error)?),
+        },
+        encrypted_data: OctetStringRef::new(&ciphertext).map_err(der_error)?,
+    }
+    .to_der()
+    .map_err(der_error)
+}
+
+fn parameters<'a>(algorithm: &AlgorithmIdentifierRef<'a>) -> Result<AnyRef<'a>> {
+    algorithm
+        .parameters
+        .ok_or_else(|| Error::Encoding("missing algorithm parameters".into()))
+}
+
+/// Check the cost of deriving a key with `kdf` is bounded.
+fn check_kdf(kdf: &Kdf<'_>) -> Result<()> {
+    match kdf {
+        Kdf::Pbkdf2(params) => {
+            if params.iteration_count == 0 || params.iteration_count > MAX_PBKDF2_ITERATIONS {
+                return Err(Error::IllegalArgument(format!(
+                    "PBKDF2 iteration count must be between 1 and {}, not {}",
+                    MAX_PBKDF2_ITERATIONS, params.iteration_count
+                )));
+            }
+        }
+        Kdf::Scrypt(params) => {
+            let memory = params.cost_parameter.saturating_mul(
+                128 * u64::from(params.block_size) * u64::from(params.parallelization),
+            );
+            if memory > MAX_SCRYPT_MEMORY {
+                return Err(Error::IllegalArgument(format!(
+                    "scrypt parameters need {} bytes of memory, more than the maximum of {}",
+                    memory, MAX_SCRYPT_MEMORY
+                )));
+            }
+        }
+        _ => return Err(unsupported_kdf()),
+    }
+    Ok(())
+}
+
+/// Derive a `key_length` byte key from `passphrase` with `kdf`. This is only needed for AES-GCM,
+/// since [pkcs5] derives the keys for the encryption schemes it supports itself.
+fn derive_key(kdf: &Kdf<'_>, passphrase: &[u8], key_length: usize) -> Result<Vec<u8>> {
+    if let Some(expected) = kdf.key_length() {
+        if usize::from(expected) != key_length {
+            return Err(Error::Encoding("key length does not match cipher".into()));
+        }
+    }
+
+    let mut key = vec![0; key_length];
+
+    match kdf {
+        Kdf::Pbkdf2(params) => {
+            let algorithm = match params.prf {
+                Pbkdf2Prf::HmacWithSha1 => pbkdf2::PBKDF2_HMAC_SHA1,
+                Pbkdf2Prf::HmacWithSha256 => pbkdf2::PBKDF2_HMAC_SHA256,
+                Pbkdf2Prf::HmacWithSha384 => pbkdf2::PBKDF2_HMAC_SHA384,
+                Pbkdf2Prf::HmacWithSha512 => pbkdf2::PBKDF2_HMAC_SHA512,
+                _ => {
+                    return Err(Error::Encoding(
+                        "unsupported PBKDF2 pseudorandom function".into(),
+                    ))
+                }
+            };
+            let iterations = NonZeroU32::new(params.iteration_count).ok_or_else(|| {
+                Error::IllegalArgument("PBKDF2 iterations must be non-zero".into())
+            })?;
+            pbkdf2::derive(algorithm, iterations, params.salt, passphrase, &mut key);
+        }
+        Kdf::Scrypt(params) => {
+            let salt = params.salt;
+            let params = scrypt::Params::try_from(params).map_err(|err| {
+                Error::IllegalArgument(format!("invalid scrypt parameters: {}", err))
+            })?;
+            scrypt::scrypt(passphrase, salt, &params, &mut key)
+                .map_err(|err| Error::Opaque(format!("scrypt failed: {}", err)))?;
+        }
+        _ => return Err(unsupported_kdf()),
+    }
+
+    Ok(key)
+}
+
+fn unsupported_kdf() -> Error {
+    Error::Encoding("unsupported key derivation function".into())
+}
+
+fn der_error(err: der::Error) -> Error {
+    Error::Encoding(format!("invalid encrypted private key: {}", err))
+}
+
+fn pkcs5_error(err: pkcs5::Error) -> Error {
+    Error::Encoding(format!("invalid encrypted private key: {}", err))
+}
+
+fn gcm_key(algorithm: &'static aead::Algorithm, key: &[u8]) -> Result<aead::LessSafeKey> {
+    aead::UnboundKey::new(algorithm, key)
+        .map(aead::LessSafeKey::new)
+        .map_err(|_| Error::IllegalArgument("invalid AES-GCM key length".into()))
+}
+
+fn gcm_decrypt(
+    algorithm: &'static aead::Algorithm,
+    key: &[u8],
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    let nonce = aead::Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| Error::Encoding("invalid AES-GCM nonce".into()))?;
+    let mut buf = ciphertext.to_vec();
+    let plaintext_length = gcm_key(algorithm, key)?
+        .open_in_place(nonce, aead::Aad::empty(), &mut buf)
+        .map_err(|_| Error::KeyDecryption)?
+        .len();
+    buf.truncate(plaintext_length);
+    Ok(buf)
+}
+
+fn gcm_encrypt(
+    algorithm: &'static aead::Algorithm,
+    key: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let nonce = aead::Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| Error::IllegalArgument("invalid AES-GCM nonce".into()))?;
+    let mut buf = plaintext.to_vec();
+    gcm_key(algorithm, key)?
+        .seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut buf)
+        .map_err(|_| Error::Opaque("failed to encrypt private key".into()))?;
+    Ok(buf)
+}
+
+fn random<const N: usize>(rng: &SystemRandom) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    rng.fill(&mut bytes)
+        .map_err(|_| Error::Opaque("failed to generate random bytes".into()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,62 @@
+//! Minimal PEM encoding, as described in [RFC 7468].
+//!
+//! [RFC 7468]: https://tools.ietf.org/html/rfc7468
+
+use {
+    crate::error::{Error, Result},
+    data_encoding::BASE64,
+};
+
+pub(crate) const PUBLIC_KEY: &str = "PUBLIC KEY";
+pub(crate) const PRIVATE_KEY: &str = "PRIVATE KEY";
+#[cfg(feature = "keyless")]
+pub(crate) const CERTIFICATE: &str = "CERTIFICATE";
+#[cfg(feature = "encrypted-keys")]
+pub(crate) const ENCRYPTED_PRIVATE_KEY: &str = "ENCRYPTED PRIVATE KEY";
+
+/// The number of base64 characters on each line.
+const LINE_LENGTH: usize = 64;
+
+/// Encode `der` as a PEM document with the label `label`.
+pub(crate) fn encode(label: &str, der: &[u8]) -> String {
+    let base64 = BASE64.encode(der);
+
+    let mut output = format!("-----BEGIN {}-----\n", label);
+    for line in base64.as_bytes().chunks(LINE_LENGTH) {
+        // Base64 output is always ASCII.
+        output.push_str(std::str::from_utf8(line).unwrap());
+        output.push('\n');
+    }
+    output.push_str(&format!("-----END {}-----\n", label));
+
+    output
+}
+
+/// Decode the first PEM document labeled `label` in `pem`. Text before and after the document is
+/// ignored.
+pub(crate) fn decode(label: &str, pem: &str) -> Result<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    let mut lines = pem.lines().map(str::trim);
+    if !lines.any(|line| line == begin) {
+        return Err(Error::Encoding(format!("PEM: missing \"{}\"", begin)));
+    }
+
+    let mut base64 = String::new();
+    for line in lines {
+        if line == end {
+            return BASE64
+                .decode(base64.as_bytes())
+                .map_err(|err| Error::Encoding(format!("PEM: invalid base64: {}", err)));
+        }
+        if line.contains(':') {
+            return Err(Error::Encoding(
+                "PEM: encapsulated headers are not supported".into(),
+            ));
+        }
+        base64.push_str(line);
+    }
+
+    Err(Error::Encoding(format!("PEM: missing \"{}\"", end)))
+}
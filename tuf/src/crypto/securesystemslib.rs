@@ -0,0 +1,106 @@
+//! The passphrase encryption used by securesystemslib for private key files.
+//!
+//! A key is encrypted with AES-256-CTR under a key derived from the passphrase with
+//! PBKDF2-HMAC-SHA256, and the ciphertext is authenticated with HMAC-SHA256 under the same key.
+//! The result is stored as `salt@@@@iterations@@@@hmac@@@@iv@@@@ciphertext`, with every field
+//! except the iteration count hex encoded.
+
+use {
+    crate::error::{Error, Result},
+    aes::cipher::{KeyIvInit, StreamCipher},
+    data_encoding::HEXLOWER,
+    ring::{
+        hmac, pbkdf2,
+        rand::{SecureRandom, SystemRandom},
+    },
+    std::num::NonZeroU32,
+};
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+const DELIMITER: &str = "@@@@";
+const SALT_LENGTH: usize = 16;
+const IV_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+/// The iteration count securesystemslib uses when encrypting keys.
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Decrypt securesystemslib encrypted key `data` with `passphrase`.
+pub(crate) fn decrypt(data: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let data = std::str::from_utf8(data)
+        .map_err(|_| Error::Encoding("encrypted key is not valid UTF-8".into()))?;
+    let fields = data.trim().split(DELIMITER).collect::<Vec<_>>();
+    let (salt, iterations, tag, iv, ciphertext) = match fields[..] {
+        [salt, iterations, tag, iv, ciphertext] => (salt, iterations, tag, iv, ciphertext),
+        _ => {
+            return Err(Error::Encoding(
+                "encrypted key must have 5 fields separated by '@@@@'".into(),
+            ))
+        }
+    };
+
+    let hex = |field: &str| {
+        HEXLOWER
+            .decode(field.as_bytes())
+            .map_err(|err| Error::Encoding(format!("encrypted key is not hex: {}", err)))
+    };
+    let salt = hex(salt)?;
+    let iterations = iterations
+        .parse::<u32>()
+        .ok()
+        .and_then(NonZeroU32::new)
+        .ok_or_else(|| Error::Encoding("invalid PBKDF2 iteration count".into()))?;
+    let tag = hex(tag)?;
+    let iv = hex(iv)?;
+    let mut plaintext = hex(ciphertext)?;
+
+    let key = derive_key(passphrase, &salt, iterations);
+    hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, &key), &plaintext, &tag)
+        .map_err(|_| Error::KeyDecryption)?;
+
+    Aes256Ctr::new_from_slices(&key, &iv)
+        .map_err(|_| Error::Encoding("invalid AES-CTR IV".into()))?
+        .apply_keystream(&mut plaintext);
+
+    Ok(plaintext)
+}
+
+/// Encrypt `plaintext` with `passphrase` the way securesystemslib does.
+pub(crate) fn encrypt(plaintext: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let rng = SystemRandom::new();
+    let mut salt = [0; SALT_LENGTH];
+    let mut iv = [0; IV_LENGTH];
+    rng.fill(&mut salt)
+        .and_then(|()| rng.fill(&mut iv))
+        .map_err(|_| Error::Opaque("failed to generate random bytes".into()))?;
+
+    let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+    let key = derive_key(passphrase, &salt, iterations);
+
+    let mut ciphertext = plaintext.to_vec();
+    Aes256Ctr::new(&key.into(), &iv.into()).apply_keystream(&mut ciphertext);
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), &ciphertext);
+
+    Ok([
+        HEXLOWER.encode(&salt),
+        iterations.to_string(),
+        HEXLOWER.encode(tag.as_ref()),
+        HEXLOWER.encode(&iv),
+        HEXLOWER.encode(&ciphertext),
+    ]
+    .join(DELIMITER)
+    .into_bytes())
+}
+
+fn derive_key(passphrase: &[u8], salt: &[u8], iterations: NonZeroU32) -> [u8; KEY_LENGTH] {
+    let mut key = [0; KEY_LENGTH];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase,
+        &mut key,
+    );
+    key
+}
@@ -0,0 +1,253 @@
+//! X.509 certificates issued by a Sigstore certificate authority such as Fulcio, as described in
+//! [RFC 5280].
+//!
+//! Certificates are parsed with the [x509-cert] crate, which only extracts the fields keyless
+//! verification needs. Chains and signatures are verified by [webpki].
+//!
+//! [RFC 5280]: https://tools.ietf.org/html/rfc5280
+//! [x509-cert]: https://docs.rs/x509-cert
+//! [webpki]: https://docs.rs/rustls-webpki
+
+use {
+    crate::error::{Error, Result},
+    chrono::{DateTime, Utc},
+    der::{asn1::ObjectIdentifier, Decode as _},
+    rustls_pki_types::{CertificateDer, SignatureVerificationAlgorithm},
+    webpki::EndEntityCert,
+    x509_cert::{
+        ext::pkix::{name::GeneralName, BasicConstraints, KeyUsage, SubjectAltName},
+        time::Time,
+    },
+};
+
+/// 1.3.6.1.5.5.7.3.3, without its tag and length.
+pub(crate) const OID_CODE_SIGNING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+/// Fulcio's deprecated OIDC issuer extension, holding the raw issuer.
+const OID_FULCIO_ISSUER: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+/// Fulcio's OIDC issuer extension, holding a DER encoded UTF8String.
+const OID_FULCIO_ISSUER_V2: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+
+/// The algorithms that may sign certificates, or be used by a leaf certificate's key. P-256 keys
+/// sign with SHA-256, and P-384 keys with SHA-384.
+pub(crate) static SIGNATURE_ALGORITHMS: &[&dyn SignatureVerificationAlgorithm] = &[
+    webpki::ring::ECDSA_P256_SHA256,
+    webpki::ring::ECDSA_P256_SHA384,
+    webpki::ring::ECDSA_P384_SHA256,
+    webpki::ring::ECDSA_P384_SHA384,
+    webpki::ring::ED25519,
+];
+
+/// The algorithms a leaf certificate's key may sign metadata with.
+static LEAF_SIGNATURE_ALGORITHMS: &[&dyn SignatureVerificationAlgorithm] = &[
+    webpki::ring::ECDSA_P256_SHA256,
+    webpki::ring::ECDSA_P384_SHA384,
+    webpki::ring::ED25519,
+];
+
+/// A parsed X.509 v3 certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Certificate {
+    der: Vec<u8>,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+    is_ca: bool,
+    key_usage: Option<KeyUsage>,
+    emails: Vec<String>,
+    uris: Vec<String>,
+    oidc_issuer: Option<String>,
+}
+
+impl Certificate {
+    /// Parse a DER encoded certificate.
+    pub(crate) fn from_der(der: &[u8]) -> Result<Self> {
+        let cert = x509_cert::Certificate::from_der(der).map_err(der_error)?;
+        let tbs = &cert.tbs_certificate;
+
+        let mut emails = vec![];
+        let mut uris = vec![];
+        if let Some((_, SubjectAltName(names))) = tbs.get().map_err(der_error)? {
+            for name in names {
+                match name {
+                    GeneralName::Rfc822Name(email) => emails.push(email.to_string()),
+                    GeneralName::UniformResourceIdentifier(uri) => uris.push(uri.to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        // Prefer the DER encoded issuer if a certificate has both.
+        let mut oidc_issuer = None;
+        for extension in tbs.extensions.iter().flatten() {
+            let value = extension.extn_value.as_bytes();
+            if extension.extn_id == OID_FULCIO_ISSUER_V2 {
+                oidc_issuer = Some(String::from_der(value).map_err(der_error)?);
+            } else if extension.extn_id == OID_FULCIO_ISSUER && oidc_issuer.is_none() {
+                oidc_issuer = Some(
+                    std::str::from_utf8(value)
+                        .map_err(|_| Error::Encoding("invalid OIDC issuer".into()))?
+                        .to_owned(),
+                );
+            }
+        }
+
+        Ok(Certificate {
+            der: der.to_vec(),
+            not_before: to_date_time(tbs.validity.not_before),
+            not_after: to_date_time(tbs.validity.not_after),
+            is_ca: tbs
+                .get::<BasicConstraints>()
+                .map_err(der_error)?
+                .map_or(false, |(_, constraints)| constraints.ca),
+            key_usage: tbs.get().map_err(der_error)?.map(|(_, usage)| usage),
+            emails,
+            uris,
+            oidc_issuer,
+        })
+    }
+
+    /// The DER encoded certificate.
+    pub(crate) fn as_der(&self) -> CertificateDer<'_> {
+        CertificateDer::from(self.der.as_slice())
+    }
+
+    /// The email addresses in the subject alternative name extension.
+    pub(crate) fn emails(&self) -> &[String] {
+        &self.emails
+    }
+
+    /// The URIs in the subject alternative name extension.
+    pub(crate) fn uris(&self) -> &[String] {
+        &self.uris
+    }
+
+    /// The OIDC issuer recorded by Fulcio.
+    pub(crate) fn oidc_issuer(&self) -> Option<&str> {
+        self.oidc_issuer.as_deref()
+    }
+
+    /// Whether the certificate may issue other certificates.
+    pub(crate) fn is_ca(&self) -> bool {
+        self.is_ca && self.key_usage.map_or(true, |usage| usage.key_cert_sign())
+    }
+
+    /// Whether the certificate's key may make digital signatures.
+    pub(crate) fn can_sign(&self) -> bool {
+        self.key_usage
+            .map_or(true, |usage| usage.digital_signature())
+    }
+
+    pub(crate) fn is_valid_at(&self, time: &DateTime<Utc>) -> bool {
+        &self.not_before <= time && time <= &self.not_after
+    }
+
+    /// Verify `sig` is this certificate's key's signature of `msg`, hashing with SHA-256 for
+    /// P-256 keys and SHA-384 for P-384 keys.
+    pub(crate) fn verify_signature(&self, msg: &[u8], sig: &[u8]) -> Result<()> {
+        let der = self.as_der();
+        let cert = EndEntityCert::try_from(&der)
+            .map_err(|err| Error::UntrustedCertificate(format!("invalid certificate: {}", err)))?;
+
+        // Only the algorithm matching the certificate's key can succeed.
+        if LEAF_SIGNATURE_ALGORITHMS
+            .iter()
+            .any(|alg| cert.verify_signature(*alg, msg, sig).is_ok())
+        {
+            Ok(())
+        } else {
+            Err(Error::UntrustedCertificate("bad signature".into()))
+        }
+    }
+}
+
+fn to_date_time(time: Time) -> DateTime<Utc> {
+    DateTime::from(time.to_system_time())
+}
+
+fn der_error(err: der::Error) -> Error {
+    Error::Encoding(format!("invalid certificate: {}", err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::pem;
+    use assert_matches::assert_matches;
+    use chrono::TimeZone as _;
+
+    fn load_der(name: &str) -> Vec<u8> {
+        let path = format!("{}/tests/sigstore/{}.pem", env!("CARGO_MANIFEST_DIR"), name);
+        let pem = std::fs::read_to_string(path).unwrap();
+        pem::decode(pem::CERTIFICATE, &pem).unwrap()
+    }
+
+    fn load(name: &str) -> Certificate {
+        Certificate::from_der(&load_der(name)).unwrap()
+    }
+
+    #[test]
+    fn parse_chain() {
+        let root = load("root");
+        let intermediate = load("intermediate");
+        let leaf = load("leaf");
+
+        assert!(root.is_ca());
+        assert!(!root.can_sign());
+        assert!(intermediate.is_ca());
+        assert!(!leaf.is_ca());
+        assert!(leaf.can_sign());
+
+        assert_eq!(leaf.emails(), &["signer@example.com".to_string()]);
+        assert!(leaf.uris().is_empty());
+        assert_eq!(leaf.oidc_issuer(), Some("https://accounts.example.com"));
+
+        assert_eq!(
+            leaf.not_before,
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            leaf.not_after,
+            Utc.with_ymd_and_hms(2124, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_deprecated_issuer_and_uri() {
+        let leaf = load("leaf-uri");
+        assert!(leaf.emails().is_empty());
+        assert_eq!(
+            leaf.uris(),
+            &[
+                "https://github.com/example/repo/.github/workflows/release.yml@refs/heads/main"
+                    .to_string()
+            ]
+        );
+        assert_eq!(
+            leaf.oidc_issuer(),
+            Some("https://token.actions.githubusercontent.com")
+        );
+    }
+
+    #[test]
+    fn reject_non_ascii_time() {
+        // Replace the "40" in the leaf's `240101000000Z` UTCTime with a two byte UTF-8 character,
+        // which used to make the time parser slice in the middle of a character and panic.
+        let mut leaf = load_der("leaf");
+        let time = leaf
+            .windows(15)
+            .position(|window| window == b"\x17\x0d240101000000Z")
+            .unwrap();
+        leaf[time + 3..time + 5].copy_from_slice("é".as_bytes());
+
+        assert_matches!(Certificate::from_der(&leaf), Err(Error::Encoding(_)));
+    }
+
+    #[test]
+    fn reject_truncated_certificate() {
+        let leaf = load_der("leaf");
+        assert_matches!(
+            Certificate::from_der(&leaf[..leaf.len() - 1]),
+            Err(Error::Encoding(_))
+        );
+    }
+}
@@ -1,19 +1,23 @@
 //! Components needed to verify TUF metadata and targets.
 
-use chrono::{offset::Utc, DateTime};
+use chrono::{offset::Utc, DateTime, Duration};
+use log::warn;
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use crate::crypto::PublicKey;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::{KeyId, PublicKey};
 use crate::error::Error;
 use crate::metadata::{
     Delegations, Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, RawSignedMetadataSet,
-    RootMetadata, SnapshotMetadata, TargetDescription, TargetPath, TargetsMetadata,
+    RoleDefinition, RootMetadata, SnapshotMetadata, TargetDescription, TargetPath, TargetsMetadata,
     TimestampMetadata,
 };
 use crate::pouf::Pouf;
-use crate::verify::{self, Verified};
+use crate::revocation::{RevocationList, SignedKeyRevocation};
+use crate::verify::{self, Verified, Verifier};
 use crate::Result;
 
 /// Contains trusted TUF metadata and can be used to verify other metadata and targets.
@@ -24,6 +28,20 @@ pub struct Database<D: Pouf> {
     trusted_snapshot: Option<Verified<SnapshotMetadata>>,
     trusted_timestamp: Option<Verified<TimestampMetadata>>,
     trusted_delegations: HashMap<MetadataPath, Verified<TargetsMetadata>>,
+    /// The role that delegated to each trusted delegated role.
+    delegation_parents: HashMap<MetadataPath, MetadataPath>,
+    /// The key ID aliases of the trusted root that the verifier's key ID policy accepts.
+    trusted_key_id_aliases: HashSet<KeyId>,
+    revocations: HashMap<MetadataPath, BTreeMap<KeyId, SignedKeyRevocation>>,
+    /// Every revocation seen, including those that couldn't be applied, so they can be published
+    /// again.
+    published_revocations: RevocationList,
+    clock: Arc<dyn Clock>,
+    expiration_grace_policy: ExpirationGracePolicy,
+    /// Whether updates are reloading metadata that was already trusted, such as from a client's
+    /// local repository, and so may use the [ExpirationGracePolicy].
+    reloading: bool,
+    verifier: Verifier,
     pouf: PhantomData<D>,
 }
 
@@ -40,7 +58,27 @@ impl<D: Pouf> Database<D> {
     where
         I: IntoIterator<Item = &'a PublicKey>,
     {
-        let verified_root = {
+        Self::from_root_with_trusted_keys_and_verifier(
+            raw_root,
+            root_threshold,
+            root_keys,
+            Verifier::default(),
+        )
+    }
+
+    /// Like [`Database::from_root_with_trusted_keys`], but the root metadata must sign itself
+    /// according to the [`KeyIdPolicy`](crate::crypto::KeyIdPolicy) of `verifier`, which the
+    /// database uses from then on.
+    pub fn from_root_with_trusted_keys_and_verifier<'a, I>(
+        raw_root: &RawSignedMetadata<D, RootMetadata>,
+        root_threshold: u32,
+        root_keys: I,
+        verifier: Verifier,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        let (verified_root, trusted_key_id_aliases) = {
             // Make sure the keys signed the root.
             let new_root = verify::verify_signatures(
                 &MetadataPath::root(),
@@ -50,18 +88,14 @@ impl<D: Pouf> Database<D> {
             )?;
 
             // Make sure the root signed itself.
-            verify::verify_signatures(
+            let key_id_aliases = verifier.accepted_key_id_aliases(&new_root);
+            let new_root = verifier.verify_accepted_signatures(
                 &MetadataPath::root(),
                 raw_root,
                 new_root.root().threshold(),
-                new_root.keys().iter().filter_map(|(k, v)| {
-                    if new_root.root().key_ids().contains(k) {
-                        Some(v)
-                    } else {
-                        None
-                    }
-                }),
-            )?
+                accepted_role_keys(&new_root, new_root.root(), &key_id_aliases),
+            )?;
+            (new_root, key_id_aliases)
         };
 
         Ok(Database {
@@ -70,6 +104,14 @@ impl<D: Pouf> Database<D> {
             trusted_targets: None,
             trusted_timestamp: None,
             trusted_delegations: HashMap::new(),
+            delegation_parents: HashMap::new(),
+            trusted_key_id_aliases,
+            revocations: HashMap::new(),
+            published_revocations: RevocationList::new(),
+            clock: Arc::new(SystemClock),
+            expiration_grace_policy: ExpirationGracePolicy::default(),
+            reloading: false,
+            verifier: verifier.clock(Arc::new(SystemClock)),
             pouf: PhantomData,
         })
     }
@@ -82,18 +124,31 @@ impl<D: Pouf> Database<D> {
     /// to potential parser exploits. This method should only be used if the metadata is loaded from
     /// a trusted source.
     pub fn from_trusted_root(raw_root: &RawSignedMetadata<D, RootMetadata>) -> Result<Self> {
-        let verified_root = {
+        Self::from_trusted_root_with_verifier(raw_root, Verifier::default())
+    }
+
+    /// Create a new [`Database`] struct from a piece of metadata that is assumed to be trusted,
+    /// checking signatures with `verifier`, such as one that accepts the key IDs the root uses.
+    ///
+    /// **WARNING**: This is trust-on-first-use (TOFU). See [`Database::from_trusted_root`].
+    pub fn from_trusted_root_with_verifier(
+        raw_root: &RawSignedMetadata<D, RootMetadata>,
+        verifier: Verifier,
+    ) -> Result<Self> {
+        let (verified_root, trusted_key_id_aliases) = {
             // **WARNING**: By deserializing the metadata before verification, we are exposing us
             // to parser exploits.
             let unverified_root = raw_root.parse_untrusted()?.assume_valid()?;
 
             // Make sure the root signed itself.
-            verify::verify_signatures(
+            let key_id_aliases = verifier.accepted_key_id_aliases(&unverified_root);
+            let verified_root = verifier.verify_accepted_signatures(
                 &MetadataPath::root(),
                 raw_root,
                 unverified_root.root().threshold(),
-                unverified_root.root_keys(),
-            )?
+                accepted_role_keys(&unverified_root, unverified_root.root(), &key_id_aliases),
+            )?;
+            (verified_root, key_id_aliases)
         };
 
         Ok(Database {
@@ -102,6 +157,14 @@ impl<D: Pouf> Database<D> {
             trusted_targets: None,
             trusted_timestamp: None,
             trusted_delegations: HashMap::new(),
+            delegation_parents: HashMap::new(),
+            trusted_key_id_aliases,
+            revocations: HashMap::new(),
+            published_revocations: RevocationList::new(),
+            clock: Arc::new(SystemClock),
+            expiration_grace_policy: ExpirationGracePolicy::default(),
+            reloading: false,
+            verifier: verifier.clock(Arc::new(SystemClock)),
             pouf: PhantomData,
         })
     }
@@ -117,22 +180,24 @@ impl<D: Pouf> Database<D> {
     where
         I: IntoIterator<Item = &'a PublicKey>,
     {
-        Self::from_metadata_with_trusted_keys_and_start_time(
-            &Utc::now(),
+        Self::from_metadata_with_trusted_keys_and_clock(
             metadata_set,
             root_threshold,
             root_keys,
+            Arc::new(SystemClock),
         )
     }
 
     /// Create a new [`Database`] struct from a set of metadata that is assumed to be trusted. The
     /// signed root metadata in the `metadata_set` must be signed with at least a `root_threshold`
     /// of the provided root_keys. It is not necessary for the root metadata to contain these keys.
-    pub fn from_metadata_with_trusted_keys_and_start_time<'a, I>(
-        start_time: &DateTime<Utc>,
+    ///
+    /// The `clock` is used to determine if metadata has expired.
+    pub fn from_metadata_with_trusted_keys_and_clock<'a, I>(
         metadata_set: &RawSignedMetadataSet<D>,
         root_threshold: u32,
         root_keys: I,
+        clock: Arc<dyn Clock>,
     ) -> Result<Self>
     where
         I: IntoIterator<Item = &'a PublicKey>,
@@ -145,8 +210,9 @@ impl<D: Pouf> Database<D> {
                 version: MetadataVersion::None,
             });
         };
+        db.set_clock(clock);
 
-        db.update_metadata_after_root(start_time, metadata_set)?;
+        db.update_metadata_after_root(metadata_set)?;
 
         Ok(db)
     }
@@ -159,19 +225,21 @@ impl<D: Pouf> Database<D> {
     /// signed properly. This exposes us to potential parser exploits. This method should only be
     /// used if the metadata is loaded from a trusted source.
     pub fn from_trusted_metadata(metadata_set: &RawSignedMetadataSet<D>) -> Result<Self> {
-        Self::from_trusted_metadata_with_start_time(metadata_set, &Utc::now())
+        Self::from_trusted_metadata_with_clock(metadata_set, Arc::new(SystemClock))
     }
 
     /// Create a new [`Database`] struct from a set of metadata that is assumed to be trusted.
     ///
+    /// The `clock` is used to determine if metadata has expired.
+    ///
     /// **WARNING**: This is trust-on-first-use (TOFU) and offers weaker security guarantees than
     /// the related method [`Database::from_metadata_with_trusted_keys`] because this method needs
     /// to deserialize the root metadata from `metadata_set` before we have verified it has been
     /// signed properly. This exposes us to potential parser exploits. This method should only be
     /// used if the metadata is loaded from a trusted source.
-    pub fn from_trusted_metadata_with_start_time(
+    pub fn from_trusted_metadata_with_clock(
         metadata_set: &RawSignedMetadataSet<D>,
-        start_time: &DateTime<Utc>,
+        clock: Arc<dyn Clock>,
     ) -> Result<Self> {
         let mut db = if let Some(root) = metadata_set.root() {
             Database::from_trusted_root(root)?
@@ -181,12 +249,55 @@ impl<D: Pouf> Database<D> {
                 version: MetadataVersion::None,
             });
         };
+        db.set_clock(clock);
 
-        db.update_metadata_after_root(start_time, metadata_set)?;
+        db.update_metadata_after_root(metadata_set)?;
 
         Ok(db)
     }
 
+    /// The [Clock] used to determine if metadata has expired. Defaults to the [SystemClock].
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Change the [Clock] used to determine if metadata has expired, and if keyless signing
+    /// certificates are valid.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.verifier = std::mem::take(&mut self.verifier).clock(Arc::clone(&clock));
+        self.clock = clock;
+    }
+
+    /// The [Verifier] used to check the signatures of updated metadata.
+    pub fn verifier(&self) -> &Verifier {
+        &self.verifier
+    }
+
+    /// Change the [Verifier] used to check the signatures of updated metadata, such as to share a
+    /// [SignatureCache](crate::verify::SignatureCache) between databases. The verifier uses the
+    /// database's [Clock].
+    pub fn set_verifier(&mut self, verifier: Verifier) {
+        self.trusted_key_id_aliases = verifier.accepted_key_id_aliases(&self.trusted_root);
+        self.verifier = verifier.clock(Arc::clone(&self.clock));
+    }
+
+    /// The policy for using expired metadata. By default, expired metadata is never used.
+    pub fn expiration_grace_policy(&self) -> &ExpirationGracePolicy {
+        &self.expiration_grace_policy
+    }
+
+    /// Change the policy for using expired metadata.
+    pub fn set_expiration_grace_policy(&mut self, policy: ExpirationGracePolicy) {
+        self.expiration_grace_policy = policy;
+    }
+
+    /// Mark whether the following updates reload metadata that was already trusted, such as from
+    /// a client's local repository. Only these updates may accept metadata within the
+    /// [ExpirationGracePolicy]; updates from a remote repository always reject expired metadata.
+    pub(crate) fn set_reloading(&mut self, reloading: bool) {
+        self.reloading = reloading;
+    }
+
     /// An immutable reference to the root metadata.
     pub fn trusted_root(&self) -> &Verified<RootMetadata> {
         &self.trusted_root
@@ -212,17 +323,9 @@ impl<D: Pouf> Database<D> {
         &self.trusted_delegations
     }
 
-    /// Verify and update metadata. Returns true if any of the metadata was updated.
+    /// Verify and update metadata, using the database's [Clock] to determine if the metadata is
+    /// expired. Returns true if any of the metadata was updated.
     pub fn update_metadata(&mut self, metadata: &RawSignedMetadataSet<D>) -> Result<bool> {
-        self.update_metadata_with_start_time(metadata, &Utc::now())
-    }
-
-    /// Verify and update metadata. Returns true if any of the metadata was updated.
-    pub fn update_metadata_with_start_time(
-        &mut self,
-        metadata: &RawSignedMetadataSet<D>,
-        start_time: &DateTime<Utc>,
-    ) -> Result<bool> {
         let updated = if let Some(root) = metadata.root() {
             self.update_root(root)?;
             true
@@ -230,7 +333,7 @@ impl<D: Pouf> Database<D> {
             false
         };
 
-        if self.update_metadata_after_root(start_time, metadata)? {
+        if self.update_metadata_after_root(metadata)? {
             Ok(true)
         } else {
             Ok(updated)
@@ -239,24 +342,23 @@ impl<D: Pouf> Database<D> {
 
     fn update_metadata_after_root(
         &mut self,
-        start_time: &DateTime<Utc>,
         metadata_set: &RawSignedMetadataSet<D>,
     ) -> Result<bool> {
         let mut updated = false;
         if let Some(timestamp) = metadata_set.timestamp() {
-            if self.update_timestamp(start_time, timestamp)?.is_some() {
+            if self.update_timestamp(timestamp)?.is_some() {
                 updated = true;
             }
         }
 
         if let Some(snapshot) = metadata_set.snapshot() {
-            if self.update_snapshot(start_time, snapshot)? {
+            if self.update_snapshot(snapshot)? {
                 updated = true;
             }
         }
 
         if let Some(targets) = metadata_set.targets() {
-            if self.update_targets(start_time, targets)? {
+            if self.update_targets(targets)? {
                 updated = true;
             }
         }
@@ -266,8 +368,9 @@ impl<D: Pouf> Database<D> {
 
     /// Verify and update the root metadata.
     pub fn update_root(&mut self, raw_root: &RawSignedMetadata<D, RootMetadata>) -> Result<()> {
-        let verified = {
+        let (verified, key_id_aliases) = {
             let trusted_root = &self.trusted_root;
+            let revoked = self.revoked_key_ids(&MetadataPath::root(), None);
 
             /////////////////////////////////////////
             // TUF-1.0.5 §5.1.3:
@@ -279,19 +382,26 @@ impl<D: Pouf> Database<D> {
             //     abort the update cycle, and report the signature failure. On the next update
             //     cycle, begin at step 0 and version N of the root metadata file.  Verify the
             //     trusted root signed the new root.
-            let new_root = verify::verify_signatures(
+            let new_root = self.verifier.verify_accepted_signatures(
                 &MetadataPath::root(),
                 raw_root,
                 trusted_root.root().threshold(),
-                trusted_root.root_keys(),
+                accepted_role_keys(
+                    trusted_root,
+                    trusted_root.root(),
+                    &self.trusted_key_id_aliases,
+                )
+                .filter(|(_, key)| !revoked.contains(key.key_id())),
             )?;
 
             // Verify the new root signed itself.
-            let new_root = verify::verify_signatures(
+            let new_key_id_aliases = self.verifier.accepted_key_id_aliases(&new_root);
+            let new_root = self.verifier.verify_accepted_signatures(
                 &MetadataPath::root(),
                 raw_root,
                 new_root.root().threshold(),
-                new_root.root_keys(),
+                accepted_role_keys(&new_root, new_root.root(), &new_key_id_aliases)
+                    .filter(|(_, key)| !revoked.contains(key.key_id())),
             )?;
 
             /////////////////////////////////////////
@@ -334,7 +444,7 @@ impl<D: Pouf> Database<D> {
             // FIXME: root metadata expiration is performed in Client. We should restructure things
             // such that it is performed here.
 
-            new_root
+            (new_root, new_key_id_aliases)
         };
 
         /////////////////////////////////////////
@@ -356,18 +466,20 @@ impl<D: Pouf> Database<D> {
         //     1.6. Set the trusted root metadata file to the new root metadata file.
 
         self.trusted_root = verified;
+        self.trusted_key_id_aliases = key_id_aliases;
 
         Ok(())
     }
 
-    /// Verify and update the timestamp metadata.
+    /// Verify and update the timestamp metadata, using the database's [Clock] to determine if it
+    /// is expired. The time it is accepted at is recorded with [Clock::record_trusted_time].
     ///
     /// Returns a reference to the parsed metadata if the metadata was newer.
     pub fn update_timestamp(
         &mut self,
-        start_time: &DateTime<Utc>,
         raw_timestamp: &RawSignedMetadata<D, TimestampMetadata>,
     ) -> Result<Option<&Verified<TimestampMetadata>>> {
+        let start_time = &self.clock.now()?;
         let verified = {
             // FIXME(https://github.com/theupdateframework/specification/issues/113) Should we
             // check if the root metadata is expired here? We do that in the other `Database::update_*`
@@ -382,11 +494,17 @@ impl<D: Pouf> Database<D> {
             //     timestamp metadata file is not properly signed, discard it, abort the update
             //     cycle, and report the signature failure.
 
-            let new_timestamp = verify::verify_signatures(
+            let revoked = self.revoked_key_ids(&MetadataPath::timestamp(), None);
+            let new_timestamp = self.verifier.verify_accepted_signatures(
                 &MetadataPath::timestamp(),
                 raw_timestamp,
                 trusted_root.timestamp().threshold(),
-                trusted_root.timestamp_keys(),
+                accepted_role_keys(
+                    trusted_root,
+                    trusted_root.timestamp(),
+                    &self.trusted_key_id_aliases,
+                )
+                .filter(|(_, key)| !revoked.contains(key.key_id())),
             )?;
 
             /////////////////////////////////////////
@@ -445,27 +563,27 @@ impl<D: Pouf> Database<D> {
             //     timestamp metadata file has expired, discard it, abort the update cycle, and
             //     report the potential freeze attack.
 
-            if new_timestamp.expires() <= start_time {
-                return Err(Error::ExpiredMetadata {
-                    path: MetadataPath::timestamp(),
-                    expiration: *new_timestamp.expires(),
-                    now: *start_time,
-                });
-            }
+            self.check_expiration(
+                &MetadataPath::timestamp(),
+                new_timestamp.expires(),
+                start_time,
+            )?;
 
             new_timestamp
         };
 
+        self.clock.record_trusted_time(*start_time);
         self.trusted_timestamp = Some(verified);
         Ok(self.trusted_timestamp.as_ref())
     }
 
-    /// Verify and update the snapshot metadata.
+    /// Verify and update the snapshot metadata, using the database's [Clock] to determine if it
+    /// is expired.
     pub fn update_snapshot(
         &mut self,
-        start_time: &DateTime<Utc>,
         raw_snapshot: &RawSignedMetadata<D, SnapshotMetadata>,
     ) -> Result<bool> {
+        let start_time = &self.clock.now()?;
         let verified = {
             /////////////////////////////////////////
             // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
@@ -518,11 +636,17 @@ impl<D: Pouf> Database<D> {
             //     is not signed as required, discard it, abort the update cycle, and report the
             //     signature failure.
 
-            let new_snapshot = verify::verify_signatures(
+            let revoked = self.revoked_key_ids(&MetadataPath::snapshot(), None);
+            let new_snapshot = self.verifier.verify_accepted_signatures(
                 &MetadataPath::snapshot(),
                 raw_snapshot,
                 trusted_root.snapshot().threshold(),
-                trusted_root.snapshot_keys(),
+                accepted_role_keys(
+                    trusted_root,
+                    trusted_root.snapshot(),
+                    &self.trusted_key_id_aliases,
+                )
+                .filter(|(_, key)| !revoked.contains(key.key_id())),
             )?;
 
             /////////////////////////////////////////
@@ -619,10 +743,14 @@ impl<D: Pouf> Database<D> {
                 None => return,
             };
             let mut purge = HashSet::new();
-            for (role, trusted_definition) in trusted_snapshot.meta().iter() {
-                let trusted_delegation = match self.trusted_delegations.get(role) {
+            for (role, trusted_delegation) in self.trusted_delegations.iter() {
+                // Delegations that are no longer listed in the snapshot are no longer trusted.
+                let trusted_definition = match trusted_snapshot.meta().get(role) {
                     Some(d) => d,
-                    None => continue,
+                    None => {
+                        let _ = purge.insert(role.clone());
+                        continue;
+                    }
                 };
 
                 if trusted_delegation.version() > trusted_definition.version() {
@@ -636,27 +764,35 @@ impl<D: Pouf> Database<D> {
 
         for role in &purge {
             let _ = self.trusted_delegations.remove(role);
+            let _ = self.delegation_parents.remove(role);
         }
     }
 
-    /// Verify and update the targets metadata.
+    /// Verify and update the targets metadata, using the database's [Clock] to determine if it is
+    /// expired.
     pub fn update_targets(
         &mut self,
-        start_time: &DateTime<Utc>,
         raw_targets: &RawSignedMetadata<D, TargetsMetadata>,
     ) -> Result<bool> {
+        let start_time = &self.clock.now()?;
         let verified = {
             // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
             // this metadata expired isn't part of the spec. Do we actually want to do this?
             let trusted_root = self.trusted_root_unexpired(start_time)?;
             let trusted_targets_version = self.trusted_targets.as_ref().map(|t| t.version());
+            let revoked = self.revoked_key_ids(&MetadataPath::targets(), None);
 
             self.verify_target_or_delegated_target(
                 start_time,
                 &MetadataPath::targets(),
                 raw_targets,
                 trusted_root.targets().threshold(),
-                trusted_root.targets_keys(),
+                accepted_role_keys(
+                    trusted_root,
+                    trusted_root.targets(),
+                    &self.trusted_key_id_aliases,
+                )
+                .filter(|(_, key)| !revoked.contains(key.key_id())),
                 trusted_targets_version,
             )?
         };
@@ -669,14 +805,15 @@ impl<D: Pouf> Database<D> {
         }
     }
 
-    /// Verify and update a delegation metadata.
+    /// Verify and update a delegation metadata, using the database's [Clock] to determine if it is
+    /// expired.
     pub fn update_delegated_targets(
         &mut self,
-        start_time: &DateTime<Utc>,
         parent_role: &MetadataPath,
         role: &MetadataPath,
         raw_delegated_targets: &RawSignedMetadata<D, TargetsMetadata>,
     ) -> Result<bool> {
+        let start_time = &self.clock.now()?;
         let verified = {
             // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
             // this metadata expired isn't part of the spec. Do we actually want to do this?
@@ -700,19 +837,25 @@ impl<D: Pouf> Database<D> {
 
             let trusted_delegated_targets_version =
                 self.trusted_delegations.get(role).map(|t| t.version());
+            let revoked = self.revoked_key_ids(role, Some(parent_role));
 
             self.verify_target_or_delegated_target(
                 start_time,
                 role,
                 raw_delegated_targets,
                 threshold,
-                keys.into_iter(),
+                keys.into_iter()
+                    .filter(|key| !revoked.contains(key.key_id()))
+                    .map(|key| (key.key_id(), key)),
                 trusted_delegated_targets_version,
             )?
         };
 
         if let Some(verified) = verified {
             let _ = self.trusted_delegations.insert(role.clone(), verified);
+            let _ = self
+                .delegation_parents
+                .insert(role.clone(), parent_role.clone());
             Ok(true)
         } else {
             Ok(false)
@@ -725,7 +868,7 @@ impl<D: Pouf> Database<D> {
         role: &MetadataPath,
         raw_targets: &RawSignedMetadata<D, TargetsMetadata>,
         trusted_targets_threshold: u32,
-        trusted_targets_keys: impl Iterator<Item = &'a PublicKey>,
+        trusted_targets_keys: impl Iterator<Item = (&'a KeyId, &'a PublicKey)>,
         trusted_targets_version: Option<u32>,
     ) -> Result<Option<Verified<TargetsMetadata>>> {
         // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
@@ -767,7 +910,7 @@ impl<D: Pouf> Database<D> {
         //     If the new targets metadata file is not signed as required, discard it, abort
         //     the update cycle, and report the failure.
 
-        let new_targets = verify::verify_signatures(
+        let new_targets = self.verifier.verify_accepted_signatures(
             role,
             raw_targets,
             trusted_targets_threshold,
@@ -814,13 +957,7 @@ impl<D: Pouf> Database<D> {
         //     metadata file is expired, discard it, abort the update cycle, and report the
         //     potential freeze attack.
 
-        if new_targets.expires() <= start_time {
-            return Err(Error::ExpiredMetadata {
-                path: role.clone(),
-                expiration: *new_targets.expires(),
-                now: *start_time,
-            });
-        }
+        self.check_expiration(role, new_targets.expires(), start_time)?;
 
         Ok(Some(new_targets))
     }
@@ -878,29 +1015,219 @@ impl<D: Pouf> Database<D> {
         Ok(None)
     }
 
+    /// Verify and apply a key revocation, which must be signed by the revoked key. From then on,
+    /// the key doesn't count towards the signature threshold of the revoked role, or of any role
+    /// delegated from it. Metadata that is already trusted stays trusted until it is updated.
+    ///
+    /// Returns `false` if the key was already revoked for the role.
+    pub fn revoke_key(&mut self, revocation: &SignedKeyRevocation) -> Result<bool> {
+        let role = revocation.revocation().role();
+        let key_id = revocation.revocation().key_id();
+
+        if self
+            .revocations
+            .get(role)
+            .map_or(false, |revocations| revocations.contains_key(key_id))
+        {
+            return Ok(false);
+        }
+
+        let key =
+            self.authorized_key(role, key_id)
+                .cloned()
+                .ok_or_else(|| Error::UnauthorizedKey {
+                    role: role.clone(),
+                    key_id: key_id.clone(),
+                })?;
+        revocation.verify::<D>(key_id, &key)?;
+
+        // Keys are revoked by their canonical ID, whichever ID the revocation names them by.
+        let revocations = self.revocations.entry(role.clone()).or_default();
+        if revocations.contains_key(key.key_id()) {
+            return Ok(false);
+        }
+        let _ = revocations.insert(key.key_id().clone(), revocation.clone());
+        let _ = self.published_revocations.insert(revocation.clone());
+
+        Ok(true)
+    }
+
+    /// Apply every revocation in `revocations` that can be verified, skipping the rest, since anyone
+    /// can add to a published list. Revocations skipped because their key is not currently
+    /// authorized to sign their role are still kept in the
+    /// [published revocations](Database::published_revocations). Returns the number of newly
+    /// revoked keys.
+    pub fn update_revocations(&mut self, revocations: &RevocationList) -> Result<usize> {
+        let mut revoked = 0;
+        for revocation in revocations.revocations() {
+            match self.revoke_key(revocation) {
+                Ok(true) => revoked += 1,
+                Ok(false) => {}
+                Err(Error::UnauthorizedKey { role, key_id }) => {
+                    warn!(
+                        "Skipping revocation of key {} for {}: the key is not authorized",
+                        key_id, role
+                    );
+                    let _ = self.published_revocations.insert(revocation.clone());
+                }
+                Err(err) => {
+                    warn!(
+                        "Skipping revocation of key {} for {}: {}",
+                        revocation.revocation().key_id(),
+                        revocation.revocation().role(),
+                        err
+                    );
+                }
+            }
+        }
+
+        Ok(revoked)
+    }
+
+    /// The revocations applied to this database.
+    pub fn revocations(&self) -> RevocationList {
+        let mut list = RevocationList::new();
+        let mut roles = self.revocations.keys().collect::<Vec<_>>();
+        roles.sort();
+        for role in roles {
+            for revocation in self.revocations[role].values() {
+                let _ = list.insert(revocation.clone());
+            }
+        }
+        list
+    }
+
+    /// Every revocation this database has been given, including those it skipped because the key
+    /// isn't authorized for a role it trusts. This is the list a repository publishes, see
+    /// [RepoBuilder::add_key_revocation](crate::repo_builder::RepoBuilder::add_key_revocation).
+    pub fn published_revocations(&self) -> &RevocationList {
+        &self.published_revocations
+    }
+
+    /// Whether the key with the canonical ID `key_id` is revoked for `role`, either directly or for
+    /// a role that `role` was delegated from.
+    pub fn is_key_revoked(&self, role: &MetadataPath, key_id: &KeyId) -> bool {
+        self.revoked_key_ids(role, self.delegation_parents.get(role))
+            .contains(key_id)
+    }
+
+    /// The keys revoked for `role`, or for `parent_role` or any of its ancestors.
+    fn revoked_key_ids(
+        &self,
+        role: &MetadataPath,
+        parent_role: Option<&MetadataPath>,
+    ) -> HashSet<KeyId> {
+        let mut revoked = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut next = Some(role);
+        let mut parent_role = parent_role;
+
+        while let Some(role) = next {
+            // Delegations may form cycles.
+            if !visited.insert(role) {
+                break;
+            }
+
+            if let Some(revocations) = self.revocations.get(role) {
+                revoked.extend(revocations.keys().cloned());
+            }
+
+            next = parent_role
+                .take()
+                .or_else(|| self.delegation_parents.get(role));
+        }
+
+        revoked
+    }
+
+    /// Find the public key `key_id` if it is authorized to sign `role`. Keys the trusted root lists
+    /// under an alias are found by either ID if the verifier's key ID policy accepts the alias.
+    fn authorized_key(&self, role: &MetadataPath, key_id: &KeyId) -> Option<&PublicKey> {
+        fn find_key<'a, M: Metadata>(
+            root: &'a RootMetadata,
+            role: &'a RoleDefinition<M>,
+            accepted_aliases: &'a HashSet<KeyId>,
+            key_id: &KeyId,
+        ) -> Option<&'a PublicKey> {
+            accepted_role_keys(root, role, accepted_aliases)
+                .find(|(id, key)| *id == key_id || key.key_id() == key_id)
+                .map(|(_, key)| key)
+        }
+
+        let root = &self.trusted_root;
+        let aliases = &self.trusted_key_id_aliases;
+        if role == &MetadataPath::root() {
+            return find_key(root, root.root(), aliases, key_id);
+        } else if role == &MetadataPath::timestamp() {
+            return find_key(root, root.timestamp(), aliases, key_id);
+        } else if role == &MetadataPath::snapshot() {
+            return find_key(root, root.snapshot(), aliases, key_id);
+        } else if role == &MetadataPath::targets() {
+            return find_key(root, root.targets(), aliases, key_id);
+        }
+
+        self.trusted_targets
+            .iter()
+            .chain(self.trusted_delegations.values())
+            .map(|targets| targets.delegations())
+            .find_map(|delegations| {
+                delegations
+                    .roles()
+                    .iter()
+                    .filter(|delegation| delegation.name() == role)
+                    .find(|delegation| delegation.key_ids().contains(key_id))
+                    .and_then(|_| delegations.keys().get(key_id))
+            })
+    }
+
     /// Get a reference to the description needed to verify the target defined by the given
     /// `TargetPath`. Returns an `Error` if the target is not defined in the trusted
     /// metadata. This may mean the target exists somewhere in the metadata, but the chain of trust
     /// to that target may be invalid or incomplete.
+    ///
+    /// This never returns a description verified with expired metadata, even if the
+    /// [ExpirationGracePolicy] allows it. Use [Database::target_description_with_freshness] to
+    /// look up targets using stale metadata.
     pub fn target_description(&self, target_path: &TargetPath) -> Result<TargetDescription> {
-        self.target_description_with_start_time(&Utc::now(), target_path)
+        let start_time = self.clock.now()?;
+        let (description, freshness) =
+            self.target_description_with_freshness_at(&start_time, target_path)?;
+        freshness.ensure_fresh(&start_time)?;
+        Ok(description)
     }
 
     /// Get a reference to the description needed to verify the target defined by the given
-    /// `TargetPath`. Returns an `Error` if the target is not defined in the trusted
-    /// metadata. This may mean the target exists somewhere in the metadata, but the chain of trust
-    /// to that target may be invalid or incomplete.
-    pub fn target_description_with_start_time(
+    /// `TargetPath`, along with whether any of the metadata used to verify it has expired.
+    ///
+    /// Expired metadata is only used if it is still within the grace period allowed by the
+    /// database's [ExpirationGracePolicy], in which case the description is marked as
+    /// [Freshness::Stale]. Returns an `Error` if the target is not defined in the trusted
+    /// metadata.
+    pub fn target_description_with_freshness(
+        &self,
+        target_path: &TargetPath,
+    ) -> Result<(TargetDescription, Freshness)> {
+        let start_time = self.clock.now()?;
+        self.target_description_with_freshness_at(&start_time, target_path)
+    }
+
+    fn target_description_with_freshness_at(
         &self,
         start_time: &DateTime<Utc>,
         target_path: &TargetPath,
-    ) -> Result<TargetDescription> {
-        let _ = self.trusted_root_unexpired(start_time)?;
-        let _ = self.trusted_snapshot_unexpired(start_time)?;
-        let targets = self.trusted_targets_unexpired(start_time)?;
+    ) -> Result<(TargetDescription, Freshness)> {
+        // Lookups may use expired metadata that is still within its grace period.
+        let _ = self.freshness(start_time, &[])?;
+        let targets = self
+            .trusted_targets()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::targets(),
+                version: MetadataVersion::None,
+            })?;
 
         if let Some(d) = targets.targets().get(target_path) {
-            return Ok(d.clone());
+            let freshness = self.freshness(start_time, &[])?;
+            return Ok((d.clone(), freshness));
         }
 
         fn lookup<'a, D: Pouf>(
@@ -912,6 +1239,7 @@ impl<D: Pouf> Database<D> {
             delegations: &'a Delegations,
             parents: &[HashSet<TargetPath>],
             visited: &mut HashSet<&'a MetadataPath>,
+            chain: &mut Vec<MetadataPath>,
         ) -> (bool, Option<TargetDescription>) {
             for delegation in delegations.roles() {
                 if visited.contains(delegation.name()) {
@@ -931,10 +1259,19 @@ impl<D: Pouf> Database<D> {
                     None => return (delegation.terminating(), None),
                 };
 
-                if trusted_delegation.expires() <= start_time {
+                if tuf
+                    .check_expiration_with_grace(
+                        delegation.name(),
+                        trusted_delegation.expires(),
+                        start_time,
+                    )
+                    .is_err()
+                {
                     return (delegation.terminating(), None);
                 }
 
+                chain.push(delegation.name().clone());
+
                 if let Some(target) = trusted_delegation.targets().get(target_path) {
                     return (delegation.terminating(), Some(target.clone()));
                 }
@@ -954,13 +1291,14 @@ impl<D: Pouf> Database<D> {
                         trusted_child_delegations,
                         &new_parents,
                         visited,
+                        chain,
                     );
-                    if term {
-                        return (true, res);
-                    } else if res.is_some() {
+                    if term || res.is_some() {
                         return (term, res);
                     }
                 }
+
+                chain.pop();
             }
             (default_terminate, None)
         }
@@ -970,7 +1308,8 @@ impl<D: Pouf> Database<D> {
             Err(Error::TargetNotFound(target_path.clone()))
         } else {
             let mut visited = HashSet::new();
-            lookup(
+            let mut chain = vec![];
+            let description = lookup(
                 start_time,
                 self,
                 false,
@@ -979,28 +1318,133 @@ impl<D: Pouf> Database<D> {
                 delegations,
                 &[],
                 &mut visited,
+                &mut chain,
             )
             .1
-            .ok_or_else(|| Error::TargetNotFound(target_path.clone()))
+            .ok_or_else(|| Error::TargetNotFound(target_path.clone()))?;
+
+            let freshness = self.freshness(start_time, &chain)?;
+            Ok((description, freshness))
         }
     }
 
+    /// Determine which of the trusted metadata needed to verify a target has expired, given the
+    /// chain of `delegated_roles` that led to the target. Returns an `Error` if any of it has
+    /// expired beyond its grace period.
+    pub(crate) fn freshness(
+        &self,
+        start_time: &DateTime<Utc>,
+        delegated_roles: &[MetadataPath],
+    ) -> Result<Freshness> {
+        let mut expired = BTreeMap::new();
+        let mut check = |role: &MetadataPath, expiration: &DateTime<Utc>| -> Result<()> {
+            self.check_expiration_with_grace(role, expiration, start_time)?;
+            if expiration <= start_time {
+                expired.insert(role.clone(), *expiration);
+            }
+            Ok(())
+        };
+
+        check(&MetadataPath::root(), self.trusted_root.expires())?;
+
+        if let Some(timestamp) = &self.trusted_timestamp {
+            check(&MetadataPath::timestamp(), timestamp.expires())?;
+        }
+
+        let snapshot = self
+            .trusted_snapshot
+            .as_ref()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::snapshot(),
+                version: MetadataVersion::None,
+            })?;
+        check(&MetadataPath::snapshot(), snapshot.expires())?;
+
+        let targets = self
+            .trusted_targets
+            .as_ref()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::targets(),
+                version: MetadataVersion::None,
+            })?;
+        check(&MetadataPath::targets(), targets.expires())?;
+
+        for role in delegated_roles {
+            let delegation =
+                self.trusted_delegations
+                    .get(role)
+                    .ok_or_else(|| Error::MetadataNotFound {
+                        path: role.clone(),
+                        version: MetadataVersion::None,
+                    })?;
+            check(role, delegation.expires())?;
+        }
+
+        if expired.is_empty() {
+            Ok(Freshness::Fresh)
+        } else {
+            Ok(Freshness::Stale(expired))
+        }
+    }
+
+    /// Returns an `Error` if metadata for `role` that expires at `expiration` has expired at
+    /// `start_time`. Updates use this strict check, so the [ExpirationGracePolicy] never lets
+    /// newly fetched expired metadata become trusted, unless it is being
+    /// [reloaded](Database::set_reloading).
+    fn check_expiration(
+        &self,
+        role: &MetadataPath,
+        expiration: &DateTime<Utc>,
+        start_time: &DateTime<Utc>,
+    ) -> Result<()> {
+        if self.reloading {
+            self.check_expiration_with_grace(role, expiration, start_time)
+        } else {
+            check_deadline(role, expiration, Some(*expiration), start_time)
+        }
+    }
+
+    /// Returns an `Error` if already trusted metadata for `role` that expires at `expiration` can
+    /// no longer be used for lookups at `start_time`, taking the [ExpirationGracePolicy] into
+    /// account.
+    fn check_expiration_with_grace(
+        &self,
+        role: &MetadataPath,
+        expiration: &DateTime<Utc>,
+        start_time: &DateTime<Utc>,
+    ) -> Result<()> {
+        let deadline = match self.expiration_grace_policy.grace_period(role) {
+            Some(grace_period) => expiration.checked_add_signed(grace_period),
+            None => Some(*expiration),
+        };
+        check_deadline(role, expiration, deadline, start_time)
+    }
+
     fn purge_metadata(&mut self) {
         self.trusted_snapshot = None;
         self.trusted_targets = None;
         self.trusted_timestamp = None;
         self.trusted_delegations.clear();
+        self.delegation_parents.clear();
     }
 
-    fn trusted_root_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&RootMetadata> {
-        let trusted_root = &self.trusted_root;
-        if trusted_root.expires() <= start_time {
+    /// Check the trusted root metadata has not expired according to the database's [Clock],
+    /// which guards against a freeze attack once the root metadata is up to date.
+    pub(crate) fn ensure_root_unexpired(&self) -> Result<()> {
+        let now = self.clock.now()?;
+        if self.trusted_root.expires() <= &now {
             return Err(Error::ExpiredMetadata {
                 path: MetadataPath::root(),
-                expiration: *trusted_root.expires(),
-                now: *start_time,
+                expiration: *self.trusted_root.expires(),
+                now,
             });
         }
+        Ok(())
+    }
+
+    fn trusted_root_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&RootMetadata> {
+        let trusted_root = &self.trusted_root;
+        self.check_expiration(&MetadataPath::root(), trusted_root.expires(), start_time)?;
         Ok(trusted_root)
     }
 
@@ -1010,13 +1454,11 @@ impl<D: Pouf> Database<D> {
     ) -> Result<&TimestampMetadata> {
         match self.trusted_timestamp {
             Some(ref trusted_timestamp) => {
-                if trusted_timestamp.expires() <= start_time {
-                    return Err(Error::ExpiredMetadata {
-                        path: MetadataPath::timestamp(),
-                        expiration: *trusted_timestamp.expires(),
-                        now: *start_time,
-                    });
-                }
+                self.check_expiration(
+                    &MetadataPath::timestamp(),
+                    trusted_timestamp.expires(),
+                    start_time,
+                )?;
                 Ok(trusted_timestamp)
             }
             None => Err(Error::MetadataNotFound {
@@ -1029,13 +1471,11 @@ impl<D: Pouf> Database<D> {
     fn trusted_snapshot_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&SnapshotMetadata> {
         match self.trusted_snapshot {
             Some(ref trusted_snapshot) => {
-                if trusted_snapshot.expires() <= start_time {
-                    return Err(Error::ExpiredMetadata {
-                        path: MetadataPath::snapshot(),
-                        expiration: *trusted_snapshot.expires(),
-                        now: *start_time,
-                    });
-                }
+                self.check_expiration(
+                    &MetadataPath::snapshot(),
+                    trusted_snapshot.expires(),
+                    start_time,
+                )?;
                 Ok(trusted_snapshot)
             }
             None => Err(Error::MetadataNotFound {
@@ -1048,13 +1488,11 @@ impl<D: Pouf> Database<D> {
     fn trusted_targets_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&TargetsMetadata> {
         match self.trusted_targets {
             Some(ref trusted_targets) => {
-                if trusted_targets.expires() <= start_time {
-                    return Err(Error::ExpiredMetadata {
-                        path: MetadataPath::targets(),
-                        expiration: *trusted_targets.expires(),
-                        now: *start_time,
-                    });
-                }
+                self.check_expiration(
+                    &MetadataPath::targets(),
+                    trusted_targets.expires(),
+                    start_time,
+                )?;
                 Ok(trusted_targets)
             }
             None => Err(Error::MetadataNotFound {
@@ -1065,6 +1503,118 @@ impl<D: Pouf> Database<D> {
     }
 }
 
+fn check_deadline(
+    role: &MetadataPath,
+    expiration: &DateTime<Utc>,
+    deadline: Option<DateTime<Utc>>,
+    start_time: &DateTime<Utc>,
+) -> Result<()> {
+    match deadline {
+        Some(deadline) if deadline <= *start_time => Err(Error::ExpiredMetadata {
+            path: role.clone(),
+            expiration: *expiration,
+            now: *start_time,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// An opt-in policy that allows expired metadata to keep being used for a grace period after it
+/// expires, such as for devices that may be offline for long periods of time.
+///
+/// The grace period only applies to metadata that is already trusted, such as the metadata a
+/// [Client](crate::client::Client) reloads from its local repository. Updates from a remote
+/// repository always reject expired metadata.
+///
+/// Expired root metadata is never used. Targets that were verified with expired metadata are
+/// reported as [Freshness::Stale], so the application can decide whether or not to use them.
+///
+/// **WARNING**: Using expired metadata opens up users to a freeze attack for the length of the
+/// grace period.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExpirationGracePolicy {
+    timestamp: Option<Duration>,
+    snapshot: Option<Duration>,
+    targets: Option<Duration>,
+}
+
+impl ExpirationGracePolicy {
+    /// Allow timestamp metadata to be used for `grace_period` after it expires.
+    pub fn timestamp(mut self, grace_period: Duration) -> Self {
+        self.timestamp = Some(grace_period);
+        self
+    }
+
+    /// Allow snapshot metadata to be used for `grace_period` after it expires.
+    pub fn snapshot(mut self, grace_period: Duration) -> Self {
+        self.snapshot = Some(grace_period);
+        self
+    }
+
+    /// Allow the top-level and delegated targets metadata to be used for `grace_period` after it
+    /// expires.
+    pub fn targets(mut self, grace_period: Duration) -> Self {
+        self.targets = Some(grace_period);
+        self
+    }
+
+    /// The grace period for the metadata of `role`, if any. This is always `None` for root.
+    pub fn grace_period(&self, role: &MetadataPath) -> Option<Duration> {
+        if *role == MetadataPath::root() {
+            None
+        } else if *role == MetadataPath::timestamp() {
+            self.timestamp
+        } else if *role == MetadataPath::snapshot() {
+            self.snapshot
+        } else {
+            self.targets
+        }
+    }
+}
+
+/// Whether the metadata used to verify a target had expired.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Freshness {
+    /// None of the metadata had expired.
+    Fresh,
+
+    /// Some of the metadata had expired, but was still within the grace period allowed by the
+    /// [ExpirationGracePolicy]. Contains the expired roles and when they expired.
+    Stale(BTreeMap<MetadataPath, DateTime<Utc>>),
+}
+
+impl Freshness {
+    /// Returns `true` if any of the metadata had expired.
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Freshness::Stale(_))
+    }
+
+    /// Returns an [Error::ExpiredMetadata] for the first expired role, if any.
+    pub(crate) fn ensure_fresh(&self, now: &DateTime<Utc>) -> Result<()> {
+        if let Freshness::Stale(expired) = self {
+            if let Some((path, expiration)) = expired.iter().next() {
+                return Err(Error::ExpiredMetadata {
+                    path: path.clone(),
+                    expiration: *expiration,
+                    now: *now,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The keys `root` authorizes for `role`, by the IDs it lists them under. Keys listed under an
+/// alias are skipped unless the alias is in `accepted_aliases`.
+fn accepted_role_keys<'a, M: Metadata>(
+    root: &'a RootMetadata,
+    role: &'a RoleDefinition<M>,
+    accepted_aliases: &'a HashSet<KeyId>,
+) -> impl Iterator<Item = (&'a KeyId, &'a PublicKey)> {
+    root.role_keys_by_id(role)
+        .filter(move |(key_id, key)| *key_id == key.key_id() || accepted_aliases.contains(*key_id))
+}
+
 impl<D: Pouf> Clone for Database<D> {
     fn clone(&self) -> Self {
         Self {
@@ -1073,6 +1623,14 @@ impl<D: Pouf> Clone for Database<D> {
             trusted_snapshot: self.trusted_snapshot.clone(),
             trusted_timestamp: self.trusted_timestamp.clone(),
             trusted_delegations: self.trusted_delegations.clone(),
+            delegation_parents: self.delegation_parents.clone(),
+            trusted_key_id_aliases: self.trusted_key_id_aliases.clone(),
+            revocations: self.revocations.clone(),
+            published_revocations: self.published_revocations.clone(),
+            clock: Arc::clone(&self.clock),
+            expiration_grace_policy: self.expiration_grace_policy.clone(),
+            reloading: self.reloading,
+            verifier: self.verifier.clone(),
             pouf: PhantomData,
         }
     }
@@ -1081,13 +1639,17 @@ impl<D: Pouf> Clone for Database<D> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::clock::FixedClock;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, KeyIdPolicy, KeyIdScheme, PrivateKey};
     use crate::metadata::{
-        RawSignedMetadataSetBuilder, RootMetadataBuilder, SnapshotMetadataBuilder,
-        TargetsMetadataBuilder, TimestampMetadataBuilder,
+        Delegation, Delegations, RawSignedMetadataSetBuilder, RootMetadataBuilder,
+        SnapshotMetadataBuilder, TargetsMetadataBuilder, TimestampMetadataBuilder,
     };
     use crate::pouf::Pouf1;
+    use crate::revocation::KeyRevocation;
+    use crate::verify::SignatureCache;
     use assert_matches::assert_matches;
+    use chrono::SubsecRound;
     use lazy_static::lazy_static;
     use std::iter::once;
 
@@ -1299,8 +1861,6 @@ mod test {
 
     #[test]
     fn good_timestamp_update() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1325,18 +1885,51 @@ mod test {
         let raw_timestamp = timestamp.to_raw().unwrap();
 
         assert_matches!(
-            tuf.update_timestamp(&now, &raw_timestamp),
+            tuf.update_timestamp(&raw_timestamp),
             Ok(Some(_parsed_timestamp))
         );
 
         // second update should do nothing
-        assert_matches!(tuf.update_timestamp(&now, &raw_timestamp), Ok(None))
+        assert_matches!(tuf.update_timestamp(&raw_timestamp), Ok(None))
     }
 
     #[test]
-    fn bad_timestamp_update_wrong_key() {
-        let now = Utc::now();
+    fn timestamp_update_uses_signature_cache() {
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[1].public().clone())
+            .timestamp_key(KEYS[1].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
 
+        // Databases sharing a cache only verify the timestamp signature once.
+        let cache = Arc::new(SignatureCache::new(16));
+        for expected_hits in 0..2 {
+            let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+            tuf.set_verifier(Verifier::new().cache(Arc::clone(&cache)));
+
+            assert_matches!(tuf.update_timestamp(&raw_timestamp), Ok(Some(_)));
+            assert_eq!(cache.hits(), expected_hits);
+            assert_eq!(cache.len(), 1);
+        }
+    }
+
+    #[test]
+    fn bad_timestamp_update_wrong_key() {
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1362,13 +1955,11 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        assert!(tuf.update_timestamp(&now, &raw_timestamp).is_err())
+        assert!(tuf.update_timestamp(&raw_timestamp).is_err())
     }
 
     #[test]
     fn good_snapshot_update() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1392,18 +1983,16 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+        tuf.update_timestamp(&raw_timestamp).unwrap();
 
-        assert_matches!(tuf.update_snapshot(&now, &raw_snapshot), Ok(true));
+        assert_matches!(tuf.update_snapshot(&raw_snapshot), Ok(true));
 
         // second update should do nothing
-        assert_matches!(tuf.update_snapshot(&now, &raw_snapshot), Ok(false));
+        assert_matches!(tuf.update_snapshot(&raw_snapshot), Ok(false));
     }
 
     #[test]
     fn bad_snapshot_update_wrong_key() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1430,15 +2019,13 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+        tuf.update_timestamp(&raw_timestamp).unwrap();
 
-        assert!(tuf.update_snapshot(&now, &raw_snapshot).is_err());
+        assert!(tuf.update_snapshot(&raw_snapshot).is_err());
     }
 
     #[test]
     fn bad_snapshot_update_wrong_version() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1464,7 +2051,7 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+        tuf.update_timestamp(&raw_timestamp).unwrap();
 
         let raw_snapshot = SnapshotMetadataBuilder::new()
             .version(1)
@@ -1473,13 +2060,11 @@ mod test {
             .to_raw()
             .unwrap();
 
-        assert!(tuf.update_snapshot(&now, &raw_snapshot).is_err());
+        assert!(tuf.update_snapshot(&raw_snapshot).is_err());
     }
 
     #[test]
     fn good_targets_update() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1512,19 +2097,17 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
-        tuf.update_snapshot(&now, &raw_snapshot).unwrap();
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        tuf.update_snapshot(&raw_snapshot).unwrap();
 
-        assert_matches!(tuf.update_targets(&now, &raw_targets), Ok(true));
+        assert_matches!(tuf.update_targets(&raw_targets), Ok(true));
 
         // second update should do nothing
-        assert_matches!(tuf.update_targets(&now, &raw_targets), Ok(false));
+        assert_matches!(tuf.update_targets(&raw_targets), Ok(false));
     }
 
     #[test]
     fn bad_targets_update_wrong_key() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1558,16 +2141,14 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
-        tuf.update_snapshot(&now, &raw_snapshot).unwrap();
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        tuf.update_snapshot(&raw_snapshot).unwrap();
 
-        assert!(tuf.update_targets(&now, &raw_targets).is_err());
+        assert!(tuf.update_targets(&raw_targets).is_err());
     }
 
     #[test]
     fn bad_targets_update_wrong_version() {
-        let now = Utc::now();
-
         let raw_root = RootMetadataBuilder::new()
             .root_key(KEYS[0].public().clone())
             .snapshot_key(KEYS[1].public().clone())
@@ -1600,8 +2181,8 @@ mod test {
                 .to_raw()
                 .unwrap();
 
-        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
-        tuf.update_snapshot(&now, &raw_snapshot).unwrap();
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        tuf.update_snapshot(&raw_snapshot).unwrap();
 
         let raw_targets = TargetsMetadataBuilder::new()
             .version(1)
@@ -1610,7 +2191,234 @@ mod test {
             .to_raw()
             .unwrap();
 
-        assert!(tuf.update_targets(&now, &raw_targets).is_err());
+        assert!(tuf.update_targets(&raw_targets).is_err());
+    }
+
+    #[test]
+    fn revoked_keys_do_not_count_towards_threshold() {
+        let delegation_path = MetadataPath::new("delegation").unwrap();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .targets_key(KEYS[4].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+
+        let signed_targets = TargetsMetadataBuilder::new()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[4].public().clone())
+                    .role(
+                        Delegation::builder(delegation_path.clone())
+                            .key(KEYS[4].public())
+                            .delegate_path(TargetPath::new("delegation/").unwrap())
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[2])
+            .unwrap();
+        let raw_targets = signed_targets.to_raw().unwrap();
+
+        let signed_delegation = TargetsMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[4])
+            .unwrap();
+        let raw_delegation = signed_delegation.to_raw().unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&signed_targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_metadata_with_path("delegation", &signed_delegation, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+        let raw_snapshot = snapshot.to_raw().unwrap();
+
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[3])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        tuf.update_snapshot(&raw_snapshot).unwrap();
+
+        // Only a key authorized for the role can be revoked.
+        let unauthorized =
+            KeyRevocation::new(MetadataPath::targets(), KEYS[5].public().key_id().clone())
+                .sign::<Pouf1>(&KEYS[5])
+                .unwrap();
+        assert_matches!(
+            tuf.revoke_key(&unauthorized),
+            Err(Error::UnauthorizedKey { role, key_id })
+                if role == MetadataPath::targets() && &key_id == KEYS[5].public().key_id()
+        );
+
+        // Revoking a targets key also revokes it for roles delegated from targets.
+        let revocation =
+            KeyRevocation::new(MetadataPath::targets(), KEYS[4].public().key_id().clone())
+                .sign::<Pouf1>(&KEYS[4])
+                .unwrap();
+        assert_matches!(tuf.revoke_key(&revocation), Ok(true));
+        assert_matches!(tuf.revoke_key(&revocation), Ok(false));
+
+        assert_matches!(tuf.update_targets(&raw_targets), Ok(true));
+        assert!(tuf.is_key_revoked(&MetadataPath::targets(), KEYS[4].public().key_id()));
+        assert!(!tuf.is_key_revoked(&MetadataPath::targets(), KEYS[2].public().key_id()));
+        assert_matches!(
+            tuf.update_delegated_targets(
+                &MetadataPath::targets(),
+                &delegation_path,
+                &raw_delegation
+            ),
+            Err(Error::MetadataMissingSignatures { role, .. }) if role == delegation_path
+        );
+
+        // Once the other targets key is revoked, new targets metadata can't be verified.
+        let revocation =
+            KeyRevocation::new(MetadataPath::targets(), KEYS[2].public().key_id().clone())
+                .sign::<Pouf1>(&KEYS[2])
+                .unwrap();
+        let mut revocations = tuf.revocations();
+        assert!(revocations.insert(revocation));
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+        assert_eq!(tuf.update_revocations(&revocations).unwrap(), 2);
+        assert_eq!(tuf.revocations().revocations().len(), 2);
+
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        tuf.update_snapshot(&raw_snapshot).unwrap();
+        assert_matches!(
+            tuf.update_targets(&raw_targets),
+            Err(Error::MetadataMissingSignatures { role, .. }) if role == MetadataPath::targets()
+        );
+    }
+
+    #[test]
+    fn revoke_key_listed_under_alias() {
+        let snapshot_key = KEYS[1].public();
+        let alias = KeyId::opaque("snapshot-key").unwrap();
+
+        let raw_root = RootMetadataBuilder::new()
+            .key_id_scheme(KeyIdScheme::Custom(HashMap::from([(
+                snapshot_key.key_id().clone(),
+                alias.clone(),
+            )])))
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(snapshot_key.clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+        let raw_snapshot = snapshot.to_raw().unwrap();
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[3])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        let revocation =
+            KeyRevocation::new(MetadataPath::snapshot(), snapshot_key.key_id().clone())
+                .sign::<Pouf1>(&KEYS[1])
+                .unwrap();
+
+        // The alias isn't accepted by default, so the key isn't authorized for the role.
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+        assert_matches!(
+            tuf.revoke_key(&revocation),
+            Err(Error::UnauthorizedKey { key_id, .. }) if &key_id == snapshot_key.key_id()
+        );
+
+        let verifier =
+            Verifier::new().key_id_policy(KeyIdPolicy::new().accept(KeyIdScheme::Custom(
+                HashMap::from([(snapshot_key.key_id().clone(), alias.clone())]),
+            )));
+        tuf.set_verifier(verifier);
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        tuf.update_snapshot(&raw_snapshot).unwrap();
+
+        // Once the alias is accepted, the key it lists can be revoked.
+        let mut tuf =
+            Database::from_trusted_root_with_verifier(&raw_root, tuf.verifier().clone()).unwrap();
+        assert_matches!(tuf.revoke_key(&revocation), Ok(true));
+        assert_matches!(tuf.revoke_key(&revocation), Ok(false));
+        assert!(tuf.is_key_revoked(&MetadataPath::snapshot(), snapshot_key.key_id()));
+
+        tuf.update_timestamp(&raw_timestamp).unwrap();
+        assert_matches!(
+            tuf.update_snapshot(&raw_snapshot),
+            Err(Error::MetadataMissingSignatures { role, .. }) if role == MetadataPath::snapshot()
+        );
+
+        // A revocation may also name the key by its alias.
+        let signed = KeyRevocation::new(MetadataPath::snapshot(), alias.clone());
+        let signature = KEYS[1]
+            .sign(&Pouf1::canonicalize(&Pouf1::serialize(&signed).unwrap()).unwrap())
+            .unwrap();
+        let alias_revocation: SignedKeyRevocation = serde_json::from_value(serde_json::json!({
+            "signed": signed,
+            "signatures": [signature],
+        }))
+        .unwrap();
+
+        let mut tuf =
+            Database::from_trusted_root_with_verifier(&raw_root, tuf.verifier().clone()).unwrap();
+        assert_matches!(tuf.revoke_key(&alias_revocation), Ok(true));
+        assert_matches!(tuf.revoke_key(&revocation), Ok(false));
+        assert!(tuf.is_key_revoked(&MetadataPath::snapshot(), snapshot_key.key_id()));
+    }
+
+    #[test]
+    fn update_revocations_skips_bad_revocations() {
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let mut tuf = Database::<Pouf1>::from_trusted_root(&raw_root).unwrap();
+
+        let revocation =
+            KeyRevocation::new(MetadataPath::targets(), KEYS[2].public().key_id().clone())
+                .sign::<Pouf1>(&KEYS[2])
+                .unwrap();
+
+        // Moving the revocation to another role invalidates its signature.
+        let mut json = serde_json::to_value(&revocation).unwrap();
+        json["signed"]["role"] = serde_json::json!("snapshot");
+        json["signed"]["keyid"] = serde_json::json!(KEYS[1].public().key_id());
+        let forged: SignedKeyRevocation = serde_json::from_value(json).unwrap();
+
+        let mut revocations = RevocationList::new();
+        assert!(revocations.insert(forged));
+        assert!(revocations.insert(revocation.clone()));
+
+        assert_eq!(tuf.update_revocations(&revocations).unwrap(), 1);
+        assert!(tuf.is_key_revoked(&MetadataPath::targets(), KEYS[2].public().key_id()));
+        assert!(!tuf.is_key_revoked(&MetadataPath::snapshot(), KEYS[1].public().key_id()));
+        assert_eq!(tuf.published_revocations().revocations(), &[revocation]);
     }
 
     #[test]
@@ -1790,4 +2598,155 @@ mod test {
             if role == MetadataPath::root()
         );
     }
+
+    #[test]
+    fn update_ignores_expiration_grace_policy() {
+        // Metadata expirations are serialized with a resolution of seconds.
+        let start = Utc::now().trunc_subsecs(0);
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[0].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .expires(start + Duration::days(1))
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+        tuf.set_clock(Arc::new(FixedClock::new(start + Duration::days(2))));
+        tuf.set_expiration_grace_policy(
+            ExpirationGracePolicy::default().timestamp(Duration::days(3)),
+        );
+
+        // Newly fetched metadata must not be expired, even within the grace period.
+        assert_matches!(
+            tuf.update_timestamp(&raw_timestamp),
+            Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+        );
+        assert_eq!(tuf.trusted_timestamp(), None);
+
+        // But metadata that was already trusted can be reloaded within the grace period.
+        tuf.set_reloading(true);
+        assert_matches!(tuf.update_timestamp(&raw_timestamp), Ok(Some(_)));
+    }
+
+    #[test]
+    fn target_description_with_expiration_grace_policy() {
+        // Metadata expirations are serialized with a resolution of seconds.
+        let start = Utc::now().trunc_subsecs(0);
+        let clock = Arc::new(FixedClock::new(start));
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[0].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let target_path = TargetPath::new("foo").unwrap();
+        let target_description =
+            TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+
+        let signed_targets = TargetsMetadataBuilder::new()
+            .insert_target_description(target_path.clone(), target_description.clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&signed_targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+
+        let timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .expires(start + Duration::days(1))
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap();
+
+        let metadata = RawSignedMetadataSetBuilder::new()
+            .root(raw_root)
+            .targets(signed_targets.to_raw().unwrap())
+            .snapshot(snapshot.to_raw().unwrap())
+            .timestamp(timestamp.to_raw().unwrap())
+            .build();
+
+        let mut tuf = Database::from_trusted_metadata_with_clock(&metadata, clock.clone()).unwrap();
+
+        assert_eq!(
+            tuf.target_description_with_freshness(&target_path).unwrap(),
+            (target_description.clone(), Freshness::Fresh)
+        );
+
+        // Without a grace period, expired timestamp metadata can't be used.
+        clock.set(start + Duration::days(2));
+        assert_matches!(
+            tuf.target_description_with_freshness(&target_path),
+            Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+        );
+
+        // Within the grace period the description is stale, but only available if asked for.
+        tuf.set_expiration_grace_policy(
+            ExpirationGracePolicy::default().timestamp(Duration::days(3)),
+        );
+        assert_eq!(
+            tuf.target_description_with_freshness(&target_path).unwrap(),
+            (
+                target_description,
+                Freshness::Stale(
+                    vec![(MetadataPath::timestamp(), start + Duration::days(1))]
+                        .into_iter()
+                        .collect()
+                )
+            )
+        );
+        assert_matches!(
+            tuf.target_description(&target_path),
+            Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+        );
+
+        // Past the grace period, the metadata is expired again.
+        clock.set(start + Duration::days(5));
+        assert_matches!(
+            tuf.target_description_with_freshness(&target_path),
+            Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+        );
+
+        // Root never gets a grace period.
+        tuf.set_expiration_grace_policy(
+            ExpirationGracePolicy::default()
+                .timestamp(Duration::days(1000))
+                .snapshot(Duration::days(1000))
+                .targets(Duration::days(1000)),
+        );
+        assert_eq!(
+            tuf.expiration_grace_policy()
+                .grace_period(&MetadataPath::root()),
+            None
+        );
+        clock.set(start + Duration::days(400));
+        assert_matches!(
+            tuf.target_description_with_freshness(&target_path),
+            Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::root()
+        );
+    }
 }
@@ -25,6 +25,15 @@ pub enum Error {
     #[error("encoding: {0}")]
     Encoding(String),
 
+    /// An encrypted private key could not be decrypted, usually because the passphrase was wrong.
+    #[error("failed to decrypt private key")]
+    KeyDecryption,
+
+    /// A certificate chain could not be verified against the trusted roots, or did not match the
+    /// expected signer identity.
+    #[error("untrusted certificate: {0}")]
+    UntrustedCertificate(String),
+
     /// Metadata was expired.
     #[error("metadata {path} expired at {expiration}, it is now {now}")]
     ExpiredMetadata {
@@ -36,6 +45,15 @@ pub enum Error {
         now: DateTime<Utc>,
     },
 
+    /// The clock reported a time earlier than one it had previously reported.
+    #[error("clock moved backwards to {now}, the last trusted time was {last_trusted_time}")]
+    ClockMovedBackwards {
+        /// The latest time that was trusted.
+        last_trusted_time: DateTime<Utc>,
+        /// The time the clock reported.
+        now: DateTime<Utc>,
+    },
+
     /// An illegal argument was passed into a function.
     #[error("illegal argument: {0}")]
     IllegalArgument(String),
@@ -88,6 +106,11 @@ pub enum Error {
         err: io::Error,
     },
 
+    /// An error occurred accessing a SQLite database.
+    #[cfg(feature = "sqlite")]
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
     /// A json serialization error occurred.
     #[error(transparent)]
     Json(#[from] serde_json::error::Error),
@@ -213,6 +236,15 @@ pub enum Error {
         child_role: MetadataPath,
     },
 
+    /// A key is not authorized to sign a role.
+    #[error("key {key_id} is not authorized to sign {role}")]
+    UnauthorizedKey {
+        /// The role.
+        role: MetadataPath,
+        /// The unauthorized key.
+        key_id: KeyId,
+    },
+
     /// The metadata must be signed with at least one private key.
     #[error("{role} must be signed with at least one private key")]
     MissingPrivateKey {
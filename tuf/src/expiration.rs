@@ -0,0 +1,793 @@
+//! Metadata expiration tracking
+//!
+//! Clients refuse to update from a repository with expired metadata, so the timestamp and
+//! snapshot metadata, which expire after a day or a week by default, must be re-signed
+//! regularly. An [ExpirationReport] describes when each trusted role expires, and which keys can
+//! re-sign it. A [Refresher] re-signs the timestamp and snapshot metadata with online keys before
+//! they expire.
+
+use {
+    crate::{
+        crypto::{KeyId, PrivateKey},
+        database::Database,
+        error::{Error, Result},
+        metadata::{Metadata, MetadataPath, RawSignedMetadataSet, TargetsMetadata},
+        pouf::Pouf,
+        repo_builder::{RepoBuilder, DEFAULT_SNAPSHOT_EXPIRATION, DEFAULT_TIMESTAMP_EXPIRATION},
+        repository::RepositoryStorage,
+    },
+    chrono::{DateTime, Duration, Utc},
+    std::{
+        collections::{HashSet, VecDeque},
+        future::Future,
+    },
+};
+
+const DEFAULT_REFRESH_HORIZON: Duration = Duration::hours(6);
+
+/// When each role trusted by a [Database] expires.
+///
+/// ```
+/// # use {
+/// #     chrono::{Duration, Utc},
+/// #     futures_executor::block_on,
+/// #     tuf::{
+/// #         crypto::{Ed25519PrivateKey, PrivateKey as _},
+/// #         database::Database,
+/// #         expiration::ExpirationReport,
+/// #         metadata::MetadataPath,
+/// #         pouf::Pouf1,
+/// #         repo_builder::RepoBuilder,
+/// #         repository::EphemeralRepository,
+/// #     },
+/// # };
+/// #
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+/// # ).unwrap();
+/// #
+/// # block_on(async {
+/// let mut repo = EphemeralRepository::<Pouf1>::new();
+/// let metadata = RepoBuilder::create(&mut repo)
+///     .trusted_root_keys(&[&key])
+///     .trusted_targets_keys(&[&key])
+///     .trusted_snapshot_keys(&[&key])
+///     .trusted_timestamp_keys(&[&key])
+///     .commit()
+///     .await
+///     .unwrap();
+/// let database = Database::from_trusted_metadata(&metadata).unwrap();
+///
+/// let report = ExpirationReport::new(&database);
+///
+/// // The timestamp expires first, after a day.
+/// let timestamp = report.next_expiration().unwrap();
+/// assert_eq!(timestamp.role(), &MetadataPath::timestamp());
+/// assert_eq!(timestamp.key_ids(), &[key.public().key_id().clone()]);
+///
+/// assert_eq!(report.expiring_within(Utc::now(), Duration::days(2)).count(), 1);
+/// # });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpirationReport {
+    roles: Vec<RoleExpiration>,
+}
+
+impl ExpirationReport {
+    /// Describe when the root, timestamp, snapshot, targets, and delegated targets metadata
+    /// trusted by `database` expire.
+    pub fn new<D: Pouf>(database: &Database<D>) -> Self {
+        let root = database.trusted_root();
+
+        let mut roles = vec![RoleExpiration {
+            role: MetadataPath::root(),
+            parent: None,
+            version: root.version(),
+            expires: *root.expires(),
+            threshold: root.root().threshold(),
+            key_ids: sorted(root.root().key_ids()),
+        }];
+
+        if let Some(timestamp) = database.trusted_timestamp() {
+            roles.push(RoleExpiration {
+                role: MetadataPath::timestamp(),
+                parent: None,
+                version: timestamp.version(),
+                expires: *timestamp.expires(),
+                threshold: root.timestamp().threshold(),
+                key_ids: sorted(root.timestamp().key_ids()),
+            });
+        }
+
+        if let Some(snapshot) = database.trusted_snapshot() {
+            roles.push(RoleExpiration {
+                role: MetadataPath::snapshot(),
+                parent: None,
+                version: snapshot.version(),
+                expires: *snapshot.expires(),
+                threshold: root.snapshot().threshold(),
+                key_ids: sorted(root.snapshot().key_ids()),
+            });
+        }
+
+        if let Some(targets) = database.trusted_targets() {
+            roles.push(RoleExpiration {
+                role: MetadataPath::targets(),
+                parent: None,
+                version: targets.version(),
+                expires: *targets.expires(),
+                threshold: root.targets().threshold(),
+                key_ids: sorted(root.targets().key_ids()),
+            });
+
+            // Walk the delegations in the order clients do, so a role delegated by multiple
+            // parents is described by the delegation clients follow first.
+            let mut visited = HashSet::new();
+            let mut queue = VecDeque::from([(MetadataPath::targets(), &**targets)]);
+            while let Some((parent, metadata)) = queue.pop_front() {
+                for delegation in metadata.delegations().roles() {
+                    let role = delegation.name();
+                    let delegated = match database.trusted_delegations().get(role) {
+                        Some(delegated) => delegated,
+                        None => continue,
+                    };
+                    if !visited.insert(role.clone()) {
+                        continue;
+                    }
+
+                    roles.push(RoleExpiration {
+                        role: role.clone(),
+                        parent: Some(parent.clone()),
+                        version: delegated.version(),
+                        expires: *delegated.expires(),
+                        threshold: delegation.threshold(),
+                        key_ids: sorted(delegation.key_ids()),
+                    });
+
+                    queue.push_back((role.clone(), &**delegated as &TargetsMetadata));
+                }
+            }
+        }
+
+        Self { roles }
+    }
+
+    /// Every role, in the order clients update them.
+    pub fn roles(&self) -> &[RoleExpiration] {
+        &self.roles
+    }
+
+    /// The role that expires first, if any.
+    pub fn next_expiration(&self) -> Option<&RoleExpiration> {
+        self.roles.iter().min_by_key(|role| role.expires)
+    }
+
+    /// The roles which have expired at `now`, or expire within `horizon` of it.
+    pub fn expiring_within(
+        &self,
+        now: DateTime<Utc>,
+        horizon: Duration,
+    ) -> impl Iterator<Item = &RoleExpiration> {
+        self.roles
+            .iter()
+            .filter(move |role| role.expires_within(now, horizon))
+    }
+}
+
+/// When a role expires, and which keys can re-sign it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoleExpiration {
+    role: MetadataPath,
+    parent: Option<MetadataPath>,
+    version: u32,
+    expires: DateTime<Utc>,
+    threshold: u32,
+    key_ids: Vec<KeyId>,
+}
+
+impl RoleExpiration {
+    /// The role.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The role which delegated this role, if it is a delegated targets role.
+    pub fn parent(&self) -> Option<&MetadataPath> {
+        self.parent.as_ref()
+    }
+
+    /// The version of the trusted metadata.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// When the trusted metadata expires.
+    pub fn expires(&self) -> &DateTime<Utc> {
+        &self.expires
+    }
+
+    /// How many of the [RoleExpiration::key_ids] must sign new metadata for the role.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The keys which can sign new metadata for the role, sorted.
+    pub fn key_ids(&self) -> &[KeyId] {
+        &self.key_ids
+    }
+
+    /// Whether the metadata has expired at `now`, or expires within `horizon` of it.
+    pub fn expires_within(&self, now: DateTime<Utc>, horizon: Duration) -> bool {
+        match now.checked_add_signed(horizon) {
+            Some(horizon) => self.expires <= horizon,
+            None => true,
+        }
+    }
+}
+
+fn sorted(key_ids: &HashSet<KeyId>) -> Vec<KeyId> {
+    let mut key_ids = key_ids.iter().cloned().collect::<Vec<_>>();
+    key_ids.sort();
+    key_ids
+}
+
+/// Re-signs the timestamp and snapshot metadata of a repository before they expire.
+///
+/// The timestamp and snapshot keys are typically kept online, unlike the root and targets keys,
+/// so the [Refresher] only re-signs those roles. Each [Refresher::refresh] uses
+/// [RepoBuilder::from_database] to stage new metadata for the roles which expire within the
+/// refresh horizon, and commits it to the repository. A new snapshot is always accompanied by a
+/// new timestamp.
+///
+/// The time is taken from the [Database::clock].
+///
+/// ```
+/// # use {
+/// #     chrono::{Duration, Utc},
+/// #     futures_executor::block_on,
+/// #     tuf::{
+/// #         crypto::Ed25519PrivateKey,
+/// #         database::Database,
+/// #         expiration::Refresher,
+/// #         pouf::Pouf1,
+/// #         repo_builder::RepoBuilder,
+/// #         repository::EphemeralRepository,
+/// #     },
+/// # };
+/// #
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+/// # ).unwrap();
+/// #
+/// # block_on(async {
+/// let repo = EphemeralRepository::<Pouf1>::new();
+/// let metadata = RepoBuilder::create(&repo)
+///     .trusted_root_keys(&[&key])
+///     .trusted_targets_keys(&[&key])
+///     .trusted_snapshot_keys(&[&key])
+///     .trusted_timestamp_keys(&[&key])
+///     .commit()
+///     .await
+///     .unwrap();
+/// let database = Database::from_trusted_metadata(&metadata).unwrap();
+///
+/// let mut refresher = Refresher::new(database, &repo)
+///     .snapshot_keys(&[&key])
+///     .timestamp_keys(&[&key])
+///     .refresh_horizon(Duration::hours(1));
+///
+/// // Nothing expires within the next hour.
+/// assert!(refresher.refresh().await.unwrap().is_none());
+///
+/// // The timestamp expires in a day, so it will be refreshed in 23 hours.
+/// let next = refresher.next_refresh().unwrap();
+/// assert!(next > Utc::now() + Duration::hours(22));
+/// # });
+/// ```
+pub struct Refresher<'a, D, R>
+where
+    D: Pouf,
+    R: RepositoryStorage<D>,
+{
+    database: Database<D>,
+    repo: R,
+    snapshot_keys: Vec<&'a dyn PrivateKey>,
+    timestamp_keys: Vec<&'a dyn PrivateKey>,
+    refresh_horizon: Duration,
+    snapshot_expiration_duration: Option<Duration>,
+    timestamp_expiration_duration: Option<Duration>,
+}
+
+impl<'a, D, R> Refresher<'a, D, R>
+where
+    D: Pouf,
+    R: RepositoryStorage<D>,
+{
+    /// Create a [Refresher] for the repository `repo`, whose latest metadata is trusted by
+    /// `database`.
+    pub fn new(database: Database<D>, repo: R) -> Self {
+        Self {
+            database,
+            repo,
+            snapshot_keys: vec![],
+            timestamp_keys: vec![],
+            refresh_horizon: DEFAULT_REFRESH_HORIZON,
+            snapshot_expiration_duration: None,
+            timestamp_expiration_duration: None,
+        }
+    }
+
+    /// The keys used to sign new snapshot metadata. These must be the snapshot keys listed in the
+    /// trusted root metadata.
+    pub fn snapshot_keys(mut self, keys: &[&'a dyn PrivateKey]) -> Self {
+        self.snapshot_keys.extend(keys);
+        self
+    }
+
+    /// The keys used to sign new timestamp metadata. These must be the timestamp keys listed in
+    /// the trusted root metadata.
+    pub fn timestamp_keys(mut self, keys: &[&'a dyn PrivateKey]) -> Self {
+        self.timestamp_keys.extend(keys);
+        self
+    }
+
+    /// Refresh metadata which expires within this duration. This must be shorter than the snapshot
+    /// and timestamp expiration durations, or [Refresher::refresh] returns an error, since the
+    /// refreshed metadata would already be due for another refresh.
+    ///
+    /// Defaults to 6 hours.
+    pub fn refresh_horizon(mut self, horizon: Duration) -> Self {
+        self.refresh_horizon = horizon;
+        self
+    }
+
+    /// Sets how long new snapshot metadata is valid for. Defaults to the
+    /// [RepoBuilder::snapshot_expiration_duration] default.
+    pub fn snapshot_expiration_duration(mut self, duration: Duration) -> Self {
+        self.snapshot_expiration_duration = Some(duration);
+        self
+    }
+
+    /// Sets how long new timestamp metadata is valid for. Defaults to the
+    /// [RepoBuilder::timestamp_expiration_duration] default.
+    pub fn timestamp_expiration_duration(mut self, duration: Duration) -> Self {
+        self.timestamp_expiration_duration = Some(duration);
+        self
+    }
+
+    /// The [Database] trusting the latest metadata in the repository.
+    pub fn database(&self) -> &Database<D> {
+        &self.database
+    }
+
+    /// When the next [Refresher::refresh] will re-sign metadata, or `None` if the database
+    /// doesn't trust a timestamp or snapshot.
+    pub fn next_refresh(&self) -> Option<DateTime<Utc>> {
+        [
+            self.database.trusted_timestamp().map(|m| *m.expires()),
+            self.database.trusted_snapshot().map(|m| *m.expires()),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|expires| {
+            expires
+                .checked_sub_signed(self.refresh_horizon)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC)
+        })
+    }
+
+    /// Re-sign the snapshot and timestamp metadata if they expire within the refresh horizon,
+    /// and commit them to the repository. Returns the new metadata, or `None` if nothing needed
+    /// to be refreshed.
+    pub async fn refresh(&mut self) -> Result<Option<RawSignedMetadataSet<D>>> {
+        self.check_refresh_horizon()?;
+
+        let now = self.database.clock().now()?;
+
+        let mut builder = RepoBuilder::from_database(&self.repo, &self.database)
+            .current_time(now)
+            .expiration_horizon(self.refresh_horizon)
+            .trusted_snapshot_keys(&self.snapshot_keys)
+            .trusted_timestamp_keys(&self.timestamp_keys);
+        if let Some(duration) = self.snapshot_expiration_duration {
+            builder = builder.snapshot_expiration_duration(duration);
+        }
+        if let Some(duration) = self.timestamp_expiration_duration {
+            builder = builder.timestamp_expiration_duration(duration);
+        }
+
+        // The root and targets keys are offline, so those roles can't be refreshed here.
+        let metadata = builder
+            .skip_root()
+            .skip_targets()
+            .stage_snapshot_if_necessary()?
+            .stage_timestamp_if_necessary()?
+            .commit()
+            .await?;
+
+        if metadata.timestamp().is_none() {
+            return Ok(None);
+        }
+
+        self.database.update_metadata(&metadata)?;
+
+        Ok(Some(metadata))
+    }
+
+    /// Check the refresh horizon is shorter than the expiration durations, so that refreshed
+    /// metadata isn't refreshed again straight away.
+    fn check_refresh_horizon(&self) -> Result<()> {
+        let expiration_duration = std::cmp::min(
+            self.snapshot_expiration_duration
+                .unwrap_or(DEFAULT_SNAPSHOT_EXPIRATION),
+            self.timestamp_expiration_duration
+                .unwrap_or(DEFAULT_TIMESTAMP_EXPIRATION),
+        );
+        if self.refresh_horizon >= expiration_duration {
+            return Err(Error::IllegalArgument(format!(
+                "refresh horizon {} must be shorter than the expiration duration {}",
+                self.refresh_horizon, expiration_duration
+            )));
+        }
+        Ok(())
+    }
+
+    /// Refresh the metadata on a schedule, until `sleep_until` returns `false`.
+    ///
+    /// After each [Refresher::refresh], `sleep_until` is called with the time of the
+    /// [next refresh](Refresher::next_refresh), and should resolve once that time is reached. It
+    /// can resolve to `false` to stop refreshing, such as when the service is shutting down.
+    pub async fn run<F, Fut>(&mut self, mut sleep_until: F) -> Result<()>
+    where
+        F: FnMut(DateTime<Utc>) -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        loop {
+            let _ = self.refresh().await?;
+
+            let next = self.next_refresh().ok_or_else(|| {
+                Error::Opaque("the database does not trust a timestamp or snapshot".into())
+            })?;
+
+            if !sleep_until(next).await {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::clock::FixedClock,
+        crate::crypto::{Ed25519PrivateKey, HashAlgorithm},
+        crate::metadata::{
+            Delegation, MetadataDescription, MetadataVersion, RawSignedMetadata,
+            RawSignedMetadataSetBuilder, TargetPath, TargetsMetadataBuilder,
+        },
+        crate::pouf::Pouf1,
+        crate::repository::{fetch_metadata_to_string, EphemeralRepository},
+        assert_matches::assert_matches,
+        chrono::TimeZone as _,
+        futures_executor::block_on,
+        lazy_static::lazy_static,
+        pretty_assertions::assert_eq,
+        std::sync::Arc,
+    };
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+            ];
+            keys.iter()
+                .map(|key| Ed25519PrivateKey::from_pkcs8(key).unwrap())
+                .collect()
+        };
+    }
+
+    fn epoch() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn key_id(key: &Ed25519PrivateKey) -> KeyId {
+        key.public().key_id().clone()
+    }
+
+    #[test]
+    fn report_describes_every_role() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .expires(epoch() + Duration::days(3))
+                .signed::<Pouf1>(&KEYS[2])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+            let metadata = RepoBuilder::create(&repo)
+                .current_time(epoch())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[2]])
+                .trusted_timestamp_keys(&[&KEYS[2]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[2].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[2].public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        MetadataDescription::from_slice(
+                            raw_delegation.as_bytes(),
+                            1,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut database = Database::from_trusted_metadata_with_clock(
+                &metadata,
+                Arc::new(FixedClock::new(epoch())),
+            )
+            .unwrap();
+            database
+                .update_delegated_targets(
+                    &MetadataPath::targets(),
+                    &delegation_path,
+                    &raw_delegation,
+                )
+                .unwrap();
+
+            let report = ExpirationReport::new(&database);
+            assert_eq!(
+                report
+                    .roles()
+                    .iter()
+                    .map(|role| (
+                        role.role().clone(),
+                        role.parent().cloned(),
+                        *role.expires(),
+                        role.key_ids().to_vec()
+                    ))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (
+                        MetadataPath::root(),
+                        None,
+                        epoch() + Duration::days(365),
+                        vec![key_id(&KEYS[0])]
+                    ),
+                    (
+                        MetadataPath::timestamp(),
+                        None,
+                        epoch() + Duration::days(1),
+                        vec![key_id(&KEYS[2])]
+                    ),
+                    (
+                        MetadataPath::snapshot(),
+                        None,
+                        epoch() + Duration::days(7),
+                        vec![key_id(&KEYS[2])]
+                    ),
+                    (
+                        MetadataPath::targets(),
+                        None,
+                        epoch() + Duration::days(90),
+                        vec![key_id(&KEYS[1])]
+                    ),
+                    (
+                        delegation_path.clone(),
+                        Some(MetadataPath::targets()),
+                        epoch() + Duration::days(3),
+                        vec![key_id(&KEYS[2])]
+                    ),
+                ]
+            );
+
+            assert_eq!(
+                report.next_expiration().unwrap().role(),
+                &MetadataPath::timestamp()
+            );
+            assert_eq!(
+                report
+                    .expiring_within(epoch(), Duration::days(3))
+                    .map(|role| role.role().clone())
+                    .collect::<Vec<_>>(),
+                vec![MetadataPath::timestamp(), delegation_path]
+            );
+        })
+    }
+
+    #[test]
+    fn refresher_only_refreshes_expiring_roles() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&repo)
+                .current_time(epoch())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .commit()
+                .await
+                .unwrap();
+
+            let clock = Arc::new(FixedClock::new(epoch()));
+            let database =
+                Database::from_trusted_metadata_with_clock(&metadata, clock.clone()).unwrap();
+
+            // Only the online keys are available.
+            let mut refresher = Refresher::new(database, &repo)
+                .snapshot_keys(&[&KEYS[1]])
+                .timestamp_keys(&[&KEYS[1]])
+                .refresh_horizon(Duration::hours(6));
+
+            assert!(refresher.refresh().await.unwrap().is_none());
+            assert_eq!(
+                refresher.next_refresh(),
+                Some(epoch() + Duration::hours(18))
+            );
+
+            // Within the horizon of the timestamp expiring.
+            clock.set(epoch() + Duration::hours(18));
+            let metadata = refresher.refresh().await.unwrap().unwrap();
+            assert!(metadata.root().is_none());
+            assert!(metadata.targets().is_none());
+            assert!(metadata.snapshot().is_none());
+            assert!(metadata.timestamp().is_some());
+            assert_eq!(
+                refresher.database().trusted_timestamp().unwrap().version(),
+                2
+            );
+            assert_eq!(
+                refresher.next_refresh(),
+                Some(epoch() + Duration::hours(36))
+            );
+
+            // Within the horizon of the snapshot expiring, which also refreshes the timestamp.
+            clock.set(epoch() + Duration::days(7) - Duration::hours(1));
+            let metadata = refresher.refresh().await.unwrap().unwrap();
+            assert!(metadata.targets().is_none());
+            assert!(metadata.snapshot().is_some());
+            assert!(metadata.timestamp().is_some());
+            assert_eq!(
+                refresher.database().trusted_snapshot().unwrap().version(),
+                2
+            );
+
+            // The refreshed metadata was committed to the repository.
+            let database = Database::from_trusted_metadata_with_clock(
+                &RawSignedMetadataSetBuilder::new()
+                    .root(metadata_from(&repo, MetadataPath::root()).await)
+                    .timestamp(metadata_from(&repo, MetadataPath::timestamp()).await)
+                    .build(),
+                clock.clone(),
+            )
+            .unwrap();
+            assert_eq!(database.trusted_timestamp().unwrap().version(), 3);
+        })
+    }
+
+    #[test]
+    fn refresher_rejects_horizon_not_shorter_than_expiration() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&repo)
+                .current_time(epoch())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let clock = Arc::new(FixedClock::new(epoch()));
+            let database = Database::from_trusted_metadata_with_clock(&metadata, clock).unwrap();
+
+            // The timestamp expires after a day, so it would always be within the horizon.
+            let mut refresher = Refresher::new(database, &repo)
+                .snapshot_keys(&[&KEYS[0]])
+                .timestamp_keys(&[&KEYS[0]])
+                .refresh_horizon(Duration::days(1));
+            assert_matches!(refresher.refresh().await, Err(Error::IllegalArgument(_)));
+
+            let mut wakeups = 0;
+            assert_matches!(
+                refresher
+                    .run(|_| {
+                        wakeups += 1;
+                        futures_util::future::ready(true)
+                    })
+                    .await,
+                Err(Error::IllegalArgument(_))
+            );
+            assert_eq!(wakeups, 0);
+
+            // A shorter timestamp expiration duration is also rejected.
+            let database = refresher.database().clone();
+            let mut refresher = Refresher::new(database, &repo)
+                .timestamp_keys(&[&KEYS[0]])
+                .timestamp_expiration_duration(Duration::hours(6));
+            assert_matches!(refresher.refresh().await, Err(Error::IllegalArgument(_)));
+        })
+    }
+
+    #[test]
+    fn refresher_runs_until_stopped() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&repo)
+                .current_time(epoch())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let clock = Arc::new(FixedClock::new(epoch()));
+            let database =
+                Database::from_trusted_metadata_with_clock(&metadata, clock.clone()).unwrap();
+
+            let mut refresher = Refresher::new(database, &repo)
+                .snapshot_keys(&[&KEYS[0]])
+                .timestamp_keys(&[&KEYS[0]]);
+
+            let mut wakeups = vec![];
+            refresher
+                .run(|next| {
+                    clock.set(next);
+                    wakeups.push(next);
+                    futures_util::future::ready(wakeups.len() < 3)
+                })
+                .await
+                .unwrap();
+
+            // The timestamp is refreshed 6 hours before it expires, each time.
+            assert_eq!(
+                wakeups,
+                vec![
+                    epoch() + Duration::hours(18),
+                    epoch() + Duration::hours(36),
+                    epoch() + Duration::hours(54),
+                ]
+            );
+            assert_eq!(
+                refresher.database().trusted_timestamp().unwrap().version(),
+                3
+            );
+        })
+    }
+
+    async fn metadata_from<M: Metadata>(
+        repo: &EphemeralRepository<Pouf1>,
+        path: MetadataPath,
+    ) -> RawSignedMetadata<Pouf1, M> {
+        let bytes = fetch_metadata_to_string(repo, &path, MetadataVersion::None)
+            .await
+            .unwrap();
+        RawSignedMetadata::new(bytes.into_bytes())
+    }
+}
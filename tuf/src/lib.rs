@@ -11,9 +11,9 @@
 //! actually implement TUF for a community repository.
 //!
 //! - [The Diplomat paper
-//! (2016)](https://www.usenix.org/conference/nsdi16/technical-sessions/presentation/kuppusamy)
+//!   (2016)](https://www.usenix.org/conference/nsdi16/technical-sessions/presentation/kuppusamy)
 //! - [The Mercury paper
-//! (2017)](https://www.usenix.org/conference/atc17/technical-sessions/presentation/kuppusamy)
+//!   (2017)](https://www.usenix.org/conference/atc17/technical-sessions/presentation/kuppusamy)
 //!
 //! Failure to read the spec and the above papers will likely lead to an implementation that does
 //! not take advantage of all the security guarantees that TUF offers.
@@ -81,11 +81,11 @@
 //! 2. `rarely-updated-projects`
 //!   - Terminating
 //!   - Signs all packages for all projects that have been "abandoned" or left unupdated for a long
-//!   time AND have not yet registered keys with TUF
+//!     time AND have not yet registered keys with TUF
 //! 3. `new-projects`
 //!   - Non-terminating
 //!   - Signs all packages for all new projects as well as projects that were relegated to
-//!   `rarely-updated-projects`
+//!     `rarely-updated-projects`
 //!
 //! The top-level `targets` role as well as `claimed-projects` and `rarely-updated-projects`
 //! **MUST** all use offline keys.
@@ -111,14 +111,20 @@
     clippy::too_many_arguments
 )]
 
+pub mod audit;
 pub mod client;
+pub mod clock;
 pub mod crypto;
 pub mod database;
 pub mod error;
+pub mod expiration;
 pub mod metadata;
+pub mod mirror;
 pub mod pouf;
 pub mod repo_builder;
+pub mod repo_gc;
 pub mod repository;
+pub mod revocation;
 pub mod verify;
 
 mod format_hex;
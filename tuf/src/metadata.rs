@@ -10,12 +10,17 @@ use std::borrow::{Borrow, Cow};
 use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Debug, Display};
 use std::marker::PhantomData;
+use std::path::Path;
 use std::str;
 
-use crate::crypto::{self, HashAlgorithm, HashValue, KeyId, PrivateKey, PublicKey, Signature};
+use crate::crypto::{
+    self, HashAlgorithm, HashValue, KeyId, KeyIdScheme, PrivateKey, PublicKey, Signature,
+};
 use crate::error::Error;
 use crate::pouf::pouf1::shims;
 use crate::pouf::Pouf;
+use crate::revocation::RevocationList;
+use crate::verify::{SignatureReport, Verifier};
 use crate::Result;
 
 #[rustfmt::skip]
@@ -483,10 +488,10 @@ where
     /// hash of the returned bytes will match a hash included in, for example, a snapshot metadata
     /// file, as:
     /// * Parsing metadata removes unknown fields, which would not be included in the returned
-    /// bytes,
+    ///   bytes,
     /// * [Pouf] implementations only guarantee the bytes are canonical for the purpose of a
-    /// signature. Metadata obtained from a remote source may have included different whitespace
-    /// or ordered fields in a way that is not preserved when parsing that metadata.
+    ///   signature. Metadata obtained from a remote source may have included different whitespace
+    ///   or ordered fields in a way that is not preserved when parsing that metadata.
     pub fn to_raw(&self) -> Result<RawSignedMetadata<D, M>> {
         let bytes = D::canonicalize(&D::serialize(self)?)?;
         Ok(RawSignedMetadata::new(bytes))
@@ -536,6 +541,10 @@ where
     /// Merge the singatures from `other` into `self` if and only if
     /// `self.as_ref() == other.as_ref()`. If `self` and `other` contain signatures from the same
     /// key ID, then the signatures from `self` will replace the signatures from `other`.
+    ///
+    /// The merged signatures are not verified. Use [`SignedMetadata::check_role_signatures`] and
+    /// [`SignedMetadata::retain_valid_signatures`] to drop any that are not valid before
+    /// publishing the metadata.
     pub fn merge_signatures(&mut self, other: &Self) -> Result<()> {
         if self.metadata != other.metadata {
             return Err(Error::IllegalArgument(
@@ -561,11 +570,92 @@ where
         Ok(())
     }
 
+    /// Check the signatures against the keys of `role`, which must be the definition of this
+    /// metadata's role in `root`, with `verifier`. Keys `root` lists under an
+    /// [alias](RootMetadata::key_id_aliases) count if the verifier's
+    /// [KeyIdPolicy](crate::crypto::KeyIdPolicy) accepts it. See
+    /// [`Verifier::check_signatures_with_key_ids`].
+    pub fn check_role_signatures(
+        &self,
+        role: &RoleDefinition<M>,
+        root: &RootMetadata,
+        verifier: &Verifier,
+    ) -> Result<SignatureReport> {
+        verifier.check_signatures_with_key_ids(
+            &MetadataPath::from(M::ROLE),
+            self,
+            role.threshold(),
+            root.role_keys_by_id(role),
+        )
+    }
+
+    /// Check the signatures against the keys of `delegation`, where `keys` are the delegated keys
+    /// of the delegating metadata, with `verifier`. See [`Verifier::check_signatures`].
+    pub fn check_delegation_signatures(
+        &self,
+        delegation: &Delegation,
+        keys: &HashMap<KeyId, PublicKey>,
+        verifier: &Verifier,
+    ) -> Result<SignatureReport> {
+        verifier.check_signatures_with_key_ids(
+            delegation.name(),
+            self,
+            delegation.threshold(),
+            delegation
+                .key_ids()
+                .iter()
+                .filter_map(|key_id| keys.get(key_id).map(|key| (key_id, key))),
+        )
+    }
+
+    /// Remove every signature that `report`, a report on this metadata, did not find valid.
+    ///
+    /// ```
+    /// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+    /// # use tuf::metadata::{RootMetadataBuilder, SnapshotMetadataBuilder};
+    /// # use tuf::pouf::Pouf1;
+    /// # use tuf::verify::Verifier;
+    /// let key_1: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+    /// let key_1 = Ed25519PrivateKey::from_pkcs8(&key_1).unwrap();
+    ///
+    /// let key_2: &[u8] = include_bytes!("../tests/ed25519/ed25519-2.pk8.der");
+    /// let key_2 = Ed25519PrivateKey::from_pkcs8(&key_2).unwrap();
+    ///
+    /// let root = RootMetadataBuilder::new()
+    ///     .root_key(key_1.public().clone())
+    ///     .snapshot_key(key_1.public().clone())
+    ///     .targets_key(key_1.public().clone())
+    ///     .timestamp_key(key_1.public().clone())
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let mut snapshot = SnapshotMetadataBuilder::new()
+    ///     .signed::<Pouf1>(&key_1)
+    ///     .unwrap();
+    /// snapshot.add_signature(&key_2).unwrap();
+    ///
+    /// let report = snapshot
+    ///     .check_role_signatures(root.snapshot(), &root, &Verifier::new())
+    ///     .unwrap();
+    /// snapshot.retain_valid_signatures(&report);
+    /// assert_eq!(snapshot.signatures().len(), 1);
+    /// ```
+    pub fn retain_valid_signatures(&mut self, report: &SignatureReport) {
+        self.signatures.retain(|sig| report.valid().contains(sig));
+        self.signatures.sort();
+        self.signatures.dedup_by(|a, b| a.key_id() == b.key_id());
+    }
+
     /// An immutable reference to the signatures.
     pub fn signatures(&self) -> &[Signature] {
         &self.signatures
     }
 
+    /// The canonical bytes that the signatures sign.
+    pub(crate) fn canonical_bytes(&self) -> Result<Vec<u8>> {
+        D::canonicalize(&self.metadata)
+    }
+
     /// Parse the version number of this metadata without verifying signatures.
     ///
     /// This operation is generally unsafe to do with metadata obtained from an untrusted source,
@@ -594,7 +684,9 @@ pub struct RootMetadataBuilder {
     version: u32,
     expires: DateTime<Utc>,
     consistent_snapshot: bool,
+    key_id_scheme: KeyIdScheme,
     keys: HashMap<KeyId, PublicKey>,
+    key_id_aliases: HashMap<KeyId, PublicKey>,
     root_threshold: u32,
     root_key_ids: HashSet<KeyId>,
     snapshot_threshold: u32,
@@ -617,7 +709,9 @@ impl RootMetadataBuilder {
             version: 1,
             expires: Utc::now() + Duration::days(365),
             consistent_snapshot: true,
+            key_id_scheme: KeyIdScheme::Canonical,
             keys: HashMap::new(),
+            key_id_aliases: HashMap::new(),
             root_threshold: 1,
             root_key_ids: HashSet::new(),
             snapshot_threshold: 1,
@@ -647,6 +741,16 @@ impl RootMetadataBuilder {
         self
     }
 
+    /// Set how the IDs of the keys are calculated. Keys that are already listed in the metadata
+    /// keep their IDs.
+    ///
+    /// Default is [KeyIdScheme::Canonical]. Clients only accept other IDs if their
+    /// [KeyIdPolicy](crate::crypto::KeyIdPolicy) accepts the scheme.
+    pub fn key_id_scheme(mut self, scheme: KeyIdScheme) -> Self {
+        self.key_id_scheme = scheme;
+        self
+    }
+
     /// Set the root threshold.
     pub fn root_threshold(mut self, threshold: u32) -> Self {
         self.root_threshold = threshold;
@@ -704,17 +808,44 @@ impl RootMetadataBuilder {
     }
 
     /// Construct a new `RootMetadata`.
-    pub fn build(self) -> Result<RootMetadata> {
-        RootMetadata::new(
+    pub fn build(mut self) -> Result<RootMetadata> {
+        let mut keys = HashMap::new();
+        let mut key_id_aliases = self.key_id_aliases;
+
+        for (canonical_key_id, key) in self.keys {
+            // Keys without an ID in the scheme keep their canonical ID.
+            let key_id = match self.key_id_scheme.key_ids(&key)?.into_iter().next() {
+                Some(key_id) if key_id != canonical_key_id => key_id,
+                _ => {
+                    keys.insert(canonical_key_id, key);
+                    continue;
+                }
+            };
+
+            for key_ids in [
+                &mut self.root_key_ids,
+                &mut self.snapshot_key_ids,
+                &mut self.targets_key_ids,
+                &mut self.timestamp_key_ids,
+            ] {
+                if key_ids.remove(&canonical_key_id) {
+                    key_ids.insert(key_id.clone());
+                }
+            }
+            key_id_aliases.insert(key_id, key);
+        }
+
+        Ok(RootMetadata::new(
             self.version,
             self.expires,
             self.consistent_snapshot,
-            self.keys,
+            keys,
             RoleDefinition::new(self.root_threshold, self.root_key_ids)?,
             RoleDefinition::new(self.snapshot_threshold, self.snapshot_key_ids)?,
             RoleDefinition::new(self.targets_threshold, self.targets_key_ids)?,
             RoleDefinition::new(self.timestamp_threshold, self.timestamp_key_ids)?,
-        )
+        )?
+        .with_key_id_aliases(key_id_aliases))
     }
 
     /// Construct a new `SignedMetadata<D, RootMetadata>`.
@@ -738,7 +869,9 @@ impl From<RootMetadata> for RootMetadataBuilder {
             version: metadata.version,
             expires: metadata.expires,
             consistent_snapshot: metadata.consistent_snapshot,
+            key_id_scheme: KeyIdScheme::Canonical,
             keys: metadata.keys,
+            key_id_aliases: metadata.key_id_aliases,
             root_threshold: metadata.root.threshold,
             root_key_ids: metadata.root.key_ids,
             snapshot_threshold: metadata.snapshot.threshold,
@@ -758,6 +891,7 @@ pub struct RootMetadata {
     expires: DateTime<Utc>,
     consistent_snapshot: bool,
     keys: HashMap<KeyId, PublicKey>,
+    key_id_aliases: HashMap<KeyId, PublicKey>,
     root: RoleDefinition<RootMetadata>,
     snapshot: RoleDefinition<SnapshotMetadata>,
     targets: RoleDefinition<TargetsMetadata>,
@@ -787,6 +921,7 @@ impl RootMetadata {
             expires,
             consistent_snapshot,
             keys,
+            key_id_aliases: HashMap::new(),
             root,
             snapshot,
             targets,
@@ -794,6 +929,12 @@ impl RootMetadata {
         })
     }
 
+    /// Also list keys under IDs other than their canonical ID.
+    pub(crate) fn with_key_id_aliases(mut self, key_id_aliases: HashMap<KeyId, PublicKey>) -> Self {
+        self.key_id_aliases = key_id_aliases;
+        self
+    }
+
     /// Whether or not this repository is currently implementing that TUF consistent snapshot
     /// feature.
     pub fn consistent_snapshot(&self) -> bool {
@@ -805,6 +946,26 @@ impl RootMetadata {
         &self.keys
     }
 
+    /// The keys listed under an ID other than their canonical [KeyId]. Whether the keys are trusted
+    /// under those IDs depends on the [KeyIdPolicy](crate::crypto::KeyIdPolicy).
+    pub fn key_id_aliases(&self) -> &HashMap<KeyId, PublicKey> {
+        &self.key_id_aliases
+    }
+
+    /// An iterator over the keys of `role`, with the IDs the metadata lists them under, including
+    /// [key ID aliases](RootMetadata::key_id_aliases).
+    pub fn role_keys_by_id<'a, M: Metadata>(
+        &'a self,
+        role: &'a RoleDefinition<M>,
+    ) -> impl Iterator<Item = (&'a KeyId, &'a PublicKey)> {
+        role.key_ids().iter().filter_map(move |key_id| {
+            self.keys
+                .get(key_id)
+                .or_else(|| self.key_id_aliases.get(key_id))
+                .map(|key| (key_id, key))
+        })
+    }
+
     /// An iterator over all the trusted root public keys.
     pub fn root_keys(&self) -> impl Iterator<Item = &PublicKey> {
         self.root
@@ -1799,6 +1960,31 @@ impl TargetDescription {
         })
     }
 
+    /// Read the file at `path` and calculate the length and hash values.
+    ///
+    /// The file is memory mapped rather than read where possible, which is much faster for large
+    /// files. The file must not be modified while it is being hashed. Unlike
+    /// [TargetDescription::from_reader], this blocks the calling thread.
+    pub fn from_path(path: &Path, hash_algs: &[HashAlgorithm]) -> Result<Self> {
+        Self::from_path_with_custom(path, hash_algs, HashMap::new())
+    }
+
+    /// Read the file at `path` and custom metadata and calculate the length and hash values.
+    ///
+    /// See [TargetDescription::from_path] for more details.
+    pub fn from_path_with_custom(
+        path: &Path,
+        hash_algs: &[HashAlgorithm],
+        custom: HashMap<String, serde_json::Value>,
+    ) -> Result<Self> {
+        let (length, hashes) = crypto::calculate_hashes_from_file(path, hash_algs)?;
+        Ok(TargetDescription {
+            length,
+            hashes,
+            custom,
+        })
+    }
+
     /// The maximum length of the target.
     pub fn length(&self) -> u64 {
         self.length
@@ -2039,6 +2225,14 @@ impl Delegations {
             ));
         }
 
+        // The revocation list shares the metadata namespace with delegated roles.
+        if roles.iter().any(|r| r.name == RevocationList::path()) {
+            return Err(Error::IllegalArgument(format!(
+                "Cannot delegate to the reserved role {}.",
+                RevocationList::path()
+            )));
+        }
+
         Ok(Delegations { keys, roles })
     }
 
@@ -2411,6 +2605,45 @@ mod test {
         assert_eq!(parsed_str, parsed_jsn);
     }
 
+    #[test]
+    fn serde_target_description_with_other_hash_algorithms() {
+        let s: &[u8] = b"from water does all life begin";
+        let description =
+            TargetDescription::from_slice(s, &[HashAlgorithm::Sha384, HashAlgorithm::Blake2b256])
+                .unwrap();
+        let jsn = json!({
+            "length": 30,
+            "hashes": {
+                "sha384": "ddbdcf828528d0fd622f23b1f926acc5e43f2b835c2d1a7989e4eef8d0c9bc05\
+                    7e2e4b128d84b3aa819ff54e8f6e84ae",
+                "blake2b-256": "7701c4ac6fa58f2c3d72decdad43ff25f62e50f4774088cd2680d76983d3fc62",
+                "md5": "00",
+            },
+        });
+        let parsed: TargetDescription = serde_json::from_value(jsn).unwrap();
+        assert_eq!(
+            parsed.hashes().get(&HashAlgorithm::Sha384),
+            description.hashes().get(&HashAlgorithm::Sha384)
+        );
+        assert_eq!(
+            parsed.hashes().get(&HashAlgorithm::Blake2b256),
+            description.hashes().get(&HashAlgorithm::Blake2b256)
+        );
+        assert_eq!(
+            crypto::retain_supported_hashes(parsed.hashes()),
+            vec![
+                (
+                    &HashAlgorithm::Sha384,
+                    description.hashes()[&HashAlgorithm::Sha384].clone()
+                ),
+                (
+                    &HashAlgorithm::Blake2b256,
+                    description.hashes()[&HashAlgorithm::Blake2b256].clone()
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn serde_role_definition() {
         // keyid ordering must be preserved.
@@ -2620,6 +2853,84 @@ mod test {
         );
         let decoded: RootMetadata = serde_json::from_str(&jsn_str).unwrap();
         assert_eq!(3, decoded.keys.len());
+
+        // The key is kept under the ID it was listed with, so clients can map it with a policy.
+        let alias =
+            KeyId::from_str("00435b260b6172bd750aeb102f54a347c56b109e0524ab1f144593c07af66356")
+                .unwrap();
+        assert_eq!(
+            decoded.key_id_aliases().keys().collect::<Vec<_>>(),
+            vec![&alias]
+        );
+        assert_eq!(
+            serde_json::from_value::<RootMetadata>(serde_json::to_value(&decoded).unwrap())
+                .unwrap(),
+            decoded
+        );
+    }
+
+    #[test]
+    fn root_metadata_key_id_scheme() {
+        let root_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let snapshot_key = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
+        let targets_key = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
+        let timestamp_key = Ed25519PrivateKey::from_pkcs8(ED25519_4_PK8).unwrap();
+        let root_key_id = KeyId::opaque("root-key").unwrap();
+
+        let root = RootMetadataBuilder::new()
+            .expires(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap())
+            .key_id_scheme(KeyIdScheme::Custom(HashMap::from([(
+                root_key.public().key_id().clone(),
+                root_key_id.clone(),
+            )])))
+            .root_key(root_key.public().clone())
+            .snapshot_key(snapshot_key.public().clone())
+            .targets_key(targets_key.public().clone())
+            .timestamp_key(timestamp_key.public().clone())
+            .build()
+            .unwrap();
+
+        // Keys without an ID in the scheme keep their canonical ID.
+        assert_eq!(root.keys().len(), 3);
+        assert_eq!(root.key_id_aliases().len(), 1);
+        assert_eq!(root.key_id_aliases()[&root_key_id], *root_key.public());
+        assert_eq!(
+            root.root().key_ids().iter().collect::<Vec<_>>(),
+            vec![&root_key_id]
+        );
+        assert_eq!(
+            root.role_keys_by_id(root.root()).collect::<Vec<_>>(),
+            vec![(&root_key_id, root_key.public())]
+        );
+        assert!(root
+            .snapshot()
+            .key_ids()
+            .contains(snapshot_key.public().key_id()));
+
+        let jsn = serde_json::to_value(&root).unwrap();
+        assert!(jsn["keys"].get(root_key_id.to_string()).is_some());
+        assert_eq!(serde_json::from_value::<RootMetadata>(jsn).unwrap(), root);
+
+        // Rebuilding the metadata keeps the IDs.
+        assert_eq!(
+            RootMetadataBuilder::from(root.clone()).build().unwrap(),
+            root
+        );
+
+        let legacy = RootMetadataBuilder::new()
+            .key_id_scheme(KeyIdScheme::Legacy)
+            .root_key(root_key.public().clone())
+            .snapshot_key(snapshot_key.public().clone())
+            .targets_key(targets_key.public().clone())
+            .timestamp_key(timestamp_key.public().clone())
+            .build()
+            .unwrap();
+        assert!(legacy.keys().is_empty());
+        assert_eq!(legacy.key_id_aliases().len(), 4);
+        assert!(legacy
+            .root()
+            .key_ids()
+            .contains(&KeyIdScheme::Legacy.key_id(root_key.public()).unwrap()));
     }
 
     #[test]
@@ -3662,6 +3973,14 @@ mod test {
         assert!(serde_json::from_value::<Delegations>(delegations).is_err());
     }
 
+    // Refuse to deserialize delegations to the name the revocation list is stored under
+    #[test]
+    fn deserialize_json_delegations_reserved_role() {
+        let mut delegations = make_delegations();
+        delegations["roles"][0]["name"] = json!("revocations");
+        assert!(serde_json::from_value::<Delegations>(delegations).is_err());
+    }
+
     // Refuse to deserialize a delegation with insufficient threshold
     #[test]
     fn deserialize_json_delegation_bad_threshold() {
@@ -3755,4 +4074,47 @@ mod test {
             result => panic!("unexpected result: {:?}", result),
         }
     }
+
+    #[test]
+    fn check_delegation_signatures() {
+        let key_1 = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let key_2 = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
+        let key_3 = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
+
+        let delegation = Delegation::builder(MetadataPath::new("foo").unwrap())
+            .key(key_1.public())
+            .key(key_2.public())
+            .threshold(2)
+            .delegate_path(TargetPath::new("foo/").unwrap())
+            .build()
+            .unwrap();
+        let keys = hashmap! {
+            key_1.public().key_id().clone() => key_1.public().clone(),
+            key_2.public().key_id().clone() => key_2.public().clone(),
+        };
+
+        let mut signed = TargetsMetadataBuilder::new()
+            .signed::<Pouf1>(&key_1)
+            .unwrap();
+        let mut other = signed.clone();
+        other.add_signature(&key_3).unwrap();
+        signed.merge_signatures(&other).unwrap();
+
+        let report = signed
+            .check_delegation_signatures(&delegation, &keys, &Verifier::new())
+            .unwrap();
+        assert!(!report.is_threshold_met());
+        assert_eq!(report.valid().len(), 1);
+        assert_eq!(report.valid()[0].key_id(), key_1.public().key_id());
+        assert_eq!(report.unknown_keys().len(), 1);
+        assert_eq!(report.unknown_keys()[0].key_id(), key_3.public().key_id());
+
+        signed.retain_valid_signatures(&report);
+        signed.add_signature(&key_2).unwrap();
+        let report = signed
+            .check_delegation_signatures(&delegation, &keys, &Verifier::new())
+            .unwrap();
+        assert!(report.is_threshold_met());
+        assert!(report.is_clean());
+    }
 }
@@ -0,0 +1,922 @@
+//! Verified repository mirroring
+//!
+//! A [Mirror] replicates a remote repository into any [RepositoryStorage], such as a
+//! [FileSystemBatchUpdate](crate::repository::FileSystemBatchUpdate). The remote is verified with
+//! a [Client] before anything is copied, so a mirror never serves metadata or targets that its
+//! own clients would reject.
+
+use {
+    crate::{
+        client::{Client, Config, Parts},
+        crypto,
+        database::Database,
+        error::{Error, Result},
+        metadata::{
+            Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata,
+            TargetDescription, TargetPath, TargetsMetadata,
+        },
+        pouf::Pouf,
+        repository::{EphemeralRepository, Repository, RepositoryProvider, RepositoryStorage},
+        util::{role_metadata, version_for, SafeAsyncRead},
+    },
+    futures_util::io::AsyncReadExt,
+    log::debug,
+    std::collections::{HashSet, VecDeque},
+};
+
+/// Replicates a remote repository into a [RepositoryStorage] after verifying it.
+///
+/// Each [Mirror::sync] updates the mirror's [Database] from the remote repository with a
+/// [Client], walks every delegated targets role listed in the snapshot, and then copies into the
+/// storage:
+///
+/// * Every root metadata version, so clients can walk the chain of roots.
+/// * The timestamp, snapshot, targets, and delegated targets metadata, including the versioned
+///   copies used when consistent snapshots are enabled.
+/// * Every target described by the targets and delegated targets metadata.
+///
+/// The first sync copies everything. Later syncs only fetch and copy the metadata and targets that
+/// changed since the previous sync. Everything is copied in the order clients fetch it in reverse,
+/// so targets are written before the metadata that describes them, and the timestamp last.
+///
+/// [Mirror::sync] returns a [PendingSync], and the mirror only considers the changes synced once
+/// it's passed to [Mirror::apply]. To update the storage atomically, pass a batch update to
+/// [Mirror::sync], commit it, and apply the [PendingSync] only if the commit succeeded. Otherwise
+/// the next sync copies the same changes again.
+///
+/// ```
+/// # use {
+/// #     futures_executor::block_on,
+/// #     tuf::{
+/// #         client::Config,
+/// #         crypto::Ed25519PrivateKey,
+/// #         database::Database,
+/// #         mirror::Mirror,
+/// #         pouf::Pouf1,
+/// #         repo_builder::RepoBuilder,
+/// #         repository::EphemeralRepository,
+/// #     },
+/// # };
+/// #
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+/// # ).unwrap();
+/// #
+/// # block_on(async {
+/// let upstream = EphemeralRepository::<Pouf1>::new();
+/// let metadata = RepoBuilder::create(&upstream)
+///     .trusted_root_keys(&[&key])
+///     .trusted_targets_keys(&[&key])
+///     .trusted_snapshot_keys(&[&key])
+///     .trusted_timestamp_keys(&[&key])
+///     .commit()
+///     .await
+///     .unwrap();
+/// let database = Database::from_trusted_root(metadata.root().unwrap()).unwrap();
+///
+/// let mirror_repo = EphemeralRepository::<Pouf1>::new();
+/// let mut mirror = Mirror::new(Config::default(), database, &upstream);
+///
+/// let batch = mirror_repo.batch_update();
+/// let pending = mirror.sync(&batch).await.unwrap();
+/// batch.commit().await.unwrap();
+/// let report = mirror.apply(pending);
+/// assert!(!report.is_empty());
+///
+/// // Nothing changed upstream, so there's nothing to copy.
+/// assert!(mirror.sync(&mirror_repo).await.unwrap().report().is_empty());
+/// # });
+/// ```
+pub struct Mirror<D, R>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    config: Config,
+    database: Database<D>,
+    remote: R,
+    synced: bool,
+}
+
+impl<D, R> Mirror<D, R>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    /// Create a mirror of `remote`, which is verified starting from the root metadata trusted by
+    /// `database`. The first sync copies everything, including the root metadata versions before
+    /// the trusted root, which are checked to form a chain that ends at the trusted root.
+    pub fn new(config: Config, database: Database<D>, remote: R) -> Self {
+        Self {
+            config,
+            database,
+            remote,
+            synced: false,
+        }
+    }
+
+    /// Create a mirror of `remote` that resumes from a previous sync, where `database` describes
+    /// the metadata and targets that were already copied into the storage, such as the
+    /// [Mirror::database] after the last sync.
+    pub fn resume(config: Config, database: Database<D>, remote: R) -> Self {
+        Self {
+            config,
+            database,
+            remote,
+            synced: true,
+        }
+    }
+
+    /// The [Database] describing the metadata and targets copied by the last sync.
+    pub fn database(&self) -> &Database<D> {
+        &self.database
+    }
+
+    /// The remote repository.
+    pub fn remote(&self) -> &R {
+        &self.remote
+    }
+
+    /// Verify the remote repository, and copy everything that changed since the last sync into
+    /// `storage`. The changes are only considered synced once the returned [PendingSync] is
+    /// passed to [Mirror::apply].
+    pub async fn sync<S>(&self, storage: &S) -> Result<PendingSync<D>>
+    where
+        S: RepositoryStorage<D> + ?Sized,
+    {
+        let remote = Repository::<_, D>::new(&self.remote);
+        let previous = if self.synced {
+            Some(&self.database)
+        } else {
+            None
+        };
+
+        let (database, root_history) = if self.synced {
+            (self.database.clone(), vec![])
+        } else {
+            fetch_root_history(&self.config, &self.database, &remote).await?
+        };
+
+        // The client stores every piece of metadata it verifies in the local repository, which
+        // is exactly what changed since the last sync.
+        let mut client = Client::from_database(
+            self.config.clone(),
+            database,
+            EphemeralRepository::new(),
+            &self.remote,
+        );
+        client.update().await?;
+
+        // The client lazily fetches delegated targets when looking up targets, so walk them here.
+        let delegations = update_delegations(&self.config, client.database_mut(), &remote).await?;
+
+        let Parts {
+            database, local, ..
+        } = client.into_parts();
+
+        let consistent_snapshot = database.trusted_root().consistent_snapshot();
+        let mut report = SyncReport::default();
+
+        for (target_path, description) in changed_targets(previous, &database) {
+            copy_target(
+                &self.remote,
+                storage,
+                consistent_snapshot,
+                &target_path,
+                &description,
+                &mut report,
+            )
+            .await?;
+        }
+
+        for (role, version, raw) in &delegations {
+            store_versioned(
+                storage,
+                consistent_snapshot,
+                role,
+                *version,
+                raw.as_bytes(),
+                &mut report,
+            )
+            .await?;
+        }
+
+        // Store the root metadata chain, followed by the latest root as `root.json`.
+        let root_path = MetadataPath::root();
+        let mut root_versions = root_history;
+        for (meta_path, version) in local.list_metadata().await? {
+            if let (true, MetadataVersion::Number(version)) = (meta_path == root_path, version) {
+                root_versions.push((version, read_metadata(&local, &meta_path, version).await?));
+            }
+        }
+        root_versions.sort_by_key(|(version, _)| *version);
+        for (version, bytes) in &root_versions {
+            store(
+                storage,
+                &root_path,
+                MetadataVersion::Number(*version),
+                bytes,
+                &mut report,
+            )
+            .await?;
+        }
+        if let Some((_, bytes)) = root_versions.last() {
+            store(
+                storage,
+                &root_path,
+                MetadataVersion::None,
+                bytes,
+                &mut report,
+            )
+            .await?;
+        }
+
+        if let Some(targets) = database.trusted_targets() {
+            copy_local_metadata(
+                &local,
+                storage,
+                consistent_snapshot,
+                &MetadataPath::targets(),
+                targets.version(),
+                &mut report,
+            )
+            .await?;
+        }
+        if let Some(snapshot) = database.trusted_snapshot() {
+            copy_local_metadata(
+                &local,
+                storage,
+                consistent_snapshot,
+                &MetadataPath::snapshot(),
+                snapshot.version(),
+                &mut report,
+            )
+            .await?;
+        }
+        if database.trusted_timestamp().is_some() {
+            // The timestamp is never versioned.
+            copy_local_metadata(
+                &local,
+                storage,
+                false,
+                &MetadataPath::timestamp(),
+                0,
+                &mut report,
+            )
+            .await?;
+        }
+
+        Ok(PendingSync { database, report })
+    }
+
+    /// Consider the changes copied by a [Mirror::sync] synced, once they have been committed to
+    /// the storage. Returns what was copied.
+    pub fn apply(&mut self, pending: PendingSync<D>) -> SyncReport {
+        self.database = pending.database;
+        self.synced = true;
+        pending.report
+    }
+}
+
+/// The changes copied into the storage by a [Mirror::sync], which are applied to the [Mirror]
+/// with [Mirror::apply].
+#[derive(Debug)]
+#[must_use = "the mirror only considers the changes synced once they're applied"]
+pub struct PendingSync<D: Pouf> {
+    database: Database<D>,
+    report: SyncReport,
+}
+
+impl<D: Pouf> PendingSync<D> {
+    /// Describes what was copied into the storage.
+    pub fn report(&self) -> &SyncReport {
+        &self.report
+    }
+
+    /// The [Database] describing the metadata and targets in the storage, once the changes are
+    /// committed.
+    pub fn database(&self) -> &Database<D> {
+        &self.database
+    }
+}
+
+/// Fetch root metadata versions from 1 to the version trusted by `database`, returning a database
+/// that only trusts that root, and the raw root metadata.
+///
+/// The roots before the trusted root can't be verified with it, so instead check that each root
+/// was signed by its predecessor, and that the chain ends at the trusted root.
+async fn fetch_root_history<D, R>(
+    config: &Config,
+    database: &Database<D>,
+    remote: &Repository<R, D>,
+) -> Result<(Database<D>, Vec<(u32, Vec<u8>)>)>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    let root_path = MetadataPath::root();
+    let mut history = vec![];
+    let mut chain: Option<Database<D>> = None;
+
+    for version in 1..=database.trusted_root().version() {
+        let raw = remote
+            .fetch_metadata::<RootMetadata>(
+                &root_path,
+                MetadataVersion::Number(version),
+                *config.max_root_length(),
+                vec![],
+            )
+            .await?;
+
+        match &mut chain {
+            Some(chain) => chain.update_root(&raw)?,
+            None => chain = Some(Database::from_trusted_root(&raw)?),
+        }
+
+        history.push((version, raw.as_bytes().to_vec()));
+    }
+
+    match chain {
+        Some(chain) if chain.trusted_root() == database.trusted_root() => Ok((chain, history)),
+        _ => Err(Error::Opaque(format!(
+            "remote root metadata does not match the trusted root version {}",
+            database.trusted_root().version()
+        ))),
+    }
+}
+
+/// Verify every delegated targets role that is listed in the snapshot, and isn't already trusted
+/// at the listed version. Returns the metadata that was fetched.
+async fn update_delegations<D, R>(
+    config: &Config,
+    database: &mut Database<D>,
+    remote: &Repository<R, D>,
+) -> Result<Vec<(MetadataPath, u32, RawSignedMetadata<D, TargetsMetadata>)>>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    let consistent_snapshot = database.trusted_root().consistent_snapshot();
+
+    let mut fetched = vec![];
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([(MetadataPath::targets(), 0)]);
+
+    while let Some((parent, depth)) = queue.pop_front() {
+        if depth >= config.max_delegation_depth() {
+            continue;
+        }
+
+        let roles = match role_metadata(database, &parent) {
+            Some(metadata) => metadata
+                .delegations()
+                .roles()
+                .iter()
+                .map(|delegation| delegation.name().clone())
+                .collect::<Vec<_>>(),
+            None => continue,
+        };
+
+        for role in roles {
+            if !visited.insert(role.clone()) {
+                continue;
+            }
+
+            // Roles that aren't listed in the snapshot can't be verified.
+            let description = match database
+                .trusted_snapshot()
+                .and_then(|snapshot| snapshot.meta().get(&role))
+            {
+                Some(description) => description.clone(),
+                None => continue,
+            };
+
+            let trusted_version = database
+                .trusted_delegations()
+                .get(&role)
+                .map(|metadata| metadata.version());
+            if trusted_version != Some(description.version()) {
+                debug!("Fetching delegated role {} from the remote", role);
+
+                let raw = remote
+                    .fetch_metadata::<TargetsMetadata>(
+                        &role,
+                        version_for(&description, consistent_snapshot),
+                        description.length().or(*config.max_targets_length()),
+                        crypto::retain_supported_hashes(description.hashes()),
+                    )
+                    .await?;
+                database.update_delegated_targets(&parent, &role, &raw)?;
+                fetched.push((role.clone(), description.version(), raw));
+            }
+
+            queue.push_back((role, depth + 1));
+        }
+    }
+
+    Ok(fetched)
+}
+
+/// Returns the targets described by `database` that weren't described the same way by `previous`.
+fn changed_targets<D: Pouf>(
+    previous: Option<&Database<D>>,
+    database: &Database<D>,
+) -> Vec<(TargetPath, TargetDescription)> {
+    let mut roles = vec![MetadataPath::targets()];
+    roles.extend(database.trusted_delegations().keys().cloned());
+
+    let mut changed = vec![];
+    for role in roles {
+        let metadata = match role_metadata(database, &role) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        let previous_targets = previous
+            .and_then(|previous| role_metadata(previous, &role))
+            .map(|metadata| metadata.targets());
+
+        for (target_path, description) in metadata.targets() {
+            if previous_targets.and_then(|targets| targets.get(target_path)) != Some(description) {
+                changed.push((target_path.clone(), description.clone()));
+            }
+        }
+    }
+
+    // Roles may describe the same target, so sort to copy them in a stable order.
+    changed.sort_by(|a, b| a.0.cmp(&b.0));
+    changed.dedup();
+    changed
+}
+
+/// Copy the target at `target_path` to `storage`, at every path clients may fetch it from.
+async fn copy_target<D, R, S>(
+    remote: &R,
+    storage: &S,
+    consistent_snapshot: bool,
+    target_path: &TargetPath,
+    description: &TargetDescription,
+    report: &mut SyncReport,
+) -> Result<()>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+    S: RepositoryStorage<D> + ?Sized,
+{
+    let hashes = crypto::retain_supported_hashes(description.hashes());
+    if hashes.is_empty() {
+        return Err(Error::NoSupportedHashAlgorithm);
+    }
+
+    let paths = if consistent_snapshot {
+        hashes
+            .iter()
+            .map(|(_, hash)| target_path.with_hash_prefix(hash))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        vec![target_path.clone()]
+    };
+
+    for path in paths {
+        debug!("Copying target {} from the remote", path);
+
+        // The target is verified as it's read, so storage fails to store a modified target.
+        let mut read = remote
+            .fetch_target(&path)
+            .await?
+            .check_length_and_hash(description.length(), hashes.clone())?;
+        storage.store_target(&path, &mut read).await?;
+
+        report.targets.push(path);
+    }
+
+    Ok(())
+}
+
+/// Copy the unversioned metadata `meta_path` verified by the client, if it was updated.
+async fn copy_local_metadata<D, S>(
+    local: &EphemeralRepository<D>,
+    storage: &S,
+    consistent_snapshot: bool,
+    meta_path: &MetadataPath,
+    version: u32,
+    report: &mut SyncReport,
+) -> Result<()>
+where
+    D: Pouf,
+    S: RepositoryStorage<D> + ?Sized,
+{
+    let mut read = match local.fetch_metadata(meta_path, MetadataVersion::None).await {
+        Ok(read) => read,
+        // The client only stores metadata that changed.
+        Err(Error::MetadataNotFound { .. }) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let mut bytes = vec![];
+    read.read_to_end(&mut bytes).await?;
+
+    store_versioned(
+        storage,
+        consistent_snapshot,
+        meta_path,
+        version,
+        &bytes,
+        report,
+    )
+    .await
+}
+
+/// Store `bytes` as the unversioned metadata `meta_path`, and also as `version` if consistent
+/// snapshots are enabled.
+async fn store_versioned<D, S>(
+    storage: &S,
+    consistent_snapshot: bool,
+    meta_path: &MetadataPath,
+    version: u32,
+    bytes: &[u8],
+    report: &mut SyncReport,
+) -> Result<()>
+where
+    D: Pouf,
+    S: RepositoryStorage<D> + ?Sized,
+{
+    if consistent_snapshot {
+        store(
+            storage,
+            meta_path,
+            MetadataVersion::Number(version),
+            bytes,
+            report,
+        )
+        .await?;
+    }
+    store(storage, meta_path, MetadataVersion::None, bytes, report).await
+}
+
+async fn store<D, S>(
+    storage: &S,
+    meta_path: &MetadataPath,
+    version: MetadataVersion,
+    bytes: &[u8],
+    report: &mut SyncReport,
+) -> Result<()>
+where
+    D: Pouf,
+    S: RepositoryStorage<D> + ?Sized,
+{
+    storage
+        .store_metadata(meta_path, version, &mut &bytes[..])
+        .await?;
+    report.metadata.push((meta_path.clone(), version));
+    Ok(())
+}
+
+async fn read_metadata<D: Pouf>(
+    local: &EphemeralRepository<D>,
+    meta_path: &MetadataPath,
+    version: u32,
+) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    local
+        .fetch_metadata(meta_path, MetadataVersion::Number(version))
+        .await?
+        .read_to_end(&mut bytes)
+        .await?;
+    Ok(bytes)
+}
+
+/// Describes what a [Mirror::sync] copied into the storage.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    metadata: Vec<(MetadataPath, MetadataVersion)>,
+    targets: Vec<TargetPath>,
+}
+
+impl SyncReport {
+    /// The metadata that was copied, in the order it was written.
+    pub fn metadata(&self) -> &[(MetadataPath, MetadataVersion)] {
+        &self.metadata
+    }
+
+    /// The targets that were copied, at the paths they were stored at.
+    pub fn targets(&self) -> &[TargetPath] {
+        &self.targets
+    }
+
+    /// Whether nothing was copied.
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.targets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::crypto::HashAlgorithm,
+        crate::crypto::{Ed25519PrivateKey, PrivateKey as _},
+        crate::metadata::{
+            Delegation, MetadataDescription, RawSignedMetadataSet, TargetsMetadataBuilder,
+        },
+        crate::pouf::Pouf1,
+        crate::repo_builder::RepoBuilder,
+        crate::repository::fetch_target_to_string,
+        assert_matches::assert_matches,
+        futures_executor::block_on,
+        futures_util::io::Cursor,
+        lazy_static::lazy_static,
+    };
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|key| Ed25519PrivateKey::from_pkcs8(key).unwrap())
+                .collect()
+        };
+    }
+
+    fn metadata_set(repo: &EphemeralRepository<Pouf1>) -> Vec<(MetadataPath, MetadataVersion)> {
+        let mut metadata = repo.metadata().into_keys().collect::<Vec<_>>();
+        metadata.sort();
+        metadata
+    }
+
+    async fn publish_v1(
+        upstream: &EphemeralRepository<Pouf1>,
+        consistent_snapshot: bool,
+    ) -> RawSignedMetadataSet<Pouf1> {
+        RepoBuilder::create(upstream)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|builder| builder.consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .add_target(TargetPath::new("a").unwrap(), Cursor::new(b"a1"))
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn mirror_copies_verified_repository_incrementally() {
+        for consistent_snapshot in [true, false] {
+            block_on(async {
+                let upstream = EphemeralRepository::<Pouf1>::new();
+                let metadata = publish_v1(&upstream, consistent_snapshot).await;
+                let mut upstream_database = Database::from_trusted_metadata(&metadata).unwrap();
+
+                let mirror_repo = EphemeralRepository::<Pouf1>::new();
+                let mut mirror = Mirror::new(
+                    Config::default(),
+                    Database::from_trusted_root(metadata.root().unwrap()).unwrap(),
+                    &upstream,
+                );
+
+                let batch = mirror_repo.batch_update();
+                let pending = mirror.sync(&batch).await.unwrap();
+                batch.commit().await.unwrap();
+                let report = mirror.apply(pending);
+
+                // The mirror has the same contents as the upstream repository.
+                assert_eq!(metadata_set(&mirror_repo), metadata_set(&upstream));
+                assert_eq!(
+                    mirror_repo.list_targets().await.unwrap(),
+                    upstream.list_targets().await.unwrap()
+                );
+                assert_eq!(
+                    report.targets(),
+                    &upstream.list_targets().await.unwrap()[..]
+                );
+
+                // Publish a new root, a new target, and leave the old target unchanged.
+                let metadata = RepoBuilder::from_database(&upstream, &upstream_database)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root_with_builder(|builder| {
+                        builder
+                            .consistent_snapshot(consistent_snapshot)
+                            .root_key(KEYS[1].public().clone())
+                    })
+                    .unwrap()
+                    .add_target(TargetPath::new("a").unwrap(), Cursor::new(b"a1"))
+                    .await
+                    .unwrap()
+                    .add_target(TargetPath::new("b").unwrap(), Cursor::new(b"b1"))
+                    .await
+                    .unwrap()
+                    .commit()
+                    .await
+                    .unwrap();
+                upstream_database.update_metadata(&metadata).unwrap();
+
+                let report = mirror.apply(mirror.sync(&mirror_repo).await.unwrap());
+                assert_eq!(metadata_set(&mirror_repo), metadata_set(&upstream));
+                assert_eq!(
+                    mirror_repo.list_targets().await.unwrap(),
+                    upstream.list_targets().await.unwrap()
+                );
+
+                // Only the new target was copied.
+                assert!(!report.targets().is_empty());
+                assert!(report
+                    .targets()
+                    .iter()
+                    .all(|path| path.as_str().ends_with('b')));
+                assert!(report
+                    .metadata()
+                    .contains(&(MetadataPath::root(), MetadataVersion::Number(2))));
+                assert!(!report
+                    .metadata()
+                    .contains(&(MetadataPath::root(), MetadataVersion::Number(1))));
+                assert_eq!(
+                    report.metadata().last(),
+                    Some(&(MetadataPath::timestamp(), MetadataVersion::None))
+                );
+
+                // Nothing changed, so nothing is copied.
+                assert_eq!(
+                    mirror.apply(mirror.sync(&mirror_repo).await.unwrap()),
+                    SyncReport::default()
+                );
+                assert_eq!(mirror.database().trusted_root().version(), 2);
+            })
+        }
+    }
+
+    #[test]
+    fn mirror_copies_delegated_targets() {
+        block_on(async {
+            let upstream = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"bar";
+
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(
+                    target_path.clone(),
+                    target_file,
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            upstream
+                .store_metadata(
+                    &delegation_path,
+                    MetadataVersion::None,
+                    &mut raw_delegation.as_bytes(),
+                )
+                .await
+                .unwrap();
+            upstream
+                .store_target(&target_path, &mut &*target_file)
+                .await
+                .unwrap();
+
+            let metadata = RepoBuilder::create(&upstream)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|builder| builder.consistent_snapshot(false))
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(target_path.clone())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        MetadataDescription::from_slice(
+                            raw_delegation.as_bytes(),
+                            1,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mirror_repo = EphemeralRepository::<Pouf1>::new();
+            let mut mirror = Mirror::new(
+                Config::default(),
+                Database::from_trusted_root(metadata.root().unwrap()).unwrap(),
+                &upstream,
+            );
+            let report = mirror.apply(mirror.sync(&mirror_repo).await.unwrap());
+
+            assert_eq!(metadata_set(&mirror_repo), metadata_set(&upstream));
+            assert!(report
+                .metadata()
+                .contains(&(delegation_path.clone(), MetadataVersion::None)));
+            assert_eq!(
+                fetch_target_to_string(&mirror_repo, &target_path)
+                    .await
+                    .unwrap(),
+                "bar"
+            );
+            assert!(mirror
+                .database()
+                .trusted_delegations()
+                .contains_key(&delegation_path));
+        })
+    }
+
+    #[test]
+    fn mirror_copies_changes_again_if_not_applied() {
+        block_on(async {
+            let upstream = EphemeralRepository::<Pouf1>::new();
+            let metadata = publish_v1(&upstream, true).await;
+
+            let mirror_repo = EphemeralRepository::<Pouf1>::new();
+            let mut mirror = Mirror::new(
+                Config::default(),
+                Database::from_trusted_root(metadata.root().unwrap()).unwrap(),
+                &upstream,
+            );
+
+            // The batch is never committed, so the changes aren't applied.
+            let batch = mirror_repo.batch_update();
+            let pending = mirror.sync(&batch).await.unwrap();
+            drop(batch);
+            assert_eq!(metadata_set(&mirror_repo), vec![]);
+
+            let report = mirror.apply(mirror.sync(&mirror_repo).await.unwrap());
+            assert_eq!(&report, pending.report());
+            assert_eq!(metadata_set(&mirror_repo), metadata_set(&upstream));
+        })
+    }
+
+    #[test]
+    fn mirror_rejects_modified_targets() {
+        block_on(async {
+            let upstream = EphemeralRepository::<Pouf1>::new();
+            let metadata = publish_v1(&upstream, false).await;
+
+            upstream
+                .store_target(&TargetPath::new("a").unwrap(), &mut "a2".as_bytes())
+                .await
+                .unwrap();
+
+            let mirror_repo = EphemeralRepository::<Pouf1>::new();
+            let mirror = Mirror::new(
+                Config::default(),
+                Database::from_trusted_root(metadata.root().unwrap()).unwrap(),
+                &upstream,
+            );
+
+            let batch = mirror_repo.batch_update();
+            assert_matches!(mirror.sync(&batch).await, Err(Error::Io(_)));
+            drop(batch);
+            assert_eq!(metadata_set(&mirror_repo), vec![]);
+        })
+    }
+
+    #[test]
+    fn mirror_rejects_root_history_that_does_not_match_trusted_root() {
+        block_on(async {
+            let upstream = EphemeralRepository::<Pouf1>::new();
+            publish_v1(&upstream, false).await;
+
+            // A different repository's root.
+            let other = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&other)
+                .trusted_root_keys(&[&KEYS[1]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mirror = Mirror::new(
+                Config::default(),
+                Database::from_trusted_root(metadata.root().unwrap()).unwrap(),
+                &upstream,
+            );
+            assert_matches!(
+                mirror.sync(&EphemeralRepository::<Pouf1>::new()).await,
+                Err(Error::Opaque(_))
+            );
+        })
+    }
+}
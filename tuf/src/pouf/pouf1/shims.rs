@@ -65,6 +65,7 @@ impl RootMetadata {
             keys: meta
                 .keys()
                 .iter()
+                .chain(meta.key_id_aliases())
                 .map(|(id, key)| (id.clone(), key.clone()))
                 .collect(),
             roles: RoleDefinitions {
@@ -91,16 +92,16 @@ impl RootMetadata {
             )));
         }
 
-        // Ignore all keys with incorrect key IDs. We should give an error if the key ID is not
+        // Keys with incorrect key IDs are kept apart. We should give an error if the key ID is not
         // correct according to TUF spec. However, due to backward compatibility, we may receive
-        // metadata with key IDs generated by TUF 0.9. We simply ignore those old keys.
-        let keys_with_correct_key_id = self
+        // metadata with key IDs generated by TUF 0.9 or chosen by the repository, which are only
+        // trusted if the client's `KeyIdPolicy` accepts them.
+        let (keys_with_correct_key_id, key_id_aliases) = self
             .keys
             .into_iter()
-            .filter(|(key_id, pkey)| key_id == pkey.key_id())
-            .collect();
+            .partition(|(key_id, pkey)| key_id == pkey.key_id());
 
-        metadata::RootMetadata::new(
+        Ok(metadata::RootMetadata::new(
             self.version,
             parse_datetime(&self.expires)?,
             self.consistent_snapshot,
@@ -109,7 +110,8 @@ impl RootMetadata {
             self.roles.snapshot,
             self.roles.targets,
             self.roles.timestamp,
-        )
+        )?
+        .with_key_id_aliases(key_id_aliases))
     }
 }
 
@@ -353,7 +355,93 @@ impl PublicKey {
             keytype,
             scheme,
             keyid_hash_algorithms,
-            keyval: PublicKeyValue { public: public_key },
+            keyval: PublicKeyValue::Public { public: public_key },
+        }
+    }
+
+    pub fn new_keyless(
+        keytype: crypto::KeyType,
+        scheme: crypto::SignatureScheme,
+        keyid_hash_algorithms: Option<Vec<String>>,
+        identity: String,
+        issuer: String,
+    ) -> Self {
+        PublicKey {
+            keytype,
+            scheme,
+            keyid_hash_algorithms,
+            keyval: PublicKeyValue::Keyless { identity, issuer },
+        }
+    }
+
+    pub fn public_key(&self) -> Option<&str> {
+        match self.keyval {
+            PublicKeyValue::Public { ref public } => Some(public),
+            PublicKeyValue::Keyless { .. } => None,
+        }
+    }
+
+    /// The identity and issuer of a keyless key.
+    pub fn keyless_identity(&self) -> Option<(&str, &str)> {
+        match self.keyval {
+            PublicKeyValue::Public { .. } => None,
+            PublicKeyValue::Keyless {
+                ref identity,
+                ref issuer,
+            } => Some((identity, issuer)),
+        }
+    }
+
+    pub fn scheme(&self) -> &crypto::SignatureScheme {
+        &self.scheme
+    }
+
+    pub fn keytype(&self) -> &crypto::KeyType {
+        &self.keytype
+    }
+
+    pub fn keyid_hash_algorithms(&self) -> &Option<Vec<String>> {
+        &self.keyid_hash_algorithms
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PublicKeyValue {
+    Public { public: String },
+    Keyless { identity: String, issuer: String },
+}
+
+/// A private key in the securesystemslib key format, which is shared by python-tuf and go-tuf.
+#[derive(Serialize, Deserialize)]
+pub struct PrivateKey {
+    keytype: crypto::KeyType,
+    scheme: crypto::SignatureScheme,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keyid: Option<crypto::KeyId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keyid_hash_algorithms: Option<Vec<String>>,
+    keyval: PrivateKeyValue,
+}
+
+impl PrivateKey {
+    pub fn new(
+        keytype: crypto::KeyType,
+        scheme: crypto::SignatureScheme,
+        keyid: crypto::KeyId,
+        keyid_hash_algorithms: Option<Vec<String>>,
+        public_key: String,
+        private_key: String,
+    ) -> Self {
+        PrivateKey {
+            keytype,
+            scheme,
+            keyid: Some(keyid),
+            keyid_hash_algorithms,
+            keyval: PrivateKeyValue {
+                public: public_key,
+                private: private_key,
+            },
         }
     }
 
@@ -361,6 +449,10 @@ impl PublicKey {
         &self.keyval.public
     }
 
+    pub fn private_key(&self) -> &str {
+        &self.keyval.private
+    }
+
     pub fn scheme(&self) -> &crypto::SignatureScheme {
         &self.scheme
     }
@@ -369,14 +461,19 @@ impl PublicKey {
         &self.keytype
     }
 
+    pub fn keyid(&self) -> Option<&crypto::KeyId> {
+        self.keyid.as_ref()
+    }
+
     pub fn keyid_hash_algorithms(&self) -> &Option<Vec<String>> {
         &self.keyid_hash_algorithms
     }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct PublicKeyValue {
+pub struct PrivateKeyValue {
     public: String,
+    private: String,
 }
 
 #[derive(Serialize, Deserialize)]
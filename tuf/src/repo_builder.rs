@@ -2,24 +2,35 @@
 
 use {
     crate::{
-        crypto::{self, HashAlgorithm, PrivateKey, PublicKey},
+        clock::{Clock, FixedClock, SystemClock},
+        crypto::{self, HashAlgorithm, KeyId, KeyIdScheme, PrivateKey, PublicKey},
         database::Database,
         error::{Error, Result},
         metadata::{
             Delegation, DelegationsBuilder, Metadata, MetadataDescription, MetadataPath,
             MetadataVersion, RawSignedMetadata, RawSignedMetadataSet, RawSignedMetadataSetBuilder,
-            RootMetadata, RootMetadataBuilder, SignedMetadataBuilder, SnapshotMetadata,
-            SnapshotMetadataBuilder, TargetDescription, TargetPath, TargetsMetadata,
-            TargetsMetadataBuilder, TimestampMetadata, TimestampMetadataBuilder,
+            RoleDefinition, RootMetadata, RootMetadataBuilder, SignedMetadataBuilder,
+            SnapshotMetadata, SnapshotMetadataBuilder, TargetDescription, TargetPath,
+            TargetsMetadata, TargetsMetadataBuilder, TimestampMetadata, TimestampMetadataBuilder,
         },
         pouf::Pouf,
         repository::RepositoryStorage,
-        verify::Verified,
+        revocation::{RevocationList, SignedKeyRevocation},
+        verify::{Verified, Verifier},
     },
+    blocking::{unblock, Unblock},
     chrono::{DateTime, Duration, Utc},
     futures_io::{AsyncRead, AsyncSeek},
     futures_util::AsyncSeekExt as _,
-    std::{collections::HashMap, io::SeekFrom, marker::PhantomData},
+    std::{
+        cell::Cell,
+        collections::{HashMap, HashSet},
+        fs::File,
+        io::SeekFrom,
+        marker::PhantomData,
+        path::Path,
+        sync::Arc,
+    },
 };
 
 mod private {
@@ -39,8 +50,8 @@ mod private {
 
 const DEFAULT_ROOT_EXPIRATION: Duration = Duration::days(365);
 const DEFAULT_TARGETS_EXPIRATION: Duration = Duration::days(90);
-const DEFAULT_SNAPSHOT_EXPIRATION: Duration = Duration::days(7);
-const DEFAULT_TIMESTAMP_EXPIRATION: Duration = Duration::days(1);
+pub(crate) const DEFAULT_SNAPSHOT_EXPIRATION: Duration = Duration::days(7);
+pub(crate) const DEFAULT_TIMESTAMP_EXPIRATION: Duration = Duration::days(1);
 
 /// Trait to track each of the [RepoBuilder] building states.
 ///
@@ -210,7 +221,8 @@ where
 {
     repo: R,
     db: Option<&'a Database<D>>,
-    current_time: DateTime<Utc>,
+    clock: Arc<dyn Clock>,
+    current_time: Cell<Option<DateTime<Utc>>>,
     signing_root_keys: Vec<&'a dyn PrivateKey>,
     signing_targets_keys: Vec<&'a dyn PrivateKey>,
     signing_snapshot_keys: Vec<&'a dyn PrivateKey>,
@@ -219,11 +231,13 @@ where
     trusted_targets_keys: Vec<&'a dyn PrivateKey>,
     trusted_snapshot_keys: Vec<&'a dyn PrivateKey>,
     trusted_timestamp_keys: Vec<&'a dyn PrivateKey>,
-    time_version: Option<u32>,
+    time_versioning: bool,
     root_expiration_duration: Duration,
     targets_expiration_duration: Duration,
     snapshot_expiration_duration: Duration,
     timestamp_expiration_duration: Duration,
+    expiration_horizon: Duration,
+    revocations: Vec<SignedKeyRevocation>,
     _pouf: PhantomData<D>,
 }
 
@@ -233,84 +247,76 @@ where
     R: RepositoryStorage<D>,
 {
     fn root_keys_changed(&self, root: &Verified<RootMetadata>) -> bool {
-        let root_keys_count = root.root_keys().count();
-        if root_keys_count != self.trusted_root_keys.len() {
+        let key_ids = role_key_ids(root, root.root());
+        if key_ids.len() != self.trusted_root_keys.len() {
             return true;
         }
 
-        for key in &self.trusted_root_keys {
-            if root.root().key_ids().get(key.public().key_id()).is_none() {
-                return true;
-            }
-        }
-
-        false
+        keys_changed(&key_ids, &self.trusted_root_keys)
     }
-    fn targets_keys_changed(&self, root: &Verified<RootMetadata>) -> bool {
-        for key in &self.trusted_targets_keys {
-            if root
-                .targets()
-                .key_ids()
-                .get(key.public().key_id())
-                .is_none()
-            {
-                return true;
-            }
-        }
 
-        false
+    fn targets_keys_changed(&self, root: &Verified<RootMetadata>) -> bool {
+        keys_changed(
+            &role_key_ids(root, root.targets()),
+            &self.trusted_targets_keys,
+        )
     }
 
     fn snapshot_keys_changed(&self, root: &Verified<RootMetadata>) -> bool {
-        for key in &self.trusted_snapshot_keys {
-            if root
-                .snapshot()
-                .key_ids()
-                .get(key.public().key_id())
-                .is_none()
-            {
-                return true;
-            }
-        }
-
-        false
+        keys_changed(
+            &role_key_ids(root, root.snapshot()),
+            &self.trusted_snapshot_keys,
+        )
     }
 
     fn timestamp_keys_changed(&self, root: &Verified<RootMetadata>) -> bool {
-        for key in &self.trusted_timestamp_keys {
-            if root
-                .timestamp()
-                .key_ids()
-                .get(key.public().key_id())
-                .is_none()
-            {
-                return true;
-            }
+        keys_changed(
+            &role_key_ids(root, root.timestamp()),
+            &self.trusted_timestamp_keys,
+        )
+    }
+
+    /// Whether metadata which expires at `expires` is expired, or expires within the expiration
+    /// horizon.
+    fn is_expiring(&self, expires: &DateTime<Utc>) -> Result<bool> {
+        match self.now()?.checked_add_signed(self.expiration_horizon) {
+            Some(horizon) => Ok(expires <= &horizon),
+            None => Ok(true),
+        }
+    }
+
+    /// The time used to check for expired metadata, and to compute new expirations. The clock is
+    /// only read once, so all the staged metadata is built against the same time.
+    fn now(&self) -> Result<DateTime<Utc>> {
+        if let Some(now) = self.current_time.get() {
+            return Ok(now);
         }
 
-        false
+        let now = self.clock.now()?;
+        self.current_time.set(Some(now));
+        Ok(now)
     }
 
     /// The initial version number for non-root metadata.
-    fn non_root_initial_version(&self) -> u32 {
-        if let Some(time_version) = self.time_version {
-            time_version
-        } else {
-            1
-        }
+    fn non_root_initial_version(&self) -> Result<u32> {
+        Ok(self.time_version()?.unwrap_or(1))
     }
 
-    /// If time versioning is enabled, this updates the current time version to match the current
-    /// time. It will disable time versioning if the current timestamp is less than or equal to
-    /// zero, or it is greater than max u32.
-    fn update_time_version(&mut self) {
+    /// If time versioning is enabled, this returns the time version that matches the current
+    /// time. Time versioning is disabled if the current timestamp is less than or equal to zero, or
+    /// it is greater than max u32.
+    fn time_version(&self) -> Result<Option<u32>> {
+        if !self.time_versioning {
+            return Ok(None);
+        }
+
         // We can use the time version if it is greater than zero and less than max u32. Otherwise
         // fall back to default monontonic versioning.
-        let timestamp = self.current_time.timestamp();
+        let timestamp = self.now()?.timestamp();
         if timestamp > 0 {
-            self.time_version = timestamp.try_into().ok();
+            Ok(timestamp.try_into().ok())
         } else {
-            self.time_version = None;
+            Ok(None)
         }
     }
 
@@ -320,7 +326,7 @@ where
         current_version: u32,
         path: fn() -> MetadataPath,
     ) -> Result<u32> {
-        if let Some(time_version) = self.time_version {
+        if let Some(time_version) = self.time_version()? {
             // We can only use the time version if it's larger than our current version. If not,
             // then fall back to the next version.
             if current_version < time_version {
@@ -410,7 +416,8 @@ where
             ctx: RepoContext {
                 repo,
                 db: None,
-                current_time: Utc::now(),
+                clock: Arc::new(SystemClock),
+                current_time: Cell::new(None),
                 signing_root_keys: vec![],
                 signing_targets_keys: vec![],
                 signing_snapshot_keys: vec![],
@@ -419,11 +426,13 @@ where
                 trusted_targets_keys: vec![],
                 trusted_snapshot_keys: vec![],
                 trusted_timestamp_keys: vec![],
-                time_version: None,
+                time_versioning: false,
                 root_expiration_duration: DEFAULT_ROOT_EXPIRATION,
                 targets_expiration_duration: DEFAULT_TARGETS_EXPIRATION,
                 snapshot_expiration_duration: DEFAULT_SNAPSHOT_EXPIRATION,
                 timestamp_expiration_duration: DEFAULT_TIMESTAMP_EXPIRATION,
+                expiration_horizon: Duration::zero(),
+                revocations: vec![],
                 _pouf: PhantomData,
             },
             state: Root {
@@ -499,7 +508,8 @@ where
             ctx: RepoContext {
                 repo,
                 db: Some(db),
-                current_time: Utc::now(),
+                clock: Arc::clone(db.clock()),
+                current_time: Cell::new(None),
                 signing_root_keys: vec![],
                 signing_targets_keys: vec![],
                 signing_snapshot_keys: vec![],
@@ -508,11 +518,13 @@ where
                 trusted_targets_keys: vec![],
                 trusted_snapshot_keys: vec![],
                 trusted_timestamp_keys: vec![],
-                time_version: None,
+                time_versioning: false,
                 root_expiration_duration: DEFAULT_ROOT_EXPIRATION,
                 targets_expiration_duration: DEFAULT_TARGETS_EXPIRATION,
                 snapshot_expiration_duration: DEFAULT_SNAPSHOT_EXPIRATION,
                 timestamp_expiration_duration: DEFAULT_TIMESTAMP_EXPIRATION,
+                expiration_horizon: Duration::zero(),
+                revocations: vec![],
                 _pouf: PhantomData,
             },
             state: Root { builder },
@@ -520,28 +532,27 @@ where
     }
 
     /// Change the time the builder will use to see if metadata is expired, and the base time to use
-    /// to compute the next expiration.
+    /// to compute the next expiration. This takes precedence over the [clock](RepoBuilder::clock).
     ///
-    /// Default is the current wall clock time in UTC.
-    pub fn current_time(mut self, current_time: DateTime<Utc>) -> Self {
-        self.ctx.current_time = current_time;
-
-        // Update our time version if enabled.
-        if self.ctx.time_version.is_some() {
-            self.ctx.update_time_version();
-        }
+    /// Default is the time reported by the clock when the builder first needs it.
+    pub fn current_time(self, current_time: DateTime<Utc>) -> Self {
+        self.ctx.current_time.set(Some(current_time));
+        self
+    }
 
+    /// Change the [Clock] the builder reads the current time from.
+    ///
+    /// Default is the [Database::clock] when created with
+    /// [from_database](RepoBuilder::from_database), otherwise the [SystemClock].
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.ctx.clock = clock;
         self
     }
 
     /// Create Non-root metadata based off the current UTC timestamp, instead of a monotonic
     /// increment.
     pub fn time_versioning(mut self, time_versioning: bool) -> Self {
-        if time_versioning {
-            self.ctx.update_time_version();
-        } else {
-            self.ctx.time_version = None;
-        }
+        self.ctx.time_versioning = time_versioning;
         self
     }
 
@@ -594,6 +605,15 @@ where
         self
     }
 
+    /// Treat metadata which expires within this duration past the current time as expired, so it
+    /// is replaced by the `stage_*_if_necessary` methods before it actually expires.
+    ///
+    /// Defaults to 0, which only replaces metadata that has already expired.
+    pub fn expiration_horizon(mut self, duration: Duration) -> Self {
+        self.ctx.expiration_horizon = duration;
+        self
+    }
+
     /// Sign the root metadata with `keys`, but do not include the keys as trusted root keys in the
     /// root metadata. This is typically used to support root key rotation.
     pub fn signing_root_keys(mut self, keys: &[&'a dyn PrivateKey]) -> Self {
@@ -670,6 +690,21 @@ where
         self
     }
 
+    /// Publish `revocation` alongside the metadata, so clients stop counting the revoked key
+    /// towards the threshold of its role. Committing fails if a staged role still needs the revoked
+    /// key to meet its threshold.
+    ///
+    /// The revocation is added to the
+    /// [published revocations](crate::database::Database::published_revocations) of the database
+    /// the builder was created from, so that database must have been given the repository's
+    /// current [RevocationList], as a [Client](crate::client::Client) does when it updates.
+    /// Clients only fetch the revocations along with new snapshot metadata, so a new snapshot is
+    /// staged if necessary.
+    pub fn add_key_revocation(mut self, revocation: SignedKeyRevocation) -> Self {
+        self.ctx.revocations.push(revocation);
+        self
+    }
+
     /// Stage a root metadata.
     ///
     /// If this is a new repository, the root will be staged with:
@@ -699,9 +734,10 @@ where
     ///
     /// * There is no trusted root metadata.
     /// * The trusted keys are different from the keys that are in the trusted root.
-    /// * The trusted root metadata has expired.
+    /// * The trusted root metadata has expired, or expires within the
+    ///   [expiration horizon](RepoBuilder::expiration_horizon).
     pub fn stage_root_if_necessary(self) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
-        if self.need_new_root() {
+        if self.need_new_root()? {
             self.stage_root()
         } else {
             Ok(self.skip_root())
@@ -756,7 +792,7 @@ where
             .state
             .builder
             .version(next_version)
-            .expires(self.ctx.current_time + self.ctx.root_expiration_duration);
+            .expires(self.ctx.now()? + self.ctx.root_expiration_duration);
         let root = f(root_builder).build()?;
 
         let raw_root = sign(
@@ -795,6 +831,22 @@ where
             .await
     }
 
+    /// Add a target that's loaded in from the file at `path`. This will store the target in the
+    /// repository, and may stage a root metadata if necessary.
+    ///
+    /// This will hash the file with [HashAlgorithm::Sha256].
+    ///
+    /// See `RepoBuilder<Targets>::add_target_from_path` for more details.
+    pub async fn add_target_from_path(
+        self,
+        target_path: TargetPath,
+        path: &Path,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
+        self.stage_root_if_necessary()?
+            .add_target_from_path(target_path, path)
+            .await
+    }
+
     /// Validate and write the metadata to the repository.
     ///
     /// This may stage a root, targets, snapshot, and timestamp metadata if necessary.
@@ -805,29 +857,29 @@ where
     }
 
     /// Check if we need a new root database.
-    fn need_new_root(&self) -> bool {
+    fn need_new_root(&self) -> Result<bool> {
         // We need a new root metadata if we don't have a database yet.
         let trusted_root = if let Some(db) = self.ctx.db {
             db.trusted_root()
         } else {
-            return true;
+            return Ok(true);
         };
 
-        // We need a new root metadata if the metadata expired.
-        if trusted_root.expires() <= &self.ctx.current_time {
-            return true;
+        // We need a new root metadata if the metadata expired, or is about to.
+        if self.ctx.is_expiring(trusted_root.expires())? {
+            return Ok(true);
         }
 
         // Sign the metadata if we passed in any old root keys.
         if !self.ctx.signing_root_keys.is_empty() {
-            return true;
+            return Ok(true);
         }
 
         // Otherwise, see if any of the keys have changed.
-        self.ctx.root_keys_changed(trusted_root)
+        Ok(self.ctx.root_keys_changed(trusted_root)
             || self.ctx.targets_keys_changed(trusted_root)
             || self.ctx.snapshot_keys_changed(trusted_root)
-            || self.ctx.timestamp_keys_changed(trusted_root)
+            || self.ctx.timestamp_keys_changed(trusted_root))
     }
 }
 
@@ -862,9 +914,10 @@ where
     /// Stage a new targets using the default settings if:
     ///
     /// * There is no trusted targets metadata.
-    /// * The trusted targets metadata has expired.
+    /// * The trusted targets metadata has expired, or expires within the
+    ///   [expiration horizon](RepoBuilder::expiration_horizon).
     pub fn stage_targets_if_necessary(self) -> Result<RepoBuilder<'a, D, R, Snapshot<D>>> {
-        if self.need_new_targets() {
+        if self.need_new_targets()? {
             self.stage_targets_with_builder(|builder| builder)
         } else {
             Ok(self.skip_targets())
@@ -900,11 +953,76 @@ where
     /// This will hash the file with the hash specified in [RepoBuilder::target_hash_algorithms]. If
     /// none was specified, the file will be hashed with [HashAlgorithm::Sha256].
     pub async fn add_target_with_custom<Rd>(
-        mut self,
+        self,
         target_path: TargetPath,
         mut reader: Rd,
         custom: HashMap<String, serde_json::Value>,
     ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
+    where
+        Rd: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        let target_description = TargetDescription::from_reader_with_custom(
+            &mut reader,
+            &self.state.file_hash_algorithms,
+            custom,
+        )
+        .await?;
+
+        self.store_target(target_path, target_description, reader)
+            .await
+    }
+
+    /// Add a target that's loaded in from the file at `path`. This will store the target in the
+    /// repository.
+    ///
+    /// This will hash the file with the hash specified in [RepoBuilder::target_hash_algorithms]. If
+    /// none was specified, the file will be hashed with [HashAlgorithm::Sha256]. The file is opened
+    /// once, and hashed like [TargetDescription::from_path] on a blocking thread pool before the
+    /// same file is stored. It must not be modified until this completes.
+    pub async fn add_target_from_path(
+        self,
+        target_path: TargetPath,
+        path: &Path,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
+        self.add_target_from_path_with_custom(target_path, path, HashMap::new())
+            .await
+    }
+
+    /// Add a target that's loaded in from the file at `path`. This will store the target in the
+    /// repository.
+    ///
+    /// See [RepoBuilder::add_target_from_path] for more details.
+    pub async fn add_target_from_path_with_custom(
+        self,
+        target_path: TargetPath,
+        path: &Path,
+        custom: HashMap<String, serde_json::Value>,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
+        let path = path.to_path_buf();
+        let hash_algs = self.state.file_hash_algorithms.clone();
+        let (file, length, hashes) = unblock(move || {
+            let file = File::open(&path).map_err(|err| Error::IoPath {
+                path: path.clone(),
+                err,
+            })?;
+            let (length, hashes) =
+                crypto::calculate_hashes_from_open_file(&file, &path, &hash_algs)?;
+            Ok::<_, Error>((file, length, hashes))
+        })
+        .await?;
+        let target_description = TargetDescription::new(length, hashes, custom)?;
+
+        self.store_target(target_path, target_description, Unblock::new(file))
+            .await
+    }
+
+    /// Store the target read from `reader` in the repository, and add it to the targets metadata.
+    async fn store_target<Rd>(
+        mut self,
+        target_path: TargetPath,
+        target_description: TargetDescription,
+        mut reader: Rd,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
     where
         Rd: AsyncRead + AsyncSeek + Unpin + Send,
     {
@@ -919,13 +1037,6 @@ where
             });
         };
 
-        let target_description = TargetDescription::from_reader_with_custom(
-            &mut reader,
-            &self.state.file_hash_algorithms,
-            custom,
-        )
-        .await?;
-
         // According to TUF section 5.5.2, when consistent snapshot is enabled, target files should be
         // stored at `$HASH.FILENAME.EXT`. Otherwise it is stored at `FILENAME.EXT`.
         if consistent_snapshot {
@@ -978,7 +1089,7 @@ where
         F: FnOnce(TargetsMetadataBuilder) -> TargetsMetadataBuilder,
     {
         let mut targets_builder = TargetsMetadataBuilder::new()
-            .expires(self.ctx.current_time + self.ctx.targets_expiration_duration);
+            .expires(self.ctx.now()? + self.ctx.targets_expiration_duration);
 
         let mut delegations_builder = DelegationsBuilder::new();
 
@@ -1005,7 +1116,7 @@ where
                 }
             }
         } else {
-            targets_builder = targets_builder.version(self.ctx.non_root_initial_version());
+            targets_builder = targets_builder.version(self.ctx.non_root_initial_version()?);
         }
 
         // Overwrite any of the old targets with the new ones.
@@ -1058,38 +1169,38 @@ where
         self.stage_targets_if_necessary()?.commit().await
     }
 
-    fn need_new_targets(&self) -> bool {
+    fn need_new_targets(&self) -> Result<bool> {
         // We need a new targets metadata if we added any targets.
         if !self.state.targets.is_empty() {
-            return true;
+            return Ok(true);
         }
 
         // We need a new targets metadata if we staged a new root.
         if self.state.staged_root.is_some() {
-            return true;
+            return Ok(true);
         }
 
         // We need a new targets metadata if we don't have a database yet.
         let db = if let Some(ref db) = self.ctx.db {
             db
         } else {
-            return true;
+            return Ok(true);
         };
 
         // We need a new targets metadata if the database doesn't have a targets.
         let trusted_targets = if let Some(trusted_targets) = db.trusted_targets() {
             trusted_targets
         } else {
-            return true;
+            return Ok(true);
         };
 
-        // We need a new targets metadata if the metadata expired.
-        if trusted_targets.expires() <= &self.ctx.current_time {
-            return true;
+        // We need a new targets metadata if the metadata expired, or is about to.
+        if self.ctx.is_expiring(trusted_targets.expires())? {
+            return Ok(true);
         }
 
         // Otherwise, see if the targets keys have changed.
-        self.ctx.targets_keys_changed(db.trusted_root())
+        Ok(self.ctx.targets_keys_changed(db.trusted_root()))
     }
 }
 
@@ -1132,9 +1243,10 @@ where
     /// Stage a new snapshot using the default settings if:
     ///
     /// * There is no trusted snapshot metadata.
-    /// * The trusted snapshot metadata has expired.
+    /// * The trusted snapshot metadata has expired, or expires within the
+    ///   [expiration horizon](RepoBuilder::expiration_horizon).
     pub fn stage_snapshot_if_necessary(self) -> Result<RepoBuilder<'a, D, R, Timestamp<D>>> {
-        if self.need_new_snapshot() {
+        if self.need_new_snapshot()? {
             self.stage_snapshot()
         } else {
             Ok(self.skip_snapshot())
@@ -1162,7 +1274,7 @@ where
         F: FnOnce(SnapshotMetadataBuilder) -> SnapshotMetadataBuilder,
     {
         let mut snapshot_builder = SnapshotMetadataBuilder::new()
-            .expires(self.ctx.current_time + self.ctx.snapshot_expiration_duration);
+            .expires(self.ctx.now()? + self.ctx.snapshot_expiration_duration);
 
         if let Some(trusted_snapshot) = self.ctx.db.and_then(|db| db.trusted_snapshot()) {
             let next_version = self
@@ -1179,7 +1291,7 @@ where
                 }
             }
         } else {
-            snapshot_builder = snapshot_builder.version(self.ctx.non_root_initial_version());
+            snapshot_builder = snapshot_builder.version(self.ctx.non_root_initial_version()?);
         }
 
         // Overwrite the targets entry if specified.
@@ -1218,38 +1330,44 @@ where
         self.stage_snapshot_if_necessary()?.commit().await
     }
 
-    fn need_new_snapshot(&self) -> bool {
+    fn need_new_snapshot(&self) -> Result<bool> {
         // We need a new snapshot metadata if we staged a new root.
         if self.state.staged_root.is_some() {
-            return true;
+            return Ok(true);
         }
 
         // We need a new snapshot metadata if we staged a new targets.
         if self.state.staged_targets.is_some() {
-            return true;
+            return Ok(true);
+        }
+
+        // We need a new snapshot metadata if we're publishing revocations, since clients only
+        // fetch them along with a new snapshot.
+        if !self.ctx.revocations.is_empty() {
+            return Ok(true);
         }
 
         // We need a new snapshot metadata if we don't have a database yet.
         let db = if let Some(ref db) = self.ctx.db {
             db
         } else {
-            return true;
+            return Ok(true);
         };
 
         // We need a new snapshot metadata if the database doesn't have a snapshot.
         let trusted_snapshot = if let Some(trusted_snapshot) = db.trusted_snapshot() {
             trusted_snapshot
         } else {
-            return true;
+            return Ok(true);
         };
 
-        // We need a new snapshot metadata if the metadata expired.
-        if trusted_snapshot.expires() <= &self.ctx.current_time {
-            return true;
+        // We need a new snapshot metadata if the metadata expired, or is about to.
+        if self.ctx.is_expiring(trusted_snapshot.expires())? {
+            return Ok(true);
         }
 
         // Otherwise, see if the snapshot keys have changed.
-        self.ctx.snapshot_keys_changed(db.trusted_root())
+        Ok(self.ctx.snapshot_keys_changed(db.trusted_root()))
     }
 }
 
@@ -1285,9 +1403,10 @@ where
     /// Stage a new timestamp using the default settings if:
     ///
     /// * There is no trusted timestamp metadata.
-    /// * The trusted timestamp metadata has expired.
+    /// * The trusted timestamp metadata has expired, or expires within the
+    ///   [expiration horizon](RepoBuilder::expiration_horizon).
     pub fn stage_timestamp_if_necessary(self) -> Result<RepoBuilder<'a, D, R, Done<D>>> {
-        if self.need_new_timestamp() {
+        if self.need_new_timestamp()? {
             self.stage_timestamp()
         } else {
             Ok(self.skip_timestamp())
@@ -1324,10 +1443,10 @@ where
                 self.ctx
                     .non_root_next_version(trusted_timestamp.version(), MetadataPath::timestamp)?
             } else {
-                self.ctx.non_root_initial_version()
+                self.ctx.non_root_initial_version()?
             }
         } else {
-            self.ctx.non_root_initial_version()
+            self.ctx.non_root_initial_version()?
         };
 
         let description = if let Some(description) = self.state.snapshot_description()? {
@@ -1345,7 +1464,7 @@ where
 
         let timestamp_builder = TimestampMetadataBuilder::from_metadata_description(description)
             .version(next_version)
-            .expires(self.ctx.current_time + self.ctx.timestamp_expiration_duration);
+            .expires(self.ctx.now()? + self.ctx.timestamp_expiration_duration);
 
         let timestamp = f(timestamp_builder).build()?;
         let raw_timestamp = sign(
@@ -1375,38 +1494,38 @@ where
         self.stage_timestamp_if_necessary()?.commit().await
     }
 
-    fn need_new_timestamp(&self) -> bool {
+    fn need_new_timestamp(&self) -> Result<bool> {
         // We need a new timestamp metadata if we staged a new root.
         if self.state.staged_root.is_some() {
-            return true;
+            return Ok(true);
         }
 
         // We need a new timestamp metadata if we staged a new snapshot.
         if self.state.staged_snapshot.is_some() {
-            return true;
+            return Ok(true);
         }
 
         // We need a new timestamp metadata if we don't have a database yet.
         let db = if let Some(ref db) = self.ctx.db {
             db
         } else {
-            return true;
+            return Ok(true);
         };
 
         // We need a new timestamp metadata if the database doesn't have a timestamp.
         let trusted_timestamp = if let Some(trusted_timestamp) = db.trusted_timestamp() {
             trusted_timestamp
         } else {
-            return true;
+            return Ok(true);
         };
 
-        // We need a new timestamp metadata if the metadata expired.
-        if trusted_timestamp.expires() <= &self.ctx.current_time {
-            return true;
+        // We need a new timestamp metadata if the metadata expired, or is about to.
+        if self.ctx.is_expiring(trusted_timestamp.expires())? {
+            return Ok(true);
         }
 
         // Otherwise, see if the timestamp keys have changed.
-        self.ctx.timestamp_keys_changed(db.trusted_root())
+        Ok(self.ctx.timestamp_keys_changed(db.trusted_root()))
     }
 }
 
@@ -1418,8 +1537,26 @@ where
     /// Commit the metadata for this repository, then write all metadata to the repository. Before
     /// writing the metadata to `repo`, this will test that a client can update to this metadata to
     /// make sure it is valid.
+    ///
+    /// The timestamp and snapshot versions trusted by the [Database] are passed to
+    /// [RepositoryStorage::expect_metadata_versions], so committing a batch update fails if another
+    /// publisher changed the repository in the meantime.
     pub async fn commit(mut self) -> Result<RawSignedMetadataSet<D>> {
         self.validate_built_metadata()?;
+
+        // Let batch updates detect if another publisher changed the repository after the
+        // metadata in the database was published.
+        let (timestamp_version, snapshot_version) = match self.ctx.db {
+            Some(db) => (
+                db.trusted_timestamp().map(|timestamp| timestamp.version()),
+                db.trusted_snapshot().map(|snapshot| snapshot.version()),
+            ),
+            None => (None, None),
+        };
+        self.ctx
+            .repo
+            .expect_metadata_versions(timestamp_version, snapshot_version);
+
         self.write_repo().await?;
 
         let mut builder = RawSignedMetadataSetBuilder::new();
@@ -1453,12 +1590,16 @@ where
             let mut db = db.clone();
 
             if let Some(ref root) = self.state.staged_root {
+                db.set_verifier(accept_key_id_aliases(db.verifier().clone(), &root.metadata));
                 db.update_root(&root.raw)?;
             }
 
             db
         } else if let Some(ref root) = self.state.staged_root {
-            Database::from_trusted_root(&root.raw)?
+            Database::from_trusted_root_with_verifier(
+                &root.raw,
+                accept_key_id_aliases(Verifier::new(), &root.metadata),
+            )?
         } else {
             return Err(Error::MetadataNotFound {
                 path: MetadataPath::root(),
@@ -1466,16 +1607,42 @@ where
             });
         };
 
+        // Check expiration at the same time the metadata was built against.
+        db.set_clock(Arc::new(FixedClock::new(self.ctx.now()?)));
+
+        // Revocations of top-level keys can be checked against the root, but revocations of
+        // delegated keys need the targets metadata that delegates to them.
+        let (top_level_revocations, delegated_revocations): (Vec<_>, Vec<_>) =
+            self.ctx.revocations.iter().partition(|revocation| {
+                let role = revocation.revocation().role();
+                role == &MetadataPath::root()
+                    || role == &MetadataPath::timestamp()
+                    || role == &MetadataPath::snapshot()
+                    || role == &MetadataPath::targets()
+            });
+
+        for revocation in top_level_revocations {
+            db.revoke_key(revocation)?;
+        }
+
         if let Some(ref timestamp) = self.state.staged_timestamp {
-            db.update_timestamp(&self.ctx.current_time, &timestamp.raw)?;
+            db.update_timestamp(&timestamp.raw)?;
         }
 
         if let Some(ref snapshot) = self.state.staged_snapshot {
-            db.update_snapshot(&self.ctx.current_time, &snapshot.raw)?;
+            db.update_snapshot(&snapshot.raw)?;
         }
 
         if let Some(ref targets) = self.state.staged_targets {
-            db.update_targets(&self.ctx.current_time, &targets.raw)?;
+            db.update_targets(&targets.raw)?;
+        }
+
+        for revocation in delegated_revocations {
+            match db.revoke_key(revocation) {
+                // The delegating metadata may not be known to the builder.
+                Ok(_) | Err(Error::UnauthorizedKey { .. }) => {}
+                Err(err) => return Err(err),
+            }
         }
 
         Ok(())
@@ -1564,20 +1731,75 @@ where
                 .await?;
         }
 
+        if !self.ctx.revocations.is_empty() {
+            // Keep the revocations published before, including those for keys the database doesn't
+            // trust.
+            let mut revocations = self
+                .ctx
+                .db
+                .map(|db| db.published_revocations().clone())
+                .unwrap_or_default();
+            for revocation in &self.ctx.revocations {
+                let _ = revocations.insert(revocation.clone());
+            }
+
+            self.ctx
+                .repo
+                .store_metadata(
+                    &RevocationList::path(),
+                    MetadataVersion::None,
+                    &mut &*revocations.to_bytes::<D>()?,
+                )
+                .await?;
+        }
+
         Ok(())
     }
 }
 
+/// Extend `verifier` to accept the key IDs `root` lists keys under, since the builder was told to
+/// use them.
+fn accept_key_id_aliases(verifier: Verifier, root: &RootMetadata) -> Verifier {
+    let policy = root.key_id_aliases().iter().fold(
+        verifier.accepted_key_ids().clone(),
+        |policy, (key_id, key)| {
+            policy.accept(KeyIdScheme::Custom(HashMap::from([(
+                key.key_id().clone(),
+                key_id.clone(),
+            )])))
+        },
+    );
+    verifier.key_id_policy(policy)
+}
+
+/// The canonical IDs of the keys `root` authorizes for `role`, whichever IDs they are listed under.
+fn role_key_ids<'a, M: Metadata>(
+    root: &'a RootMetadata,
+    role: &'a RoleDefinition<M>,
+) -> HashSet<&'a KeyId> {
+    root.role_keys_by_id(role)
+        .map(|(_, key)| key.key_id())
+        .collect()
+}
+
+/// Whether any of the trusted `keys` is missing from `key_ids`.
+fn keys_changed(key_ids: &HashSet<&KeyId>, keys: &[&dyn PrivateKey]) -> bool {
+    keys.iter()
+        .any(|key| !key_ids.contains(key.public().key_id()))
+}
+
 #[cfg(test)]
 mod tests {
     use {
         super::*,
         crate::{
             client::{Client, Config},
-            crypto::Ed25519PrivateKey,
+            clock::FixedClock,
+            crypto::{Ed25519PrivateKey, KeyIdPolicy},
             metadata::SignedMetadata,
             pouf::Pouf1,
             repository::{EphemeralRepository, RepositoryProvider},
+            revocation::KeyRevocation,
         },
         assert_matches::assert_matches,
         chrono::{
@@ -1589,7 +1811,7 @@ mod tests {
         lazy_static::lazy_static,
         maplit::hashmap,
         pretty_assertions::assert_eq,
-        std::collections::BTreeMap,
+        std::{collections::BTreeMap, sync::Arc},
     };
 
     lazy_static! {
@@ -2412,6 +2634,74 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_add_target_from_path() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let dir = tempfile::tempdir().unwrap();
+
+            let hash_algs = &[HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+
+            // Large enough to be hashed in parallel.
+            let target_path = TargetPath::new("foo/large").unwrap();
+            let target_file = (0..3 * 1024 * 1024)
+                .map(|i| (i % 251) as u8)
+                .collect::<Vec<_>>();
+            let target_file_path = dir.path().join("large");
+            std::fs::write(&target_file_path, &target_file).unwrap();
+
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|builder| builder.consistent_snapshot(true))
+                .unwrap()
+                .target_hash_algorithms(hash_algs)
+                .add_target_from_path(target_path.clone(), &target_file_path)
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+
+            client.update().await.unwrap();
+
+            assert_eq!(
+                client.fetch_target_description(&target_path).await.unwrap(),
+                TargetDescription::from_slice(&target_file, hash_algs).unwrap(),
+            );
+
+            let mut rdr = client.fetch_target(&target_path).await.unwrap();
+            let mut buf = vec![];
+            rdr.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, target_file);
+
+            // Missing files are reported with their path.
+            let missing = dir.path().join("missing");
+            assert_matches!(
+                RepoBuilder::create(&mut EphemeralRepository::<Pouf1>::new())
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .add_target_from_path(target_path, &missing)
+                    .await
+                    .map(|_| ()),
+                Err(Error::IoPath { path, .. }) if path == missing
+            );
+        })
+    }
+
     #[test]
     fn test_do_not_require_all_keys_to_be_online() {
         block_on(async {
@@ -2886,8 +3176,9 @@ mod tests {
                 .await
                 .unwrap();
 
+            let clock = Arc::new(FixedClock::new(current_time));
             let mut db =
-                Database::from_trusted_metadata_with_start_time(&metadata1, &current_time).unwrap();
+                Database::from_trusted_metadata_with_clock(&metadata1, clock.clone()).unwrap();
 
             // Advance time to past the timestamp expiration.
             let current_time = epoch + timestamp_expires + Duration::seconds(1);
@@ -2906,8 +3197,8 @@ mod tests {
             assert!(metadata2.snapshot().is_none());
             assert!(metadata2.timestamp().is_some());
 
-            db.update_metadata_with_start_time(&metadata2, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata2).unwrap();
 
             assert_eq!(db.trusted_root().version(), 1);
             assert_eq!(db.trusted_targets().unwrap().version(), 1);
@@ -2931,8 +3222,8 @@ mod tests {
             assert!(metadata3.snapshot().is_some());
             assert!(metadata3.timestamp().is_some());
 
-            db.update_metadata_with_start_time(&metadata3, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata3).unwrap();
 
             assert_eq!(db.trusted_root().version(), 1);
             assert_eq!(db.trusted_targets().unwrap().version(), 1);
@@ -2956,8 +3247,8 @@ mod tests {
             assert!(metadata4.snapshot().is_some());
             assert!(metadata4.timestamp().is_some());
 
-            db.update_metadata_with_start_time(&metadata4, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata4).unwrap();
 
             assert_eq!(db.trusted_root().version(), 1);
             assert_eq!(db.trusted_targets().unwrap().version(), 2);
@@ -2986,8 +3277,8 @@ mod tests {
             assert!(metadata5.snapshot().is_some());
             assert!(metadata5.timestamp().is_some());
 
-            db.update_metadata_with_start_time(&metadata5, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata5).unwrap();
 
             assert_eq!(db.trusted_root().version(), 2);
             assert_eq!(db.trusted_targets().unwrap().version(), 3);
@@ -2996,6 +3287,80 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_builder_expiration_horizon_refreshes_expiring_metadata() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            let epoch = Utc.timestamp_opt(0, 0).unwrap();
+            let snapshot_expires = Duration::seconds(20);
+            let timestamp_expires = Duration::seconds(10);
+
+            let metadata1 = RepoBuilder::create(&mut repo)
+                .current_time(epoch)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .snapshot_expiration_duration(snapshot_expires)
+                .timestamp_expiration_duration(timestamp_expires)
+                .commit()
+                .await
+                .unwrap();
+
+            let clock = Arc::new(FixedClock::new(epoch));
+            let mut db =
+                Database::from_trusted_metadata_with_clock(&metadata1, clock.clone()).unwrap();
+
+            // Nothing expires within the horizon, so nothing is staged.
+            let current_time = epoch + Duration::seconds(4);
+            let metadata2 = RepoBuilder::from_database(&mut repo, &db)
+                .current_time(current_time)
+                .expiration_horizon(Duration::seconds(5))
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(metadata2.root().is_none());
+            assert!(metadata2.targets().is_none());
+            assert!(metadata2.snapshot().is_none());
+            assert!(metadata2.timestamp().is_none());
+
+            // The timestamp expires within the horizon, so it is refreshed before it expires.
+            let current_time = epoch + Duration::seconds(5);
+            let metadata3 = RepoBuilder::from_database(&mut repo, &db)
+                .current_time(current_time)
+                .expiration_horizon(Duration::seconds(5))
+                .timestamp_expiration_duration(timestamp_expires)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(metadata3.root().is_none());
+            assert!(metadata3.targets().is_none());
+            assert!(metadata3.snapshot().is_none());
+            assert!(metadata3.timestamp().is_some());
+
+            clock.set(current_time);
+            db.update_metadata(&metadata3).unwrap();
+
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 1);
+            assert_eq!(db.trusted_timestamp().unwrap().version(), 2);
+            assert_eq!(
+                db.trusted_timestamp().unwrap().expires(),
+                &(current_time + timestamp_expires)
+            );
+        })
+    }
+
     #[test]
     fn test_adding_target_refreshes_metadata() {
         block_on(async move {
@@ -3058,8 +3423,9 @@ mod tests {
                 .await
                 .unwrap();
 
+            let clock = Arc::new(FixedClock::new(current_time));
             let mut db =
-                Database::from_trusted_metadata_with_start_time(&metadata, &current_time).unwrap();
+                Database::from_trusted_metadata_with_clock(&metadata, clock.clone()).unwrap();
 
             // The initial version should be the current time.
             assert_eq!(db.trusted_root().version(), 1);
@@ -3083,8 +3449,8 @@ mod tests {
                 .await
                 .unwrap();
 
-            db.update_metadata_with_start_time(&metadata, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata).unwrap();
 
             assert_eq!(db.trusted_root().version(), 2);
             assert_eq!(db.trusted_targets().map(|m| m.version()), Some(6));
@@ -3108,8 +3474,8 @@ mod tests {
                 .await
                 .unwrap();
 
-            db.update_metadata_with_start_time(&metadata, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata).unwrap();
 
             assert_eq!(db.trusted_root().version(), 3);
             assert_eq!(db.trusted_targets().map(|m| m.version()), Some(10));
@@ -3136,8 +3502,9 @@ mod tests {
                 .await
                 .unwrap();
 
+            let clock = Arc::new(FixedClock::new(current_time));
             let mut db =
-                Database::from_trusted_metadata_with_start_time(&metadata, &current_time).unwrap();
+                Database::from_trusted_metadata_with_clock(&metadata, clock.clone()).unwrap();
 
             assert_eq!(db.trusted_root().version(), 1);
             assert_eq!(db.trusted_targets().map(|m| m.version()), Some(1));
@@ -3161,8 +3528,8 @@ mod tests {
                 .await
                 .unwrap();
 
-            db.update_metadata_with_start_time(&metadata, &current_time)
-                .unwrap();
+            clock.set(current_time);
+            db.update_metadata(&metadata).unwrap();
 
             assert_eq!(db.trusted_root().version(), 2);
             assert_eq!(db.trusted_targets().map(|m| m.version()), Some(2));
@@ -3171,6 +3538,216 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_key_revocations() {
+        block_on(async {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+
+            let revocation =
+                KeyRevocation::new(MetadataPath::targets(), KEYS[1].public().key_id().clone())
+                    .sign::<Pouf1>(&KEYS[1])
+                    .unwrap();
+
+            // Targets signed only by the revoked key can't be committed.
+            assert_matches!(
+                RepoBuilder::from_database(&mut repo, &db)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[1]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .add_key_revocation(revocation.clone())
+                    .stage_root()
+                    .unwrap()
+                    .commit()
+                    .await,
+                Err(Error::MetadataMissingSignatures { role, .. })
+                if role == MetadataPath::targets()
+            );
+
+            RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_key_revocation(revocation.clone())
+                .stage_root()
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut rdr = repo
+                .fetch_metadata(&RevocationList::path(), MetadataVersion::None)
+                .await
+                .unwrap();
+            let mut buf = vec![];
+            rdr.read_to_end(&mut buf).await.unwrap();
+            drop(rdr);
+
+            let revocations = RevocationList::from_slice::<Pouf1>(&buf).unwrap();
+            assert_eq!(revocations.revocations(), &[revocation]);
+
+            let mut db = db;
+            assert_eq!(db.update_revocations(&revocations).unwrap(), 1);
+            assert!(db.is_key_revoked(&MetadataPath::targets(), KEYS[1].public().key_id()));
+        })
+    }
+
+    async fn fetch_revocations(repo: &EphemeralRepository<Pouf1>) -> RevocationList {
+        let mut rdr = repo
+            .fetch_metadata(&RevocationList::path(), MetadataVersion::None)
+            .await
+            .unwrap();
+        let mut buf = vec![];
+        rdr.read_to_end(&mut buf).await.unwrap();
+        RevocationList::from_slice::<Pouf1>(&buf).unwrap()
+    }
+
+    #[test]
+    fn test_key_revocations_keep_published_revocations() {
+        block_on(async {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1], &KEYS[2]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let revoke = |role: MetadataPath, key: &Ed25519PrivateKey| {
+                KeyRevocation::new(role, key.public().key_id().clone())
+                    .sign::<Pouf1>(key)
+                    .unwrap()
+            };
+            let targets_revocation = revoke(MetadataPath::targets(), &KEYS[1]);
+            // A revocation for a delegated role the builder's database doesn't know about.
+            let delegated_revocation = revoke(MetadataPath::new("delegated").unwrap(), &KEYS[3]);
+
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+            RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1], &KEYS[2]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_key_revocation(targets_revocation.clone())
+                .add_key_revocation(delegated_revocation.clone())
+                .stage_root()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // A database that was given the published list, like a client's, skips the delegated
+            // revocation but still publishes it again.
+            let mut db = db;
+            let published = fetch_revocations(&repo).await;
+            assert_eq!(db.update_revocations(&published).unwrap(), 1);
+
+            let second_revocation = revoke(MetadataPath::targets(), &KEYS[2]);
+            RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0], &KEYS[1], &KEYS[2]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_key_revocation(second_revocation.clone())
+                .stage_root()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            assert_eq!(
+                fetch_revocations(&repo).await.revocations(),
+                &[targets_revocation, delegated_revocation, second_revocation]
+            );
+        })
+    }
+
+    #[test]
+    fn test_key_id_scheme() {
+        block_on(async {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|builder| builder.key_id_scheme(KeyIdScheme::Legacy))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let raw_root = metadata.root().unwrap();
+            let root = raw_root.parse_untrusted().unwrap().assume_valid().unwrap();
+            let legacy_key_id = KeyIdScheme::Legacy.key_id(KEYS[0].public()).unwrap();
+            assert!(root.keys().is_empty());
+            assert_eq!(
+                root.key_id_aliases().keys().collect::<Vec<_>>(),
+                vec![&legacy_key_id]
+            );
+
+            // Clients only trust the metadata if they accept the key IDs.
+            assert_matches!(
+                Database::from_trusted_metadata(&metadata),
+                Err(Error::MetadataMissingSignatures { role, .. })
+                if role == MetadataPath::root()
+            );
+
+            let config = Config::build()
+                .verifier(
+                    Verifier::new().key_id_policy(KeyIdPolicy::new().accept(KeyIdScheme::Legacy)),
+                )
+                .finish()
+                .unwrap();
+            let mut client = Client::with_trusted_root(
+                config,
+                raw_root,
+                EphemeralRepository::<Pouf1>::new(),
+                &mut repo,
+            )
+            .await
+            .unwrap();
+            assert!(client.update().await.unwrap());
+            let db = client.database().clone();
+            drop(client);
+            assert_eq!(db.trusted_targets().map(|m| m.version()), Some(1));
+
+            // The keys are unchanged, so the root is not staged again.
+            let metadata = RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_if_necessary()
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            assert!(metadata.root().is_none());
+
+            let mut db = db;
+            assert_matches!(db.update_metadata(&metadata), Ok(true));
+            assert_eq!(db.trusted_targets().map(|m| m.version()), Some(2));
+        })
+    }
+
     #[test]
     fn test_builder_errs_if_no_keys() {
         block_on(async move {
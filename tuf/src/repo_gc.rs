@@ -0,0 +1,676 @@
+//! Repository garbage collection
+//!
+//! When consistent snapshots are enabled, every publish writes new `VERSION.snapshot.json`,
+//! `VERSION.targets.json` and hash-prefixed targets to the repository, and nothing ever removes the
+//! old ones. The [GarbageCollector] removes the metadata versions and targets that are no longer
+//! needed by clients of the repository.
+
+use {
+    crate::{
+        database::Database,
+        error::{Error, Result},
+        metadata::{
+            Metadata, MetadataPath, MetadataVersion, SnapshotMetadata, TargetDescription,
+            TargetPath, TargetsMetadata,
+        },
+        pouf::Pouf,
+        repository::{resolve_listed_metadata, Repository, RepositoryProvider, RepositoryStorage},
+    },
+    log::debug,
+    std::collections::{BTreeMap, BTreeSet, HashSet},
+};
+
+const DEFAULT_KEEP_VERSIONS: usize = 2;
+
+/// Removes old metadata versions, and the targets only they reference, from a repository.
+///
+/// Given the trusted [Database] of the repository, the collector keeps:
+///
+/// * Every version of the root metadata, since clients need the whole chain to update.
+/// * Every unversioned metadata file, such as `timestamp.json`.
+/// * The last `keep_versions` versions of the timestamp, snapshot, targets and delegated targets
+///   metadata that are not newer than the versions trusted by the [Database], and any newer
+///   versions.
+/// * Every metadata version listed by a snapshot that is kept.
+/// * Every target referenced by targets metadata that is kept.
+///
+/// Everything else is removed. Delegated roles which are no longer listed in the trusted snapshot
+/// only keep the versions that are listed by a snapshot that is kept.
+///
+/// ```
+/// # use {
+/// #     futures_executor::block_on,
+/// #     tuf::{
+/// #         crypto::Ed25519PrivateKey,
+/// #         database::Database,
+/// #         pouf::Pouf1,
+/// #         repo_builder::RepoBuilder,
+/// #         repo_gc::GarbageCollector,
+/// #         repository::EphemeralRepository,
+/// #     },
+/// # };
+/// #
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+/// # ).unwrap();
+/// #
+/// # block_on(async {
+/// let mut repo = EphemeralRepository::<Pouf1>::new();
+/// let metadata = RepoBuilder::create(&mut repo)
+///     .trusted_root_keys(&[&key])
+///     .trusted_targets_keys(&[&key])
+///     .trusted_snapshot_keys(&[&key])
+///     .trusted_timestamp_keys(&[&key])
+///     .commit()
+///     .await
+///     .unwrap();
+/// let database = Database::from_trusted_metadata(&metadata).unwrap();
+///
+/// let mut gc = GarbageCollector::new(&database, &repo).keep_versions(1);
+///
+/// // There is only one version of everything, so there is nothing to collect.
+/// assert!(gc.dry_run().await.unwrap().is_empty());
+/// assert!(gc.collect().await.unwrap().is_empty());
+/// # });
+/// ```
+pub struct GarbageCollector<'a, D, R>
+where
+    D: Pouf,
+{
+    database: &'a Database<D>,
+    repo: Repository<R, D>,
+    keep_versions: usize,
+}
+
+impl<'a, D, R> GarbageCollector<'a, D, R>
+where
+    D: Pouf,
+    R: RepositoryProvider<D> + RepositoryStorage<D>,
+{
+    /// Create a [GarbageCollector] for the repository `repo`, which is described by the trusted
+    /// `database`.
+    pub fn new(database: &'a Database<D>, repo: R) -> Self {
+        Self {
+            database,
+            repo: Repository::new(repo),
+            keep_versions: DEFAULT_KEEP_VERSIONS,
+        }
+    }
+
+    /// Keep the last `keep_versions` versions of each role. The version trusted by the [Database]
+    /// is always kept, so a value of `0` is treated as `1`. Defaults to 2, so clients that fetched
+    /// the timestamp just before a publish can still fetch the rest of the metadata they need.
+    pub fn keep_versions(mut self, keep_versions: usize) -> Self {
+        self.keep_versions = keep_versions.max(1);
+        self
+    }
+
+    /// Returns the inner repository.
+    pub fn into_inner(self) -> R {
+        self.repo.into_inner()
+    }
+
+    /// Report the metadata and targets that would be removed by [GarbageCollector::collect],
+    /// without modifying the repository.
+    pub async fn dry_run(&self) -> Result<GarbageCollectionReport> {
+        let snapshot = self
+            .database
+            .trusted_snapshot()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::snapshot(),
+                version: MetadataVersion::None,
+            })?;
+
+        // Split the metadata in the repository into the versioned files, grouped by role, and the
+        // unversioned files.
+        let consistent_snapshot = self.database.trusted_root().consistent_snapshot();
+        let listed_metadata = self
+            .repo
+            .list_metadata()
+            .await?
+            .into_iter()
+            .map(|(path, version)| resolve_listed_metadata(path, version, consistent_snapshot))
+            .collect::<Vec<_>>();
+        let mut versions: BTreeMap<MetadataPath, BTreeSet<u32>> = BTreeMap::new();
+        let mut unversioned = vec![];
+        for (path, version) in &listed_metadata {
+            match version {
+                MetadataVersion::Number(version) => {
+                    versions.entry(path.clone()).or_default().insert(*version);
+                }
+                MetadataVersion::None => unversioned.push(path.clone()),
+            }
+        }
+
+        // Keep the latest versions of every role, along with every metadata version listed by the
+        // snapshots we keep.
+        let snapshot_path = MetadataPath::snapshot();
+        let mut keep = HashSet::new();
+        for (path, role_versions) in &versions {
+            let current = if *path == snapshot_path {
+                Some(snapshot.version())
+            } else if *path == MetadataPath::timestamp() {
+                self.database.trusted_timestamp().map(|t| t.version())
+            } else {
+                snapshot.meta().get(path).map(|d| d.version())
+            };
+
+            for version in retained_versions(role_versions, current, self.keep_versions) {
+                keep.insert((path.clone(), version));
+
+                if *path != snapshot_path {
+                    continue;
+                }
+
+                let meta = if version == snapshot.version() {
+                    snapshot.meta().clone()
+                } else {
+                    self.fetch_metadata::<SnapshotMetadata>(path, MetadataVersion::Number(version))
+                        .await?
+                        .meta()
+                        .clone()
+                };
+
+                for (path, description) in meta {
+                    keep.insert((path, description.version()));
+                }
+            }
+        }
+
+        let mut report = GarbageCollectionReport::default();
+        for (path, role_versions) in &versions {
+            if *path == MetadataPath::root() {
+                continue;
+            }
+
+            for version in role_versions {
+                if !keep.contains(&(path.clone(), *version)) {
+                    report
+                        .metadata
+                        .push((path.clone(), MetadataVersion::Number(*version)));
+                }
+            }
+        }
+
+        // Find every target referenced by the targets metadata we are keeping.
+        let mut referenced = HashSet::new();
+        if let Some(targets) = self.database.trusted_targets() {
+            insert_referenced_targets(&mut referenced, targets.targets().iter())?;
+        }
+        for targets in self.database.trusted_delegations().values() {
+            insert_referenced_targets(&mut referenced, targets.targets().iter())?;
+        }
+
+        let kept_targets_metadata = unversioned
+            .into_iter()
+            .map(|path| (path, MetadataVersion::None))
+            .chain(
+                keep.into_iter()
+                    .filter(|(path, version)| {
+                        versions
+                            .get(path)
+                            .map(|role_versions| role_versions.contains(version))
+                            .unwrap_or(false)
+                    })
+                    .map(|(path, version)| (path, MetadataVersion::Number(version))),
+            )
+            .filter(|(path, _)| !is_top_level_role(path) || *path == MetadataPath::targets());
+        for (path, version) in kept_targets_metadata {
+            let targets = self
+                .fetch_metadata::<TargetsMetadata>(&path, version)
+                .await?;
+            insert_referenced_targets(&mut referenced, targets.targets().iter())?;
+        }
+
+        // If the metadata and targets share a directory, the metadata may also be listed as
+        // targets, so make sure we never remove it.
+        for (path, version) in &listed_metadata {
+            if let Ok(target_path) = TargetPath::new(path.components::<D>(*version).join("/")) {
+                referenced.insert(target_path);
+            }
+        }
+
+        for target_path in self.repo.as_inner().list_targets().await? {
+            if !referenced.contains(&target_path) {
+                report.targets.push(target_path);
+            }
+        }
+
+        report.metadata.sort();
+        report.targets.sort();
+
+        Ok(report)
+    }
+
+    /// Remove the metadata and targets that are no longer needed from the repository, and report
+    /// what was removed.
+    pub async fn collect(&mut self) -> Result<GarbageCollectionReport> {
+        let report = self.dry_run().await?;
+
+        for (path, version) in &report.metadata {
+            debug!("Removing metadata {} version {}", path, version);
+            self.repo.remove_metadata(path, *version).await?;
+        }
+
+        for target_path in &report.targets {
+            debug!("Removing target {}", target_path);
+            self.repo.as_inner().remove_target(target_path).await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Fetch and parse metadata from the repository. The metadata is not verified, since it is
+    /// only used to find out what it references.
+    async fn fetch_metadata<M>(&self, path: &MetadataPath, version: MetadataVersion) -> Result<M>
+    where
+        M: Metadata,
+    {
+        self.repo
+            .fetch_metadata::<M>(path, version, None, vec![])
+            .await?
+            .parse_untrusted()?
+            .assume_valid()
+    }
+}
+
+/// The metadata and targets removed by a [GarbageCollector], or that would be removed in a dry
+/// run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GarbageCollectionReport {
+    metadata: Vec<(MetadataPath, MetadataVersion)>,
+    targets: Vec<TargetPath>,
+}
+
+impl GarbageCollectionReport {
+    /// The metadata that was removed, in sorted order.
+    pub fn metadata(&self) -> &[(MetadataPath, MetadataVersion)] {
+        &self.metadata
+    }
+
+    /// The targets that were removed, in sorted order.
+    pub fn targets(&self) -> &[TargetPath] {
+        &self.targets
+    }
+
+    /// Whether nothing was removed.
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.targets.is_empty()
+    }
+}
+
+fn is_top_level_role(path: &MetadataPath) -> bool {
+    *path == MetadataPath::root()
+        || *path == MetadataPath::timestamp()
+        || *path == MetadataPath::snapshot()
+        || *path == MetadataPath::targets()
+}
+
+/// Returns the versions that are newer than `current`, and the `keep` latest versions that are not.
+/// If there is no `current` version, no versions are returned.
+fn retained_versions(versions: &BTreeSet<u32>, current: Option<u32>, keep: usize) -> Vec<u32> {
+    match current {
+        Some(current) => versions
+            .range(..=current)
+            .rev()
+            .take(keep)
+            .chain(versions.range(current.saturating_add(1)..))
+            .copied()
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Insert the paths a target could be stored at, with and without consistent snapshot hash
+/// prefixes.
+fn insert_referenced_targets<'a>(
+    referenced: &mut HashSet<TargetPath>,
+    targets: impl Iterator<Item = (&'a TargetPath, &'a TargetDescription)>,
+) -> Result<()> {
+    for (target_path, description) in targets {
+        for hash in description.hashes().values() {
+            referenced.insert(target_path.with_hash_prefix(hash)?);
+        }
+        referenced.insert(target_path.clone());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::metadata::{Delegation, MetadataDescription, TargetsMetadataBuilder};
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::{EphemeralRepository, FileSystemRepository};
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEY: Ed25519PrivateKey =
+            Ed25519PrivateKey::from_pkcs8(include_bytes!("../tests/ed25519/ed25519-1.pk8.der"))
+                .unwrap();
+    }
+
+    /// Publish a new version of the repository, where the target `foo` contains `contents`.
+    async fn publish<R>(repo: &R, database: &mut Database<Pouf1>, contents: &'static [u8])
+    where
+        R: RepositoryStorage<Pouf1> + Sync,
+    {
+        let metadata = RepoBuilder::from_database(repo, database)
+            .trusted_root_keys(&[&*KEY])
+            .trusted_targets_keys(&[&*KEY])
+            .trusted_snapshot_keys(&[&*KEY])
+            .trusted_timestamp_keys(&[&*KEY])
+            .skip_root()
+            .add_target(TargetPath::new("foo").unwrap(), Cursor::new(contents))
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+        database.update_metadata(&metadata).unwrap();
+    }
+
+    /// Publish `version` of the delegated role `role`, where the target `bar` contains `contents`.
+    async fn publish_delegation<R>(
+        repo: &R,
+        database: &mut Database<Pouf1>,
+        role: &MetadataPath,
+        version: u32,
+        contents: &'static [u8],
+    ) where
+        R: RepositoryStorage<Pouf1> + Sync,
+    {
+        let consistent_snapshot = database.trusted_root().consistent_snapshot();
+        let target_path = TargetPath::new("bar").unwrap();
+        let description =
+            TargetDescription::from_slice(contents, &[HashAlgorithm::Sha256]).unwrap();
+        let stored_target_path = if consistent_snapshot {
+            hash_prefixed("bar", contents)
+        } else {
+            target_path.clone()
+        };
+        repo.store_target(&stored_target_path, &mut Cursor::new(contents))
+            .await
+            .unwrap();
+
+        let raw_delegation = TargetsMetadataBuilder::new()
+            .version(version)
+            .insert_target_description(target_path, description)
+            .signed::<Pouf1>(&*KEY)
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let mut versions = vec![MetadataVersion::None];
+        if consistent_snapshot {
+            versions.push(MetadataVersion::Number(version));
+        }
+        for meta_version in versions {
+            repo.store_metadata(role, meta_version, &mut raw_delegation.as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let metadata = RepoBuilder::from_database(repo, database)
+            .trusted_root_keys(&[&*KEY])
+            .trusted_targets_keys(&[&*KEY])
+            .trusted_snapshot_keys(&[&*KEY])
+            .trusted_timestamp_keys(&[&*KEY])
+            .skip_root()
+            .skip_targets()
+            .stage_snapshot_with_builder(|builder| {
+                builder.insert_metadata_description(
+                    role.clone(),
+                    MetadataDescription::from_slice(
+                        raw_delegation.as_bytes(),
+                        version,
+                        &[HashAlgorithm::Sha256],
+                    )
+                    .unwrap(),
+                )
+            })
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+        database.update_metadata(&metadata).unwrap();
+        database
+            .update_delegated_targets(&MetadataPath::targets(), role, &raw_delegation)
+            .unwrap();
+    }
+
+    /// Create a repository that delegates `bar` to the role `role`.
+    async fn create_with_delegation<R>(
+        repo: &R,
+        role: &MetadataPath,
+        consistent_snapshot: bool,
+    ) -> Database<Pouf1>
+    where
+        R: RepositoryStorage<Pouf1> + Sync,
+    {
+        let metadata = RepoBuilder::create(repo)
+            .trusted_root_keys(&[&*KEY])
+            .trusted_targets_keys(&[&*KEY])
+            .trusted_snapshot_keys(&[&*KEY])
+            .trusted_timestamp_keys(&[&*KEY])
+            .stage_root_with_builder(|builder| builder.consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .add_delegation_key(KEY.public().clone())
+            .add_delegation_role(
+                Delegation::builder(role.clone())
+                    .key(KEY.public())
+                    .delegate_path(TargetPath::new("bar").unwrap())
+                    .build()
+                    .unwrap(),
+            )
+            .commit()
+            .await
+            .unwrap();
+        Database::from_trusted_metadata(&metadata).unwrap()
+    }
+
+    fn hash_prefixed(name: &str, contents: &[u8]) -> TargetPath {
+        let description =
+            TargetDescription::from_slice(contents, &[HashAlgorithm::Sha256]).unwrap();
+        let hash = description.hashes().values().next().unwrap();
+        TargetPath::new(name)
+            .unwrap()
+            .with_hash_prefix(hash)
+            .unwrap()
+    }
+
+    async fn check_keeps_latest_versions<R>(repo: R)
+    where
+        R: RepositoryProvider<Pouf1> + RepositoryStorage<Pouf1> + Sync,
+    {
+        let metadata = RepoBuilder::create(&repo)
+            .trusted_root_keys(&[&*KEY])
+            .trusted_targets_keys(&[&*KEY])
+            .trusted_snapshot_keys(&[&*KEY])
+            .trusted_timestamp_keys(&[&*KEY])
+            .stage_root()
+            .unwrap()
+            .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo 1"))
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+        let mut database = Database::from_trusted_metadata(&metadata).unwrap();
+
+        publish(&repo, &mut database, b"foo 2").await;
+        publish(&repo, &mut database, b"foo 3").await;
+
+        let before = repo.list_metadata().await.unwrap();
+        let mut gc = GarbageCollector::new(&database, &repo).keep_versions(2);
+
+        // A dry run reports what would be removed, but doesn't change the repository.
+        let report = gc.dry_run().await.unwrap();
+        assert_eq!(
+            report.metadata(),
+            &[
+                (MetadataPath::snapshot(), MetadataVersion::Number(1)),
+                (MetadataPath::targets(), MetadataVersion::Number(1)),
+            ]
+        );
+        assert_eq!(report.targets(), &[hash_prefixed("foo", b"foo 1")]);
+        assert_eq!(repo.list_metadata().await.unwrap(), before);
+
+        assert_eq!(gc.collect().await.unwrap(), report);
+        assert!(gc.dry_run().await.unwrap().is_empty());
+
+        let mut expected = before;
+        expected.retain(|meta| !report.metadata().contains(meta));
+        assert_eq!(repo.list_metadata().await.unwrap(), expected);
+
+        let mut expected_targets = vec![
+            hash_prefixed("foo", b"foo 2"),
+            hash_prefixed("foo", b"foo 3"),
+        ];
+        expected_targets.sort();
+        assert_eq!(repo.list_targets().await.unwrap(), expected_targets);
+
+        // Only keeping the trusted version removes the rest.
+        let report = GarbageCollector::new(&database, &repo)
+            .keep_versions(0)
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            report.metadata(),
+            &[
+                (MetadataPath::snapshot(), MetadataVersion::Number(2)),
+                (MetadataPath::targets(), MetadataVersion::Number(2)),
+            ]
+        );
+        assert_eq!(report.targets(), &[hash_prefixed("foo", b"foo 2")]);
+        assert_eq!(
+            repo.list_targets().await.unwrap(),
+            vec![hash_prefixed("foo", b"foo 3")]
+        );
+    }
+
+    #[test]
+    fn garbage_collector_keeps_latest_versions() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            check_keeps_latest_versions(FileSystemRepository::<Pouf1>::new(
+                temp_dir.path().to_path_buf(),
+            ))
+            .await;
+        })
+    }
+
+    #[test]
+    fn garbage_collector_keeps_latest_versions_ephemeral() {
+        block_on(check_keeps_latest_versions(
+            EphemeralRepository::<Pouf1>::new(),
+        ))
+    }
+
+    #[cfg(feature = "object-store")]
+    #[test]
+    fn garbage_collector_keeps_latest_versions_object_store() {
+        block_on(check_keeps_latest_versions(
+            crate::repository::mock_object_store_repository(),
+        ))
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn garbage_collector_keeps_latest_versions_sqlite() {
+        block_on(check_keeps_latest_versions(
+            crate::repository::SqliteRepository::<Pouf1>::open_in_memory().unwrap(),
+        ))
+    }
+
+    #[test]
+    fn garbage_collector_keeps_latest_delegated_versions() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegation").unwrap();
+            let mut database = create_with_delegation(&repo, &role, true).await;
+
+            publish_delegation(&repo, &mut database, &role, 1, b"bar 1").await;
+            publish_delegation(&repo, &mut database, &role, 2, b"bar 2").await;
+            publish_delegation(&repo, &mut database, &role, 3, b"bar 3").await;
+
+            let report = GarbageCollector::new(&database, &repo)
+                .keep_versions(2)
+                .collect()
+                .await
+                .unwrap();
+
+            // The snapshots that are kept list the last two delegated versions, which keep their
+            // targets.
+            assert_eq!(
+                report.metadata(),
+                &[
+                    (role.clone(), MetadataVersion::Number(1)),
+                    (MetadataPath::snapshot(), MetadataVersion::Number(1)),
+                    (MetadataPath::snapshot(), MetadataVersion::Number(2)),
+                ]
+            );
+            assert_eq!(report.targets(), &[hash_prefixed("bar", b"bar 1")]);
+
+            let mut expected_targets = vec![
+                hash_prefixed("bar", b"bar 2"),
+                hash_prefixed("bar", b"bar 3"),
+            ];
+            expected_targets.sort();
+            assert_eq!(repo.list_targets().await.unwrap(), expected_targets);
+        })
+    }
+
+    #[test]
+    fn garbage_collector_keeps_unversioned_metadata_without_consistent_snapshot() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+
+            // Without consistent snapshots, `1.delegation.json` is the metadata of the role
+            // `1.delegation`, rather than version 1 of `delegation`.
+            let role = MetadataPath::new("1.delegation").unwrap();
+            let mut database = create_with_delegation(&repo, &role, false).await;
+
+            publish(&repo, &mut database, b"foo 1").await;
+            publish_delegation(&repo, &mut database, &role, 1, b"bar 1").await;
+            publish(&repo, &mut database, b"foo 2").await;
+            publish_delegation(&repo, &mut database, &role, 2, b"bar 2").await;
+
+            // The targets are overwritten in place, and only the root metadata is versioned, so
+            // there is nothing to collect.
+            let before = repo.list_metadata().await.unwrap();
+            assert!(before.contains(&(
+                MetadataPath::new("delegation").unwrap(),
+                MetadataVersion::Number(1)
+            )));
+
+            let report = GarbageCollector::new(&database, &repo)
+                .keep_versions(1)
+                .collect()
+                .await
+                .unwrap();
+            assert!(report.is_empty(), "{:?}", report);
+            assert_eq!(repo.list_metadata().await.unwrap(), before);
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![
+                    TargetPath::new("bar").unwrap(),
+                    TargetPath::new("foo").unwrap()
+                ]
+            );
+        })
+    }
+}
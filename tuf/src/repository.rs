@@ -5,6 +5,7 @@ use crate::metadata::{
     Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, TargetDescription, TargetPath,
 };
 use crate::pouf::Pouf;
+use crate::revocation::RevocationList;
 use crate::util::SafeAsyncRead;
 use crate::{Error, Result};
 
@@ -25,9 +26,36 @@ mod http;
 #[cfg(feature = "hyper")]
 pub use self::http::{HttpRepository, HttpRepositoryBuilder};
 
+#[cfg(feature = "object-store")]
+mod object_store;
+
+#[cfg(feature = "object-store")]
+pub use self::object_store::{
+    ObjectStoreBatchUpdate, ObjectStoreBody, ObjectStoreClient, ObjectStoreCredentials,
+    ObjectStoreRepository, ObjectStoreRepositoryBuilder,
+};
+
+#[cfg(all(test, feature = "object-store"))]
+pub(crate) use self::object_store::test::mock_repository as mock_object_store_repository;
+
+#[cfg(feature = "bundle")]
+mod bundle;
+
+#[cfg(feature = "bundle")]
+pub use self::bundle::{BundleExporter, BundleRepository};
+
 mod ephemeral;
 pub use self::ephemeral::{EphemeralBatchUpdate, EphemeralRepository};
 
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::{SqliteBatchUpdate, SqliteRepository};
+
+#[cfg(any(feature = "object-store", feature = "sqlite"))]
+mod staging;
+
 #[cfg(test)]
 mod error_repo;
 #[cfg(test)]
@@ -38,6 +66,54 @@ mod track_repo;
 #[cfg(test)]
 pub(crate) use self::track_repo::{Track, TrackRepository};
 
+/// Error returned when committing the batch update of a [FileSystemRepository],
+/// [ObjectStoreRepository], or [SqliteRepository].
+///
+/// [ObjectStoreRepository]: crate::repository::ObjectStoreRepository
+/// [SqliteRepository]: crate::repository::SqliteRepository
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CommitError {
+    /// Conflict occurred during commit.
+    #[error("conflicting change occurred during commit")]
+    Conflict,
+
+    /// The repository's metadata changed since the batch was created. See
+    /// [RepositoryStorage::expect_metadata_versions].
+    #[error(
+        "expected {path} to be at version {expected:?}, but the repository has version {actual:?}"
+    )]
+    UnexpectedVersion {
+        /// The metadata that changed.
+        path: MetadataPath,
+
+        /// The expected version, or `None` if the metadata was expected not to exist.
+        expected: Option<u32>,
+
+        /// The version in the repository, or `None` if the metadata does not exist.
+        actual: Option<u32>,
+    },
+
+    /// The repository could not be read or written.
+    #[error(transparent)]
+    Storage(#[from] Error),
+
+    /// An IO error occurred.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// An IO error occurred for a path.
+    #[error("IO error on path {path}")]
+    IoPath {
+        /// Path where the error occurred.
+        path: std::path::PathBuf,
+
+        /// The IO error.
+        #[source]
+        err: std::io::Error,
+    },
+}
+
 /// A readable TUF repository.
 pub trait RepositoryProvider<D>
 where
@@ -130,6 +206,82 @@ where
         target_path: &TargetPath,
         target: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>>;
+
+    /// Remove the metadata identified by `meta_path`, `version`, and
+    /// [`D::extension()`][extension]. Removing metadata that does not exist is not an error.
+    ///
+    /// The default implementation returns an error, since the repository does not support
+    /// removing metadata.
+    ///
+    /// [extension]: crate::pouf::Pouf::extension
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let _ = version;
+        let meta_path = meta_path.clone();
+        Box::pin(async move {
+            Err(Error::Opaque(format!(
+                "repository does not support removing metadata {}",
+                meta_path
+            )))
+        })
+    }
+
+    /// Remove the target identified by `target_path`. Removing a target that does not exist is
+    /// not an error.
+    ///
+    /// The default implementation returns an error, since the repository does not support
+    /// removing targets.
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let target_path = target_path.clone();
+        Box::pin(async move {
+            Err(Error::Opaque(format!(
+                "repository does not support removing target {}",
+                target_path
+            )))
+        })
+    }
+
+    /// List the path and version of every piece of metadata in the repository, in sorted order.
+    ///
+    /// A file name that starts with a number followed by a `.` is listed as a versioned file. Use
+    /// [resolve_listed_metadata] to list the metadata of a repository that does not use
+    /// consistent snapshots.
+    ///
+    /// The default implementation lists nothing, so the
+    /// [GarbageCollector](crate::repo_gc::GarbageCollector) and the
+    /// [Client](crate::client::Client) leave the metadata in the repository alone.
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    /// List the path of every target in the repository, in sorted order.
+    ///
+    /// The default implementation lists nothing, so the
+    /// [GarbageCollector](crate::repo_gc::GarbageCollector) leaves the targets in the repository
+    /// alone.
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    /// Require the repository's unversioned timestamp and snapshot metadata to be at
+    /// `timestamp_version` and `snapshot_version` when the stored changes are committed, or to
+    /// not exist if the version is `None`. This lets a batch update detect that another publisher
+    /// changed the repository after the metadata was staged, in which case the commit fails with
+    /// [CommitError::UnexpectedVersion].
+    /// [RepoBuilder::commit](crate::repo_builder::RepoBuilder::commit) sets these to the versions
+    /// trusted by its [Database](crate::database::Database).
+    ///
+    /// The default implementation does nothing, since the changes are not committed separately.
+    fn expect_metadata_versions(
+        &self,
+        timestamp_version: Option<u32>,
+        snapshot_version: Option<u32>,
+    ) {
+        let _ = (timestamp_version, snapshot_version);
+    }
 }
 
 /// A subtrait of both RepositoryStorage and RepositoryProvider. This is useful to create
@@ -196,6 +348,36 @@ macro_rules! impl_storage {
             ) -> BoxFuture<'a, Result<()>> {
                 (**self).store_target(target_path, target)
             }
+
+            fn remove_metadata<'a>(
+                &'a self,
+                meta_path: &MetadataPath,
+                version: MetadataVersion,
+            ) -> BoxFuture<'a, Result<()>> {
+                (**self).remove_metadata(meta_path, version)
+            }
+
+            fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+                (**self).remove_target(target_path)
+            }
+
+            fn list_metadata(
+                &self,
+            ) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+                (**self).list_metadata()
+            }
+
+            fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+                (**self).list_targets()
+            }
+
+            fn expect_metadata_versions(
+                &self,
+                timestamp_version: Option<u32>,
+                snapshot_version: Option<u32>,
+            ) {
+                (**self).expect_metadata_versions(timestamp_version, snapshot_version)
+            }
         }
     };
 }
@@ -290,6 +472,21 @@ where
         Ok(RawSignedMetadata::new(buf))
     }
 
+    /// Fetch the contents of the [RevocationList] published by the repository, returning an error
+    /// if they exceed `max_length` bytes.
+    pub(crate) async fn fetch_revocations(&self, max_length: Option<usize>) -> Result<Vec<u8>> {
+        let mut reader = self
+            .repository
+            .fetch_metadata(&RevocationList::path(), MetadataVersion::None)
+            .await?
+            .check_length_and_hash(max_length.unwrap_or(usize::MAX) as u64, vec![])?;
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await?;
+
+        Ok(buf)
+    }
+
     /// Fetch the target identified by `target_path` through the returned `AsyncRead`, verifying
     /// that the target matches the preferred hash specified in `target_description` and that it is
     /// the expected length. Such verification errors will be provided by a read failure on the
@@ -365,6 +562,17 @@ where
             .await
     }
 
+    /// Store the contents of a [RevocationList].
+    pub(crate) async fn store_revocations(&mut self, revocations: &[u8]) -> Result<()> {
+        self.repository
+            .store_metadata(
+                &RevocationList::path(),
+                MetadataVersion::None,
+                &mut &*revocations,
+            )
+            .await
+    }
+
     /// Store the provided `target` in a location identified by `target_path`.
     pub async fn store_target<'a>(
         &'a mut self,
@@ -373,6 +581,88 @@ where
     ) -> Result<()> {
         self.repository.store_target(target_path, target).await
     }
+
+    /// Remove the metadata identified by `meta_path`, `version`, and
+    /// [`D::extension()`][extension], if it exists.
+    ///
+    /// [extension]: crate::pouf::Pouf::extension
+    pub async fn remove_metadata(
+        &mut self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> Result<()> {
+        self.repository.remove_metadata(meta_path, version).await
+    }
+
+    /// List the path and version of every piece of metadata in the repository.
+    pub async fn list_metadata(&self) -> Result<Vec<(MetadataPath, MetadataVersion)>> {
+        self.repository.list_metadata().await
+    }
+}
+
+/// Parse the path components of a metadata file back into its [MetadataPath] and
+/// [MetadataVersion]. This is the inverse of [MetadataPath::components].
+///
+/// Returns `None` if the file does not have the extension of `D`, or does not name valid
+/// metadata. A file name that starts with a number followed by a `.` is always treated as a
+/// versioned file, see [resolve_listed_metadata].
+fn parse_metadata_components<D>(components: &[String]) -> Option<(MetadataPath, MetadataVersion)>
+where
+    D: Pouf,
+{
+    let (file_name, dirs) = components.split_last()?;
+    let stem = file_name.strip_suffix(D::extension())?.strip_suffix('.')?;
+
+    let (version, name) = match stem.split_once('.') {
+        Some((version, name))
+            if !name.is_empty() && version.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            match version.parse() {
+                Ok(version) => (MetadataVersion::Number(version), name),
+                Err(_) => (MetadataVersion::None, stem),
+            }
+        }
+        _ => (MetadataVersion::None, stem),
+    };
+
+    let mut path = dirs.to_vec();
+    path.push(name.to_string());
+
+    let meta_path = MetadataPath::new(path.join("/")).ok()?;
+    Some((meta_path, version))
+}
+
+/// Resolve metadata listed by [RepositoryStorage::list_metadata] for a repository that does, or
+/// does not, use consistent snapshots.
+///
+/// The listing can't tell a versioned file like `1.foo.json` apart from the metadata of a role
+/// named `1.foo`. Only the root metadata is versioned when consistent snapshots are not in use,
+/// so then every other versioned listing is resolved to the unversioned metadata of the role
+/// whose name starts with the version.
+pub fn resolve_listed_metadata(
+    meta_path: MetadataPath,
+    version: MetadataVersion,
+    consistent_snapshot: bool,
+) -> (MetadataPath, MetadataVersion) {
+    let version_number = match version {
+        MetadataVersion::Number(version) => version,
+        MetadataVersion::None => return (meta_path, version),
+    };
+
+    if consistent_snapshot || meta_path == MetadataPath::root() {
+        return (meta_path, version);
+    }
+
+    let name = meta_path.to_string();
+    let name = match name.rsplit_once('/') {
+        Some((dirs, name)) => format!("{}/{}.{}", dirs, version_number, name),
+        None => format!("{}.{}", version_number, name),
+    };
+
+    match MetadataPath::new(name) {
+        Ok(unversioned_path) => (unversioned_path, MetadataVersion::None),
+        Err(_) => (meta_path, version),
+    }
 }
 
 #[cfg(test)]
@@ -384,6 +674,61 @@ mod test {
     use assert_matches::assert_matches;
     use futures_executor::block_on;
 
+    #[test]
+    fn parse_metadata_components_is_inverse_of_components() {
+        for (meta_path, version) in [
+            (MetadataPath::root(), MetadataVersion::None),
+            (MetadataPath::root(), MetadataVersion::Number(42)),
+            (MetadataPath::new("foo/bar").unwrap(), MetadataVersion::None),
+            (
+                MetadataPath::new("foo/bar").unwrap(),
+                MetadataVersion::Number(1),
+            ),
+        ] {
+            assert_eq!(
+                parse_metadata_components::<Pouf1>(&meta_path.components::<Pouf1>(version)),
+                Some((meta_path, version))
+            );
+        }
+
+        assert_eq!(
+            parse_metadata_components::<Pouf1>(&["root.txt".into()]),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_listed_metadata_only_versions_consistent_snapshots() {
+        let listed =
+            parse_metadata_components::<Pouf1>(&["delegations".into(), "1.foo.json".into()])
+                .unwrap();
+        assert_eq!(
+            listed,
+            (
+                MetadataPath::new("delegations/foo").unwrap(),
+                MetadataVersion::Number(1)
+            )
+        );
+
+        assert_eq!(
+            resolve_listed_metadata(listed.0.clone(), listed.1, true),
+            listed
+        );
+        assert_eq!(
+            resolve_listed_metadata(listed.0, listed.1, false),
+            (
+                MetadataPath::new("delegations/1.foo").unwrap(),
+                MetadataVersion::None
+            )
+        );
+
+        // The root metadata is always versioned.
+        assert_eq!(
+            resolve_listed_metadata(MetadataPath::root(), MetadataVersion::Number(2), false),
+            (MetadataPath::root(), MetadataVersion::Number(2))
+        );
+    }
+
     #[test]
     fn repository_forwards_not_found_error() {
         block_on(async {
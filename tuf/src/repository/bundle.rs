@@ -0,0 +1,1021 @@
+//! Offline update bundles, which package a repository's metadata and targets into a single tar
+//! archive.
+//!
+//! Metadata is stored under `metadata/` and targets under `targets/`, using the same file names
+//! as a repository stored with [FileSystemRepository](crate::repository::FileSystemRepository).
+
+use {
+    crate::{
+        crypto,
+        database::Database,
+        error::{Error, Result},
+        metadata::{
+            Metadata, MetadataDescription, MetadataPath, MetadataVersion, RawSignedMetadata,
+            RootMetadata, SnapshotMetadata, TargetPath, TargetsMetadata, TimestampMetadata,
+        },
+        pouf::Pouf,
+        repository::{Repository, RepositoryProvider},
+        util::{version_for, SafeAsyncRead},
+    },
+    blocking::{unblock, Unblock},
+    futures_io::AsyncRead,
+    futures_util::{
+        future::{BoxFuture, FutureExt},
+        io::AsyncReadExt,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+        fs::File,
+        io::{self, Seek, SeekFrom, Write},
+        marker::PhantomData,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+const METADATA_DIR: &str = "metadata";
+const TARGETS_DIR: &str = "targets";
+
+/// The size of the blocks in a tar archive, which entries are padded to.
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// The size of the chunks targets are streamed into the archive in.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Exports the metadata and targets a client needs to update from a repository into a tar
+/// archive, which can be served to an offline client with [BundleRepository].
+///
+/// The bundle contains:
+///
+/// * Every version of the root metadata, starting at [BundleExporter::from_root_version], so
+///   clients that trust an older root can walk the chain of roots.
+/// * The timestamp, snapshot, and targets metadata trusted by the [Database], and every delegated
+///   targets role reachable from the targets metadata, so clients can look up any target.
+/// * The targets added with [BundleExporter::target], stored at the paths a client would fetch
+///   them from.
+///
+/// Metadata and targets are fetched from the repository and checked against the lengths and
+/// hashes trusted by the [Database], so a repository that changed after the [Database] was
+/// updated produces an error rather than a bundle clients would reject. Archives are
+/// reproducible, since entries are written in a fixed order without timestamps or ownership.
+///
+/// ```
+/// # use {
+/// #     futures_executor::block_on,
+/// #     tuf::{
+/// #         crypto::Ed25519PrivateKey,
+/// #         database::Database,
+/// #         pouf::Pouf1,
+/// #         repo_builder::RepoBuilder,
+/// #         repository::{BundleExporter, EphemeralRepository},
+/// #     },
+/// # };
+/// #
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     include_bytes!("../../tests/ed25519/ed25519-1.pk8.der")
+/// # ).unwrap();
+/// #
+/// # block_on(async {
+/// let repo = EphemeralRepository::<Pouf1>::new();
+/// let metadata = RepoBuilder::create(&repo)
+///     .trusted_root_keys(&[&key])
+///     .trusted_targets_keys(&[&key])
+///     .trusted_snapshot_keys(&[&key])
+///     .trusted_timestamp_keys(&[&key])
+///     .commit()
+///     .await
+///     .unwrap();
+/// let database = Database::from_trusted_metadata(&metadata).unwrap();
+///
+/// let bundle = BundleExporter::new(&database, &repo)
+///     .export(vec![])
+///     .await
+///     .unwrap();
+/// # assert!(!bundle.is_empty());
+/// # });
+/// ```
+pub struct BundleExporter<'a, D, R>
+where
+    D: Pouf,
+{
+    database: &'a Database<D>,
+    repo: &'a R,
+    from_root_version: u32,
+    targets: BTreeSet<TargetPath>,
+}
+
+impl<'a, D, R> BundleExporter<'a, D, R>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    /// Create a new exporter of the metadata trusted by `database`, fetched from `repo`.
+    pub fn new(database: &'a Database<D>, repo: &'a R) -> Self {
+        Self {
+            database,
+            repo,
+            from_root_version: 1,
+            targets: BTreeSet::new(),
+        }
+    }
+
+    /// Include the root metadata starting at `version`, which is the oldest root version trusted
+    /// by the clients the bundle is made for. Defaults to 1, which includes every root version.
+    pub fn from_root_version(mut self, version: u32) -> Self {
+        self.from_root_version = version;
+        self
+    }
+
+    /// Include the target `target_path`, which must be described by the metadata trusted by the
+    /// [Database].
+    pub fn target(mut self, target_path: TargetPath) -> Self {
+        self.targets.insert(target_path);
+        self
+    }
+
+    /// Write the bundle to `writer`, returning the writer once the archive is complete.
+    ///
+    /// Delegated targets metadata that isn't trusted by the [Database] yet is fetched and verified
+    /// against a copy of it, which leaves the [Database] itself unchanged. Targets are streamed
+    /// into the archive while they're verified, so if verification fails the archive written so
+    /// far is incomplete.
+    pub async fn export<W: Write>(&self, writer: W) -> Result<W> {
+        let repo = Repository::<_, D>::new(self.repo);
+        let consistent_snapshot = self.database.trusted_root().consistent_snapshot();
+
+        let mut archive = tar::Builder::new(writer);
+
+        let root_path = MetadataPath::root();
+        let trusted_root_version = self.database.trusted_root().version();
+        for version in self.from_root_version.max(1)..=trusted_root_version {
+            let version = MetadataVersion::Number(version);
+            let raw = repo
+                .fetch_metadata::<RootMetadata>(&root_path, version, None, vec![])
+                .await?;
+            append_metadata::<D, _>(&mut archive, &root_path, version, raw.as_bytes())?;
+        }
+
+        let timestamp = match self.database.trusted_timestamp() {
+            Some(timestamp) => timestamp,
+            None => {
+                // Without a timestamp, clients can only update their root metadata.
+                return Ok(archive.into_inner()?);
+            }
+        };
+
+        // The timestamp is always fetched unversioned, so make sure it's the one we trust.
+        let timestamp_path = MetadataPath::timestamp();
+        let raw = repo
+            .fetch_metadata::<TimestampMetadata>(
+                &timestamp_path,
+                MetadataVersion::None,
+                None,
+                vec![],
+            )
+            .await?;
+        check_version(&timestamp_path, &raw, timestamp.version())?;
+        append_metadata::<D, _>(
+            &mut archive,
+            &timestamp_path,
+            MetadataVersion::None,
+            raw.as_bytes(),
+        )?;
+
+        let snapshot_path = MetadataPath::snapshot();
+        let raw = fetch_described_metadata::<D, _, SnapshotMetadata>(
+            &repo,
+            &snapshot_path,
+            timestamp.snapshot(),
+            consistent_snapshot,
+        )
+        .await?;
+        append_metadata::<D, _>(
+            &mut archive,
+            &snapshot_path,
+            version_for(timestamp.snapshot(), consistent_snapshot),
+            raw.as_bytes(),
+        )?;
+
+        let snapshot = match self.database.trusted_snapshot() {
+            Some(snapshot) => snapshot,
+            None => return Ok(archive.into_inner()?),
+        };
+
+        let targets_path = MetadataPath::targets();
+        let description = describe(snapshot, &targets_path)?;
+        let raw = fetch_described_metadata::<D, _, TargetsMetadata>(
+            &repo,
+            &targets_path,
+            description,
+            consistent_snapshot,
+        )
+        .await?;
+        append_metadata::<D, _>(
+            &mut archive,
+            &targets_path,
+            version_for(description, consistent_snapshot),
+            raw.as_bytes(),
+        )?;
+
+        let trusted_targets = match self.database.trusted_targets() {
+            Some(targets) => targets,
+            None => return Ok(archive.into_inner()?),
+        };
+
+        // Walk every delegation, verifying the roles the database doesn't trust yet with a copy of
+        // it. Roles are stored sorted by name so the archive is reproducible.
+        let mut database = self.database.clone();
+        let mut delegations = BTreeMap::new();
+        let mut queue = trusted_targets
+            .delegations()
+            .roles()
+            .iter()
+            .map(|delegation| (targets_path.clone(), delegation.name().clone()))
+            .collect::<VecDeque<_>>();
+
+        while let Some((parent_role, role)) = queue.pop_front() {
+            if delegations.contains_key(&role) {
+                continue;
+            }
+
+            let description = describe(snapshot, &role)?;
+            let raw = fetch_described_metadata::<D, _, TargetsMetadata>(
+                &repo,
+                &role,
+                description,
+                consistent_snapshot,
+            )
+            .await?;
+            if !database.trusted_delegations().contains_key(&role) {
+                database.update_delegated_targets(&parent_role, &role, &raw)?;
+            }
+
+            if let Some(delegated) = database.trusted_delegations().get(&role) {
+                queue.extend(
+                    delegated
+                        .delegations()
+                        .roles()
+                        .iter()
+                        .map(|delegation| (role.clone(), delegation.name().clone())),
+                );
+            }
+
+            delegations.insert(role, (version_for(description, consistent_snapshot), raw));
+        }
+
+        for (role, (version, raw)) in &delegations {
+            append_metadata::<D, _>(&mut archive, role, *version, raw.as_bytes())?;
+        }
+
+        for target_path in &self.targets {
+            let description = database.target_description(target_path)?;
+            let hashes = crypto::retain_supported_hashes(description.hashes());
+            if hashes.is_empty() {
+                return Err(Error::NoSupportedHashAlgorithm);
+            }
+
+            // Store the target at the path the client will fetch it from.
+            let (remote_path, read) = if consistent_snapshot {
+                fetch_hash_prefixed_target(self.repo, target_path, &hashes).await?
+            } else {
+                (
+                    target_path.clone(),
+                    self.repo.fetch_target(target_path).await?,
+                )
+            };
+
+            let mut read = read.check_length_and_hash(description.length(), hashes)?;
+            append_stream(
+                &mut archive,
+                &bundle_path(TARGETS_DIR, &remote_path.components()),
+                description.length(),
+                &mut read,
+            )
+            .await?;
+        }
+
+        Ok(archive.into_inner()?)
+    }
+}
+
+/// Returns the snapshot's description of the targets metadata `role`.
+fn describe<'a>(
+    snapshot: &'a SnapshotMetadata,
+    role: &MetadataPath,
+) -> Result<&'a MetadataDescription<TargetsMetadata>> {
+    snapshot
+        .meta()
+        .get(role)
+        .ok_or_else(|| Error::Opaque(format!("snapshot does not describe the metadata {}", role)))
+}
+
+async fn fetch_described_metadata<D, R, M>(
+    repo: &Repository<R, D>,
+    meta_path: &MetadataPath,
+    description: &MetadataDescription<M>,
+    consistent_snapshot: bool,
+) -> Result<RawSignedMetadata<D, M>>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+    M: Metadata,
+{
+    let raw = repo
+        .fetch_metadata::<M>(
+            meta_path,
+            version_for(description, consistent_snapshot),
+            description.length(),
+            crypto::retain_supported_hashes(description.hashes()),
+        )
+        .await?;
+    check_version(meta_path, &raw, description.version())?;
+    Ok(raw)
+}
+
+fn check_version<D, M>(
+    meta_path: &MetadataPath,
+    raw: &RawSignedMetadata<D, M>,
+    expected: u32,
+) -> Result<()>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    let actual = raw.parse_untrusted()?.parse_version_untrusted()?;
+    if actual != expected {
+        return Err(Error::Opaque(format!(
+            "repository has version {} of {}, but the database trusts version {}",
+            actual, meta_path, expected
+        )));
+    }
+    Ok(())
+}
+
+async fn fetch_hash_prefixed_target<'a, D, R>(
+    repo: &'a R,
+    target_path: &TargetPath,
+    hashes: &[(&'static crypto::HashAlgorithm, crypto::HashValue)],
+) -> Result<(TargetPath, Box<dyn AsyncRead + Send + Unpin + 'a>)>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    for (_, hash) in hashes {
+        let remote_path = target_path.with_hash_prefix(hash)?;
+        match repo.fetch_target(&remote_path).await {
+            Ok(read) => return Ok((remote_path, read)),
+            Err(Error::TargetNotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(Error::TargetNotFound(target_path.clone()))
+}
+
+fn append_metadata<D: Pouf, W: Write>(
+    archive: &mut tar::Builder<W>,
+    meta_path: &MetadataPath,
+    version: MetadataVersion,
+    bytes: &[u8],
+) -> Result<()> {
+    append_file(
+        archive,
+        &bundle_path(METADATA_DIR, &meta_path.components::<D>(version)),
+        bytes,
+    )
+}
+
+fn append_file<W: Write>(archive: &mut tar::Builder<W>, path: &str, bytes: &[u8]) -> Result<()> {
+    let mut header = file_header(bytes.len() as u64);
+    archive.append_data(&mut header, path, bytes)?;
+    Ok(())
+}
+
+/// Append a file of `size` bytes at `path`, streaming its contents from `read`.
+async fn append_stream<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    size: u64,
+    read: &mut (dyn AsyncRead + Send + Unpin),
+) -> Result<()> {
+    // tar only appends entries from a blocking reader, so append the header of an entry of
+    // `size` bytes with no contents, which tar doesn't pad, and then write the contents and the
+    // padding ourselves.
+    let mut header = file_header(size);
+    archive.append_data(&mut header, path, io::empty())?;
+
+    let mut buf = vec![0; STREAM_CHUNK_SIZE];
+    let mut written = 0;
+    loop {
+        let n = read.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        archive.get_mut().write_all(&buf[..n])?;
+        written += n as u64;
+    }
+
+    // The reader checks the length, but make sure we never write a malformed archive.
+    if written != size {
+        return Err(Error::Opaque(format!(
+            "expected {} to be {} bytes, but it was {} bytes",
+            path, size, written
+        )));
+    }
+
+    let padding = (TAR_BLOCK_SIZE - size % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+    archive
+        .get_mut()
+        .write_all(&[0; TAR_BLOCK_SIZE as usize][..padding as usize])?;
+
+    Ok(())
+}
+
+fn file_header(size: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header
+}
+
+fn bundle_path(dir: &str, components: &[String]) -> String {
+    format!("{}/{}", dir, components.join("/"))
+}
+
+/// The location of a file's contents in the archive.
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    offset: u64,
+    size: u64,
+}
+
+/// A read-only repository served from a bundle created by [BundleExporter].
+///
+/// This is typically used as the remote repository of a [Client](crate::client::Client) on a
+/// machine without network access, so bundles are verified just like any other repository.
+/// Opening the bundle only reads the archive's index. Files are read from the archive when
+/// they're fetched.
+#[derive(Debug)]
+pub struct BundleRepository<D> {
+    path: PathBuf,
+    metadata: HashMap<(MetadataPath, MetadataVersion), Entry>,
+    targets: BTreeMap<TargetPath, Entry>,
+    _pouf: PhantomData<D>,
+}
+
+impl<D> BundleRepository<D>
+where
+    D: Pouf,
+{
+    /// Open the bundle at `path`.
+    ///
+    /// Entries that aren't regular files, or that aren't valid metadata or target paths, are
+    /// ignored.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let (metadata, targets) = Self::index(&path).map_err(|err| Error::IoPath {
+            path: path.clone(),
+            err,
+        })?;
+
+        Ok(Self {
+            path,
+            metadata,
+            targets,
+            _pouf: PhantomData,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn index(
+        path: &Path,
+    ) -> io::Result<(
+        HashMap<(MetadataPath, MetadataVersion), Entry>,
+        BTreeMap<TargetPath, Entry>,
+    )> {
+        let mut metadata = HashMap::new();
+        let mut targets = BTreeMap::new();
+
+        let mut archive = tar::Archive::new(File::open(path)?);
+        for entry in archive.entries_with_seek()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let components = match path_components(&entry.path()?) {
+                Some(components) => components,
+                None => continue,
+            };
+            let location = Entry {
+                offset: entry.raw_file_position(),
+                size: entry.size(),
+            };
+
+            match components.split_first() {
+                Some((dir, rest)) if dir == METADATA_DIR => {
+                    if let Some(key) = super::parse_metadata_components::<D>(rest) {
+                        metadata.insert(key, location);
+                    }
+                }
+                Some((dir, rest)) if dir == TARGETS_DIR => {
+                    if let Ok(target_path) = TargetPath::new(rest.join("/")) {
+                        targets.insert(target_path, location);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok((metadata, targets))
+    }
+
+    /// List the path and version of every piece of metadata in the bundle.
+    pub fn list_metadata(&self) -> Vec<(MetadataPath, MetadataVersion)> {
+        let mut metadata = self.metadata.keys().cloned().collect::<Vec<_>>();
+        metadata.sort();
+        metadata
+    }
+
+    /// List the path of every target in the bundle, as stored in the bundle. When consistent
+    /// snapshots are enabled, these paths are prefixed with the target's hash.
+    pub fn list_targets(&self) -> Vec<TargetPath> {
+        self.targets.keys().cloned().collect()
+    }
+
+    fn read_entry<'a>(
+        &'a self,
+        entry: Entry,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let path = self.path.clone();
+
+        async move {
+            let file = unblock({
+                let path = path.clone();
+                move || {
+                    let mut file = File::open(path)?;
+                    file.seek(SeekFrom::Start(entry.offset))?;
+                    Ok(file)
+                }
+            })
+            .await
+            .map_err(|err| Error::IoPath { path, err })?;
+
+            let reader: Box<dyn AsyncRead + Send + Unpin> =
+                Box::new(Unblock::new(file).take(entry.size));
+            Ok(reader)
+        }
+        .boxed()
+    }
+}
+
+impl<D> RepositoryProvider<D> for BundleRepository<D>
+where
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        match self.metadata.get(&(meta_path.clone(), version)) {
+            Some(entry) => self.read_entry(*entry),
+            None => {
+                let err = Error::MetadataNotFound {
+                    path: meta_path.clone(),
+                    version,
+                };
+                async move { Err(err) }.boxed()
+            }
+        }
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        match self.targets.get(target_path) {
+            Some(entry) => self.read_entry(*entry),
+            None => {
+                let err = Error::TargetNotFound(target_path.clone());
+                async move { Err(err) }.boxed()
+            }
+        }
+    }
+}
+
+/// Returns the components of a relative path in an archive, or `None` if it could refer to a
+/// file outside of the archive's root.
+fn path_components(path: &Path) -> Option<Vec<String>> {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str().map(str::to_string),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::client::{Client, Config},
+        crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey as _},
+        crate::metadata::{Delegation, Delegations, TargetDescription, TargetsMetadataBuilder},
+        crate::pouf::Pouf1,
+        crate::repo_builder::RepoBuilder,
+        crate::repository::{fetch_target_to_string, EphemeralRepository, RepositoryStorage},
+        assert_matches::assert_matches,
+        futures_executor::block_on,
+        futures_util::io::Cursor,
+        lazy_static::lazy_static,
+    };
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|key| Ed25519PrivateKey::from_pkcs8(key).unwrap())
+                .collect()
+        };
+    }
+
+    /// Publish two root versions and a target to `repo`, returning the trusted database.
+    async fn publish(
+        repo: &EphemeralRepository<Pouf1>,
+        consistent_snapshot: bool,
+    ) -> Database<Pouf1> {
+        let metadata = RepoBuilder::create(repo)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|builder| builder.consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+        let mut database = Database::from_trusted_metadata(&metadata).unwrap();
+
+        let metadata = RepoBuilder::from_database(repo, &database)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|builder| {
+                builder
+                    .consistent_snapshot(consistent_snapshot)
+                    .root_key(KEYS[1].public().clone())
+            })
+            .unwrap()
+            .add_target(
+                TargetPath::new("foo/bar").unwrap(),
+                Cursor::new(b"hello world"),
+            )
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+        database.update_metadata(&metadata).unwrap();
+
+        database
+    }
+
+    /// Publish the target `a/b` in the role `b`, which is delegated to by the role `a`, which is
+    /// delegated to by the targets role. Returns a database that doesn't trust the delegated roles.
+    async fn publish_with_delegations(
+        repo: &EphemeralRepository<Pouf1>,
+        consistent_snapshot: bool,
+    ) -> Database<Pouf1> {
+        let role_a = MetadataPath::new("a").unwrap();
+        let role_b = MetadataPath::new("b").unwrap();
+        let target_path = TargetPath::new("a/b").unwrap();
+        let contents: &[u8] = b"delegated";
+
+        let description =
+            TargetDescription::from_slice(contents, &[HashAlgorithm::Sha256]).unwrap();
+        let stored_target_path = if consistent_snapshot {
+            let hash = description.hashes().values().next().unwrap();
+            target_path.with_hash_prefix(hash).unwrap()
+        } else {
+            target_path.clone()
+        };
+        repo.store_target(&stored_target_path, &mut Cursor::new(contents))
+            .await
+            .unwrap();
+
+        let delegation = |role: &MetadataPath| {
+            Delegation::builder(role.clone())
+                .key(KEYS[0].public())
+                .delegate_path(TargetPath::new("a/").unwrap())
+                .build()
+                .unwrap()
+        };
+
+        let raw_a = TargetsMetadataBuilder::new()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[0].public().clone())
+                    .role(delegation(&role_b))
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let raw_b = TargetsMetadataBuilder::new()
+            .insert_target_description(target_path.clone(), description)
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        for (role, raw) in [(&role_a, &raw_a), (&role_b, &raw_b)] {
+            let version = if consistent_snapshot {
+                MetadataVersion::Number(1)
+            } else {
+                MetadataVersion::None
+            };
+            repo.store_metadata(role, version, &mut raw.as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let metadata = RepoBuilder::create(repo)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|builder| builder.consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .add_delegation_key(KEYS[0].public().clone())
+            .add_delegation_role(delegation(&role_a))
+            .stage_targets()
+            .unwrap()
+            .stage_snapshot_with_builder(|builder| {
+                builder
+                    .insert_metadata_description(
+                        role_a.clone(),
+                        MetadataDescription::from_slice(
+                            raw_a.as_bytes(),
+                            1,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap(),
+                    )
+                    .insert_metadata_description(
+                        role_b.clone(),
+                        MetadataDescription::from_slice(
+                            raw_b.as_bytes(),
+                            1,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap(),
+                    )
+            })
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        Database::from_trusted_metadata(&metadata).unwrap()
+    }
+
+    async fn export_to_file(
+        database: &Database<Pouf1>,
+        repo: &EphemeralRepository<Pouf1>,
+        dir: &Path,
+    ) -> PathBuf {
+        let bundle = BundleExporter::new(database, repo)
+            .target(TargetPath::new("foo/bar").unwrap())
+            .export(vec![])
+            .await
+            .unwrap();
+
+        let path = dir.join("bundle.tar");
+        std::fs::write(&path, bundle).unwrap();
+        path
+    }
+
+    #[test]
+    fn client_updates_from_bundle() {
+        for consistent_snapshot in [true, false] {
+            block_on(async {
+                let temp_dir = tempfile::Builder::new()
+                    .prefix("rust-tuf")
+                    .tempdir()
+                    .unwrap();
+                let repo = EphemeralRepository::<Pouf1>::new();
+                let database = publish(&repo, consistent_snapshot).await;
+                let path = export_to_file(&database, &repo, temp_dir.path()).await;
+
+                let bundle = BundleRepository::<Pouf1>::open(&path).unwrap();
+                let mut client = Client::with_trusted_root_keys(
+                    Config::default(),
+                    MetadataVersion::Number(1),
+                    1,
+                    [KEYS[0].public()],
+                    EphemeralRepository::new(),
+                    bundle,
+                )
+                .await
+                .unwrap();
+
+                assert!(client.update().await.unwrap());
+                assert_eq!(client.database().trusted_root().version(), 2);
+
+                let mut bytes = vec![];
+                client
+                    .fetch_target(&TargetPath::new("foo/bar").unwrap())
+                    .await
+                    .unwrap()
+                    .read_to_end(&mut bytes)
+                    .await
+                    .unwrap();
+                assert_eq!(bytes, b"hello world");
+            })
+        }
+    }
+
+    #[test]
+    fn export_walks_delegations() {
+        for consistent_snapshot in [true, false] {
+            block_on(async {
+                let temp_dir = tempfile::Builder::new()
+                    .prefix("rust-tuf")
+                    .tempdir()
+                    .unwrap();
+                let repo = EphemeralRepository::<Pouf1>::new();
+                let database = publish_with_delegations(&repo, consistent_snapshot).await;
+                assert!(database.trusted_delegations().is_empty());
+
+                let target_path = TargetPath::new("a/b").unwrap();
+                let bundle = BundleExporter::new(&database, &repo)
+                    .target(target_path.clone())
+                    .export(vec![])
+                    .await
+                    .unwrap();
+                let path = temp_dir.path().join("bundle.tar");
+                std::fs::write(&path, bundle).unwrap();
+
+                // The export doesn't update the database.
+                assert!(database.trusted_delegations().is_empty());
+
+                let bundle = BundleRepository::<Pouf1>::open(&path).unwrap();
+                let version = if consistent_snapshot {
+                    MetadataVersion::Number(1)
+                } else {
+                    MetadataVersion::None
+                };
+                assert!(bundle
+                    .list_metadata()
+                    .contains(&(MetadataPath::new("a").unwrap(), version)));
+                assert!(bundle
+                    .list_metadata()
+                    .contains(&(MetadataPath::new("b").unwrap(), version)));
+
+                let mut client = Client::with_trusted_root_keys(
+                    Config::default(),
+                    MetadataVersion::Number(1),
+                    1,
+                    [KEYS[0].public()],
+                    EphemeralRepository::new(),
+                    bundle,
+                )
+                .await
+                .unwrap();
+                assert!(client.update().await.unwrap());
+
+                let mut bytes = vec![];
+                client
+                    .fetch_target(&target_path)
+                    .await
+                    .unwrap()
+                    .read_to_end(&mut bytes)
+                    .await
+                    .unwrap();
+                assert_eq!(bytes, b"delegated");
+            })
+        }
+    }
+
+    #[test]
+    fn bundle_contains_metadata_chain() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let database = publish(&repo, false).await;
+
+            let bundle = BundleExporter::new(&database, &repo)
+                .from_root_version(2)
+                .export(vec![])
+                .await
+                .unwrap();
+            let path = temp_dir.path().join("bundle.tar");
+            std::fs::write(&path, &bundle).unwrap();
+
+            let bundle = BundleRepository::<Pouf1>::open(&path).unwrap();
+            assert_eq!(
+                bundle.list_metadata(),
+                vec![
+                    (MetadataPath::root(), MetadataVersion::Number(2)),
+                    (MetadataPath::snapshot(), MetadataVersion::None),
+                    (MetadataPath::targets(), MetadataVersion::None),
+                    (MetadataPath::timestamp(), MetadataVersion::None),
+                ]
+            );
+            assert_eq!(bundle.list_targets(), vec![]);
+            assert_matches!(
+                fetch_target_to_string(&bundle, &TargetPath::new("foo/bar").unwrap()).await,
+                Err(Error::TargetNotFound(_))
+            );
+
+            // Exports are reproducible.
+            let bundle1 = BundleExporter::new(&database, &repo)
+                .target(TargetPath::new("foo/bar").unwrap())
+                .export(vec![])
+                .await
+                .unwrap();
+            let bundle2 = BundleExporter::new(&database, &repo)
+                .target(TargetPath::new("foo/bar").unwrap())
+                .export(vec![])
+                .await
+                .unwrap();
+            assert_eq!(bundle1, bundle2);
+        })
+    }
+
+    #[test]
+    fn export_fails_if_target_is_modified() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let database = publish(&repo, false).await;
+
+            repo.store_target(
+                &TargetPath::new("foo/bar").unwrap(),
+                &mut "hello wyrld".as_bytes(),
+            )
+            .await
+            .unwrap();
+
+            let mut bytes = vec![];
+            let res = BundleExporter::new(&database, &repo)
+                .target(TargetPath::new("foo/bar").unwrap())
+                .export(&mut bytes)
+                .await;
+            // The hash mismatch is reported when the target is read.
+            assert_matches!(res, Err(Error::Io(_)));
+        })
+    }
+
+    #[test]
+    fn bundle_ignores_unsafe_entries() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("rust-tuf")
+            .tempdir()
+            .unwrap();
+        let path = temp_dir.path().join("bundle.tar");
+
+        let mut archive = tar::Builder::new(vec![]);
+        append_file(&mut archive, "targets/ok", b"ok").unwrap();
+        append_file(&mut archive, "./targets/dot", b"dot").unwrap();
+        append_file(&mut archive, "other/file", b"other").unwrap();
+        append_file(&mut archive, "metadata/not-metadata.txt", b"").unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        archive
+            .append_link(&mut header, "targets/link", "/etc/passwd")
+            .unwrap();
+
+        // The tar crate refuses to write these paths, so write them directly into the header.
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..15].copy_from_slice(b"targets/../evil");
+        header.set_size(4);
+        header.set_cksum();
+        archive.append(&header, &b"evil"[..]).unwrap();
+
+        std::fs::write(&path, archive.into_inner().unwrap()).unwrap();
+
+        let bundle = BundleRepository::<Pouf1>::open(&path).unwrap();
+        assert_eq!(bundle.list_metadata(), vec![]);
+        assert_eq!(
+            bundle.list_targets(),
+            vec![
+                TargetPath::new("dot").unwrap(),
+                TargetPath::new("ok").unwrap()
+            ]
+        );
+    }
+}
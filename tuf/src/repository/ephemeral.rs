@@ -14,7 +14,7 @@ use {
         io::{AsyncReadExt, Cursor},
     },
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
         marker::PhantomData,
         sync::{Arc, RwLock},
     },
@@ -64,6 +64,7 @@ where
                 metadata: MetadataMap::new(),
                 targets: TargetsMap::new(),
             }),
+            removed: RwLock::new(Removed::default()),
             _pouf: self._pouf,
         }
     }
@@ -131,6 +132,58 @@ where
     ) -> BoxFuture<'a, Result<()>> {
         store_target(&self.inner, target_path, read)
     }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let mut inner = self.inner.write().unwrap();
+        if inner
+            .metadata
+            .remove(&(meta_path.clone(), version))
+            .is_some()
+        {
+            // Increment the version since we changed.
+            inner.version += 1;
+        }
+        futures_util::future::ready(Ok(())).boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let mut inner = self.inner.write().unwrap();
+        if inner.targets.remove(target_path).is_some() {
+            // Increment the version since we changed.
+            inner.version += 1;
+        }
+        futures_util::future::ready(Ok(())).boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        let mut metadata = self
+            .inner
+            .read()
+            .unwrap()
+            .metadata
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        metadata.sort();
+        futures_util::future::ready(Ok(metadata)).boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        let mut targets = self
+            .inner
+            .read()
+            .unwrap()
+            .targets
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        targets.sort();
+        futures_util::future::ready(Ok(targets)).boxed()
+    }
 }
 
 /// [EphemeralBatchUpdate] is a special repository that is designed to write the metadata and
@@ -143,9 +196,17 @@ pub struct EphemeralBatchUpdate<'a, D> {
     initial_parent_version: u64,
     parent_repo: &'a RwLock<Inner>,
     staging_repo: RwLock<Inner>,
+    removed: RwLock<Removed>,
     _pouf: PhantomData<D>,
 }
 
+/// Metadata and targets that were removed in an [EphemeralBatchUpdate].
+#[derive(Debug, Default)]
+struct Removed {
+    metadata: HashSet<(MetadataPath, MetadataVersion)>,
+    targets: HashSet<TargetPath>,
+}
+
 /// Conflict occurred during commit.
 #[derive(Debug, thiserror::Error)]
 pub enum CommitError {
@@ -168,7 +229,16 @@ where
             return Err(CommitError::Conflict);
         }
 
-        // Since parent hasn't changed, merged everything we wrote into its tables.
+        // Since parent hasn't changed, remove everything we deleted and merge everything we wrote
+        // into its tables.
+        let removed = self.removed.into_inner().unwrap();
+        for key in &removed.metadata {
+            parent_repo.metadata.remove(key);
+        }
+        for target_path in &removed.targets {
+            parent_repo.targets.remove(target_path);
+        }
+
         let staging_repo = self.staging_repo.into_inner().unwrap();
         parent_repo.metadata.extend(staging_repo.metadata);
         parent_repo.targets.extend(staging_repo.targets);
@@ -192,6 +262,11 @@ where
         let key = (meta_path.clone(), version);
         let bytes = if let Some(bytes) = self.staging_repo.read().unwrap().metadata.get(&key) {
             Ok(Arc::clone(bytes))
+        } else if self.removed.read().unwrap().metadata.contains(&key) {
+            Err(Error::MetadataNotFound {
+                path: meta_path.clone(),
+                version,
+            })
         } else {
             self.parent_repo
                 .read()
@@ -214,6 +289,8 @@ where
         let bytes = if let Some(bytes) = self.staging_repo.read().unwrap().targets.get(target_path)
         {
             Ok(Arc::clone(bytes))
+        } else if self.removed.read().unwrap().targets.contains(target_path) {
+            Err(Error::TargetNotFound(target_path.clone()))
         } else {
             self.parent_repo
                 .read()
@@ -237,6 +314,11 @@ where
         version: MetadataVersion,
         metadata: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
+        self.removed
+            .write()
+            .unwrap()
+            .metadata
+            .remove(&(meta_path.clone(), version));
         store_metadata(&self.staging_repo, meta_path, version, metadata)
     }
 
@@ -245,8 +327,68 @@ where
         target_path: &TargetPath,
         read: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
+        self.removed.write().unwrap().targets.remove(target_path);
         store_target(&self.staging_repo, target_path, read)
     }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = (meta_path.clone(), version);
+        self.staging_repo.write().unwrap().metadata.remove(&key);
+        self.removed.write().unwrap().metadata.insert(key);
+        futures_util::future::ready(Ok(())).boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.staging_repo
+            .write()
+            .unwrap()
+            .targets
+            .remove(target_path);
+        self.removed
+            .write()
+            .unwrap()
+            .targets
+            .insert(target_path.clone());
+        futures_util::future::ready(Ok(())).boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        let removed = self.removed.read().unwrap();
+        let mut metadata = self
+            .parent_repo
+            .read()
+            .unwrap()
+            .metadata
+            .keys()
+            .filter(|key| !removed.metadata.contains(key))
+            .chain(self.staging_repo.read().unwrap().metadata.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        metadata.sort();
+        metadata.dedup();
+        futures_util::future::ready(Ok(metadata)).boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        let removed = self.removed.read().unwrap();
+        let mut targets = self
+            .parent_repo
+            .read()
+            .unwrap()
+            .targets
+            .keys()
+            .filter(|target_path| !removed.targets.contains(*target_path))
+            .chain(self.staging_repo.read().unwrap().targets.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        targets.sort();
+        targets.dedup();
+        futures_util::future::ready(Ok(targets)).boxed()
+    }
 }
 
 fn store_metadata<'a>(
@@ -345,6 +487,114 @@ mod test {
         })
     }
 
+    #[test]
+    fn ephemeral_repo_remove_and_list() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+
+            let meta_path = MetadataPath::new("meta").unwrap();
+            let target_path = TargetPath::new("target").unwrap();
+
+            repo.store_metadata(&meta_path, MetadataVersion::None, &mut "meta".as_bytes())
+                .await
+                .unwrap();
+            repo.store_metadata(
+                &meta_path,
+                MetadataVersion::Number(1),
+                &mut "meta".as_bytes(),
+            )
+            .await
+            .unwrap();
+            repo.store_target(&target_path, &mut "target".as_bytes())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![
+                    (meta_path.clone(), MetadataVersion::None),
+                    (meta_path.clone(), MetadataVersion::Number(1)),
+                ]
+            );
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![target_path.clone()]
+            );
+
+            repo.remove_metadata(&meta_path, MetadataVersion::None)
+                .await
+                .unwrap();
+            repo.remove_target(&target_path).await.unwrap();
+
+            assert_matches!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::None).await,
+                Err(Error::MetadataNotFound { .. })
+            );
+            assert_matches!(
+                fetch_target_to_string(&repo, &target_path).await,
+                Err(Error::TargetNotFound(_))
+            );
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![(meta_path.clone(), MetadataVersion::Number(1))]
+            );
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+
+            // Removing something that doesn't exist is not an error.
+            repo.remove_target(&target_path).await.unwrap();
+        })
+    }
+
+    #[test]
+    fn ephemeral_repo_batch_remove() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+
+            let meta_path = MetadataPath::new("meta").unwrap();
+            let meta_version = MetadataVersion::None;
+            let target_path = TargetPath::new("target").unwrap();
+
+            repo.store_metadata(&meta_path, meta_version, &mut "meta".as_bytes())
+                .await
+                .unwrap();
+            repo.store_target(&target_path, &mut "target".as_bytes())
+                .await
+                .unwrap();
+
+            let batch = repo.batch_update();
+            batch
+                .remove_metadata(&meta_path, meta_version)
+                .await
+                .unwrap();
+            batch.remove_target(&target_path).await.unwrap();
+
+            // The removals are visible in the batch, but not the repository.
+            assert_matches!(
+                fetch_metadata_to_string(&batch, &meta_path, meta_version).await,
+                Err(Error::MetadataNotFound { .. })
+            );
+            assert_matches!(
+                fetch_target_to_string(&batch, &target_path).await,
+                Err(Error::TargetNotFound(_))
+            );
+            assert_eq!(batch.list_metadata().await.unwrap(), vec![]);
+            assert_eq!(batch.list_targets().await.unwrap(), vec![]);
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![(meta_path.clone(), meta_version)]
+            );
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![target_path.clone()]
+            );
+
+            batch.commit().await.unwrap();
+
+            assert_eq!(repo.list_metadata().await.unwrap(), vec![]);
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+        })
+    }
+
     #[test]
     fn ephemeral_repo_batch_update() {
         block_on(async {
@@ -3,25 +3,37 @@
 use {
     crate::{
         error::{Error, Result},
-        metadata::{MetadataPath, MetadataVersion, TargetPath},
+        metadata::{
+            Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, SnapshotMetadata,
+            TargetPath, TimestampMetadata,
+        },
         pouf::Pouf,
-        repository::{RepositoryProvider, RepositoryStorage},
+        repository::{CommitError, RepositoryProvider, RepositoryStorage},
     },
+    blocking::{unblock, Unblock},
     futures_io::AsyncRead,
     futures_util::future::{BoxFuture, FutureExt},
-    futures_util::io::{copy, AllowStdIo},
-    log::debug,
+    futures_util::io::AsyncReadExt,
+    futures_util::lock::Mutex,
+    root_dir::{RootDir, TempPath},
     std::{
-        collections::HashMap,
-        fs::{DirBuilder, File},
-        io,
+        collections::{HashMap, HashSet},
+        fs::File,
+        io::{self, Read as _, Write as _},
         marker::PhantomData,
-        path::{Path, PathBuf},
+        path::PathBuf,
         sync::RwLock,
     },
-    tempfile::{NamedTempFile, TempPath},
 };
 
+mod root_dir;
+
+/// The size of the chunks written to the file system by the repository.
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The name of the file used to lock the repository, which is stored in the metadata directory.
+const LOCK_FILE_NAME: &str = ".tuf-lock";
+
 /// A builder to create a repository contained on the local file system.
 pub struct FileSystemRepositoryBuilder<D> {
     local_path: PathBuf,
@@ -80,22 +92,36 @@ where
 
         FileSystemRepository {
             version: RwLock::new(0),
-            metadata_path,
-            targets_path,
+            write_lock: Mutex::new(()),
+            metadata_dir: RootDir::new(metadata_path),
+            targets_dir: RootDir::new(targets_path),
             _pouf: PhantomData,
         }
     }
 }
 
 /// A repository contained on the local file system.
+///
+/// Metadata and target paths are resolved relative to the repository directories without following
+/// symlinks, so a compromised directory can't redirect reads or writes outside of the repository.
+/// Reading or writing through a symlink inside the repository fails, and symlinks are skipped when
+/// listing. Storing over a symlink replaces the symlink itself.
+///
+/// File system operations are run on a dedicated pool of threads, so the returned futures don't
+/// block the executor and can be used with any executor.
+///
+/// Note: If metadata and targets share a directory, [RepositoryStorage::list_targets] will include
+/// the metadata files.
 #[derive(Debug)]
 pub struct FileSystemRepository<D>
 where
     D: Pouf,
 {
     version: RwLock<u64>,
-    metadata_path: PathBuf,
-    targets_path: PathBuf,
+    // Held while writing to the repository, to serialize writers with each other.
+    write_lock: Mutex<()>,
+    metadata_dir: RootDir,
+    targets_dir: RootDir,
     _pouf: PhantomData<D>,
 }
 
@@ -124,80 +150,202 @@ where
     /// calling [FileSystemRepository::store_metadata], [FileSystemRepository::store_target], or
     /// another [FileSystemRepository::batch_update].
     ///
-    /// Warning: The current implementation makes no effort to prevent manipulations of the
-    /// underlying filesystem, either in-process, or by an external process.
-    pub fn batch_update(&self) -> FileSystemBatchUpdate<D> {
+    /// Conflicting writes are only detected within this process. To detect writes by other
+    /// processes, such as another publisher, use [RepositoryStorage::expect_metadata_versions],
+    /// which [RepoBuilder::commit](crate::repo_builder::RepoBuilder::commit) sets from its
+    /// database.
+    /// Commits hold an advisory lock on the repository, so those checks can't race with another
+    /// commit.
+    ///
+    /// **WARNING**: Advisory locks are only supported on Unix platforms. Elsewhere commits do not
+    /// lock the repository, so two publishers committing at the same time can both pass the
+    /// version checks and overwrite each other's metadata.
+    pub fn batch_update(&self) -> FileSystemBatchUpdate<'_, D> {
         FileSystemBatchUpdate {
             initial_parent_version: *self.version.read().unwrap(),
             parent_repo: self,
             metadata: RwLock::new(HashMap::new()),
             targets: RwLock::new(HashMap::new()),
+            removed_metadata: RwLock::new(HashSet::new()),
+            removed_targets: RwLock::new(HashSet::new()),
+            expected_timestamp_version: RwLock::new(None),
+            expected_snapshot_version: RwLock::new(None),
+        }
+    }
+
+    /// Take an exclusive advisory lock on the repository, waiting until it's available. The lock
+    /// is released when the returned file is dropped.
+    ///
+    /// **WARNING**: Advisory locks are currently only supported on Unix platforms. Elsewhere this
+    /// only creates the lock file, and does not exclude other publishers.
+    async fn lock(&self) -> io::Result<File> {
+        let dir = self.metadata_dir.clone();
+        unblock(move || {
+            let file = dir.open_or_create(LOCK_FILE_NAME)?;
+            #[cfg(unix)]
+            rustix::fs::flock(&file, rustix::fs::FlockOperation::LockExclusive)?;
+            Ok(file)
+        })
+        .await
+    }
+
+    /// Read the version of the unversioned metadata at `meta_path`, or `None` if it doesn't exist.
+    async fn read_version<M>(&self, meta_path: &MetadataPath) -> Result<Option<u32>>
+    where
+        M: Metadata,
+    {
+        let components = meta_path.components::<D>(MetadataVersion::None);
+        let path = self.metadata_dir.join(&components);
+        let dir = self.metadata_dir.clone();
+
+        let res = unblock(move || {
+            let mut bytes = vec![];
+            dir.open(&components)?.read_to_end(&mut bytes)?;
+            Ok::<_, io::Error>(bytes)
+        })
+        .await;
+
+        let bytes = match res {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::IoPath { path, err }),
+        };
+
+        let version = RawSignedMetadata::<D, M>::new(bytes)
+            .parse_untrusted()?
+            .parse_version_untrusted()?;
+
+        Ok(Some(version))
+    }
+
+    fn remove_file<'a>(
+        &'a self,
+        dir: &RootDir,
+        components: Vec<String>,
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = dir.join(&components);
+        let dir = dir.clone();
+
+        async move {
+            // Lock the repository to prevent other writers from manipulating the repository to
+            // avoid race conditions.
+            let _write_lock = self.write_lock.lock().await;
+
+            let removed = unblock(move || dir.remove_file(&components))
+                .await
+                .map_err(|err| Error::IoPath { path, err })?;
+
+            if removed {
+                // Increment our version since the repository changed.
+                *self.version.write().unwrap() += 1;
+            }
+
+            Ok(())
         }
+        .boxed()
     }
 
-    fn metadata_path(&self, meta_path: &MetadataPath, version: MetadataVersion) -> PathBuf {
-        let mut path = self.metadata_path.clone();
-        path.extend(meta_path.components::<D>(version));
-        path
+    async fn list_metadata_on_disk(&self) -> Result<Vec<(MetadataPath, MetadataVersion)>> {
+        let mut metadata = list_files(&self.metadata_dir)
+            .await?
+            .into_iter()
+            .filter_map(|components| super::parse_metadata_components::<D>(&components))
+            .collect::<Vec<_>>();
+        metadata.sort();
+        Ok(metadata)
     }
 
-    fn target_path(&self, target_path: &TargetPath) -> PathBuf {
-        let mut path = self.targets_path.clone();
-        path.extend(target_path.components());
-        path
+    async fn list_targets_on_disk(&self) -> Result<Vec<TargetPath>> {
+        let mut targets = list_files(&self.targets_dir)
+            .await?
+            .into_iter()
+            .filter_map(|components| TargetPath::new(components.join("/")).ok())
+            .collect::<Vec<_>>();
+        targets.sort();
+        Ok(targets)
     }
 
-    fn fetch_metadata_from_path(
+    fn fetch_metadata_from(
         &self,
         meta_path: &MetadataPath,
         version: MetadataVersion,
-        path: &Path,
+        dir: &RootDir,
+        components: Vec<String>,
     ) -> BoxFuture<'_, Result<Box<dyn AsyncRead + Send + Unpin + '_>>> {
-        let reader = File::open(path).map_err(|err| {
-            if err.kind() == io::ErrorKind::NotFound {
-                Error::MetadataNotFound {
-                    path: meta_path.clone(),
-                    version,
-                }
-            } else {
-                Error::IoPath {
-                    path: path.to_path_buf(),
-                    err,
-                }
-            }
-        });
+        let meta_path = meta_path.clone();
+        let path = dir.join(&components);
+        let dir = dir.clone();
 
         async move {
-            let reader = reader?;
-            let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(AllowStdIo::new(reader));
+            let file = unblock(move || dir.open(&components))
+                .await
+                .map_err(|err| {
+                    if err.kind() == io::ErrorKind::NotFound {
+                        Error::MetadataNotFound {
+                            path: meta_path,
+                            version,
+                        }
+                    } else {
+                        Error::IoPath { path, err }
+                    }
+                })?;
+
+            let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(Unblock::new(file));
             Ok(reader)
         }
         .boxed()
     }
 
-    fn fetch_target_from_path(
+    fn fetch_target_from(
         &self,
         target_path: &TargetPath,
-        path: &Path,
+        dir: &RootDir,
+        components: Vec<String>,
     ) -> BoxFuture<'_, Result<Box<dyn AsyncRead + Send + Unpin + '_>>> {
-        let reader = File::open(path).map_err(|err| {
-            if err.kind() == io::ErrorKind::NotFound {
-                Error::TargetNotFound(target_path.clone())
-            } else {
-                Error::IoPath {
-                    path: path.to_path_buf(),
-                    err,
-                }
-            }
-        });
+        let target_path = target_path.clone();
+        let path = dir.join(&components);
+        let dir = dir.clone();
 
         async move {
-            let reader = reader?;
-            let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(AllowStdIo::new(reader));
+            let file = unblock(move || dir.open(&components))
+                .await
+                .map_err(|err| {
+                    if err.kind() == io::ErrorKind::NotFound {
+                        Error::TargetNotFound(target_path)
+                    } else {
+                        Error::IoPath { path, err }
+                    }
+                })?;
+
+            let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(Unblock::new(file));
             Ok(reader)
         }
         .boxed()
     }
+
+    /// Move `temp_path` into place at `components` beneath `dir`.
+    async fn persist(
+        &self,
+        dir: &RootDir,
+        components: &[String],
+        temp_path: TempPath,
+    ) -> Result<()> {
+        // Lock the repository to prevent other writers from manipulating the repository to avoid
+        // race conditions.
+        let _write_lock = self.write_lock.lock().await;
+
+        unblock(move || temp_path.persist())
+            .await
+            .map_err(|err| Error::IoPath {
+                path: dir.join(components),
+                err,
+            })?;
+
+        // Increment our version since the repository changed.
+        *self.version.write().unwrap() += 1;
+
+        Ok(())
+    }
 }
 
 impl<D> RepositoryProvider<D> for FileSystemRepository<D>
@@ -209,16 +357,16 @@ where
         meta_path: &MetadataPath,
         version: MetadataVersion,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        let path = self.metadata_path(meta_path, version);
-        self.fetch_metadata_from_path(meta_path, version, &path)
+        let components = meta_path.components::<D>(version);
+        self.fetch_metadata_from(meta_path, version, &self.metadata_dir, components)
     }
 
     fn fetch_target<'a>(
         &'a self,
         target_path: &TargetPath,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        let path = self.target_path(target_path);
-        self.fetch_target_from_path(target_path, &path)
+        let components = target_path.components();
+        self.fetch_target_from(target_path, &self.targets_dir, components)
     }
 }
 
@@ -232,34 +380,12 @@ where
         version: MetadataVersion,
         metadata: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
-        let path = self.metadata_path(meta_path, version);
+        let components = meta_path.components::<D>(version);
 
         async move {
-            if path.exists() {
-                debug!("Metadata path exists. Overwriting: {:?}", path);
-            }
-
-            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(metadata, &mut temp_file).await {
-                return Err(Error::IoPath { path, err });
-            }
-
-            // Lock the version counter to prevent other writers from manipulating the repository to
-            // avoid race conditions.
-            let mut version = self.version.write().unwrap();
-
-            temp_file
-                .into_inner()
-                .persist(&path)
-                .map_err(|err| Error::IoPath {
-                    path,
-                    err: err.error,
-                })?;
-
-            // Increment our version since the repository changed.
-            *version += 1;
-
-            Ok(())
+            let temp_path = write_temp_file(&self.metadata_dir, &components, metadata).await?;
+            self.persist(&self.metadata_dir, &components, temp_path)
+                .await
         }
         .boxed()
     }
@@ -269,34 +395,34 @@ where
         target_path: &TargetPath,
         read: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
-        let path = self.target_path(target_path);
+        let components = target_path.components();
 
         async move {
-            if path.exists() {
-                debug!("Target path exists. Overwriting: {:?}", path);
-            }
-
-            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(read, &mut temp_file).await {
-                return Err(Error::IoPath { path, err });
-            }
+            let temp_path = write_temp_file(&self.targets_dir, &components, read).await?;
+            self.persist(&self.targets_dir, &components, temp_path)
+                .await
+        }
+        .boxed()
+    }
 
-            let mut version = self.version.write().unwrap();
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.remove_file(&self.metadata_dir, meta_path.components::<D>(version))
+    }
 
-            temp_file
-                .into_inner()
-                .persist(&path)
-                .map_err(|err| Error::IoPath {
-                    path,
-                    err: err.error,
-                })?;
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.remove_file(&self.targets_dir, target_path.components())
+    }
 
-            // Increment our version since the repository changed.
-            *version += 1;
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        self.list_metadata_on_disk().boxed()
+    }
 
-            Ok(())
-        }
-        .boxed()
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.list_targets_on_disk().boxed()
     }
 }
 
@@ -309,29 +435,13 @@ where
 pub struct FileSystemBatchUpdate<'a, D: Pouf> {
     initial_parent_version: u64,
     parent_repo: &'a FileSystemRepository<D>,
-    metadata: RwLock<HashMap<PathBuf, TempPath>>,
-    targets: RwLock<HashMap<PathBuf, TempPath>>,
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum CommitError {
-    /// Conflict occurred during commit.
-    #[error("conflicting change occurred during commit")]
-    Conflict,
-
-    #[error(transparent)]
-    Io(#[from] std::io::Error),
-
-    /// An IO error occurred for a path.
-    #[error("IO error on path {path}")]
-    IoPath {
-        /// Path where the error occurred.
-        path: std::path::PathBuf,
-
-        /// The IO error.
-        #[source]
-        err: io::Error,
-    },
+    metadata: RwLock<HashMap<(MetadataPath, MetadataVersion), TempPath>>,
+    targets: RwLock<HashMap<TargetPath, TempPath>>,
+    removed_metadata: RwLock<HashSet<(MetadataPath, MetadataVersion)>>,
+    removed_targets: RwLock<HashSet<TargetPath>>,
+    // `None` if unchecked, and `Some(None)` if the metadata must not exist.
+    expected_timestamp_version: RwLock<Option<Option<u32>>>,
+    expected_snapshot_version: RwLock<Option<Option<u32>>>,
 }
 
 impl<'a, D> FileSystemBatchUpdate<'a, D>
@@ -341,37 +451,137 @@ where
     /// Write all the metadata and targets the [FileSystemBatchUpdate] to the source
     /// [FileSystemRepository] in a single batch operation.
     ///
+    /// This holds an advisory lock on the repository while committing, and fails with
+    /// [CommitError::UnexpectedVersion] if the timestamp or snapshot metadata no longer have
+    /// the versions set by [RepositoryStorage::expect_metadata_versions]. The lock is only taken on Unix platforms, see
+    /// [FileSystemRepository::batch_update].
+    ///
     /// Note: While this function will atomically write each file, it's possible that this could
     /// fail with part of the files written if we experience a system error during the process.
     pub async fn commit(self) -> std::result::Result<(), CommitError> {
-        let mut parent_version = self.parent_repo.version.write().unwrap();
+        let _write_lock = self.parent_repo.write_lock.lock().await;
 
-        if self.initial_parent_version != *parent_version {
+        if self.initial_parent_version != *self.parent_repo.version.read().unwrap() {
             return Err(CommitError::Conflict);
         }
 
-        for (path, tmp_path) in self.targets.into_inner().unwrap() {
-            if path.exists() {
-                debug!("Target path exists. Overwriting: {:?}", path);
-            }
-            tmp_path.persist(&path).map_err(|err| CommitError::IoPath {
-                path,
-                err: err.error,
+        let _lock = self
+            .parent_repo
+            .lock()
+            .await
+            .map_err(|err| CommitError::IoPath {
+                path: self.parent_repo.metadata_dir.path().join(LOCK_FILE_NAME),
+                err,
             })?;
-        }
 
-        for (path, tmp_path) in self.metadata.into_inner().unwrap() {
-            if path.exists() {
-                debug!("Metadata path exists. Overwriting: {:?}", path);
+        let expected_timestamp_version = *self.expected_timestamp_version.read().unwrap();
+        self.check_version::<TimestampMetadata>(
+            &MetadataPath::timestamp(),
+            expected_timestamp_version,
+        )
+        .await?;
+        let expected_snapshot_version = *self.expected_snapshot_version.read().unwrap();
+        self.check_version::<SnapshotMetadata>(
+            &MetadataPath::snapshot(),
+            expected_snapshot_version,
+        )
+        .await?;
+
+        let metadata_dir = self.parent_repo.metadata_dir.clone();
+        let targets_dir = self.parent_repo.targets_dir.clone();
+
+        let removed_targets = self
+            .removed_targets
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|target_path| target_path.components())
+            .collect::<Vec<_>>();
+        let removed_metadata = self
+            .removed_metadata
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(meta_path, version)| meta_path.components::<D>(version))
+            .collect::<Vec<_>>();
+        let targets = self
+            .targets
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(target_path, temp_path)| (target_path.components(), temp_path))
+            .collect::<Vec<_>>();
+        let metadata = self
+            .metadata
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|((meta_path, version), temp_path)| {
+                (meta_path.components::<D>(version), temp_path)
+            })
+            .collect::<Vec<_>>();
+
+        unblock(move || {
+            for components in removed_targets {
+                targets_dir
+                    .remove_file(&components)
+                    .map_err(|err| CommitError::IoPath {
+                        path: targets_dir.join(&components),
+                        err,
+                    })?;
             }
-            tmp_path.persist(&path).map_err(|err| CommitError::IoPath {
-                path,
-                err: err.error,
-            })?;
-        }
+
+            for components in removed_metadata {
+                metadata_dir
+                    .remove_file(&components)
+                    .map_err(|err| CommitError::IoPath {
+                        path: metadata_dir.join(&components),
+                        err,
+                    })?;
+            }
+
+            for (components, temp_path) in targets {
+                temp_path.persist().map_err(|err| CommitError::IoPath {
+                    path: targets_dir.join(&components),
+                    err,
+                })?;
+            }
+
+            for (components, temp_path) in metadata {
+                temp_path.persist().map_err(|err| CommitError::IoPath {
+                    path: metadata_dir.join(&components),
+                    err,
+                })?;
+            }
+
+            Ok::<_, CommitError>(())
+        })
+        .await?;
 
         // Increment the version because we wrote to it.
-        *parent_version += 1;
+        *self.parent_repo.version.write().unwrap() += 1;
+
+        Ok(())
+    }
+
+    async fn check_version<M>(
+        &self,
+        meta_path: &MetadataPath,
+        expected: Option<Option<u32>>,
+    ) -> std::result::Result<(), CommitError>
+    where
+        M: Metadata,
+    {
+        if let Some(expected) = expected {
+            let actual = self.parent_repo.read_version::<M>(meta_path).await?;
+            if actual != expected {
+                return Err(CommitError::UnexpectedVersion {
+                    path: meta_path.clone(),
+                    expected,
+                    actual,
+                });
+            }
+        }
 
         Ok(())
     }
@@ -386,13 +596,22 @@ where
         meta_path: &MetadataPath,
         version: MetadataVersion,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        let path = self.parent_repo.metadata_path(meta_path, version);
-        if let Some(temp_path) = self.metadata.read().unwrap().get(&path) {
-            self.parent_repo
-                .fetch_metadata_from_path(meta_path, version, temp_path)
+        let key = (meta_path.clone(), version);
+        if let Some(temp_path) = self.metadata.read().unwrap().get(&key) {
+            self.parent_repo.fetch_metadata_from(
+                meta_path,
+                version,
+                temp_path.root(),
+                temp_path.temp_components(),
+            )
+        } else if self.removed_metadata.read().unwrap().contains(&key) {
+            let err = Error::MetadataNotFound {
+                path: meta_path.clone(),
+                version,
+            };
+            async move { Err(err) }.boxed()
         } else {
-            self.parent_repo
-                .fetch_metadata_from_path(meta_path, version, &path)
+            self.parent_repo.fetch_metadata(meta_path, version)
         }
     }
 
@@ -400,12 +619,17 @@ where
         &'a self,
         target_path: &TargetPath,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        let path = self.parent_repo.target_path(target_path);
-        if let Some(temp_path) = self.targets.read().unwrap().get(&path) {
-            self.parent_repo
-                .fetch_target_from_path(target_path, temp_path)
+        if let Some(temp_path) = self.targets.read().unwrap().get(target_path) {
+            self.parent_repo.fetch_target_from(
+                target_path,
+                temp_path.root(),
+                temp_path.temp_components(),
+            )
+        } else if self.removed_targets.read().unwrap().contains(target_path) {
+            let err = Error::TargetNotFound(target_path.clone());
+            async move { Err(err) }.boxed()
         } else {
-            self.parent_repo.fetch_target_from_path(target_path, &path)
+            self.parent_repo.fetch_target(target_path)
         }
     }
 }
@@ -420,17 +644,14 @@ where
         version: MetadataVersion,
         read: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
-        let path = self.parent_repo.metadata_path(meta_path, version);
+        let key = (meta_path.clone(), version);
+        let components = meta_path.components::<D>(version);
 
         async move {
-            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(read, &mut temp_file).await {
-                return Err(Error::IoPath { path, err });
-            }
-            self.metadata
-                .write()
-                .unwrap()
-                .insert(path, temp_file.into_inner().into_temp_path());
+            let temp_path =
+                write_temp_file(&self.parent_repo.metadata_dir, &components, read).await?;
+            self.removed_metadata.write().unwrap().remove(&key);
+            self.metadata.write().unwrap().insert(key, temp_path);
 
             Ok(())
         }
@@ -442,56 +663,147 @@ where
         target_path: &TargetPath,
         read: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
-        let path = self.parent_repo.target_path(target_path);
+        let target_path = target_path.clone();
+        let components = target_path.components();
 
         async move {
-            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(read, &mut temp_file).await {
-                return Err(Error::IoPath { path, err });
-            }
-            self.targets
-                .write()
-                .unwrap()
-                .insert(path, temp_file.into_inner().into_temp_path());
+            let temp_path =
+                write_temp_file(&self.parent_repo.targets_dir, &components, read).await?;
+            self.removed_targets.write().unwrap().remove(&target_path);
+            self.targets.write().unwrap().insert(target_path, temp_path);
 
             Ok(())
         }
         .boxed()
     }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = (meta_path.clone(), version);
+        self.metadata.write().unwrap().remove(&key);
+        self.removed_metadata.write().unwrap().insert(key);
+        async move { Ok(()) }.boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.targets.write().unwrap().remove(target_path);
+        self.removed_targets
+            .write()
+            .unwrap()
+            .insert(target_path.clone());
+        async move { Ok(()) }.boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        async move {
+            let metadata = self.parent_repo.list_metadata_on_disk().await?;
+            let removed = self.removed_metadata.read().unwrap();
+            let mut metadata = metadata
+                .into_iter()
+                .filter(|key| !removed.contains(key))
+                .chain(self.metadata.read().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            metadata.sort();
+            metadata.dedup();
+            Ok(metadata)
+        }
+        .boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        async move {
+            let targets = self.parent_repo.list_targets_on_disk().await?;
+            let removed = self.removed_targets.read().unwrap();
+            let mut targets = targets
+                .into_iter()
+                .filter(|target_path| !removed.contains(target_path))
+                .chain(self.targets.read().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            targets.sort();
+            targets.dedup();
+            Ok(targets)
+        }
+        .boxed()
+    }
+
+    fn expect_metadata_versions(
+        &self,
+        timestamp_version: Option<u32>,
+        snapshot_version: Option<u32>,
+    ) {
+        *self.expected_timestamp_version.write().unwrap() = Some(timestamp_version);
+        *self.expected_snapshot_version.write().unwrap() = Some(snapshot_version);
+    }
+}
+
+/// Recursively list the files under `dir`, returning the path components of each file relative to
+/// `dir`. Symlinks, temporary files from in-progress writes, the lock file, and files with
+/// non-UTF-8 names are skipped.
+async fn list_files(dir: &RootDir) -> Result<Vec<Vec<String>>> {
+    let path = dir.path().to_path_buf();
+    let dir = dir.clone();
+    let mut files = unblock(move || dir.list_files())
+        .await
+        .map_err(|err| Error::IoPath { path, err })?;
+    files.retain(|components| components != &[LOCK_FILE_NAME]);
+    Ok(files)
 }
 
-fn create_temp_file(path: &Path) -> Result<NamedTempFile> {
+/// Write `read` to a new temporary file in the directory that will contain `components`.
+async fn write_temp_file(
+    dir: &RootDir,
+    components: &[String],
+    read: &mut (dyn AsyncRead + Send + Unpin),
+) -> Result<TempPath> {
     // We want to atomically write the file to make sure clients can never see a partially written
     // file.  In order to do this, we'll write to a temporary file in the same directory as our
     // target, otherwise we risk writing the temporary file to one mountpoint, and then
     // non-atomically copying the file to another mountpoint.
+    let path = dir.join(components);
+    let (mut temp_file, temp_path) = {
+        let dir = dir.clone();
+        let components = components.to_vec();
+        unblock(move || dir.create_temp_file(&components))
+            .await
+            .map_err(|err| Error::IoPath { path, err })?
+    };
 
-    if let Some(parent) = path.parent() {
-        DirBuilder::new()
-            .recursive(true)
-            .create(parent)
-            .map_err(|err| Error::IoPath {
-                path: parent.to_path_buf(),
-                err,
-            })?;
-        Ok(NamedTempFile::new_in(parent).map_err(|err| Error::IoPath {
-            path: parent.to_path_buf(),
+    let mut buf = vec![0; WRITE_CHUNK_SIZE];
+    loop {
+        let n = read.read(&mut buf).await.map_err(|err| Error::IoPath {
+            path: temp_path.path(),
             err,
-        })?)
-    } else {
-        Ok(NamedTempFile::new_in(".").map_err(|err| Error::IoPath {
-            path: path.to_path_buf(),
+        })?;
+        if n == 0 {
+            break;
+        }
+
+        (temp_file, buf) = unblock(move || {
+            temp_file.write_all(&buf[..n])?;
+            Ok((temp_file, buf))
+        })
+        .await
+        .map_err(|err: io::Error| Error::IoPath {
+            path: temp_path.path(),
             err,
-        })?)
+        })?;
     }
+
+    Ok(temp_path)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::database::Database;
     use crate::error::Error;
     use crate::metadata::RootMetadata;
     use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
     use crate::repository::{fetch_metadata_to_string, fetch_target_to_string, Repository};
     use assert_matches::assert_matches;
     use futures_executor::block_on;
@@ -569,6 +881,577 @@ mod test {
         })
     }
 
+    #[test]
+    fn file_system_repo_remove_and_list() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+
+            // An empty repository has no metadata or targets.
+            assert_eq!(repo.list_metadata().await.unwrap(), vec![]);
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+
+            let root_path = MetadataPath::root();
+            let delegation_path = MetadataPath::new("foo/bar").unwrap();
+            let target_path = TargetPath::new("foo/bar/baz").unwrap();
+
+            repo.store_metadata(&root_path, MetadataVersion::None, &mut "root".as_bytes())
+                .await
+                .unwrap();
+            repo.store_metadata(
+                &root_path,
+                MetadataVersion::Number(1),
+                &mut "root".as_bytes(),
+            )
+            .await
+            .unwrap();
+            repo.store_metadata(
+                &delegation_path,
+                MetadataVersion::None,
+                &mut "delegation".as_bytes(),
+            )
+            .await
+            .unwrap();
+            repo.store_target(&target_path, &mut "target".as_bytes())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![
+                    (delegation_path.clone(), MetadataVersion::None),
+                    (root_path.clone(), MetadataVersion::None),
+                    (root_path.clone(), MetadataVersion::Number(1)),
+                ]
+            );
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![target_path.clone()]
+            );
+
+            repo.remove_metadata(&delegation_path, MetadataVersion::None)
+                .await
+                .unwrap();
+            repo.remove_target(&target_path).await.unwrap();
+            assert!(!temp_dir
+                .path()
+                .join("metadata")
+                .join("foo")
+                .join("bar.json")
+                .exists());
+            assert!(!temp_dir
+                .path()
+                .join("targets")
+                .join("foo")
+                .join("bar")
+                .join("baz")
+                .exists());
+
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![
+                    (root_path.clone(), MetadataVersion::None),
+                    (root_path.clone(), MetadataVersion::Number(1)),
+                ]
+            );
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+
+            // Removing something that doesn't exist is not an error.
+            repo.remove_target(&target_path).await.unwrap();
+        })
+    }
+
+    #[test]
+    fn file_system_repo_batch_remove() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+
+            let meta_path = MetadataPath::new("meta").unwrap();
+            let meta_version = MetadataVersion::None;
+            let target_path = TargetPath::new("target").unwrap();
+
+            repo.store_metadata(&meta_path, meta_version, &mut "meta".as_bytes())
+                .await
+                .unwrap();
+            repo.store_target(&target_path, &mut "target".as_bytes())
+                .await
+                .unwrap();
+
+            let batch = repo.batch_update();
+            batch
+                .remove_metadata(&meta_path, meta_version)
+                .await
+                .unwrap();
+            batch.remove_target(&target_path).await.unwrap();
+
+            // The removals are visible in the batch, but not the repository.
+            assert_matches!(
+                fetch_metadata_to_string(&batch, &meta_path, meta_version).await,
+                Err(Error::MetadataNotFound { .. })
+            );
+            assert_matches!(
+                fetch_target_to_string(&batch, &target_path).await,
+                Err(Error::TargetNotFound(_))
+            );
+            assert_eq!(batch.list_metadata().await.unwrap(), vec![]);
+            assert_eq!(batch.list_targets().await.unwrap(), vec![]);
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![(meta_path.clone(), meta_version)]
+            );
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![target_path.clone()]
+            );
+
+            batch.commit().await.unwrap();
+
+            assert_eq!(repo.list_metadata().await.unwrap(), vec![]);
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+        })
+    }
+
+    #[test]
+    fn file_system_repo_batch_commit_fails_with_unexpected_versions() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let key = Ed25519PrivateKey::from_pkcs8(include_bytes!(
+                "../../tests/ed25519/ed25519-1.pk8.der"
+            ))
+            .unwrap();
+
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(None, None);
+
+            // Another writer publishes to the repository, which we can't detect in-process.
+            let other_repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+            RepoBuilder::create(&other_repo)
+                .trusted_root_keys(&[&key])
+                .trusted_targets_keys(&[&key])
+                .trusted_snapshot_keys(&[&key])
+                .trusted_timestamp_keys(&[&key])
+                .commit()
+                .await
+                .unwrap();
+
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: None, actual: Some(1) })
+                if path == MetadataPath::timestamp()
+            );
+
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(Some(1), Some(2));
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: Some(2), actual: Some(1) })
+                if path == MetadataPath::snapshot()
+            );
+
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(Some(1), Some(1));
+            assert_matches!(batch.commit().await, Ok(()));
+        })
+    }
+
+    #[test]
+    fn file_system_repo_builder_commit_detects_other_publishers() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let key = Ed25519PrivateKey::from_pkcs8(include_bytes!(
+                "../../tests/ed25519/ed25519-1.pk8.der"
+            ))
+            .unwrap();
+
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&key])
+                .trusted_targets_keys(&[&key])
+                .trusted_snapshot_keys(&[&key])
+                .trusted_timestamp_keys(&[&key])
+                .commit()
+                .await
+                .unwrap();
+            let database = Database::from_trusted_metadata(&metadata).unwrap();
+
+            // Stage a new version from the database in a batch.
+            let batch = repo.batch_update();
+            RepoBuilder::from_database(&batch, &database)
+                .trusted_root_keys(&[&key])
+                .trusted_targets_keys(&[&key])
+                .trusted_snapshot_keys(&[&key])
+                .trusted_timestamp_keys(&[&key])
+                .skip_root()
+                .stage_targets()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // Meanwhile, another publisher also publishes a new version.
+            let other_repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+            RepoBuilder::from_database(&other_repo, &database)
+                .trusted_root_keys(&[&key])
+                .trusted_targets_keys(&[&key])
+                .trusted_snapshot_keys(&[&key])
+                .trusted_timestamp_keys(&[&key])
+                .skip_root()
+                .stage_targets()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: Some(1), actual: Some(2) })
+                if path == MetadataPath::timestamp()
+            );
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_repo_does_not_block_executor_while_waiting_for_lock() {
+        use futures_util::future::{select, Either};
+
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepositoryBuilder::<Pouf1>::new(temp_dir.path()).build();
+            let target_path = TargetPath::new("foo").unwrap();
+            repo.store_target(&target_path, &mut "foo".as_bytes())
+                .await
+                .unwrap();
+
+            // Simulate another process holding the repository lock.
+            let lock = File::options()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(temp_dir.path().join(LOCK_FILE_NAME))
+                .unwrap();
+            rustix::fs::flock(&lock, rustix::fs::FlockOperation::LockExclusive).unwrap();
+
+            let batch = repo.batch_update();
+            batch
+                .store_target(&TargetPath::new("bar").unwrap(), &mut "bar".as_bytes())
+                .await
+                .unwrap();
+
+            // The commit waits for the lock without blocking this single threaded executor, so
+            // other operations can make progress.
+            let commit = batch.commit().boxed();
+            let fetch = fetch_target_to_string(&repo, &target_path).boxed();
+            let commit = match select(commit, fetch).await {
+                Either::Left(_) => panic!("commit should wait for the lock"),
+                Either::Right((fetched, commit)) => {
+                    assert_eq!(fetched.unwrap(), "foo");
+                    commit
+                }
+            };
+
+            drop(lock);
+            commit.await.unwrap();
+
+            assert_eq!(
+                fetch_target_to_string(&repo, &TargetPath::new("bar").unwrap())
+                    .await
+                    .unwrap(),
+                "bar"
+            );
+        })
+    }
+
+    #[test]
+    fn file_system_repo_does_not_list_lock_file() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepositoryBuilder::<Pouf1>::new(temp_dir.path()).build();
+
+            let batch = repo.batch_update();
+            batch
+                .store_target(&TargetPath::new("foo").unwrap(), &mut "foo".as_bytes())
+                .await
+                .unwrap();
+            batch.commit().await.unwrap();
+
+            assert!(temp_dir.path().join(LOCK_FILE_NAME).exists());
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![TargetPath::new("foo").unwrap()]
+            );
+        })
+    }
+
+    #[test]
+    fn file_system_repo_lists_targets_named_like_temp_files() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepositoryBuilder::<Pouf1>::new(temp_dir.path()).build();
+
+            let mut expected = vec![];
+            for name in [".tmp", ".tmpfoo", ".tuf-tmp-foo"] {
+                let target_path = TargetPath::new(name).unwrap();
+                repo.store_target(&target_path, &mut name.as_bytes())
+                    .await
+                    .unwrap();
+                expected.push(target_path);
+            }
+
+            let mut targets = repo.list_targets().await.unwrap();
+            targets.sort();
+            expected.sort();
+            assert_eq!(targets, expected);
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_repo_temp_files_are_private() {
+        use std::{fs, os::unix::fs::PermissionsExt as _, path::Path};
+
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepositoryBuilder::<Pouf1>::new(temp_dir.path())
+                .targets_prefix("targets")
+                .build();
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+            let batch = repo.batch_update();
+            batch
+                .store_target(&TargetPath::new("foo").unwrap(), &mut "foo".as_bytes())
+                .await
+                .unwrap();
+
+            let staged = fs::read_dir(temp_dir.path().join("targets"))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect::<Vec<_>>();
+            assert_eq!(staged.len(), 1);
+            assert_eq!(mode(&staged[0]), 0o600);
+
+            // Once persisted, files get the mode of any other new file.
+            batch.commit().await.unwrap();
+            let reference = temp_dir.path().join("reference");
+            File::create(&reference).unwrap();
+            assert_eq!(
+                mode(&temp_dir.path().join("targets").join("foo")),
+                mode(&reference)
+            );
+        })
+    }
+
+    #[cfg(unix)]
+    fn symlinked_repo() -> (tempfile::TempDir, FileSystemRepository<Pouf1>, PathBuf) {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("rust-tuf")
+            .tempdir()
+            .unwrap();
+
+        // Create a file and directory outside of the repository.
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir_all(outside.join("dir")).unwrap();
+        std::fs::write(outside.join("secret"), b"secret").unwrap();
+
+        // Create malicious symlinks inside the repository that point outside of it.
+        let repo_dir = temp_dir.path().join("repo");
+        for dir in ["metadata", "targets"] {
+            let dir = repo_dir.join(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            symlink(outside.join("secret"), dir.join("link")).unwrap();
+            symlink(outside.join("secret"), dir.join("root.json")).unwrap();
+            symlink(outside.join("dir"), dir.join("dir")).unwrap();
+        }
+
+        let repo = FileSystemRepository::new(repo_dir);
+
+        (temp_dir, repo, outside)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_repo_does_not_follow_symlinks_when_fetching() {
+        block_on(async {
+            let (_temp_dir, repo, _outside) = symlinked_repo();
+
+            assert_matches!(
+                fetch_metadata_to_string(&repo, &MetadataPath::root(), MetadataVersion::None).await,
+                Err(Error::IoPath { .. })
+            );
+            assert_matches!(
+                fetch_target_to_string(&repo, &TargetPath::new("link").unwrap()).await,
+                Err(Error::IoPath { .. })
+            );
+            assert_matches!(
+                fetch_target_to_string(&repo, &TargetPath::new("dir/secret").unwrap()).await,
+                Err(Error::IoPath { .. })
+            );
+
+            // Symlinks are not listed.
+            assert_eq!(repo.list_metadata().await.unwrap(), vec![]);
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_repo_does_not_open_fifos() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path().to_path_buf());
+            let targets_dir = temp_dir.path().join("targets");
+            std::fs::create_dir(&targets_dir).unwrap();
+            rustix::fs::mkfifoat(
+                rustix::fs::CWD,
+                targets_dir.join("fifo"),
+                rustix::fs::Mode::from_raw_mode(0o666),
+            )
+            .unwrap();
+
+            // Opening the FIFO would block forever waiting for a writer if it wasn't rejected.
+            assert_matches!(
+                fetch_target_to_string(&repo, &TargetPath::new("fifo").unwrap()).await,
+                Err(Error::IoPath { .. })
+            );
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_repo_does_not_follow_symlinks_when_storing() {
+        block_on(async {
+            let (temp_dir, repo, outside) = symlinked_repo();
+
+            // Writing through a symlinked directory fails without writing outside the repository.
+            assert_matches!(
+                repo.store_target(&TargetPath::new("dir/foo").unwrap(), &mut "foo".as_bytes())
+                    .await,
+                Err(Error::IoPath { .. })
+            );
+            assert_matches!(
+                repo.store_metadata(
+                    &MetadataPath::new("dir/foo").unwrap(),
+                    MetadataVersion::None,
+                    &mut "foo".as_bytes()
+                )
+                .await,
+                Err(Error::IoPath { .. })
+            );
+            assert_eq!(std::fs::read_dir(outside.join("dir")).unwrap().count(), 0);
+
+            // Writing over a symlink replaces the symlink, rather than the file it points to.
+            repo.store_target(&TargetPath::new("link").unwrap(), &mut "foo".as_bytes())
+                .await
+                .unwrap();
+            repo.store_metadata(
+                &MetadataPath::root(),
+                MetadataVersion::None,
+                &mut "root".as_bytes(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(std::fs::read(outside.join("secret")).unwrap(), b"secret");
+
+            let link = temp_dir.path().join("repo").join("targets").join("link");
+            assert!(!link.symlink_metadata().unwrap().file_type().is_symlink());
+            assert_eq!(
+                fetch_target_to_string(&repo, &TargetPath::new("link").unwrap())
+                    .await
+                    .unwrap(),
+                "foo"
+            );
+            assert_eq!(
+                fetch_metadata_to_string(&repo, &MetadataPath::root(), MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "root"
+            );
+
+            // The same holds for batch updates.
+            let batch = repo.batch_update();
+            assert_matches!(
+                batch
+                    .store_target(&TargetPath::new("dir/bar").unwrap(), &mut "bar".as_bytes())
+                    .await,
+                Err(Error::IoPath { .. })
+            );
+            batch.commit().await.unwrap();
+            assert_eq!(std::fs::read_dir(outside.join("dir")).unwrap().count(), 0);
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_repo_does_not_follow_symlinks_when_removing() {
+        block_on(async {
+            let (temp_dir, repo, outside) = symlinked_repo();
+            std::fs::write(outside.join("dir").join("foo"), b"foo").unwrap();
+
+            // Removing through a symlinked directory fails without removing anything.
+            assert_matches!(
+                repo.remove_target(&TargetPath::new("dir/foo").unwrap())
+                    .await,
+                Err(Error::IoPath { .. })
+            );
+            assert!(outside.join("dir").join("foo").exists());
+
+            // Removing a symlink removes the symlink, rather than the file it points to.
+            repo.remove_target(&TargetPath::new("link").unwrap())
+                .await
+                .unwrap();
+            let batch = repo.batch_update();
+            batch
+                .remove_metadata(&MetadataPath::root(), MetadataVersion::None)
+                .await
+                .unwrap();
+            batch.commit().await.unwrap();
+
+            let repo_dir = temp_dir.path().join("repo");
+            assert!(repo_dir
+                .join("targets")
+                .join("link")
+                .symlink_metadata()
+                .is_err());
+            assert!(repo_dir
+                .join("metadata")
+                .join("root.json")
+                .symlink_metadata()
+                .is_err());
+            assert_eq!(std::fs::read(outside.join("secret")).unwrap(), b"secret");
+        })
+    }
+
     #[test]
     fn file_system_repo_batch_update() {
         block_on(async {
@@ -0,0 +1,532 @@
+//! Access to files beneath a directory that never follows symlinks or escapes the directory.
+
+use {
+    data_encoding::HEXLOWER,
+    log::debug,
+    ring::rand::{SecureRandom as _, SystemRandom},
+    std::{
+        fs::{self, File},
+        io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The prefix of the temporary files created by [RootDir::create_temp_file], which is followed by
+/// [TEMP_FILE_RANDOM_LENGTH] random bytes in lowercase hex.
+const TEMP_FILE_PREFIX: &str = ".tuf-tmp-";
+const TEMP_FILE_RANDOM_LENGTH: usize = 8;
+
+/// A directory on the local file system that files are read from and written to.
+///
+/// Paths are resolved one component at a time relative to the directory, so they can never escape
+/// it, and symlinks beneath the directory are never followed. On Unix this uses `openat` with
+/// `O_NOFOLLOW`, so it is not subject to races with other processes that manipulate the
+/// directory. On other platforms each component is checked before it is used, which is best effort.
+///
+/// The path of the directory itself is trusted, and may contain symlinks.
+#[derive(Clone, Debug)]
+pub(super) struct RootDir {
+    path: PathBuf,
+}
+
+impl RootDir {
+    pub(super) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of `components` beneath this directory. This should only be used for
+    /// diagnostics, since the path may traverse symlinks.
+    pub(super) fn join(&self, components: &[String]) -> PathBuf {
+        let mut path = self.path.clone();
+        path.extend(components);
+        path
+    }
+
+    /// Open the file at `components` for reading.
+    pub(super) fn open(&self, components: &[String]) -> io::Result<File> {
+        let (name, dirs) = split_components(components)?;
+        let dir = self.open_dirs(dirs, false)?;
+        sys::open_file(&dir, name)
+    }
+
+    /// Open the file `name` directly beneath this directory for writing, creating it and this
+    /// directory if they don't exist.
+    pub(super) fn open_or_create(&self, name: &str) -> io::Result<File> {
+        check_component(name)?;
+        let dir = self.open_dirs(&[], true)?;
+        sys::open_or_create_file(&dir, name)
+    }
+
+    /// Create a new temporary file in the directory that will contain `components`, creating any
+    /// missing directories. Only the owner can access the file until it is moved into place with
+    /// [TempPath::persist].
+    pub(super) fn create_temp_file(&self, components: &[String]) -> io::Result<(File, TempPath)> {
+        let (name, dirs) = split_components(components)?;
+        let dir = self.open_dirs(dirs, true)?;
+
+        let rng = SystemRandom::new();
+        loop {
+            let mut random = [0; TEMP_FILE_RANDOM_LENGTH];
+            rng.fill(&mut random).map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "failed to generate file name")
+            })?;
+            let temp_name = format!("{}{}", TEMP_FILE_PREFIX, HEXLOWER.encode(&random));
+
+            match sys::create_new_file(&dir, &temp_name) {
+                Ok((file, mode)) => {
+                    let temp_path = TempPath {
+                        root: self.clone(),
+                        dirs: dirs.to_vec(),
+                        temp_name,
+                        name: name.clone(),
+                        mode,
+                        persisted: false,
+                    };
+                    return Ok((file, temp_path));
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Remove the file at `components`, returning `false` if it did not exist.
+    pub(super) fn remove_file(&self, components: &[String]) -> io::Result<bool> {
+        let (name, dirs) = split_components(components)?;
+        let dir = match self.open_dirs(dirs, false) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        match sys::remove_file(&dir, name) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Recursively list the regular files beneath this directory, returning the path components of
+    /// each file. Symlinks, temporary files, and files with non-UTF-8 names are skipped.
+    pub(super) fn list_files(&self) -> io::Result<Vec<Vec<String>>> {
+        fn walk(
+            dir: &sys::Dir,
+            prefix: &mut Vec<String>,
+            files: &mut Vec<Vec<String>>,
+        ) -> io::Result<()> {
+            for (name, kind) in sys::entries(dir)? {
+                if is_temp_file(&name) {
+                    continue;
+                }
+
+                prefix.push(name);
+                match kind {
+                    EntryKind::File => files.push(prefix.clone()),
+                    EntryKind::Dir => {
+                        let subdir = sys::open_dir(dir, prefix.last().unwrap())?;
+                        walk(&subdir, prefix, files)?;
+                    }
+                    EntryKind::Other => debug!("Skipping non-regular file: {:?}", prefix),
+                }
+                prefix.pop();
+            }
+
+            Ok(())
+        }
+
+        let dir = match sys::open_root(&self.path) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut files = vec![];
+        walk(&dir, &mut vec![], &mut files)?;
+        Ok(files)
+    }
+
+    /// Open the directory at `dirs`, optionally creating any that are missing.
+    fn open_dirs(&self, dirs: &[String], create: bool) -> io::Result<sys::Dir> {
+        if create {
+            fs::create_dir_all(&self.path)?;
+        }
+
+        let mut dir = sys::open_root(&self.path)?;
+        for name in dirs {
+            check_component(name)?;
+
+            dir = match sys::open_dir(&dir, name) {
+                Err(err) if create && err.kind() == io::ErrorKind::NotFound => {
+                    match sys::create_dir(&dir, name) {
+                        Ok(()) => {}
+                        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                        Err(err) => return Err(err),
+                    }
+                    sys::open_dir(&dir, name)?
+                }
+                res => res?,
+            };
+        }
+
+        Ok(dir)
+    }
+}
+
+/// A temporary file created by [RootDir::create_temp_file]. The file is removed on drop unless it
+/// was persisted.
+#[derive(Debug)]
+pub(super) struct TempPath {
+    root: RootDir,
+    dirs: Vec<String>,
+    temp_name: String,
+    name: String,
+    /// The mode to give the file once it's persisted.
+    mode: sys::FileMode,
+    persisted: bool,
+}
+
+impl TempPath {
+    /// Returns the directory containing the temporary file.
+    pub(super) fn root(&self) -> &RootDir {
+        &self.root
+    }
+
+    /// Returns the path components of the temporary file beneath [TempPath::root].
+    pub(super) fn temp_components(&self) -> Vec<String> {
+        let mut components = self.dirs.clone();
+        components.push(self.temp_name.clone());
+        components
+    }
+
+    /// Returns the path of the temporary file. This should only be used for diagnostics.
+    pub(super) fn path(&self) -> PathBuf {
+        self.root.join(&self.temp_components())
+    }
+
+    /// Atomically move the temporary file into place, replacing any existing file.
+    pub(super) fn persist(mut self) -> io::Result<()> {
+        let dir = self.root.open_dirs(&self.dirs, false)?;
+        sys::set_mode(&dir, &self.temp_name, self.mode)?;
+        sys::rename(&dir, &self.temp_name, &self.name)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.persisted {
+            return;
+        }
+
+        if let Ok(dir) = self.root.open_dirs(&self.dirs, false) {
+            let _ = sys::remove_file(&dir, &self.temp_name);
+        }
+    }
+}
+
+enum EntryKind {
+    File,
+    Dir,
+    Other,
+}
+
+/// Whether `name` is a temporary file created by [RootDir::create_temp_file].
+fn is_temp_file(name: &str) -> bool {
+    name.strip_prefix(TEMP_FILE_PREFIX).map_or(false, |random| {
+        random.len() == 2 * TEMP_FILE_RANDOM_LENGTH
+            && random
+                .bytes()
+                .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    })
+}
+
+fn split_components(components: &[String]) -> io::Result<(&String, &[String])> {
+    let (name, dirs) = components
+        .split_last()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path cannot be empty"))?;
+    check_component(name)?;
+    Ok((name, dirs))
+}
+
+/// Make sure a path component can only refer to an entry directly beneath its directory.
+fn check_component(name: &str) -> io::Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("illegal path component {:?}", name),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+mod sys {
+    use {
+        super::EntryKind,
+        rustix::fs::{self as rfs, AtFlags, FileType, Mode, OFlags},
+        std::{fs::File, io, os::unix::io::OwnedFd, path::Path},
+    };
+
+    pub(super) type Dir = OwnedFd;
+    pub(super) type FileMode = Mode;
+
+    const FILE_MODE: u32 = 0o666;
+    const TEMP_FILE_MODE: u32 = 0o600;
+    const DIR_MODE: u32 = 0o777;
+
+    pub(super) fn open_root(path: &Path) -> io::Result<Dir> {
+        Ok(rfs::open(
+            path,
+            OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC,
+            Mode::empty(),
+        )?)
+    }
+
+    pub(super) fn open_dir(dir: &Dir, name: &str) -> io::Result<Dir> {
+        Ok(rfs::openat(
+            dir,
+            name,
+            OFlags::RDONLY | OFlags::DIRECTORY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+            Mode::empty(),
+        )?)
+    }
+
+    pub(super) fn create_dir(dir: &Dir, name: &str) -> io::Result<()> {
+        Ok(rfs::mkdirat(dir, name, Mode::from_raw_mode(DIR_MODE))?)
+    }
+
+    pub(super) fn open_file(dir: &Dir, name: &str) -> io::Result<File> {
+        let fd = rfs::openat(
+            dir,
+            name,
+            OFlags::RDONLY | OFlags::NOFOLLOW | OFlags::CLOEXEC | OFlags::NONBLOCK,
+            Mode::empty(),
+        )?;
+        into_regular_file(fd, name)
+    }
+
+    pub(super) fn open_or_create_file(dir: &Dir, name: &str) -> io::Result<File> {
+        let fd = rfs::openat(
+            dir,
+            name,
+            OFlags::WRONLY | OFlags::CREATE | OFlags::NOFOLLOW | OFlags::CLOEXEC | OFlags::NONBLOCK,
+            Mode::from_raw_mode(FILE_MODE),
+        )?;
+        into_regular_file(fd, name)
+    }
+
+    /// Fail unless `fd` is a regular file, rather than a FIFO or a device node. Files are opened with
+    /// `O_NONBLOCK` so opening a FIFO can't block, which is cleared once we know it's a regular
+    /// file.
+    fn into_regular_file(fd: OwnedFd, name: &str) -> io::Result<File> {
+        if FileType::from_raw_mode(rfs::fstat(&fd)?.st_mode) != FileType::RegularFile {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{:?} is not a regular file", name),
+            ));
+        }
+
+        rfs::fcntl_setfl(&fd, rfs::fcntl_getfl(&fd)? - OFlags::NONBLOCK)?;
+        Ok(fd.into())
+    }
+
+    /// Create `name`, failing if it already exists. The file is restricted to its owner, and the
+    /// mode it was created with, which respects the umask, is returned so it can be restored with
+    /// [set_mode]. The file is still empty while it has that mode.
+    pub(super) fn create_new_file(dir: &Dir, name: &str) -> io::Result<(File, FileMode)> {
+        let fd = rfs::openat(
+            dir,
+            name,
+            OFlags::RDWR | OFlags::CREATE | OFlags::EXCL | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+            Mode::from_raw_mode(FILE_MODE),
+        )?;
+        let mode = Mode::from_raw_mode(rfs::fstat(&fd)?.st_mode) & Mode::from_raw_mode(FILE_MODE);
+        rfs::fchmod(&fd, Mode::from_raw_mode(TEMP_FILE_MODE))?;
+        Ok((fd.into(), mode))
+    }
+
+    /// Change the mode of the regular file `name`, without following symlinks.
+    pub(super) fn set_mode(dir: &Dir, name: &str, mode: FileMode) -> io::Result<()> {
+        let file = open_file(dir, name)?;
+        Ok(rfs::fchmod(&file, mode)?)
+    }
+
+    /// Rename `from` to `to`. If `to` is a symlink, the symlink itself is replaced.
+    pub(super) fn rename(dir: &Dir, from: &str, to: &str) -> io::Result<()> {
+        Ok(rfs::renameat(dir, from, dir, to)?)
+    }
+
+    /// Remove `name`. If `name` is a symlink, the symlink itself is removed.
+    pub(super) fn remove_file(dir: &Dir, name: &str) -> io::Result<()> {
+        Ok(rfs::unlinkat(dir, name, AtFlags::empty())?)
+    }
+
+    pub(super) fn entries(dir: &Dir) -> io::Result<Vec<(String, EntryKind)>> {
+        let mut entries = vec![];
+        for entry in rfs::Dir::read_from(dir)? {
+            let entry = entry?;
+            let name = match entry.file_name().to_str() {
+                Ok(".") | Ok("..") => continue,
+                Ok(name) => name.to_string(),
+                Err(_) => {
+                    log::debug!("Skipping non-UTF-8 file name: {:?}", entry.file_name());
+                    continue;
+                }
+            };
+
+            let file_type = match entry.file_type() {
+                FileType::Unknown => FileType::from_raw_mode(
+                    rfs::statat(dir, name.as_str(), AtFlags::SYMLINK_NOFOLLOW)?.st_mode,
+                ),
+                file_type => file_type,
+            };
+
+            let kind = match file_type {
+                FileType::RegularFile => EntryKind::File,
+                FileType::Directory => EntryKind::Dir,
+                _ => EntryKind::Other,
+            };
+
+            entries.push((name, kind));
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use {
+        super::EntryKind,
+        std::{
+            fs::{self, File, OpenOptions},
+            io,
+            path::{Path, PathBuf},
+        },
+    };
+
+    pub(super) type Dir = PathBuf;
+    pub(super) type FileMode = ();
+
+    /// Fail if `path` is a symlink.
+    fn check_not_symlink(path: &Path) -> io::Result<()> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("refusing to follow symlink {:?}", path),
+            )),
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub(super) fn open_root(path: &Path) -> io::Result<Dir> {
+        if !fs::metadata(path)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{:?} is not a directory", path),
+            ));
+        }
+        Ok(path.to_path_buf())
+    }
+
+    pub(super) fn open_dir(dir: &Dir, name: &str) -> io::Result<Dir> {
+        let path = dir.join(name);
+        if !fs::symlink_metadata(&path)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{:?} is not a directory", path),
+            ));
+        }
+        Ok(path)
+    }
+
+    pub(super) fn create_dir(dir: &Dir, name: &str) -> io::Result<()> {
+        fs::create_dir(dir.join(name))
+    }
+
+    pub(super) fn open_file(dir: &Dir, name: &str) -> io::Result<File> {
+        let path = dir.join(name);
+        check_not_symlink(&path)?;
+        check_regular_file(File::open(&path)?, &path)
+    }
+
+    pub(super) fn open_or_create_file(dir: &Dir, name: &str) -> io::Result<File> {
+        let path = dir.join(name);
+        check_not_symlink(&path)?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        check_regular_file(file, &path)
+    }
+
+    /// Fail unless `file` is a regular file, rather than a device.
+    fn check_regular_file(file: File, path: &Path) -> io::Result<File> {
+        if !file.metadata()?.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{:?} is not a regular file", path),
+            ));
+        }
+        Ok(file)
+    }
+
+    pub(super) fn create_new_file(dir: &Dir, name: &str) -> io::Result<(File, FileMode)> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(dir.join(name))?;
+        Ok((file, ()))
+    }
+
+    /// Files keep the permissions they were created with.
+    pub(super) fn set_mode(_dir: &Dir, _name: &str, _mode: FileMode) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub(super) fn rename(dir: &Dir, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(dir.join(from), dir.join(to))
+    }
+
+    pub(super) fn remove_file(dir: &Dir, name: &str) -> io::Result<()> {
+        fs::remove_file(dir.join(name))
+    }
+
+    pub(super) fn entries(dir: &Dir) -> io::Result<Vec<(String, EntryKind)>> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(name) => {
+                    log::debug!("Skipping non-UTF-8 file name: {:?}", name);
+                    continue;
+                }
+            };
+
+            // `DirEntry::file_type` does not follow symlinks.
+            let file_type = entry.file_type()?;
+            let kind = if file_type.is_file() {
+                EntryKind::File
+            } else if file_type.is_dir() {
+                EntryKind::Dir
+            } else {
+                EntryKind::Other
+            };
+
+            entries.push((name, kind));
+        }
+
+        Ok(entries)
+    }
+}
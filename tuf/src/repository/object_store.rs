@@ -0,0 +1,1852 @@
+//! Repository implementation backed by an S3-compatible object store.
+
+use {
+    crate::{
+        clock::{Clock, SystemClock},
+        error::{Error, Result},
+        metadata::{
+            Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, SnapshotMetadata,
+            TargetPath, TimestampMetadata,
+        },
+        pouf::Pouf,
+        repository::{staging::StagedFile, CommitError, RepositoryProvider, RepositoryStorage},
+        util::SafeAsyncRead,
+    },
+    blocking::Unblock,
+    futures_io::AsyncRead,
+    futures_util::{
+        future::{BoxFuture, FutureExt},
+        io::AsyncReadExt,
+        lock::Mutex,
+    },
+    http::{header, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri},
+    percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC},
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+        marker::PhantomData,
+        sync::{Arc, RwLock},
+    },
+};
+
+mod sigv4;
+
+/// The default size of the parts of a multipart upload. Objects smaller than this are uploaded
+/// with a single request.
+const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+// Everything except the unreserved characters, as required by Signature Version 4.
+const URI_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// The body of a response from an [ObjectStoreClient].
+pub type ObjectStoreBody = Box<dyn AsyncRead + Send + Unpin>;
+
+/// An HTTP client used by [ObjectStoreRepository] to send requests to the object store.
+///
+/// This is implemented for [hyper::Client] when the `hyper` feature is enabled.
+pub trait ObjectStoreClient: Send + Sync {
+    /// Send `request` and return the response, without checking its status.
+    fn send(&self, request: Request<Vec<u8>>) -> BoxFuture<'_, Result<Response<ObjectStoreBody>>>;
+}
+
+impl<T: ObjectStoreClient + ?Sized> ObjectStoreClient for Arc<T> {
+    fn send(&self, request: Request<Vec<u8>>) -> BoxFuture<'_, Result<Response<ObjectStoreBody>>> {
+        (**self).send(request)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl<C> ObjectStoreClient for hyper::Client<C>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    fn send(&self, request: Request<Vec<u8>>) -> BoxFuture<'_, Result<Response<ObjectStoreBody>>> {
+        use futures_util::stream::TryStreamExt;
+
+        let uri = request.uri().to_string();
+        let response = self.request(request.map(hyper::Body::from));
+
+        async move {
+            let response = response.await.map_err(|err| Error::Hyper { uri, err })?;
+
+            Ok(response.map(|body| {
+                let body: ObjectStoreBody = Box::new(
+                    body.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+                        .into_async_read(),
+                );
+                body
+            }))
+        }
+        .boxed()
+    }
+}
+
+/// Credentials used to sign requests to the object store.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ObjectStoreCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl ObjectStoreCredentials {
+    /// Create new credentials from an access key pair.
+    pub fn new<A: Into<String>, S: Into<String>>(access_key_id: A, secret_access_key: S) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+
+    /// Set the session token for temporary credentials.
+    pub fn session_token<T: Into<String>>(mut self, session_token: T) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// The access key ID.
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+}
+
+impl fmt::Debug for ObjectStoreCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't leak the secrets into logs.
+        f.debug_struct("ObjectStoreCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A builder to create a repository stored in an S3-compatible object store.
+pub struct ObjectStoreRepositoryBuilder<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    client: C,
+    endpoint: Uri,
+    bucket: String,
+    region: String,
+    credentials: Option<ObjectStoreCredentials>,
+    virtual_hosted_style: bool,
+    metadata_prefix: Option<String>,
+    targets_prefix: Option<String>,
+    part_size: usize,
+    user_agent: Option<String>,
+    min_bytes_per_second: u32,
+    clock: Arc<dyn Clock>,
+    _pouf: PhantomData<D>,
+}
+
+impl<C, D> ObjectStoreRepositoryBuilder<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    /// Create a new repository stored in `bucket` of the object store at `endpoint`, such as
+    /// `https://s3.us-east-1.amazonaws.com` or `http://localhost:9000`.
+    pub fn new<B: Into<String>>(endpoint: Uri, bucket: B, client: C) -> Self {
+        ObjectStoreRepositoryBuilder {
+            client,
+            endpoint,
+            bucket: bucket.into(),
+            region: "us-east-1".into(),
+            credentials: None,
+            virtual_hosted_style: false,
+            metadata_prefix: None,
+            targets_prefix: None,
+            part_size: DEFAULT_PART_SIZE,
+            user_agent: None,
+            min_bytes_per_second: 4096,
+            clock: Arc::new(SystemClock),
+            _pouf: PhantomData,
+        }
+    }
+
+    /// The region used to sign requests. Defaults to `us-east-1`.
+    pub fn region<R: Into<String>>(mut self, region: R) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    /// Sign requests with `credentials`. If unset, requests are sent anonymously.
+    pub fn credentials(mut self, credentials: ObjectStoreCredentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Address the bucket as a subdomain of the endpoint, as in `https://bucket.s3.amazonaws.com/`,
+    /// rather than as the first component of the path, as in `https://s3.amazonaws.com/bucket/`.
+    /// Defaults to `false`, which is what most S3-compatible stores expect.
+    pub fn virtual_hosted_style(mut self, virtual_hosted_style: bool) -> Self {
+        self.virtual_hosted_style = virtual_hosted_style;
+        self
+    }
+
+    /// The argument `metadata_prefix` is used to provide an alternate key prefix where metadata is
+    /// stored in the bucket. If `None`, metadata is stored at the root of the bucket. For example,
+    /// passing `"meta"` would cause `root.json` to be stored with the key `meta/root.json`.
+    pub fn metadata_prefix<P: Into<String>>(mut self, metadata_prefix: P) -> Self {
+        self.metadata_prefix = Some(metadata_prefix.into());
+        self
+    }
+
+    /// The argument `targets_prefix` is used to provide an alternate key prefix where targets are
+    /// stored in the bucket. If `None`, targets are stored at the root of the bucket. For example,
+    /// passing `"targets"` would cause `hello-world` to be stored with the key
+    /// `targets/hello-world`.
+    pub fn targets_prefix<P: Into<String>>(mut self, targets_prefix: P) -> Self {
+        self.targets_prefix = Some(targets_prefix.into());
+        self
+    }
+
+    /// Objects of at least `part_size` bytes are uploaded in parts of this size with a multipart
+    /// upload. Defaults to 8 MiB. Note that S3 requires every part but the last to be at least
+    /// 5 MiB.
+    pub fn part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size.max(1);
+        self
+    }
+
+    /// Set the User-Agent prefix.
+    pub fn user_agent<T: Into<String>>(mut self, user_agent: T) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Set the minimum bytes per second for a read to be considered good.
+    pub fn min_bytes_per_second(mut self, min: u32) -> Self {
+        self.min_bytes_per_second = min;
+        self
+    }
+
+    /// Set the [Clock] used to date signed requests. Defaults to [SystemClock].
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Build an [ObjectStoreRepository].
+    pub fn build(self) -> ObjectStoreRepository<C, D> {
+        let user_agent = match self.user_agent {
+            Some(user_agent) => user_agent,
+            None => "rust-tuf".into(),
+        };
+
+        ObjectStoreRepository {
+            client: self.client,
+            endpoint: self.endpoint,
+            bucket: self.bucket,
+            region: self.region,
+            credentials: self.credentials,
+            virtual_hosted_style: self.virtual_hosted_style,
+            metadata_prefix: key_prefix(self.metadata_prefix),
+            targets_prefix: key_prefix(self.targets_prefix),
+            part_size: self.part_size,
+            user_agent,
+            min_bytes_per_second: self.min_bytes_per_second,
+            clock: self.clock,
+            version: RwLock::new(0),
+            write_lock: Mutex::new(()),
+            _pouf: PhantomData,
+        }
+    }
+}
+
+/// A repository stored in an S3-compatible object store, such as Amazon S3 or MinIO.
+///
+/// Requests are signed with [Signature Version 4] when credentials are provided. Objects of at
+/// least [ObjectStoreRepositoryBuilder::part_size] bytes are uploaded with multipart uploads.
+///
+/// Note: If metadata and targets share a prefix, [RepositoryStorage::list_targets] will include
+/// the metadata objects.
+///
+/// [Signature Version 4]: https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-authenticating-requests.html
+pub struct ObjectStoreRepository<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    client: C,
+    endpoint: Uri,
+    bucket: String,
+    region: String,
+    credentials: Option<ObjectStoreCredentials>,
+    virtual_hosted_style: bool,
+    metadata_prefix: String,
+    targets_prefix: String,
+    part_size: usize,
+    user_agent: String,
+    min_bytes_per_second: u32,
+    clock: Arc<dyn Clock>,
+    version: RwLock<u64>,
+    // Held while writing to the repository, to serialize writers with each other.
+    write_lock: Mutex<()>,
+    _pouf: PhantomData<D>,
+}
+
+impl<C, D> fmt::Debug for ObjectStoreRepository<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjectStoreRepository")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("credentials", &self.credentials)
+            .field("metadata_prefix", &self.metadata_prefix)
+            .field("targets_prefix", &self.targets_prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, D> ObjectStoreRepository<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    /// Create an [ObjectStoreRepositoryBuilder].
+    pub fn builder<B: Into<String>>(
+        endpoint: Uri,
+        bucket: B,
+        client: C,
+    ) -> ObjectStoreRepositoryBuilder<C, D> {
+        ObjectStoreRepositoryBuilder::new(endpoint, bucket, client)
+    }
+
+    /// Returns an [ObjectStoreBatchUpdate] for manipulating this repository. This allows callers
+    /// to stage a number of mutations, and optionally write them all at once.
+    ///
+    /// Staged targets are written to temporary files until the batch is committed, while staged
+    /// metadata is buffered in memory.
+    ///
+    /// [ObjectStoreBatchUpdate] detects conflicting writes from this process like
+    /// [FileSystemBatchUpdate](crate::repository::FileSystemBatchUpdate). Writes by other
+    /// publishers are detected with conditional requests, see [ObjectStoreBatchUpdate::commit].
+    pub fn batch_update(&self) -> ObjectStoreBatchUpdate<'_, C, D> {
+        ObjectStoreBatchUpdate {
+            initial_parent_version: *self.version.read().unwrap(),
+            parent_repo: self,
+            metadata: RwLock::new(HashMap::new()),
+            targets: RwLock::new(HashMap::new()),
+            removed_metadata: RwLock::new(HashSet::new()),
+            removed_targets: RwLock::new(HashSet::new()),
+            expected_timestamp_version: RwLock::new(None),
+            expected_snapshot_version: RwLock::new(None),
+        }
+    }
+
+    fn metadata_key(&self, meta_path: &MetadataPath, version: MetadataVersion) -> String {
+        format!(
+            "{}{}",
+            self.metadata_prefix,
+            meta_path.components::<D>(version).join("/")
+        )
+    }
+
+    fn target_key(&self, target_path: &TargetPath) -> String {
+        format!(
+            "{}{}",
+            self.targets_prefix,
+            target_path.components().join("/")
+        )
+    }
+
+    /// Returns the URI of the object `key`, which addresses the bucket itself if `key` is empty.
+    fn object_uri(&self, key: &str, query: &[(&str, &str)]) -> Result<Uri> {
+        let invalid_uri = || {
+            Error::IllegalArgument(format!(
+                "invalid object store URI: {}, {:?}, {:?}",
+                self.endpoint, self.bucket, key
+            ))
+        };
+
+        let scheme = self.endpoint.scheme_str().unwrap_or("https");
+        let authority = self.endpoint.authority().ok_or_else(invalid_uri)?.as_str();
+
+        let mut path = self.endpoint.path().trim_end_matches('/').to_string();
+        let authority = if self.virtual_hosted_style {
+            format!("{}.{}", self.bucket, authority)
+        } else {
+            path.push('/');
+            path.extend(utf8_percent_encode(&self.bucket, URI_ENCODE));
+            authority.to_string()
+        };
+
+        path.push('/');
+        let segments = key
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, URI_ENCODE).to_string())
+            .collect::<Vec<_>>();
+        path.push_str(&segments.join("/"));
+
+        let query = query
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(name, URI_ENCODE),
+                    utf8_percent_encode(value, URI_ENCODE)
+                )
+            })
+            .collect::<Vec<_>>();
+        if !query.is_empty() {
+            path.push('?');
+            path.push_str(&query.join("&"));
+        }
+
+        format!("{}://{}{}", scheme, authority, path)
+            .parse()
+            .map_err(|_| invalid_uri())
+    }
+
+    /// Sign and send a request.
+    async fn send(
+        &self,
+        method: Method,
+        uri: &Uri,
+        headers: &[(HeaderName, String)],
+        body: Vec<u8>,
+    ) -> Result<Response<ObjectStoreBody>> {
+        let mut builder = Request::builder().method(method).uri(uri.clone());
+        for (name, value) in headers {
+            builder = builder.header(name, value.as_str());
+        }
+        let mut request = builder.body(body).map_err(|err| Error::Http {
+            uri: uri.to_string(),
+            err,
+        })?;
+
+        if let Some(credentials) = &self.credentials {
+            sigv4::sign(&mut request, credentials, &self.region, self.clock.now()?)?;
+        }
+
+        // The User-Agent isn't signed, since proxies may modify it.
+        let user_agent = HeaderValue::from_str(&self.user_agent).map_err(|_| {
+            Error::IllegalArgument(format!("invalid user agent: {:?}", self.user_agent))
+        })?;
+        request.headers_mut().insert(header::USER_AGENT, user_agent);
+
+        self.client.send(request).await
+    }
+
+    /// Fetch the object `key`, or `None` if it doesn't exist.
+    async fn get_object(&self, key: &str) -> Result<Option<Response<ObjectStoreBody>>> {
+        let uri = self.object_uri(key, &[])?;
+        let response = self.send(Method::GET, &uri, &[], vec![]).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response)),
+            code => Err(Error::BadHttpStatus {
+                uri: uri.to_string(),
+                code,
+            }),
+        }
+    }
+
+    fn fetch_object<'a>(
+        &'a self,
+        key: String,
+        not_found: Error,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        async move {
+            match self.get_object(&key).await? {
+                Some(response) => {
+                    let reader = response
+                        .into_body()
+                        .enforce_minimum_bitrate(self.min_bytes_per_second);
+                    let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(reader);
+                    Ok(reader)
+                }
+                None => Err(not_found),
+            }
+        }
+        .boxed()
+    }
+
+    /// Read the object `key` and its entity tag, or `None` if it doesn't exist.
+    async fn read_object(&self, key: &str) -> Result<Option<(Vec<u8>, Option<String>)>> {
+        let response = match self.get_object(key).await? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+
+        let mut bytes = vec![];
+        response.into_body().read_to_end(&mut bytes).await?;
+
+        Ok(Some((bytes, etag)))
+    }
+
+    /// Upload `read` to the object `key`, with a multipart upload if it's at least `part_size`
+    /// bytes. The `conditions` headers are sent with the request that creates the object.
+    async fn upload(
+        &self,
+        key: &str,
+        read: &mut (dyn AsyncRead + Send + Unpin),
+        conditions: &[(HeaderName, String)],
+    ) -> Result<()> {
+        let part = read_part(read, self.part_size).await?;
+        if part.len() < self.part_size {
+            return self.put_object(key, part, conditions).await;
+        }
+
+        let upload_id = self.create_multipart_upload(key).await?;
+        let res = self
+            .upload_parts(key, &upload_id, part, read, conditions)
+            .await;
+
+        if res.is_err() {
+            // Clean up the parts, which are otherwise retained by the store.
+            let _ = self.abort_multipart_upload(key, &upload_id).await;
+        }
+
+        res
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        conditions: &[(HeaderName, String)],
+    ) -> Result<()> {
+        let uri = self.object_uri(key, &[])?;
+        let response = self.send(Method::PUT, &uri, conditions, body).await?;
+        check_status(&uri, &response)
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String> {
+        let uri = self.object_uri(key, &[("uploads", "")])?;
+        let response = self.send(Method::POST, &uri, &[], vec![]).await?;
+        check_status(&uri, &response)?;
+
+        let body = read_xml(response).await?;
+        xml_elements(&body, "UploadId")
+            .first()
+            .map(|upload_id| xml_unescape(upload_id))
+            .ok_or_else(|| Error::Opaque(format!("missing UploadId in response from {}", uri)))
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        mut part: Vec<u8>,
+        read: &mut (dyn AsyncRead + Send + Unpin),
+        conditions: &[(HeaderName, String)],
+    ) -> Result<()> {
+        let mut etags = vec![];
+        while !part.is_empty() {
+            let part_number = (etags.len() + 1).to_string();
+            let uri = self.object_uri(
+                key,
+                &[("partNumber", &part_number), ("uploadId", upload_id)],
+            )?;
+            let response = self.send(Method::PUT, &uri, &[], part).await?;
+            check_status(&uri, &response)?;
+
+            let etag = response
+                .headers()
+                .get(header::ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .ok_or_else(|| Error::Opaque(format!("missing ETag in response from {}", uri)))?;
+            etags.push(etag.to_string());
+
+            part = read_part(read, self.part_size).await?;
+        }
+
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in etags.iter().enumerate() {
+            body.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                i + 1,
+                xml_escape(etag)
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+
+        let uri = self.object_uri(key, &[("uploadId", upload_id)])?;
+        let response = self
+            .send(Method::POST, &uri, conditions, body.into_bytes())
+            .await?;
+        check_status(&uri, &response)?;
+
+        // Completing an upload can fail after the store has started responding successfully, in
+        // which case the error is reported in the body.
+        let body = read_xml(response).await?;
+        if let Some(error) = xml_elements(&body, "Error").first() {
+            return Err(match xml_elements(error, "Code").first() {
+                Some(&"PreconditionFailed") => Error::BadHttpStatus {
+                    uri: uri.to_string(),
+                    code: StatusCode::PRECONDITION_FAILED,
+                },
+                code => Error::Opaque(format!(
+                    "failed to complete multipart upload to {}: {}",
+                    uri,
+                    code.map_or("unknown error", |code| code)
+                )),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<()> {
+        let uri = self.object_uri(key, &[("uploadId", upload_id)])?;
+        let response = self.send(Method::DELETE, &uri, &[], vec![]).await?;
+        check_status(&uri, &response)
+    }
+
+    /// Delete the object `key`. Deleting an object that doesn't exist is not an error.
+    async fn delete_object(&self, key: &str) -> Result<()> {
+        let uri = self.object_uri(key, &[])?;
+        let response = self.send(Method::DELETE, &uri, &[], vec![]).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            Ok(())
+        } else {
+            check_status(&uri, &response)
+        }
+    }
+
+    /// List the keys of every object that starts with `prefix`.
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = vec![];
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &continuation_token {
+                query.push(("continuation-token", token.as_str()));
+            }
+
+            let uri = self.object_uri("", &query)?;
+            let response = self.send(Method::GET, &uri, &[], vec![]).await?;
+            check_status(&uri, &response)?;
+            let body = read_xml(response).await?;
+
+            for contents in xml_elements(&body, "Contents") {
+                if let Some(key) = xml_elements(contents, "Key").first() {
+                    keys.push(xml_unescape(key));
+                }
+            }
+
+            continuation_token = match xml_elements(&body, "IsTruncated").first() {
+                Some(&"true") => xml_elements(&body, "NextContinuationToken")
+                    .first()
+                    .map(|token| xml_unescape(token)),
+                _ => None,
+            };
+            if continuation_token.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Store `read` at `key`, and increment the repository version.
+    async fn store_object(
+        &self,
+        key: &str,
+        read: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<()> {
+        let _write_lock = self.write_lock.lock().await;
+        self.upload(key, read, &[]).await?;
+
+        // Increment our version since the repository changed.
+        *self.version.write().unwrap() += 1;
+
+        Ok(())
+    }
+
+    /// Remove the object at `key`, and increment the repository version.
+    async fn remove_object(&self, key: &str) -> Result<()> {
+        let _write_lock = self.write_lock.lock().await;
+        self.delete_object(key).await?;
+
+        // Increment our version since the repository changed.
+        *self.version.write().unwrap() += 1;
+
+        Ok(())
+    }
+
+    async fn list_metadata_in_store(&self) -> Result<Vec<(MetadataPath, MetadataVersion)>> {
+        let mut metadata = self
+            .list_keys(&self.metadata_prefix)
+            .await?
+            .iter()
+            .filter_map(|key| {
+                let components = key[self.metadata_prefix.len()..]
+                    .split('/')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                super::parse_metadata_components::<D>(&components)
+            })
+            .collect::<Vec<_>>();
+        metadata.sort();
+        Ok(metadata)
+    }
+
+    async fn list_targets_in_store(&self) -> Result<Vec<TargetPath>> {
+        let mut targets = self
+            .list_keys(&self.targets_prefix)
+            .await?
+            .iter()
+            .filter_map(|key| TargetPath::new(&key[self.targets_prefix.len()..]).ok())
+            .collect::<Vec<_>>();
+        targets.sort();
+        Ok(targets)
+    }
+}
+
+impl<C, D> RepositoryProvider<D> for ObjectStoreRepository<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let not_found = Error::MetadataNotFound {
+            path: meta_path.clone(),
+            version,
+        };
+        self.fetch_object(self.metadata_key(meta_path, version), not_found)
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let not_found = Error::TargetNotFound(target_path.clone());
+        self.fetch_object(self.target_key(target_path), not_found)
+    }
+}
+
+impl<C, D> RepositoryStorage<D> for ObjectStoreRepository<C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    fn store_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        metadata: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = self.metadata_key(meta_path, version);
+        async move { self.store_object(&key, metadata).await }.boxed()
+    }
+
+    fn store_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = self.target_key(target_path);
+        async move { self.store_object(&key, read).await }.boxed()
+    }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = self.metadata_key(meta_path, version);
+        async move { self.remove_object(&key).await }.boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let key = self.target_key(target_path);
+        async move { self.remove_object(&key).await }.boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        self.list_metadata_in_store().boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.list_targets_in_store().boxed()
+    }
+}
+
+/// [ObjectStoreBatchUpdate] is a special repository that is designed to write the metadata and
+/// targets to an [ObjectStoreRepository] in a single batch.
+///
+/// Note: `ObjectStoreBatchUpdate::commit()` must be called in order to write the metadata and
+/// targets to the [ObjectStoreRepository]. Otherwise any queued changes will be lost on drop.
+#[derive(Debug)]
+pub struct ObjectStoreBatchUpdate<'a, C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    initial_parent_version: u64,
+    parent_repo: &'a ObjectStoreRepository<C, D>,
+    metadata: RwLock<HashMap<(MetadataPath, MetadataVersion), Vec<u8>>>,
+    targets: RwLock<HashMap<TargetPath, StagedFile>>,
+    removed_metadata: RwLock<HashSet<(MetadataPath, MetadataVersion)>>,
+    removed_targets: RwLock<HashSet<TargetPath>>,
+    // `None` if unchecked, and `Some(None)` if the metadata must not exist.
+    expected_timestamp_version: RwLock<Option<Option<u32>>>,
+    expected_snapshot_version: RwLock<Option<Option<u32>>>,
+}
+
+impl<'a, C, D> ObjectStoreBatchUpdate<'a, C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    /// Write all the metadata and targets the [ObjectStoreBatchUpdate] to the source
+    /// [ObjectStoreRepository] in a single batch operation.
+    ///
+    /// Targets are written first, then metadata, with `snapshot.json` and `timestamp.json` last,
+    /// so clients never see metadata that refers to objects that haven't been written yet.
+    /// Removals happen after everything else is written.
+    ///
+    /// `snapshot.json` and `timestamp.json` are written with conditional requests, which fail
+    /// with [CommitError::Conflict] if another publisher changed them after this commit read
+    /// them. This fails with [CommitError::UnexpectedVersion] if they don't have the versions
+    /// set by [RepositoryStorage::expect_metadata_versions].
+    ///
+    /// Note: Object stores don't support transactions, so it's possible that this could fail with
+    /// part of the objects written.
+    pub async fn commit(self) -> std::result::Result<(), CommitError> {
+        let parent_repo = self.parent_repo;
+        let _write_lock = parent_repo.write_lock.lock().await;
+
+        if self.initial_parent_version != *parent_repo.version.read().unwrap() {
+            return Err(CommitError::Conflict);
+        }
+
+        let metadata = self.metadata.into_inner().unwrap();
+        let targets = self.targets.into_inner().unwrap();
+        let removed_metadata = self.removed_metadata.into_inner().unwrap();
+        let removed_targets = self.removed_targets.into_inner().unwrap();
+
+        let timestamp_key = (MetadataPath::timestamp(), MetadataVersion::None);
+        let snapshot_key = (MetadataPath::snapshot(), MetadataVersion::None);
+
+        let timestamp_condition = check_version::<C, D, TimestampMetadata>(
+            parent_repo,
+            &timestamp_key.0,
+            self.expected_timestamp_version.into_inner().unwrap(),
+            metadata.contains_key(&timestamp_key) || removed_metadata.contains(&timestamp_key),
+        )
+        .await?;
+        let snapshot_condition = check_version::<C, D, SnapshotMetadata>(
+            parent_repo,
+            &snapshot_key.0,
+            self.expected_snapshot_version.into_inner().unwrap(),
+            metadata.contains_key(&snapshot_key) || removed_metadata.contains(&snapshot_key),
+        )
+        .await?;
+
+        for (target_path, staged) in targets {
+            let mut read = Unblock::new(staged.open().await?);
+            parent_repo
+                .upload(&parent_repo.target_key(&target_path), &mut read, &[])
+                .await?;
+        }
+
+        let mut metadata = metadata.into_iter().collect::<Vec<_>>();
+        metadata.sort_by_key(|(key, _)| {
+            if *key == timestamp_key {
+                2
+            } else if *key == snapshot_key {
+                1
+            } else {
+                0
+            }
+        });
+
+        for (key, bytes) in metadata {
+            let conditions = if key == timestamp_key {
+                &timestamp_condition[..]
+            } else if key == snapshot_key {
+                &snapshot_condition[..]
+            } else {
+                &[]
+            };
+
+            parent_repo
+                .upload(
+                    &parent_repo.metadata_key(&key.0, key.1),
+                    &mut &bytes[..],
+                    conditions,
+                )
+                .await
+                .map_err(|err| match err {
+                    Error::BadHttpStatus {
+                        code: StatusCode::PRECONDITION_FAILED,
+                        ..
+                    } => CommitError::Conflict,
+                    err => CommitError::Storage(err),
+                })?;
+        }
+
+        for (meta_path, version) in removed_metadata {
+            parent_repo
+                .delete_object(&parent_repo.metadata_key(&meta_path, version))
+                .await?;
+        }
+
+        for target_path in removed_targets {
+            parent_repo
+                .delete_object(&parent_repo.target_key(&target_path))
+                .await?;
+        }
+
+        // Increment the version because we wrote to it.
+        *parent_repo.version.write().unwrap() += 1;
+
+        Ok(())
+    }
+}
+
+/// Check the version of the unversioned metadata `meta_path` against `expected`. If `conditional`
+/// is set, returns the headers that make a write to `meta_path` fail if it changed since it was
+/// read.
+async fn check_version<C, D, M>(
+    repo: &ObjectStoreRepository<C, D>,
+    meta_path: &MetadataPath,
+    expected: Option<Option<u32>>,
+    conditional: bool,
+) -> std::result::Result<Vec<(HeaderName, String)>, CommitError>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+    M: Metadata,
+{
+    if expected.is_none() && !conditional {
+        return Ok(vec![]);
+    }
+
+    let (actual, etag) = match repo
+        .read_object(&repo.metadata_key(meta_path, MetadataVersion::None))
+        .await?
+    {
+        Some((bytes, etag)) => {
+            let version = RawSignedMetadata::<D, M>::new(bytes)
+                .parse_untrusted()?
+                .parse_version_untrusted()?;
+            (Some(version), etag)
+        }
+        None => (None, None),
+    };
+
+    if let Some(expected) = expected {
+        if actual != expected {
+            return Err(CommitError::UnexpectedVersion {
+                path: meta_path.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+
+    Ok(match (actual, etag) {
+        (None, _) => vec![(header::IF_NONE_MATCH, "*".into())],
+        (Some(_), Some(etag)) => vec![(header::IF_MATCH, etag)],
+        // Without an entity tag the write can't be made conditional.
+        (Some(_), None) => vec![],
+    })
+}
+
+impl<C, D> RepositoryProvider<D> for ObjectStoreBatchUpdate<'_, C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let key = (meta_path.clone(), version);
+        if let Some(bytes) = self.metadata.read().unwrap().get(&key) {
+            let reader: Box<dyn AsyncRead + Send + Unpin> =
+                Box::new(futures_util::io::Cursor::new(bytes.clone()));
+            async move { Ok(reader) }.boxed()
+        } else if self.removed_metadata.read().unwrap().contains(&key) {
+            let err = Error::MetadataNotFound {
+                path: meta_path.clone(),
+                version,
+            };
+            async move { Err(err) }.boxed()
+        } else {
+            self.parent_repo.fetch_metadata(meta_path, version)
+        }
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        if let Some(staged) = self.targets.read().unwrap().get(target_path).cloned() {
+            async move {
+                let reader: Box<dyn AsyncRead + Send + Unpin> =
+                    Box::new(Unblock::new(staged.open().await?));
+                Ok(reader)
+            }
+            .boxed()
+        } else if self.removed_targets.read().unwrap().contains(target_path) {
+            let err = Error::TargetNotFound(target_path.clone());
+            async move { Err(err) }.boxed()
+        } else {
+            self.parent_repo.fetch_target(target_path)
+        }
+    }
+}
+
+impl<C, D> RepositoryStorage<D> for ObjectStoreBatchUpdate<'_, C, D>
+where
+    C: ObjectStoreClient,
+    D: Pouf,
+{
+    fn store_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = (meta_path.clone(), version);
+
+        async move {
+            let mut bytes = vec![];
+            read.read_to_end(&mut bytes).await?;
+            self.removed_metadata.write().unwrap().remove(&key);
+            self.metadata.write().unwrap().insert(key, bytes);
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn store_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let target_path = target_path.clone();
+
+        async move {
+            let staged = StagedFile::new(read).await?;
+            self.removed_targets.write().unwrap().remove(&target_path);
+            self.targets.write().unwrap().insert(target_path, staged);
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = (meta_path.clone(), version);
+        self.metadata.write().unwrap().remove(&key);
+        self.removed_metadata.write().unwrap().insert(key);
+        async move { Ok(()) }.boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.targets.write().unwrap().remove(target_path);
+        self.removed_targets
+            .write()
+            .unwrap()
+            .insert(target_path.clone());
+        async move { Ok(()) }.boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        async move {
+            let metadata = self.parent_repo.list_metadata_in_store().await?;
+            let removed = self.removed_metadata.read().unwrap();
+            let mut metadata = metadata
+                .into_iter()
+                .filter(|key| !removed.contains(key))
+                .chain(self.metadata.read().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            metadata.sort();
+            metadata.dedup();
+            Ok(metadata)
+        }
+        .boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        async move {
+            let targets = self.parent_repo.list_targets_in_store().await?;
+            let removed = self.removed_targets.read().unwrap();
+            let mut targets = targets
+                .into_iter()
+                .filter(|target_path| !removed.contains(target_path))
+                .chain(self.targets.read().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            targets.sort();
+            targets.dedup();
+            Ok(targets)
+        }
+        .boxed()
+    }
+
+    fn expect_metadata_versions(
+        &self,
+        timestamp_version: Option<u32>,
+        snapshot_version: Option<u32>,
+    ) {
+        *self.expected_timestamp_version.write().unwrap() = Some(timestamp_version);
+        *self.expected_snapshot_version.write().unwrap() = Some(snapshot_version);
+    }
+}
+
+/// Normalize a key prefix so it's either empty, or ends with a single `/`.
+fn key_prefix(prefix: Option<String>) -> String {
+    match prefix.as_deref().map(|prefix| prefix.trim_matches('/')) {
+        Some(prefix) if !prefix.is_empty() => format!("{}/", prefix),
+        _ => String::new(),
+    }
+}
+
+fn check_status<B>(uri: &Uri, response: &Response<B>) -> Result<()> {
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(Error::BadHttpStatus {
+            uri: uri.to_string(),
+            code: response.status(),
+        })
+    }
+}
+
+/// Read up to `size` bytes from `read`, returning fewer only if `read` is exhausted.
+async fn read_part(read: &mut (dyn AsyncRead + Send + Unpin), size: usize) -> Result<Vec<u8>> {
+    let mut part = vec![];
+    read.take(size as u64).read_to_end(&mut part).await?;
+    Ok(part)
+}
+
+async fn read_xml(response: Response<ObjectStoreBody>) -> Result<String> {
+    let mut body = String::new();
+    response.into_body().read_to_string(&mut body).await?;
+    Ok(body)
+}
+
+/// Returns the contents of every `<tag>` element in `xml`. This only understands the simple
+/// documents returned by S3, where the elements of interest don't have attributes.
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+
+    let mut elements = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        match rest.find(&close) {
+            Some(end) => {
+                elements.push(&rest[..end]);
+                rest = &rest[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    elements
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn xml_unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let c = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use {
+        super::*,
+        crate::crypto::Ed25519PrivateKey,
+        crate::pouf::Pouf1,
+        crate::repo_builder::RepoBuilder,
+        crate::repository::{fetch_metadata_to_string, fetch_target_to_string},
+        assert_matches::assert_matches,
+        futures_executor::block_on,
+        futures_util::io::Cursor,
+        percent_encoding::percent_decode_str,
+        std::collections::BTreeMap,
+    };
+
+    const BUCKET: &str = "tuf";
+
+    fn credentials() -> ObjectStoreCredentials {
+        ObjectStoreCredentials::new("minio", "minio-secret")
+    }
+
+    #[derive(Default)]
+    struct State {
+        objects: BTreeMap<String, (Vec<u8>, String)>,
+        uploads: HashMap<String, (String, BTreeMap<u32, Vec<u8>>)>,
+        next_upload_id: u64,
+        completed_uploads: usize,
+        // Objects written by another publisher just before a conditional write to their key.
+        races: HashMap<String, Vec<u8>>,
+    }
+
+    /// An in-memory stand-in for an S3-compatible object store like MinIO, which supports the
+    /// subset of the S3 API used by [ObjectStoreRepository] and checks request signatures.
+    #[derive(Clone, Default)]
+    pub(crate) struct MockObjectStore {
+        state: Arc<std::sync::Mutex<State>>,
+    }
+
+    impl MockObjectStore {
+        fn object(&self, key: &str) -> Option<Vec<u8>> {
+            let state = self.state.lock().unwrap();
+            state.objects.get(key).map(|(bytes, _)| bytes.clone())
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.state.lock().unwrap().objects.keys().cloned().collect()
+        }
+
+        fn completed_uploads(&self) -> usize {
+            self.state.lock().unwrap().completed_uploads
+        }
+
+        /// Simulate another publisher writing `bytes` to `key` right before our next conditional
+        /// write to it.
+        fn race_conditional_write(&self, key: &str, bytes: &[u8]) {
+            let mut state = self.state.lock().unwrap();
+            state.races.insert(key.into(), bytes.to_vec());
+        }
+
+        fn handle(&self, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+            if !sigv4::verify(request, &credentials(), "us-east-1") {
+                return response(StatusCode::FORBIDDEN, error_body("SignatureDoesNotMatch"));
+            }
+
+            let path = percent_decode_str(request.uri().path())
+                .decode_utf8()
+                .unwrap()
+                .into_owned();
+            let key = match path.strip_prefix(&format!("/{}/", BUCKET)) {
+                Some(key) => key.to_string(),
+                None => return response(StatusCode::NOT_FOUND, error_body("NoSuchBucket")),
+            };
+            let query = request
+                .uri()
+                .query()
+                .unwrap_or("")
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    let value = percent_decode_str(value).decode_utf8().unwrap();
+                    (name.to_string(), value.into_owned())
+                })
+                .collect::<HashMap<_, _>>();
+
+            let mut state = self.state.lock().unwrap();
+            let method = request.method().clone();
+            match (method, key.is_empty()) {
+                (Method::GET, true) if query.get("list-type").map(String::as_str) == Some("2") => {
+                    list_objects(&state, &query)
+                }
+                (Method::GET, false) => match state.objects.get(&key) {
+                    Some((bytes, etag)) => {
+                        let mut response = response(StatusCode::OK, bytes.clone());
+                        response
+                            .headers_mut()
+                            .insert(header::ETAG, etag.parse().unwrap());
+                        response
+                    }
+                    None => response(StatusCode::NOT_FOUND, error_body("NoSuchKey")),
+                },
+                (Method::PUT, false) => {
+                    if let (Some(part_number), Some(upload_id)) =
+                        (query.get("partNumber"), query.get("uploadId"))
+                    {
+                        let part = request.body().clone();
+                        let etag = etag(&part);
+                        match state.uploads.get_mut(upload_id) {
+                            Some((upload_key, parts)) if *upload_key == key => {
+                                parts.insert(part_number.parse().unwrap(), part);
+                            }
+                            _ => {
+                                return response(StatusCode::NOT_FOUND, error_body("NoSuchUpload"))
+                            }
+                        }
+                        let mut response = response(StatusCode::OK, vec![]);
+                        response
+                            .headers_mut()
+                            .insert(header::ETAG, etag.parse().unwrap());
+                        return response;
+                    }
+
+                    if !check_conditions(&mut state, request, &key) {
+                        return response(
+                            StatusCode::PRECONDITION_FAILED,
+                            error_body("PreconditionFailed"),
+                        );
+                    }
+                    let bytes = request.body().clone();
+                    let etag = etag(&bytes);
+                    state.objects.insert(key, (bytes, etag));
+                    response(StatusCode::OK, vec![])
+                }
+                (Method::POST, false) if query.contains_key("uploads") => {
+                    state.next_upload_id += 1;
+                    let upload_id = format!("upload-{}", state.next_upload_id);
+                    state
+                        .uploads
+                        .insert(upload_id.clone(), (key, BTreeMap::new()));
+                    response(
+                        StatusCode::OK,
+                        format!(
+                            "<InitiateMultipartUploadResult><Bucket>{}</Bucket>\
+                             <UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+                            BUCKET, upload_id
+                        )
+                        .into_bytes(),
+                    )
+                }
+                (Method::POST, false) if query.contains_key("uploadId") => {
+                    let upload_id = &query["uploadId"];
+                    if !state.uploads.contains_key(upload_id) {
+                        return response(StatusCode::NOT_FOUND, error_body("NoSuchUpload"));
+                    }
+                    if !check_conditions(&mut state, request, &key) {
+                        // Like S3, report the failure in the body of a successful response.
+                        return response(StatusCode::OK, error_body("PreconditionFailed"));
+                    }
+
+                    let (_, parts) = state.uploads.remove(upload_id).unwrap();
+                    let body = String::from_utf8(request.body().clone()).unwrap();
+                    let mut bytes = vec![];
+                    for part in xml_elements(&body, "Part") {
+                        let part_number = xml_elements(part, "PartNumber")[0].parse().unwrap();
+                        let part_etag = xml_unescape(xml_elements(part, "ETag")[0]);
+                        match parts.get(&part_number) {
+                            Some(part) if etag(part) == part_etag => bytes.extend(part),
+                            _ => {
+                                return response(StatusCode::BAD_REQUEST, error_body("InvalidPart"))
+                            }
+                        }
+                    }
+
+                    let etag = etag(&bytes);
+                    state.objects.insert(key, (bytes, etag));
+                    state.completed_uploads += 1;
+                    response(
+                        StatusCode::OK,
+                        b"<CompleteMultipartUploadResult></CompleteMultipartUploadResult>".to_vec(),
+                    )
+                }
+                (Method::DELETE, false) => {
+                    if let Some(upload_id) = query.get("uploadId") {
+                        state.uploads.remove(upload_id);
+                    } else {
+                        state.objects.remove(&key);
+                    }
+                    response(StatusCode::NO_CONTENT, vec![])
+                }
+                _ => response(StatusCode::NOT_IMPLEMENTED, error_body("NotImplemented")),
+            }
+        }
+    }
+
+    impl ObjectStoreClient for MockObjectStore {
+        fn send(
+            &self,
+            request: Request<Vec<u8>>,
+        ) -> BoxFuture<'_, Result<Response<ObjectStoreBody>>> {
+            let response = self.handle(&request).map(|body| {
+                let body: ObjectStoreBody = Box::new(Cursor::new(body));
+                body
+            });
+            async move { Ok(response) }.boxed()
+        }
+    }
+
+    fn response(status: StatusCode, body: Vec<u8>) -> Response<Vec<u8>> {
+        Response::builder().status(status).body(body).unwrap()
+    }
+
+    fn error_body(code: &str) -> Vec<u8> {
+        format!("<Error><Code>{}</Code></Error>", code).into_bytes()
+    }
+
+    fn etag(bytes: &[u8]) -> String {
+        format!("\"{}\"", &sigv4::hex_sha256(bytes)[..32])
+    }
+
+    fn check_conditions(state: &mut State, request: &Request<Vec<u8>>, key: &str) -> bool {
+        let if_match = request.headers().get(header::IF_MATCH);
+        let if_none_match = request.headers().get(header::IF_NONE_MATCH);
+
+        if if_match.is_some() || if_none_match.is_some() {
+            if let Some(bytes) = state.races.remove(key) {
+                let etag = etag(&bytes);
+                state.objects.insert(key.into(), (bytes, etag));
+            }
+        }
+
+        let current = state.objects.get(key).map(|(_, etag)| etag.as_str());
+        if let Some(if_match) = if_match {
+            if current != Some(if_match.to_str().unwrap()) {
+                return false;
+            }
+        }
+        if if_none_match.is_some() && current.is_some() {
+            return false;
+        }
+        true
+    }
+
+    fn list_objects(state: &State, query: &HashMap<String, String>) -> Response<Vec<u8>> {
+        // Use small pages to exercise pagination.
+        const MAX_KEYS: usize = 2;
+
+        let prefix = query.get("prefix").map(String::as_str).unwrap_or("");
+        let start_after = query.get("continuation-token");
+        let keys = state
+            .objects
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .filter(|key| start_after.map_or(true, |start_after| *key > start_after))
+            .collect::<Vec<_>>();
+
+        let mut body = String::from("<ListBucketResult>");
+        for key in keys.iter().take(MAX_KEYS) {
+            body.push_str(&format!(
+                "<Contents><Key>{}</Key></Contents>",
+                xml_escape(key)
+            ));
+        }
+        if keys.len() > MAX_KEYS {
+            body.push_str(&format!(
+                "<IsTruncated>true</IsTruncated><NextContinuationToken>{}</NextContinuationToken>",
+                xml_escape(keys[MAX_KEYS - 1])
+            ));
+        } else {
+            body.push_str("<IsTruncated>false</IsTruncated>");
+        }
+        body.push_str("</ListBucketResult>");
+
+        response(StatusCode::OK, body.into_bytes())
+    }
+
+    fn builder(store: &MockObjectStore) -> ObjectStoreRepositoryBuilder<MockObjectStore, Pouf1> {
+        ObjectStoreRepositoryBuilder::new(
+            "http://localhost:9000".parse().unwrap(),
+            BUCKET,
+            store.clone(),
+        )
+        .credentials(credentials())
+        .metadata_prefix("metadata")
+        .targets_prefix("targets")
+    }
+
+    /// A repository stored in a new [MockObjectStore], for tests of other modules.
+    pub(crate) fn mock_repository() -> ObjectStoreRepository<MockObjectStore, Pouf1> {
+        builder(&MockObjectStore::default()).build()
+    }
+
+    #[test]
+    fn object_store_repo_store_fetch_list_and_remove() {
+        block_on(async {
+            let store = MockObjectStore::default();
+            let repo = builder(&store).build();
+
+            let meta_path = MetadataPath::new("foo/bar").unwrap();
+            let target_path = TargetPath::new("a dir/b&c").unwrap();
+
+            assert_matches!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::None).await,
+                Err(Error::MetadataNotFound { path, version: MetadataVersion::None })
+                if path == meta_path
+            );
+            assert_matches!(
+                fetch_target_to_string(&repo, &target_path).await,
+                Err(Error::TargetNotFound(path))
+                if path == target_path
+            );
+
+            for version in [MetadataVersion::None, MetadataVersion::Number(1)] {
+                repo.store_metadata(&meta_path, version, &mut "meta".as_bytes())
+                    .await
+                    .unwrap();
+            }
+            repo.store_metadata(
+                &MetadataPath::root(),
+                MetadataVersion::Number(1),
+                &mut "root".as_bytes(),
+            )
+            .await
+            .unwrap();
+            for target in ["a dir/b&c", "d", "e/f"] {
+                repo.store_target(&TargetPath::new(target).unwrap(), &mut target.as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            assert_eq!(
+                store.keys(),
+                vec![
+                    "metadata/1.root.json",
+                    "metadata/foo/1.bar.json",
+                    "metadata/foo/bar.json",
+                    "targets/a dir/b&c",
+                    "targets/d",
+                    "targets/e/f",
+                ]
+            );
+            assert_eq!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::Number(1))
+                    .await
+                    .unwrap(),
+                "meta"
+            );
+            assert_eq!(
+                fetch_target_to_string(&repo, &target_path).await.unwrap(),
+                "a dir/b&c"
+            );
+
+            // Listing spans multiple pages.
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![
+                    (meta_path.clone(), MetadataVersion::None),
+                    (meta_path.clone(), MetadataVersion::Number(1)),
+                    (MetadataPath::root(), MetadataVersion::Number(1)),
+                ]
+            );
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![
+                    TargetPath::new("a dir/b&c").unwrap(),
+                    TargetPath::new("d").unwrap(),
+                    TargetPath::new("e/f").unwrap(),
+                ]
+            );
+
+            repo.remove_metadata(&meta_path, MetadataVersion::None)
+                .await
+                .unwrap();
+            repo.remove_target(&target_path).await.unwrap();
+            // Removing objects that don't exist is not an error.
+            repo.remove_target(&target_path).await.unwrap();
+
+            assert_eq!(
+                store.keys(),
+                vec![
+                    "metadata/1.root.json",
+                    "metadata/foo/1.bar.json",
+                    "targets/d",
+                    "targets/e/f",
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn object_store_repo_uses_multipart_uploads_for_large_objects() {
+        block_on(async {
+            let store = MockObjectStore::default();
+            let repo = builder(&store).part_size(10).build();
+
+            let small = b"012345678".to_vec();
+            let large = (0..95u8).collect::<Vec<_>>();
+            repo.store_target(&TargetPath::new("small").unwrap(), &mut &small[..])
+                .await
+                .unwrap();
+            assert_eq!(store.completed_uploads(), 0);
+
+            repo.store_target(&TargetPath::new("large").unwrap(), &mut &large[..])
+                .await
+                .unwrap();
+            assert_eq!(store.completed_uploads(), 1);
+
+            assert_eq!(store.object("targets/small"), Some(small));
+            assert_eq!(store.object("targets/large"), Some(large));
+            assert!(store.state.lock().unwrap().uploads.is_empty());
+        })
+    }
+
+    #[test]
+    fn object_store_repo_signs_requests() {
+        block_on(async {
+            let store = MockObjectStore::default();
+
+            let anonymous = ObjectStoreRepositoryBuilder::<_, Pouf1>::new(
+                "http://localhost:9000".parse().unwrap(),
+                BUCKET,
+                store.clone(),
+            )
+            .build();
+            assert_matches!(
+                anonymous
+                    .store_target(&TargetPath::new("foo").unwrap(), &mut "foo".as_bytes())
+                    .await,
+                Err(Error::BadHttpStatus {
+                    code: StatusCode::FORBIDDEN,
+                    ..
+                })
+            );
+
+            let wrong_region = builder(&store).region("us-west-2").build();
+            assert_matches!(
+                wrong_region
+                    .store_target(&TargetPath::new("foo").unwrap(), &mut "foo".as_bytes())
+                    .await,
+                Err(Error::BadHttpStatus {
+                    code: StatusCode::FORBIDDEN,
+                    ..
+                })
+            );
+            assert_eq!(store.keys(), Vec::<String>::new());
+        })
+    }
+
+    #[test]
+    fn object_store_repo_batch_update() {
+        block_on(async {
+            let store = MockObjectStore::default();
+            let repo = builder(&store).build();
+
+            let meta_path = MetadataPath::new("foo").unwrap();
+            repo.store_target(&TargetPath::new("old").unwrap(), &mut "old".as_bytes())
+                .await
+                .unwrap();
+
+            let batch = repo.batch_update();
+            batch
+                .store_metadata(&meta_path, MetadataVersion::None, &mut "foo".as_bytes())
+                .await
+                .unwrap();
+            batch
+                .store_target(&TargetPath::new("new").unwrap(), &mut "new".as_bytes())
+                .await
+                .unwrap();
+            batch
+                .remove_target(&TargetPath::new("old").unwrap())
+                .await
+                .unwrap();
+
+            // The batch sees its own changes, but the repository doesn't until they're committed.
+            assert_eq!(
+                fetch_metadata_to_string(&batch, &meta_path, MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "foo"
+            );
+            assert_eq!(
+                batch.list_targets().await.unwrap(),
+                vec![TargetPath::new("new").unwrap()]
+            );
+            assert_eq!(store.keys(), vec!["targets/old"]);
+
+            batch.commit().await.unwrap();
+            assert_eq!(store.keys(), vec!["metadata/foo.json", "targets/new"]);
+
+            // Writes by this process after a batch is created conflict with it.
+            let batch = repo.batch_update();
+            repo.store_target(&TargetPath::new("other").unwrap(), &mut "other".as_bytes())
+                .await
+                .unwrap();
+            assert_matches!(batch.commit().await, Err(CommitError::Conflict));
+        })
+    }
+
+    #[test]
+    fn object_store_repo_batch_commit_checks_versions() {
+        block_on(async {
+            let store = MockObjectStore::default();
+            let key = Ed25519PrivateKey::from_pkcs8(include_bytes!(
+                "../../tests/ed25519/ed25519-1.pk8.der"
+            ))
+            .unwrap();
+
+            let repo = builder(&store).build();
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(None, None);
+
+            // Another publisher writes to the repository.
+            let other_repo = builder(&store).build();
+            RepoBuilder::create(&other_repo)
+                .trusted_root_keys(&[&key])
+                .trusted_targets_keys(&[&key])
+                .trusted_snapshot_keys(&[&key])
+                .trusted_timestamp_keys(&[&key])
+                .commit()
+                .await
+                .unwrap();
+
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: None, actual: Some(1) })
+                if path == MetadataPath::timestamp()
+            );
+
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(Some(1), Some(2));
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: Some(2), actual: Some(1) })
+                if path == MetadataPath::snapshot()
+            );
+
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(Some(1), Some(1));
+            assert_matches!(batch.commit().await, Ok(()));
+        })
+    }
+
+    #[test]
+    fn object_store_repo_batch_commit_uses_conditional_writes() {
+        block_on(async {
+            let store = MockObjectStore::default();
+            let repo = builder(&store).build();
+            let timestamp = MetadataPath::timestamp();
+
+            // Another publisher creates the timestamp while the commit is in progress.
+            let batch = repo.batch_update();
+            batch
+                .store_metadata(&timestamp, MetadataVersion::None, &mut "ours".as_bytes())
+                .await
+                .unwrap();
+            store.race_conditional_write("metadata/timestamp.json", b"theirs");
+            assert_matches!(batch.commit().await, Err(CommitError::Conflict));
+            assert_eq!(
+                store.object("metadata/timestamp.json"),
+                Some(b"theirs".to_vec())
+            );
+
+            // The same holds for multipart uploads, which report the failure in the response body.
+            let repo = builder(&store)
+                .metadata_prefix("multipart")
+                .part_size(2)
+                .build();
+            let batch = repo.batch_update();
+            batch
+                .store_metadata(&timestamp, MetadataVersion::None, &mut "ours".as_bytes())
+                .await
+                .unwrap();
+            store.race_conditional_write("multipart/timestamp.json", b"theirs again");
+            assert_matches!(batch.commit().await, Err(CommitError::Conflict));
+            assert_eq!(
+                store.object("multipart/timestamp.json"),
+                Some(b"theirs again".to_vec())
+            );
+        })
+    }
+
+    #[test]
+    fn object_store_repo_uri_construction() {
+        let store = MockObjectStore::default();
+
+        let repo = ObjectStoreRepositoryBuilder::<_, Pouf1>::new(
+            "https://s3.us-east-1.amazonaws.com/".parse().unwrap(),
+            "bucket",
+            store.clone(),
+        )
+        .build();
+        assert_eq!(
+            repo.object_uri("a b/c+d", &[]).unwrap(),
+            "https://s3.us-east-1.amazonaws.com/bucket/a%20b/c%2Bd"
+        );
+        assert_eq!(
+            repo.object_uri("", &[("list-type", "2"), ("prefix", "a/b")])
+                .unwrap(),
+            "https://s3.us-east-1.amazonaws.com/bucket/?list-type=2&prefix=a%2Fb"
+        );
+
+        let repo = ObjectStoreRepositoryBuilder::<_, Pouf1>::new(
+            "https://s3.us-east-1.amazonaws.com".parse().unwrap(),
+            "bucket",
+            store,
+        )
+        .virtual_hosted_style(true)
+        .build();
+        assert_eq!(
+            repo.object_uri("a", &[("uploads", "")]).unwrap(),
+            "https://bucket.s3.us-east-1.amazonaws.com/a?uploads="
+        );
+    }
+
+    #[test]
+    fn xml_helpers() {
+        let xml = "<a><Key>x&amp;y</Key><Key>&lt;&#65;&#x42;&gt;</Key><Key>&bogus;</Key></a>";
+        assert_eq!(
+            xml_elements(xml, "Key")
+                .into_iter()
+                .map(xml_unescape)
+                .collect::<Vec<_>>(),
+            vec!["x&y", "<AB>", "&bogus;"]
+        );
+        assert_eq!(xml_escape("\"a\" & <b>"), "&quot;a&quot; &amp; &lt;b&gt;");
+    }
+}
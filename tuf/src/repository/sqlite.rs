@@ -0,0 +1,926 @@
+//! Repository implementation backed by a SQLite database.
+
+use {
+    crate::{
+        error::{Error, Result},
+        metadata::{
+            Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, SnapshotMetadata,
+            TargetPath, TimestampMetadata,
+        },
+        pouf::Pouf,
+        repository::{staging::StagedFile, CommitError, RepositoryProvider, RepositoryStorage},
+    },
+    blocking::{unblock, Unblock},
+    futures_io::AsyncRead,
+    futures_util::{
+        future::{BoxFuture, FutureExt},
+        io::{AsyncReadExt, Cursor},
+        lock::Mutex,
+    },
+    rusqlite::{params, Connection, DatabaseName, OptionalExtension, TransactionBehavior},
+    std::{
+        collections::{HashMap, HashSet},
+        io,
+        marker::PhantomData,
+        path::Path,
+        sync::{Arc, RwLock},
+    },
+};
+
+/// Unversioned metadata is stored with this version, since SQLite considers NULLs in a primary
+/// key to be distinct from each other.
+const UNVERSIONED: i64 = -1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tuf_metadata (
+        path TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        bytes BLOB NOT NULL,
+        PRIMARY KEY (path, version)
+    );
+    CREATE TABLE IF NOT EXISTS tuf_targets (
+        path TEXT NOT NULL PRIMARY KEY,
+        bytes BLOB NOT NULL
+    );
+";
+
+/// A repository stored in a SQLite database.
+///
+/// Metadata and targets are stored in the `tuf_metadata` and `tuf_targets` tables, so the
+/// database may be shared with other application data. Every write is a transaction, and the
+/// database is opened in write-ahead logging mode with full syncs, so changes are atomic and
+/// durable across power loss. Since targets are read into memory, this is best suited to
+/// storing metadata and small targets.
+#[derive(Debug)]
+pub struct SqliteRepository<D> {
+    conn: Arc<std::sync::Mutex<Connection>>,
+    version: RwLock<u64>,
+    // Held while writing to the repository, to serialize writers with each other.
+    write_lock: Mutex<()>,
+    _pouf: PhantomData<D>,
+}
+
+impl<D> SqliteRepository<D>
+where
+    D: Pouf,
+{
+    /// Open the repository stored in the database at `path`, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Create a repository in a new in-memory database.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Create a repository stored in the database opened by `conn`, creating the tables if they
+    /// don't exist.
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.pragma_update(None, "synchronous", "FULL")?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            conn: Arc::new(std::sync::Mutex::new(conn)),
+            version: RwLock::new(0),
+            write_lock: Mutex::new(()),
+            _pouf: PhantomData,
+        })
+    }
+
+    /// Returns a [SqliteBatchUpdate] for manipulating this repository. This allows callers to
+    /// stage a number of mutations, and optionally atomically write them all at once.
+    ///
+    /// [SqliteBatchUpdate] will fail to commit if there are any conflicting writes, either by
+    /// directly calling [SqliteRepository::store_metadata], [SqliteRepository::store_target], or
+    /// another [SqliteRepository::batch_update].
+    ///
+    /// Conflicting writes are only detected within this process. To detect writes by other
+    /// processes, use [RepositoryStorage::expect_metadata_versions].
+    pub fn batch_update(&self) -> SqliteBatchUpdate<'_, D> {
+        SqliteBatchUpdate {
+            initial_parent_version: *self.version.read().unwrap(),
+            parent_repo: self,
+            metadata: RwLock::new(HashMap::new()),
+            targets: RwLock::new(HashMap::new()),
+            removed_metadata: RwLock::new(HashSet::new()),
+            removed_targets: RwLock::new(HashSet::new()),
+            expected_timestamp_version: RwLock::new(None),
+            expected_snapshot_version: RwLock::new(None),
+        }
+    }
+
+    async fn read_metadata(&self, path: String, version: i64) -> Result<Option<Vec<u8>>> {
+        with_conn(&self.conn, move |conn| {
+            conn.query_row(
+                "SELECT bytes FROM tuf_metadata WHERE path = ?1 AND version = ?2",
+                params![path, version],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn read_target(&self, path: String) -> Result<Option<Vec<u8>>> {
+        with_conn(&self.conn, move |conn| {
+            conn.query_row(
+                "SELECT bytes FROM tuf_targets WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .await
+    }
+
+    /// Execute a statement that modifies the repository, and increment the repository version if
+    /// any rows changed.
+    async fn write<P>(&self, sql: &'static str, params: P) -> Result<()>
+    where
+        P: rusqlite::Params + Send + 'static,
+    {
+        // Lock the repository to prevent other writers from manipulating the repository to
+        // avoid race conditions.
+        let _write_lock = self.write_lock.lock().await;
+
+        let changed = with_conn(&self.conn, move |conn| conn.execute(sql, params)).await?;
+        if changed > 0 {
+            // Increment our version since the repository changed.
+            *self.version.write().unwrap() += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn list_metadata_in_db(&self) -> Result<Vec<(MetadataPath, MetadataVersion)>> {
+        let rows = with_conn(&self.conn, |conn| {
+            conn.prepare("SELECT path, version FROM tuf_metadata")?
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .await?;
+
+        // Skip any rows that weren't written by this library.
+        let mut metadata = rows
+            .into_iter()
+            .filter_map(|(path, version)| {
+                let version = if version == UNVERSIONED {
+                    MetadataVersion::None
+                } else {
+                    MetadataVersion::Number(u32::try_from(version).ok()?)
+                };
+                Some((MetadataPath::new(path).ok()?, version))
+            })
+            .collect::<Vec<_>>();
+        metadata.sort();
+        Ok(metadata)
+    }
+
+    async fn list_targets_in_db(&self) -> Result<Vec<TargetPath>> {
+        let rows = with_conn(&self.conn, |conn| {
+            conn.prepare("SELECT path FROM tuf_targets")?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .await?;
+
+        let mut targets = rows
+            .into_iter()
+            .filter_map(|path| TargetPath::new(path).ok())
+            .collect::<Vec<_>>();
+        targets.sort();
+        Ok(targets)
+    }
+}
+
+impl<D> RepositoryProvider<D> for SqliteRepository<D>
+where
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let meta_path = meta_path.clone();
+
+        async move {
+            match self
+                .read_metadata(meta_path.to_string(), version_column(version))
+                .await?
+            {
+                Some(bytes) => Ok(bytes_to_reader(bytes)),
+                None => Err(Error::MetadataNotFound {
+                    path: meta_path,
+                    version,
+                }),
+            }
+        }
+        .boxed()
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let target_path = target_path.clone();
+
+        async move {
+            match self.read_target(target_path.to_string()).await? {
+                Some(bytes) => Ok(bytes_to_reader(bytes)),
+                None => Err(Error::TargetNotFound(target_path)),
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<D> RepositoryStorage<D> for SqliteRepository<D>
+where
+    D: Pouf,
+{
+    fn store_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        metadata: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = meta_path.to_string();
+
+        async move {
+            let mut bytes = vec![];
+            metadata.read_to_end(&mut bytes).await?;
+
+            self.write(
+                "INSERT OR REPLACE INTO tuf_metadata (path, version, bytes) VALUES (?1, ?2, ?3)",
+                (path, version_column(version), bytes),
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn store_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = target_path.to_string();
+
+        async move {
+            let mut bytes = vec![];
+            read.read_to_end(&mut bytes).await?;
+
+            self.write(
+                "INSERT OR REPLACE INTO tuf_targets (path, bytes) VALUES (?1, ?2)",
+                (path, bytes),
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = meta_path.to_string();
+
+        async move {
+            self.write(
+                "DELETE FROM tuf_metadata WHERE path = ?1 AND version = ?2",
+                (path, version_column(version)),
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let path = target_path.to_string();
+
+        async move {
+            self.write("DELETE FROM tuf_targets WHERE path = ?1", (path,))
+                .await
+        }
+        .boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        self.list_metadata_in_db().boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.list_targets_in_db().boxed()
+    }
+}
+
+/// [SqliteBatchUpdate] is a special repository that is designed to write the metadata and
+/// targets to a [SqliteRepository] in a single transaction.
+///
+/// Note: `SqliteBatchUpdate::commit()` must be called in order to write the metadata and targets
+/// to the [SqliteRepository]. Otherwise any queued changes will be lost on drop.
+#[derive(Debug)]
+pub struct SqliteBatchUpdate<'a, D> {
+    initial_parent_version: u64,
+    parent_repo: &'a SqliteRepository<D>,
+    metadata: RwLock<HashMap<(MetadataPath, MetadataVersion), Vec<u8>>>,
+    targets: RwLock<HashMap<TargetPath, StagedFile>>,
+    removed_metadata: RwLock<HashSet<(MetadataPath, MetadataVersion)>>,
+    removed_targets: RwLock<HashSet<TargetPath>>,
+    // `None` if unchecked, and `Some(None)` if the metadata must not exist.
+    expected_timestamp_version: RwLock<Option<Option<u32>>>,
+    expected_snapshot_version: RwLock<Option<Option<u32>>>,
+}
+
+impl<'a, D> SqliteBatchUpdate<'a, D>
+where
+    D: Pouf,
+{
+    /// Write all the metadata and targets in the [SqliteBatchUpdate] to the source
+    /// [SqliteRepository] in a single transaction, so either all or none of the changes are
+    /// written, even if the system loses power.
+    ///
+    /// This fails with [CommitError::UnexpectedVersion] if the timestamp or snapshot metadata
+    /// don't have the versions set by [RepositoryStorage::expect_metadata_versions], and with [CommitError::Conflict] if another process
+    /// changed them while this commit was checking them.
+    pub async fn commit(self) -> std::result::Result<(), CommitError> {
+        let parent_repo = self.parent_repo;
+        let _write_lock = parent_repo.write_lock.lock().await;
+
+        // Check if the parent changed while this batch was being processed.
+        if self.initial_parent_version != *parent_repo.version.read().unwrap() {
+            return Err(CommitError::Conflict);
+        }
+
+        // The metadata checked against the expected versions, which must be unchanged when the
+        // transaction is committed.
+        let mut checked = vec![];
+        if let Some(expected) = self.expected_timestamp_version.into_inner().unwrap() {
+            checked.push(
+                check_version::<D, TimestampMetadata>(
+                    parent_repo,
+                    &MetadataPath::timestamp(),
+                    expected,
+                )
+                .await?,
+            );
+        }
+        if let Some(expected) = self.expected_snapshot_version.into_inner().unwrap() {
+            checked.push(
+                check_version::<D, SnapshotMetadata>(
+                    parent_repo,
+                    &MetadataPath::snapshot(),
+                    expected,
+                )
+                .await?,
+            );
+        }
+
+        let removed_metadata = self
+            .removed_metadata
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(meta_path, version)| (meta_path.to_string(), version_column(version)))
+            .collect::<Vec<_>>();
+        let removed_targets = self
+            .removed_targets
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|target_path| target_path.to_string())
+            .collect::<Vec<_>>();
+        let metadata = self
+            .metadata
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|((meta_path, version), bytes)| {
+                (meta_path.to_string(), version_column(version), bytes)
+            })
+            .collect::<Vec<_>>();
+        let mut targets = vec![];
+        for (target_path, staged) in self.targets.into_inner().unwrap() {
+            targets.push((target_path.to_string(), staged.open().await?));
+        }
+
+        let committed = with_conn(&parent_repo.conn, move |conn| {
+            // Take the write lock on the database up front, so the checked metadata can't change
+            // before we commit.
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+            for (path, bytes) in &checked {
+                let current = tx
+                    .query_row(
+                        "SELECT bytes FROM tuf_metadata WHERE path = ?1 AND version = ?2",
+                        params![path, UNVERSIONED],
+                        |row| row.get::<_, Vec<u8>>(0),
+                    )
+                    .optional()?;
+                if current != *bytes {
+                    return Ok(false);
+                }
+            }
+
+            for (path, version) in removed_metadata {
+                tx.execute(
+                    "DELETE FROM tuf_metadata WHERE path = ?1 AND version = ?2",
+                    params![path, version],
+                )?;
+            }
+            for path in removed_targets {
+                tx.execute("DELETE FROM tuf_targets WHERE path = ?1", params![path])?;
+            }
+            for (path, version, bytes) in metadata {
+                tx.execute(
+                    "INSERT OR REPLACE INTO tuf_metadata (path, version, bytes) \
+                     VALUES (?1, ?2, ?3)",
+                    params![path, version, bytes],
+                )?;
+            }
+            // Stream the targets into their rows, rather than reading them into memory.
+            for (path, mut file) in targets {
+                let len = file.metadata()?.len();
+                tx.execute(
+                    "INSERT OR REPLACE INTO tuf_targets (path, bytes) VALUES (?1, zeroblob(?2))",
+                    params![path, len],
+                )?;
+                let mut blob = tx.blob_open(
+                    DatabaseName::Main,
+                    "tuf_targets",
+                    "bytes",
+                    tx.last_insert_rowid(),
+                    false,
+                )?;
+                io::copy(&mut file, &mut blob)?;
+            }
+
+            tx.commit()?;
+            Ok::<_, Error>(true)
+        })
+        .await?;
+
+        if !committed {
+            return Err(CommitError::Conflict);
+        }
+
+        // Increment the version because we wrote to it.
+        *parent_repo.version.write().unwrap() += 1;
+
+        Ok(())
+    }
+}
+
+/// Check that the unversioned metadata `meta_path` is at version `expected`, returning its path
+/// and contents.
+async fn check_version<D, M>(
+    repo: &SqliteRepository<D>,
+    meta_path: &MetadataPath,
+    expected: Option<u32>,
+) -> std::result::Result<(String, Option<Vec<u8>>), CommitError>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    let path = meta_path.to_string();
+    let bytes = repo.read_metadata(path.clone(), UNVERSIONED).await?;
+
+    let actual = match &bytes {
+        Some(bytes) => Some(
+            RawSignedMetadata::<D, M>::new(bytes.clone())
+                .parse_untrusted()?
+                .parse_version_untrusted()?,
+        ),
+        None => None,
+    };
+
+    if actual != expected {
+        return Err(CommitError::UnexpectedVersion {
+            path: meta_path.clone(),
+            expected,
+            actual,
+        });
+    }
+
+    Ok((path, bytes))
+}
+
+impl<D> RepositoryProvider<D> for SqliteBatchUpdate<'_, D>
+where
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let key = (meta_path.clone(), version);
+        if let Some(bytes) = self.metadata.read().unwrap().get(&key) {
+            let reader = bytes_to_reader(bytes.clone());
+            async move { Ok(reader) }.boxed()
+        } else if self.removed_metadata.read().unwrap().contains(&key) {
+            let err = Error::MetadataNotFound {
+                path: meta_path.clone(),
+                version,
+            };
+            async move { Err(err) }.boxed()
+        } else {
+            self.parent_repo.fetch_metadata(meta_path, version)
+        }
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        if let Some(staged) = self.targets.read().unwrap().get(target_path).cloned() {
+            async move {
+                let reader: Box<dyn AsyncRead + Send + Unpin> =
+                    Box::new(Unblock::new(staged.open().await?));
+                Ok(reader)
+            }
+            .boxed()
+        } else if self.removed_targets.read().unwrap().contains(target_path) {
+            let err = Error::TargetNotFound(target_path.clone());
+            async move { Err(err) }.boxed()
+        } else {
+            self.parent_repo.fetch_target(target_path)
+        }
+    }
+}
+
+impl<D> RepositoryStorage<D> for SqliteBatchUpdate<'_, D>
+where
+    D: Pouf,
+{
+    fn store_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = (meta_path.clone(), version);
+
+        async move {
+            let mut bytes = vec![];
+            read.read_to_end(&mut bytes).await?;
+            self.removed_metadata.write().unwrap().remove(&key);
+            self.metadata.write().unwrap().insert(key, bytes);
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn store_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let target_path = target_path.clone();
+
+        async move {
+            let staged = StagedFile::new(read).await?;
+            self.removed_targets.write().unwrap().remove(&target_path);
+            self.targets.write().unwrap().insert(target_path, staged);
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        let key = (meta_path.clone(), version);
+        self.metadata.write().unwrap().remove(&key);
+        self.removed_metadata.write().unwrap().insert(key);
+        futures_util::future::ready(Ok(())).boxed()
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.targets.write().unwrap().remove(target_path);
+        self.removed_targets
+            .write()
+            .unwrap()
+            .insert(target_path.clone());
+        futures_util::future::ready(Ok(())).boxed()
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        async move {
+            let metadata = self.parent_repo.list_metadata_in_db().await?;
+            let removed = self.removed_metadata.read().unwrap();
+            let mut metadata = metadata
+                .into_iter()
+                .filter(|key| !removed.contains(key))
+                .chain(self.metadata.read().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            metadata.sort();
+            metadata.dedup();
+            Ok(metadata)
+        }
+        .boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        async move {
+            let targets = self.parent_repo.list_targets_in_db().await?;
+            let removed = self.removed_targets.read().unwrap();
+            let mut targets = targets
+                .into_iter()
+                .filter(|target_path| !removed.contains(target_path))
+                .chain(self.targets.read().unwrap().keys().cloned())
+                .collect::<Vec<_>>();
+            targets.sort();
+            targets.dedup();
+            Ok(targets)
+        }
+        .boxed()
+    }
+
+    fn expect_metadata_versions(
+        &self,
+        timestamp_version: Option<u32>,
+        snapshot_version: Option<u32>,
+    ) {
+        *self.expected_timestamp_version.write().unwrap() = Some(timestamp_version);
+        *self.expected_snapshot_version.write().unwrap() = Some(snapshot_version);
+    }
+}
+
+/// Run `f` with the connection on the blocking thread pool.
+async fn with_conn<T, E, F>(conn: &Arc<std::sync::Mutex<Connection>>, f: F) -> Result<T>
+where
+    F: FnOnce(&mut Connection) -> std::result::Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+    Error: From<E>,
+{
+    let conn = Arc::clone(conn);
+    let res = unblock(move || f(&mut conn.lock().unwrap())).await;
+    Ok(res?)
+}
+
+fn version_column(version: MetadataVersion) -> i64 {
+    match version {
+        MetadataVersion::None => UNVERSIONED,
+        MetadataVersion::Number(version) => version.into(),
+    }
+}
+
+fn bytes_to_reader<'a>(bytes: Vec<u8>) -> Box<dyn AsyncRead + Send + Unpin + 'a> {
+    Box::new(Cursor::new(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::crypto::Ed25519PrivateKey,
+        crate::pouf::Pouf1,
+        crate::repo_builder::RepoBuilder,
+        crate::repository::{fetch_metadata_to_string, fetch_target_to_string},
+        assert_matches::assert_matches,
+        futures_executor::block_on,
+    };
+
+    #[test]
+    fn sqlite_repo_store_fetch_list_and_remove() {
+        block_on(async {
+            let repo = SqliteRepository::<Pouf1>::open_in_memory().unwrap();
+
+            let meta_path = MetadataPath::new("foo/bar").unwrap();
+            let target_path = TargetPath::new("a/b").unwrap();
+
+            assert_matches!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::None).await,
+                Err(Error::MetadataNotFound { path, version: MetadataVersion::None })
+                if path == meta_path
+            );
+            assert_matches!(
+                fetch_target_to_string(&repo, &target_path).await,
+                Err(Error::TargetNotFound(path))
+                if path == target_path
+            );
+
+            repo.store_metadata(&meta_path, MetadataVersion::None, &mut "new".as_bytes())
+                .await
+                .unwrap();
+            repo.store_metadata(&meta_path, MetadataVersion::Number(1), &mut "v1".as_bytes())
+                .await
+                .unwrap();
+            repo.store_target(&target_path, &mut "old".as_bytes())
+                .await
+                .unwrap();
+            // Storing again replaces the contents.
+            repo.store_target(&target_path, &mut "target".as_bytes())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "new"
+            );
+            assert_eq!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::Number(1))
+                    .await
+                    .unwrap(),
+                "v1"
+            );
+            assert_eq!(
+                fetch_target_to_string(&repo, &target_path).await.unwrap(),
+                "target"
+            );
+
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![
+                    (meta_path.clone(), MetadataVersion::None),
+                    (meta_path.clone(), MetadataVersion::Number(1)),
+                ]
+            );
+            assert_eq!(
+                repo.list_targets().await.unwrap(),
+                vec![target_path.clone()]
+            );
+
+            repo.remove_metadata(&meta_path, MetadataVersion::None)
+                .await
+                .unwrap();
+            repo.remove_target(&target_path).await.unwrap();
+            // Removing missing entries is not an error.
+            repo.remove_target(&target_path).await.unwrap();
+
+            assert_eq!(
+                repo.list_metadata().await.unwrap(),
+                vec![(meta_path.clone(), MetadataVersion::Number(1))]
+            );
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+        })
+    }
+
+    #[test]
+    fn sqlite_repo_persists_across_reopen() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let db_path = temp_dir.path().join("tuf.db");
+            let target_path = TargetPath::new("foo").unwrap();
+
+            let repo = SqliteRepository::<Pouf1>::open(&db_path).unwrap();
+            repo.store_target(&target_path, &mut "foo".as_bytes())
+                .await
+                .unwrap();
+            drop(repo);
+
+            let repo = SqliteRepository::<Pouf1>::open(&db_path).unwrap();
+            assert_eq!(
+                fetch_target_to_string(&repo, &target_path).await.unwrap(),
+                "foo"
+            );
+        })
+    }
+
+    #[test]
+    fn sqlite_repo_batch_update() {
+        block_on(async {
+            let repo = SqliteRepository::<Pouf1>::open_in_memory().unwrap();
+            let meta_path = MetadataPath::new("foo").unwrap();
+            let old_target = TargetPath::new("old").unwrap();
+            let new_target = TargetPath::new("new").unwrap();
+
+            repo.store_target(&old_target, &mut "old".as_bytes())
+                .await
+                .unwrap();
+
+            let batch = repo.batch_update();
+            batch
+                .store_metadata(&meta_path, MetadataVersion::None, &mut "foo".as_bytes())
+                .await
+                .unwrap();
+            batch
+                .store_target(&new_target, &mut "new".as_bytes())
+                .await
+                .unwrap();
+            batch.remove_target(&old_target).await.unwrap();
+
+            // The batch sees its own changes, but the repository doesn't until they're committed.
+            assert_eq!(
+                fetch_metadata_to_string(&batch, &meta_path, MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "foo"
+            );
+            assert_matches!(
+                fetch_target_to_string(&batch, &old_target).await,
+                Err(Error::TargetNotFound(_))
+            );
+            assert_eq!(
+                batch.list_targets().await.unwrap(),
+                vec![new_target.clone()]
+            );
+            assert_matches!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::None).await,
+                Err(Error::MetadataNotFound { .. })
+            );
+            assert_eq!(repo.list_targets().await.unwrap(), vec![old_target.clone()]);
+
+            batch.commit().await.unwrap();
+
+            assert_eq!(
+                fetch_metadata_to_string(&repo, &meta_path, MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "foo"
+            );
+            assert_eq!(repo.list_targets().await.unwrap(), vec![new_target]);
+        })
+    }
+
+    #[test]
+    fn sqlite_repo_batch_commit_fails_with_conflicting_writes() {
+        block_on(async {
+            let repo = SqliteRepository::<Pouf1>::open_in_memory().unwrap();
+            let target_path = TargetPath::new("foo").unwrap();
+
+            let batch1 = repo.batch_update();
+            let batch2 = repo.batch_update();
+            batch1
+                .store_target(&target_path, &mut "batch1".as_bytes())
+                .await
+                .unwrap();
+            batch2
+                .store_target(&target_path, &mut "batch2".as_bytes())
+                .await
+                .unwrap();
+
+            batch1.commit().await.unwrap();
+            assert_matches!(batch2.commit().await, Err(CommitError::Conflict));
+
+            let batch = repo.batch_update();
+            repo.remove_target(&target_path).await.unwrap();
+            assert_matches!(batch.commit().await, Err(CommitError::Conflict));
+        })
+    }
+
+    #[test]
+    fn sqlite_repo_batch_commit_fails_with_unexpected_versions() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let db_path = temp_dir.path().join("tuf.db");
+            let key = Ed25519PrivateKey::from_pkcs8(include_bytes!(
+                "../../tests/ed25519/ed25519-1.pk8.der"
+            ))
+            .unwrap();
+
+            let repo = SqliteRepository::<Pouf1>::open(&db_path).unwrap();
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(None, None);
+
+            // Another process publishes to the repository, which we can't detect in-process.
+            let other_repo = SqliteRepository::<Pouf1>::open(&db_path).unwrap();
+            RepoBuilder::create(&other_repo)
+                .trusted_root_keys(&[&key])
+                .trusted_targets_keys(&[&key])
+                .trusted_snapshot_keys(&[&key])
+                .trusted_timestamp_keys(&[&key])
+                .commit()
+                .await
+                .unwrap();
+
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: None, actual: Some(1) })
+                if path == MetadataPath::timestamp()
+            );
+
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(Some(1), Some(2));
+            assert_matches!(
+                batch.commit().await,
+                Err(CommitError::UnexpectedVersion { path, expected: Some(2), actual: Some(1) })
+                if path == MetadataPath::snapshot()
+            );
+
+            let batch = repo.batch_update();
+            batch.expect_metadata_versions(Some(1), Some(1));
+            assert_matches!(batch.commit().await, Ok(()));
+        })
+    }
+}
@@ -0,0 +1,60 @@
+//! Temporary files that hold the targets staged in a batch update until it is committed.
+
+use {
+    crate::error::{Error, Result},
+    blocking::unblock,
+    futures_io::AsyncRead,
+    futures_util::io::AsyncReadExt,
+    std::{fs::File, io::Write as _, sync::Arc},
+    tempfile::NamedTempFile,
+};
+
+/// The size of the chunks copied into a [StagedFile].
+const WRITE_CHUNK_SIZE: usize = 8 * 1024;
+
+/// A temporary file, which is removed once every handle to it is dropped.
+#[derive(Clone, Debug)]
+pub(super) struct StagedFile(Arc<NamedTempFile>);
+
+impl StagedFile {
+    /// Copy `read` into a new temporary file.
+    pub(super) async fn new(read: &mut (dyn AsyncRead + Send + Unpin)) -> Result<Self> {
+        let mut temp_file = unblock(NamedTempFile::new).await?;
+
+        let mut buf = vec![0; WRITE_CHUNK_SIZE];
+        loop {
+            let n = read.read(&mut buf).await.map_err(|err| Error::IoPath {
+                path: temp_file.path().to_path_buf(),
+                err,
+            })?;
+            if n == 0 {
+                break;
+            }
+
+            let res;
+            (temp_file, buf, res) = unblock(move || {
+                let res = temp_file.write_all(&buf[..n]);
+                (temp_file, buf, res)
+            })
+            .await;
+            res.map_err(|err| Error::IoPath {
+                path: temp_file.path().to_path_buf(),
+                err,
+            })?;
+        }
+
+        Ok(Self(Arc::new(temp_file)))
+    }
+
+    /// Open a new handle to the file, positioned at the start.
+    pub(super) async fn open(&self) -> Result<File> {
+        let temp_file = Arc::clone(&self.0);
+        unblock(move || {
+            temp_file.reopen().map_err(|err| Error::IoPath {
+                path: temp_file.path().to_path_buf(),
+                err,
+            })
+        })
+        .await
+    }
+}
@@ -139,6 +139,26 @@ where
     ) -> BoxFuture<'a, Result<()>> {
         self.repo.store_target(target_path, target)
     }
+
+    fn remove_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.repo.remove_metadata(meta_path, version)
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.repo.remove_target(target_path)
+    }
+
+    fn list_metadata(&self) -> BoxFuture<'_, Result<Vec<(MetadataPath, MetadataVersion)>>> {
+        self.repo.list_metadata()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.repo.list_targets()
+    }
 }
 
 impl<D, R> RepositoryProvider<D> for TrackRepository<R>
@@ -0,0 +1,330 @@
+//! Key revocation, in the style of [TAP 20] self-revocation.
+//!
+//! Normally a compromised key stops being trusted only once the role that delegated to it signs new
+//! metadata without it. A [KeyRevocation] lets the holder of the key revoke it directly, by signing
+//! the revocation with the revoked key itself. Since anyone holding the key could sign one, a
+//! revocation can only take trust away: once a [Database](crate::database::Database) accepts it,
+//! the key no longer counts towards the signature threshold of the role, or of any role delegated
+//! from that role.
+//!
+//! Repositories publish revocations in a [RevocationList], stored as the `revocations` metadata
+//! file. Delegations can't use that name. [Client::update](crate::client::Client::update) applies the list after updating the
+//! snapshot metadata, and before updating the targets metadata.
+//!
+//! [TAP 20]: https://github.com/theupdateframework/taps/blob/master/tap20.md
+
+use {
+    crate::{
+        crypto::{KeyId, PrivateKey, PublicKey, Signature},
+        error::{Error, Result},
+        metadata::MetadataPath,
+        pouf::Pouf,
+    },
+    log::warn,
+    serde_derive::{Deserialize, Serialize},
+};
+
+/// A statement that the key `key_id` must no longer be trusted to sign `role`.
+///
+/// ```
+/// # use tuf::{
+/// #     crypto::{Ed25519PrivateKey, PrivateKey as _},
+/// #     metadata::MetadataPath,
+/// #     pouf::Pouf1,
+/// #     revocation::KeyRevocation,
+/// # };
+/// let key = Ed25519PrivateKey::from_pkcs8(
+///     include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+/// ).unwrap();
+///
+/// let revocation = KeyRevocation::new(MetadataPath::targets(), key.public().key_id().clone())
+///     .sign::<Pouf1>(&key)
+///     .unwrap();
+/// revocation
+///     .verify::<Pouf1>(key.public().key_id(), key.public())
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "_type", rename = "revocation", try_from = "KeyRevocationShim")]
+pub struct KeyRevocation {
+    role: MetadataPath,
+    #[serde(rename = "keyid")]
+    key_id: KeyId,
+}
+
+impl KeyRevocation {
+    /// Create a revocation of the key `key_id` for `role`.
+    pub fn new(role: MetadataPath, key_id: KeyId) -> Self {
+        KeyRevocation { role, key_id }
+    }
+
+    /// The role the key is revoked for.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The ID of the revoked key.
+    pub fn key_id(&self) -> &KeyId {
+        &self.key_id
+    }
+
+    /// Sign the revocation with `private_key`, which must be the key being revoked.
+    pub fn sign<D: Pouf>(self, private_key: &dyn PrivateKey) -> Result<SignedKeyRevocation> {
+        if private_key.public().key_id() != &self.key_id {
+            return Err(Error::IllegalArgument(format!(
+                "revocation of key {} must be signed by that key, not {}",
+                self.key_id,
+                private_key.public().key_id()
+            )));
+        }
+
+        let bytes = D::canonicalize(&D::serialize(&self)?)?;
+        let signature = private_key.sign(&bytes)?;
+
+        Ok(SignedKeyRevocation {
+            signed: self,
+            signatures: vec![signature],
+        })
+    }
+}
+
+/// Serde doesn't check the tag of internally tagged structs when deserializing them.
+#[derive(Deserialize)]
+struct KeyRevocationShim {
+    #[serde(rename = "_type")]
+    typ: String,
+    role: MetadataPath,
+    keyid: KeyId,
+}
+
+impl TryFrom<KeyRevocationShim> for KeyRevocation {
+    type Error = Error;
+
+    fn try_from(shim: KeyRevocationShim) -> Result<Self> {
+        if shim.typ != "revocation" {
+            return Err(Error::Encoding(format!(
+                "Attempted to decode revocation metadata labeled as {:?}",
+                shim.typ
+            )));
+        }
+
+        Ok(KeyRevocation::new(shim.role, shim.keyid))
+    }
+}
+
+/// A [KeyRevocation] signed by the revoked key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedKeyRevocation {
+    signed: KeyRevocation,
+    signatures: Vec<Signature>,
+}
+
+impl SignedKeyRevocation {
+    /// The revocation.
+    pub fn revocation(&self) -> &KeyRevocation {
+        &self.signed
+    }
+
+    /// The signatures of the revocation.
+    pub fn signatures(&self) -> &[Signature] {
+        &self.signatures
+    }
+
+    /// Verify that `key`, the revoked key, signed the revocation. `key_id` is the ID the role's
+    /// metadata lists the key under, which is the key's canonical ID unless the metadata uses
+    /// another [KeyIdScheme](crate::crypto::KeyIdScheme).
+    pub fn verify<D: Pouf>(&self, key_id: &KeyId, key: &PublicKey) -> Result<()> {
+        let role = self.signed.role();
+        if key_id != self.signed.key_id() {
+            return Err(Error::IllegalArgument(format!(
+                "revocation of key {} can't be verified with key {}",
+                self.signed.key_id(),
+                key_id
+            )));
+        }
+
+        // The signature may name the key by either ID.
+        let bytes = D::canonicalize(&D::serialize(&self.signed)?)?;
+        let mut signatures = self
+            .signatures
+            .iter()
+            .filter(|sig| sig.key_id() == key_id || sig.key_id() == key.key_id())
+            .peekable();
+        if signatures.peek().is_none() {
+            return Err(Error::BadSignature(role.clone()));
+        }
+
+        if signatures.any(|sig| key.verify(role, &bytes, sig).is_ok()) {
+            Ok(())
+        } else {
+            Err(Error::BadSignature(role.clone()))
+        }
+    }
+}
+
+/// The revocations published by a repository.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationList {
+    revocations: Vec<SignedKeyRevocation>,
+}
+
+impl RevocationList {
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path of the metadata file the list is stored in.
+    pub fn path() -> MetadataPath {
+        MetadataPath::new("revocations").unwrap()
+    }
+
+    /// Add `revocation` to the list, unless the list already revokes the same key for the same
+    /// role. Returns whether the revocation was added.
+    pub fn insert(&mut self, revocation: SignedKeyRevocation) -> bool {
+        if self
+            .revocations
+            .iter()
+            .any(|r| r.revocation() == revocation.revocation())
+        {
+            return false;
+        }
+
+        self.revocations.push(revocation);
+        true
+    }
+
+    /// The revocations in the list.
+    pub fn revocations(&self) -> &[SignedKeyRevocation] {
+        &self.revocations
+    }
+
+    /// Whether the list has no revocations.
+    pub fn is_empty(&self) -> bool {
+        self.revocations.is_empty()
+    }
+
+    /// Parse a list from the contents of the revocations metadata file. Anyone can add to the
+    /// published list, so revocations that can't be parsed are skipped rather than failing the
+    /// whole list.
+    pub fn from_slice<D: Pouf>(bytes: &[u8]) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct RawRevocationList<D: Pouf> {
+            revocations: Vec<D::RawData>,
+        }
+
+        let raw: RawRevocationList<D> = D::from_slice(bytes)?;
+        let mut list = RevocationList::new();
+        for revocation in &raw.revocations {
+            match D::deserialize(revocation) {
+                Ok(revocation) => {
+                    let _ = list.insert(revocation);
+                }
+                Err(err) => warn!("Skipping malformed revocation: {}", err),
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Serialize the list into the contents of the revocations metadata file.
+    pub fn to_bytes<D: Pouf>(&self) -> Result<Vec<u8>> {
+        D::canonicalize(&D::serialize(self)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::pouf::Pouf1;
+    use assert_matches::assert_matches;
+    use serde_json::json;
+
+    fn key(n: usize) -> Ed25519PrivateKey {
+        let keys: &[&[u8]] = &[
+            include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+            include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+        ];
+        Ed25519PrivateKey::from_pkcs8(keys[n]).unwrap()
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let key = key(0);
+        let revocation = KeyRevocation::new(MetadataPath::targets(), key.public().key_id().clone())
+            .sign::<Pouf1>(&key)
+            .unwrap();
+        revocation
+            .verify::<Pouf1>(key.public().key_id(), key.public())
+            .unwrap();
+
+        // Only the revoked key can sign or verify the revocation.
+        let other = self::key(1);
+        assert_matches!(
+            KeyRevocation::new(MetadataPath::targets(), key.public().key_id().clone())
+                .sign::<Pouf1>(&other),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            revocation.verify::<Pouf1>(other.public().key_id(), other.public()),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // The signature covers the role.
+        let mut json = serde_json::to_value(&revocation).unwrap();
+        json["signed"]["role"] = json!("snapshot");
+        let tampered: SignedKeyRevocation = serde_json::from_value(json).unwrap();
+        assert_matches!(
+            tampered.verify::<Pouf1>(key.public().key_id(), key.public()),
+            Err(Error::BadSignature(role)) if role == MetadataPath::snapshot()
+        );
+    }
+
+    #[test]
+    fn serde_revocation_list() {
+        let key = key(0);
+        let revocation = KeyRevocation::new(MetadataPath::targets(), key.public().key_id().clone())
+            .sign::<Pouf1>(&key)
+            .unwrap();
+
+        let mut list = RevocationList::new();
+        assert!(list.insert(revocation.clone()));
+        assert!(!list.insert(revocation.clone()));
+
+        let bytes = list.to_bytes::<Pouf1>().unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "revocations": [{
+                    "signed": {
+                        "_type": "revocation",
+                        "role": "targets",
+                        "keyid": key.public().key_id(),
+                    },
+                    "signatures": revocation.signatures(),
+                }],
+            })
+        );
+        assert_eq!(RevocationList::from_slice::<Pouf1>(&bytes).unwrap(), list);
+
+        // Malformed revocations are skipped.
+        let mut json = json;
+        json["revocations"].as_array_mut().unwrap().insert(
+            0,
+            json!({"signed": {"_type": "revocation"}, "signatures": []}),
+        );
+        assert_eq!(
+            RevocationList::from_slice::<Pouf1>(&serde_json::to_vec(&json).unwrap()).unwrap(),
+            list
+        );
+
+        assert!(serde_json::from_value::<KeyRevocation>(json!({
+            "_type": "targets",
+            "role": "targets",
+            "keyid": key.public().key_id(),
+        }))
+        .is_err());
+    }
+}
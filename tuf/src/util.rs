@@ -1,15 +1,44 @@
 use futures_io::AsyncRead;
 use futures_util::ready;
-use ring::digest;
 use std::io::{self, ErrorKind};
 use std::marker::Unpin;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 
-use crate::crypto::{HashAlgorithm, HashValue};
+use crate::crypto::{DigestContext, HashAlgorithm, HashValue};
+use crate::database::Database;
+use crate::metadata::{
+    Metadata, MetadataDescription, MetadataPath, MetadataVersion, TargetsMetadata,
+};
+use crate::pouf::Pouf;
+use crate::verify::Verified;
 use crate::Result;
 
+/// Returns the trusted targets metadata for the top-level or delegated targets role `role`.
+pub(crate) fn role_metadata<'a, D: Pouf>(
+    database: &'a Database<D>,
+    role: &MetadataPath,
+) -> Option<&'a Verified<TargetsMetadata>> {
+    if *role == MetadataPath::targets() {
+        database.trusted_targets()
+    } else {
+        database.trusted_delegations().get(role)
+    }
+}
+
+/// Returns the version clients use to fetch the metadata described by `description`.
+pub(crate) fn version_for<M: Metadata>(
+    description: &MetadataDescription<M>,
+    consistent_snapshot: bool,
+) -> MetadataVersion {
+    if consistent_snapshot {
+        MetadataVersion::Number(description.version())
+    } else {
+        MetadataVersion::None
+    }
+}
+
 pub(crate) trait SafeAsyncRead: AsyncRead + Sized + Unpin {
     /// Creates an `AsyncRead` adapter which will fail transfers slower than
     /// `min_bytes_per_second`.
@@ -106,7 +135,7 @@ impl<R: AsyncRead + Unpin> AsyncRead for EnforceMinimumBitrate<R> {
 pub(crate) struct SafeReader<R> {
     inner: R,
     max_size: u64,
-    hashers: Vec<(digest::Context, HashValue)>,
+    hashers: Vec<(DigestContext, HashValue)>,
     bytes_read: u64,
 }
 
@@ -147,7 +176,7 @@ impl<R: AsyncRead + Unpin> AsyncRead for SafeReader<R> {
         if read_bytes == 0 {
             for (context, expected_hash) in self.hashers.drain(..) {
                 let generated_hash = context.finish();
-                if generated_hash.as_ref() != expected_hash.value() {
+                if generated_hash != expected_hash.value() {
                     return Poll::Ready(Err(io::Error::new(
                         ErrorKind::InvalidData,
                         "Calculated hash did not match the required hash.",
@@ -181,7 +210,7 @@ mod test {
     use super::*;
     use futures_executor::block_on;
     use futures_util::io::AsyncReadExt;
-    use ring::digest::SHA256;
+    use ring::digest::{self, SHA256};
 
     #[test]
     fn valid_read() {
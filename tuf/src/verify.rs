@@ -1,12 +1,22 @@
 //! The `verify` module performs signature verification.
 
 use log::{debug, warn};
+use ring::digest::{self, SHA256};
 use serde_derive::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
 
-use crate::crypto::{KeyId, PublicKey, Signature};
+use crate::clock::Clock;
+#[cfg(feature = "keyless")]
+use crate::clock::SystemClock;
+#[cfg(feature = "batch-verify")]
+use crate::crypto::SignatureScheme;
+#[cfg(feature = "keyless")]
+use crate::crypto::{self, KeylessTrustRoots};
+use crate::crypto::{KeyId, KeyIdPolicy, KeylessIdentity, PublicKey, Signature};
 use crate::error::Error;
-use crate::metadata::{Metadata, MetadataPath, RawSignedMetadata};
+use crate::metadata::{Metadata, MetadataPath, RawSignedMetadata, RootMetadata, SignedMetadata};
 use crate::pouf::Pouf;
 
 /// `Verified` is a wrapper type that signifies the inner type has had it's signature verified.
@@ -93,75 +103,1171 @@ where
     M: Metadata,
     I: IntoIterator<Item = &'a PublicKey>,
 {
-    if threshold < 1 {
-        return Err(Error::MetadataThresholdMustBeGreaterThanZero(role.clone()));
+    Verifier::default().verify_signatures(role, raw_metadata, threshold, authorized_keys)
+}
+
+/// How the signatures of some signed metadata relate to the keys authorized to sign its role. See
+/// [`Verifier::check_signatures`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignatureReport {
+    threshold: u32,
+    valid: Vec<Signature>,
+    invalid: Vec<Signature>,
+    unknown_keys: Vec<Signature>,
+    duplicates: Vec<Signature>,
+}
+
+impl SignatureReport {
+    /// The number of valid signatures the role requires.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Whether there are at least [`SignatureReport::threshold`] valid signatures.
+    pub fn is_threshold_met(&self) -> bool {
+        self.valid.len() >= self.threshold as usize
+    }
+
+    /// The signatures from authorized keys that verified, at most one per key.
+    pub fn valid(&self) -> &[Signature] {
+        &self.valid
+    }
+
+    /// The signatures from authorized keys that did not verify.
+    pub fn invalid(&self) -> &[Signature] {
+        &self.invalid
+    }
+
+    /// The signatures from keys that are not authorized to sign the role.
+    pub fn unknown_keys(&self) -> &[Signature] {
+        &self.unknown_keys
+    }
+
+    /// The signatures from keys that already had a valid signature.
+    pub fn duplicates(&self) -> &[Signature] {
+        &self.duplicates
+    }
+
+    /// Whether every signature is valid.
+    pub fn is_clean(&self) -> bool {
+        self.invalid.is_empty() && self.unknown_keys.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+/// A bounded cache of signatures that have already been verified.
+///
+/// Entries are keyed by the key ID, a SHA-256 hash of the signed canonical bytes, and the
+/// signature value, and only successful verifications are cached. Once the cache is full, the
+/// oldest entries are evicted first. The cache can be shared between [`Verifier`]s, and so between
+/// [`Database`](crate::database::Database)s, by wrapping it in an [`Arc`].
+pub struct SignatureCache {
+    capacity: usize,
+    state: Mutex<SignatureCacheState>,
+}
+
+#[derive(Default)]
+struct SignatureCacheState {
+    entries: HashSet<SignatureCacheKey>,
+    order: VecDeque<SignatureCacheKey>,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct SignatureCacheKey {
+    key_id: KeyId,
+    message_hash: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignatureCache {
+    /// Create a cache that holds at most `capacity` verified signatures.
+    pub fn new(capacity: usize) -> Self {
+        SignatureCache {
+            capacity,
+            state: Mutex::new(SignatureCacheState::default()),
+        }
     }
 
-    let authorized_keys = authorized_keys
-        .into_iter()
-        .map(|k| (k.key_id(), k))
-        .collect::<HashMap<&KeyId, &PublicKey>>();
+    /// The maximum number of signatures held in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of signatures held in the cache.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Whether the cache holds no signatures.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of signatures that were found in the cache, and so not verified again.
+    pub fn hits(&self) -> u64 {
+        self.state.lock().unwrap().hits
+    }
+
+    /// The number of signatures that were not found in the cache.
+    pub fn misses(&self) -> u64 {
+        self.state.lock().unwrap().misses
+    }
+
+    /// Remove every signature from the cache.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    fn contains(&self, key: &SignatureCacheKey) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.entries.contains(key) {
+            state.hits += 1;
+            true
+        } else {
+            state.misses += 1;
+            false
+        }
+    }
 
-    // Extract the signatures and canonicalize the bytes.
-    let (signatures, canonical_bytes) = {
-        #[derive(Deserialize)]
-        pub struct SignedMetadata<D: Pouf> {
-            signatures: Vec<Signature>,
-            signed: D::RawData,
+    fn insert(&self, key: SignatureCacheKey) {
+        if self.capacity == 0 {
+            return;
         }
 
-        let unverified: SignedMetadata<D> = D::from_slice(raw_metadata.as_bytes())?;
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.insert(key.clone()) {
+            return;
+        }
+        state.order.push_back(key);
+
+        while state.order.len() > self.capacity {
+            if let Some(oldest) = state.order.pop_front() {
+                state.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SignatureCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("SignatureCache")
+            .field("capacity", &self.capacity)
+            .field("len", &state.entries.len())
+            .field("hits", &state.hits)
+            .field("misses", &state.misses)
+            .finish()
+    }
+}
 
-        let canonical_bytes = D::canonicalize(&unverified.signed)?;
-        (unverified.signatures, canonical_bytes)
-    };
+/// Verifies metadata signatures, optionally consulting a [`SignatureCache`] and verifying ed25519
+/// signatures in batches.
+///
+/// The default `Verifier` verifies every signature individually, like [`verify_signatures`], and
+/// rejects every keyless signature.
+#[derive(Clone, Debug, Default)]
+pub struct Verifier {
+    cache: Option<Arc<SignatureCache>>,
+    #[cfg(feature = "keyless")]
+    keyless_trust_roots: Option<KeylessTrustRoots>,
+    clock: Option<Arc<dyn Clock>>,
+    key_id_policy: KeyIdPolicy,
+    #[cfg(feature = "batch-verify")]
+    batch: bool,
+}
 
-    let mut signatures_needed = threshold;
+// Caches and clocks can't be compared, so verifiers are only equal if they share the same cache
+// and clock.
+impl PartialEq for Verifier {
+    fn eq(&self, other: &Self) -> bool {
+        let same_cache = match (&self.cache, &other.cache) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let same_clock = match (&self.clock, &other.clock) {
+            (Some(a), Some(b)) => Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const (),
+            (None, None) => true,
+            _ => false,
+        };
 
-    // Create a key_id->signature map to deduplicate the key_ids.
-    let signatures = signatures
-        .iter()
-        .map(|sig| (sig.key_id(), sig))
-        .collect::<HashMap<&KeyId, &Signature>>();
+        #[cfg(feature = "batch-verify")]
+        let same_batch = self.batch == other.batch;
+        #[cfg(not(feature = "batch-verify"))]
+        let same_batch = true;
 
-    for (key_id, sig) in signatures {
-        match authorized_keys.get(key_id) {
-            Some(pub_key) => match pub_key.verify(role, &canonical_bytes, sig) {
-                Ok(()) => {
+        #[cfg(feature = "keyless")]
+        let same_roots = self.keyless_trust_roots == other.keyless_trust_roots;
+        #[cfg(not(feature = "keyless"))]
+        let same_roots = true;
+
+        same_cache
+            && same_clock
+            && same_batch
+            && same_roots
+            && self.key_id_policy == other.key_id_policy
+    }
+}
+
+impl Eq for Verifier {}
+
+impl Verifier {
+    /// Create a `Verifier` that verifies every signature individually.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip verifying signatures found in `cache`, and record newly verified signatures in it.
+    pub fn cache(mut self, cache: Arc<SignatureCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The signature cache, if any.
+    pub fn signature_cache(&self) -> Option<&Arc<SignatureCache>> {
+        self.cache.as_ref()
+    }
+
+    /// Verify keyless signatures by `sigstore-oidc` keys against `roots`.
+    #[cfg(feature = "keyless")]
+    pub fn keyless_trust_roots(mut self, roots: KeylessTrustRoots) -> Self {
+        self.keyless_trust_roots = Some(roots);
+        self
+    }
+
+    /// Check keyless signing certificates are valid at the current time of `clock`, unless the
+    /// keyless trust roots have a verification time. Defaults to the [SystemClock](crate::clock::SystemClock). A
+    /// [Database](crate::database::Database) replaces this with its own clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Accept keys listed under the IDs that `policy` allows. Default is [`KeyIdPolicy::new`], which
+    /// only accepts canonical key IDs.
+    pub fn key_id_policy(mut self, policy: KeyIdPolicy) -> Self {
+        self.key_id_policy = policy;
+        self
+    }
+
+    /// The key ID policy.
+    pub fn accepted_key_ids(&self) -> &KeyIdPolicy {
+        &self.key_id_policy
+    }
+
+    /// The [key ID aliases](RootMetadata::key_id_aliases) of `root` that the key ID policy
+    /// accepts. Checking an alias may hash the key under every accepted scheme, so this is done
+    /// once per root rather than on every verification.
+    pub(crate) fn accepted_key_id_aliases(&self, root: &RootMetadata) -> HashSet<KeyId> {
+        root.key_id_aliases()
+            .iter()
+            .filter(|(key_id, key)| self.accepts_key_id(key_id, key))
+            .map(|(key_id, _)| key_id.clone())
+            .collect()
+    }
+
+    /// Whether the key ID policy accepts `key` listed under `key_id`.
+    fn accepts_key_id(&self, key_id: &KeyId, key: &PublicKey) -> bool {
+        let accepted = self.key_id_policy.accepts(key_id, key);
+        if !accepted {
+            warn!(
+                "Key ID {:?} does not match key {:?} under the key ID policy.",
+                key_id,
+                key.key_id()
+            );
+        }
+        accepted
+    }
+
+    /// Verify the ed25519 signatures needed to meet a threshold in a single batch, which is faster
+    /// than verifying them one at a time. If the batch fails, every signature is verified
+    /// individually to find the good ones.
+    ///
+    /// Batch verification uses the cofactored verification equation, so it may accept some
+    /// maliciously crafted signatures that individual verification rejects. Such signatures can
+    /// only be made by the holder of the private key.
+    #[cfg(feature = "batch-verify")]
+    pub fn batch(mut self, batch: bool) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Verify `raw_metadata` was signed by at least `threshold` of the `authorized_keys`. See
+    /// [`verify_signatures`].
+    pub fn verify_signatures<'a, D, M, I>(
+        &self,
+        role: &MetadataPath,
+        raw_metadata: &RawSignedMetadata<D, M>,
+        threshold: u32,
+        authorized_keys: I,
+    ) -> Result<Verified<M>, Error>
+    where
+        D: Pouf,
+        M: Metadata,
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        self.verify_signatures_with_key_ids(
+            role,
+            raw_metadata,
+            threshold,
+            authorized_keys.into_iter().map(|key| (key.key_id(), key)),
+        )
+    }
+
+    /// Like [`Verifier::verify_signatures`], but with the IDs the metadata lists the
+    /// `authorized_keys` under, such as those from [`RootMetadata::role_keys_by_id`]. Keys listed
+    /// under an ID other than their canonical [`KeyId`] are only authorized if the
+    /// [`KeyIdPolicy`] accepts the ID. Each key counts at most once towards the threshold.
+    ///
+    /// [`RootMetadata::role_keys_by_id`]: crate::metadata::RootMetadata::role_keys_by_id
+    pub fn verify_signatures_with_key_ids<'a, D, M, I>(
+        &self,
+        role: &MetadataPath,
+        raw_metadata: &RawSignedMetadata<D, M>,
+        threshold: u32,
+        authorized_keys: I,
+    ) -> Result<Verified<M>, Error>
+    where
+        D: Pouf,
+        M: Metadata,
+        I: IntoIterator<Item = (&'a KeyId, &'a PublicKey)>,
+    {
+        self.verify_accepted_signatures(
+            role,
+            raw_metadata,
+            threshold,
+            authorized_keys
+                .into_iter()
+                .filter(|(key_id, key)| self.accepts_key_id(key_id, key)),
+        )
+    }
+
+    /// Like [`Verifier::verify_signatures_with_key_ids`], but every ID the `authorized_keys` are
+    /// listed under has already been accepted, such as by [`Verifier::accepted_key_id_aliases`].
+    pub(crate) fn verify_accepted_signatures<'a, D, M, I>(
+        &self,
+        role: &MetadataPath,
+        raw_metadata: &RawSignedMetadata<D, M>,
+        threshold: u32,
+        authorized_keys: I,
+    ) -> Result<Verified<M>, Error>
+    where
+        D: Pouf,
+        M: Metadata,
+        I: IntoIterator<Item = (&'a KeyId, &'a PublicKey)>,
+    {
+        if threshold < 1 {
+            return Err(Error::MetadataThresholdMustBeGreaterThanZero(role.clone()));
+        }
+
+        // Signatures may name a key by its canonical ID or by any accepted ID it is listed under.
+        let mut key_ids = HashMap::new();
+        for (key_id, key) in authorized_keys {
+            key_ids.insert(key_id, key);
+            key_ids.insert(key.key_id(), key);
+        }
+
+        // Extract the signatures and canonicalize the bytes.
+        let (signatures, canonical_bytes) = {
+            #[derive(Deserialize)]
+            pub struct SignedMetadata<D: Pouf> {
+                signatures: Vec<Signature>,
+                signed: D::RawData,
+            }
+
+            let unverified: SignedMetadata<D> = D::from_slice(raw_metadata.as_bytes())?;
+
+            let canonical_bytes = D::canonicalize(&unverified.signed)?;
+            (unverified.signatures, canonical_bytes)
+        };
+
+        let mut signatures_needed = threshold;
+
+        // Group the signatures by the authorized key that made them. A key may have several
+        // candidate signatures, such as one under each ID it is listed under, but it counts at
+        // most once.
+        let mut authorized_signatures = HashMap::<&KeyId, (&PublicKey, Vec<&Signature>)>::new();
+        for sig in &signatures {
+            match key_ids.get(sig.key_id()) {
+                Some(pub_key) => {
+                    authorized_signatures
+                        .entry(pub_key.key_id())
+                        .or_insert_with(|| (*pub_key, vec![]))
+                        .1
+                        .push(sig);
+                }
+                None => {
+                    warn!(
+                        "Key ID {:?} was not found in the set of authorized keys.",
+                        sig.key_id()
+                    );
+                }
+            }
+        }
+
+        let message_hash = self
+            .cache
+            .as_ref()
+            .map(|_| digest::digest(&SHA256, &canonical_bytes).as_ref().to_vec());
+        // Keyless signatures depend on the verification time and trust roots, so they're not cached.
+        let cache_key = |pub_key: &PublicKey, sig: &Signature| {
+            if pub_key.keyless_identity().is_some() {
+                return None;
+            }
+            message_hash.as_ref().map(|message_hash| SignatureCacheKey {
+                key_id: pub_key.key_id().clone(),
+                message_hash: message_hash.clone(),
+                signature: sig.value().as_bytes().to_vec(),
+            })
+        };
+
+        // Skip any keys with a signature we have already verified.
+        let mut unverified = vec![];
+        for (pub_key, sigs) in authorized_signatures.into_values() {
+            if signatures_needed == 0 {
+                break;
+            }
+
+            let cached = match &self.cache {
+                Some(cache) => sigs
+                    .iter()
+                    .filter_map(|sig| cache_key(pub_key, sig))
+                    .any(|key| cache.contains(&key)),
+                None => false,
+            };
+
+            if cached {
+                debug!("Cached good signature from key ID {:?}", pub_key.key_id());
+                signatures_needed -= 1;
+            } else {
+                unverified.push((pub_key, sigs));
+            }
+        }
+
+        // Batches only check each key's first candidate signature. If that fails, every candidate
+        // is checked individually below.
+        #[cfg(feature = "batch-verify")]
+        if self.batch && signatures_needed > 0 {
+            let batch = unverified
+                .iter()
+                .take(signatures_needed as usize)
+                .map(|(pub_key, sigs)| (*pub_key, sigs[0]))
+                .collect::<Vec<_>>();
+
+            if batch.len() > 1 && verify_batch(&canonical_bytes, &batch) {
+                for (pub_key, sig) in batch {
                     debug!("Good signature from key ID {:?}", pub_key.key_id());
+                    if let (Some(cache), Some(key)) = (&self.cache, cache_key(pub_key, sig)) {
+                        cache.insert(key);
+                    }
                     signatures_needed -= 1;
                 }
-                Err(e) => {
-                    warn!("Bad signature from key ID {:?}: {:?}", pub_key.key_id(), e);
+                unverified.clear();
+            }
+        }
+
+        for (pub_key, sigs) in unverified {
+            if signatures_needed == 0 {
+                break;
+            }
+
+            for sig in sigs {
+                match self.verify_signature(role, pub_key, &canonical_bytes, sig) {
+                    Ok(()) => {
+                        debug!("Good signature from key ID {:?}", pub_key.key_id());
+                        if let (Some(cache), Some(key)) = (&self.cache, cache_key(pub_key, sig)) {
+                            cache.insert(key);
+                        }
+                        signatures_needed -= 1;
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Bad signature from key ID {:?}: {:?}", pub_key.key_id(), e);
+                    }
                 }
-            },
-            None => {
-                warn!(
-                    "Key ID {:?} was not found in the set of authorized keys.",
-                    sig.key_id()
-                );
             }
         }
-        if signatures_needed == 0 {
-            break;
+
+        if signatures_needed > 0 {
+            return Err(Error::MetadataMissingSignatures {
+                role: role.clone(),
+                number_of_valid_signatures: threshold - signatures_needed,
+                threshold,
+            });
         }
+
+        // Everything looks good so deserialize the metadata.
+        //
+        // Note: Canonicalization (or any other transformation of data) could modify or filter out
+        // information about the data. Therefore, while we've confirmed the canonical bytes are
+        // signed, we shouldn't interpret this as if the raw bytes were signed. So we deserialize
+        // from the `canonical_bytes`, rather than from `raw_meta.as_bytes()`.
+        let verified_metadata = D::from_slice(&canonical_bytes)?;
+
+        Ok(Verified::new(verified_metadata))
+    }
+
+    /// Check every signature of `metadata` against the `authorized_keys` of `role`, without
+    /// stopping once `threshold` is met. Unlike [`Verifier::verify_signatures`], this doesn't fail
+    /// if the threshold isn't met, so it can be used to inspect metadata before publishing it.
+    ///
+    /// ```
+    /// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+    /// # use tuf::metadata::{MetadataPath, SnapshotMetadataBuilder};
+    /// # use tuf::pouf::Pouf1;
+    /// # use tuf::verify::Verifier;
+    /// let key_1: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+    /// let key_1 = Ed25519PrivateKey::from_pkcs8(&key_1).unwrap();
+    ///
+    /// let key_2: &[u8] = include_bytes!("../tests/ed25519/ed25519-2.pk8.der");
+    /// let key_2 = Ed25519PrivateKey::from_pkcs8(&key_2).unwrap();
+    ///
+    /// let mut snapshot = SnapshotMetadataBuilder::new()
+    ///     .signed::<Pouf1>(&key_1)
+    ///     .unwrap();
+    /// snapshot.add_signature(&key_2).unwrap();
+    ///
+    /// let report = Verifier::new()
+    ///     .check_signatures(&MetadataPath::snapshot(), &snapshot, 1, vec![key_1.public()])
+    ///     .unwrap();
+    /// assert!(report.is_threshold_met());
+    /// assert_eq!(report.valid().len(), 1);
+    /// assert_eq!(report.unknown_keys().len(), 1);
+    /// ```
+    pub fn check_signatures<'a, D, M, I>(
+        &self,
+        role: &MetadataPath,
+        metadata: &SignedMetadata<D, M>,
+        threshold: u32,
+        authorized_keys: I,
+    ) -> Result<SignatureReport, Error>
+    where
+        D: Pouf,
+        M: Metadata,
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        self.check_signatures_with_key_ids(
+            role,
+            metadata,
+            threshold,
+            authorized_keys.into_iter().map(|key| (key.key_id(), key)),
+        )
     }
 
-    if signatures_needed > 0 {
-        return Err(Error::MetadataMissingSignatures {
-            role: role.clone(),
-            number_of_valid_signatures: threshold - signatures_needed,
+    /// Like [`Verifier::check_signatures`], but with the IDs the metadata lists the
+    /// `authorized_keys` under, as for [`Verifier::verify_signatures_with_key_ids`]. Each key has at
+    /// most one valid signature, whichever IDs its signatures name.
+    pub fn check_signatures_with_key_ids<'a, D, M, I>(
+        &self,
+        role: &MetadataPath,
+        metadata: &SignedMetadata<D, M>,
+        threshold: u32,
+        authorized_keys: I,
+    ) -> Result<SignatureReport, Error>
+    where
+        D: Pouf,
+        M: Metadata,
+        I: IntoIterator<Item = (&'a KeyId, &'a PublicKey)>,
+    {
+        if threshold < 1 {
+            return Err(Error::MetadataThresholdMustBeGreaterThanZero(role.clone()));
+        }
+
+        // Signatures may name a key by its canonical ID or by any accepted ID it is listed under.
+        let mut key_ids = HashMap::new();
+        for (key_id, key) in authorized_keys {
+            if self.accepts_key_id(key_id, key) {
+                key_ids.insert(key_id, key);
+                key_ids.insert(key.key_id(), key);
+            }
+        }
+        let canonical_bytes = metadata.canonical_bytes()?;
+
+        let mut report = SignatureReport {
             threshold,
-        });
+            ..SignatureReport::default()
+        };
+        // The canonical IDs of the keys with a valid signature.
+        let mut signed_keys = HashSet::new();
+
+        for sig in metadata.signatures() {
+            let pub_key = match key_ids.get(sig.key_id()) {
+                Some(pub_key) => pub_key,
+                None => {
+                    report.unknown_keys.push(sig.clone());
+                    continue;
+                }
+            };
+
+            if signed_keys.contains(pub_key.key_id()) {
+                report.duplicates.push(sig.clone());
+                continue;
+            }
+
+            match self.verify_signature(role, pub_key, &canonical_bytes, sig) {
+                Ok(()) => {
+                    let _ = signed_keys.insert(pub_key.key_id());
+                    report.valid.push(sig.clone());
+                }
+                Err(e) => {
+                    debug!("Bad signature from key ID {:?}: {:?}", sig.key_id(), e);
+                    report.invalid.push(sig.clone());
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn verify_signature(
+        &self,
+        role: &MetadataPath,
+        pub_key: &PublicKey,
+        msg: &[u8],
+        sig: &Signature,
+    ) -> Result<(), Error> {
+        let identity = match pub_key.keyless_identity() {
+            Some(identity) => identity,
+            None => return pub_key.verify(role, msg, sig),
+        };
+
+        self.verify_keyless_signature(identity, msg, sig)
+    }
+
+    #[cfg(feature = "keyless")]
+    fn verify_keyless_signature(
+        &self,
+        identity: &KeylessIdentity,
+        msg: &[u8],
+        sig: &Signature,
+    ) -> Result<(), Error> {
+        let roots = self.keyless_trust_roots.as_ref().ok_or_else(|| {
+            Error::UntrustedCertificate("no keyless trust roots are configured".into())
+        })?;
+        let bundle = sig
+            .bundle()
+            .ok_or_else(|| Error::UntrustedCertificate("keyless signature has no bundle".into()))?;
+
+        let clock = self.clock.as_deref().unwrap_or(&SystemClock);
+        crypto::keyless::verify(roots, identity, bundle, msg, clock)
+    }
+
+    #[cfg(not(feature = "keyless"))]
+    fn verify_keyless_signature(
+        &self,
+        _identity: &KeylessIdentity,
+        _msg: &[u8],
+        _sig: &Signature,
+    ) -> Result<(), Error> {
+        Err(Error::UntrustedCertificate(
+            "keyless signatures require the `keyless` feature".into(),
+        ))
+    }
+}
+
+/// Verify a batch of ed25519 signatures over `msg`, returning `false` if any are invalid or are not
+/// ed25519 signatures.
+#[cfg(feature = "batch-verify")]
+fn verify_batch(msg: &[u8], batch: &[(&PublicKey, &Signature)]) -> bool {
+    let mut keys = Vec::with_capacity(batch.len());
+    let mut signatures = Vec::with_capacity(batch.len());
+
+    for (pub_key, sig) in batch {
+        if pub_key.scheme() != &SignatureScheme::Ed25519 {
+            return false;
+        }
+
+        let key = <&[u8; 32]>::try_from(pub_key.as_bytes())
+            .ok()
+            .and_then(|bytes| ed25519_dalek::VerifyingKey::from_bytes(bytes).ok());
+        let signature = ed25519_dalek::Signature::from_slice(sig.value().as_bytes()).ok();
+
+        match (key, signature) {
+            (Some(key), Some(signature)) => {
+                keys.push(key);
+                signatures.push(signature);
+            }
+            _ => return false,
+        }
+    }
+
+    let messages = vec![msg; batch.len()];
+    ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, KeyIdScheme, PrivateKey};
+    use crate::metadata::{SnapshotMetadata, SnapshotMetadataBuilder};
+    use crate::pouf::Pouf1;
+    use assert_matches::assert_matches;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn signed_snapshot(version: u32) -> RawSignedMetadata<Pouf1, SnapshotMetadata> {
+        let mut snapshot = SnapshotMetadataBuilder::new()
+            .version(version)
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        for key in &KEYS[1..] {
+            snapshot.add_signature(key).unwrap();
+        }
+        snapshot.to_raw().unwrap()
     }
 
-    // Everything looks good so deserialize the metadata.
-    //
-    // Note: Canonicalization (or any other transformation of data) could modify or filter out
-    // information about the data. Therefore, while we've confirmed the canonical bytes are signed,
-    // we shouldn't interpret this as if the raw bytes were signed. So we deserialize from the
-    // `canonical_bytes`, rather than from `raw_meta.as_bytes()`.
-    let verified_metadata = D::from_slice(&canonical_bytes)?;
+    /// Replace the signature from `key` with one over a different message.
+    fn corrupt_signature(
+        raw: &RawSignedMetadata<Pouf1, SnapshotMetadata>,
+        key: &Ed25519PrivateKey,
+    ) -> RawSignedMetadata<Pouf1, SnapshotMetadata> {
+        let mut json: serde_json::Value = serde_json::from_slice(raw.as_bytes()).unwrap();
+        let bad = serde_json::to_value(key.sign(b"not the metadata").unwrap()).unwrap();
+        for sig in json["signatures"].as_array_mut().unwrap() {
+            if sig["keyid"] == bad["keyid"] {
+                *sig = bad.clone();
+            }
+        }
+        RawSignedMetadata::new(serde_json::to_vec(&json).unwrap())
+    }
+
+    fn public_keys() -> Vec<&'static PublicKey> {
+        KEYS.iter().map(|k| k.public()).collect()
+    }
 
-    Ok(Verified::new(verified_metadata))
+    #[test]
+    fn cache_skips_verified_signatures() {
+        let cache = Arc::new(SignatureCache::new(16));
+        let verifier = Verifier::new().cache(Arc::clone(&cache));
+        let raw = signed_snapshot(1);
+        let role = MetadataPath::snapshot();
+
+        verifier
+            .verify_signatures(&role, &raw, 3, public_keys())
+            .unwrap();
+        assert_eq!(cache.len(), 3);
+        assert_eq!((cache.hits(), cache.misses()), (0, 3));
+
+        verifier
+            .verify_signatures(&role, &raw, 3, public_keys())
+            .unwrap();
+        assert_eq!(cache.len(), 3);
+        assert_eq!((cache.hits(), cache.misses()), (3, 3));
+
+        // A corrupted signature is not in the cache, so it is verified and rejected.
+        let corrupted = corrupt_signature(&raw, &KEYS[2]);
+        assert_matches!(
+            verifier.verify_signatures(&role, &corrupted, 3, public_keys()),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 2,
+                threshold: 3,
+                ..
+            })
+        );
+        assert_eq!(cache.len(), 3);
+
+        // Cached signatures are only used for keys that are still authorized.
+        assert_matches!(
+            verifier.verify_signatures(&role, &raw, 3, public_keys().into_iter().take(2)),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 2,
+                threshold: 3,
+                ..
+            })
+        );
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let cache = Arc::new(SignatureCache::new(4));
+        let verifier = Verifier::new().cache(Arc::clone(&cache));
+
+        for version in 1..=3 {
+            verifier
+                .verify_signatures(
+                    &MetadataPath::snapshot(),
+                    &signed_snapshot(version),
+                    3,
+                    public_keys(),
+                )
+                .unwrap();
+            assert!(cache.len() <= cache.capacity());
+        }
+        assert_eq!(cache.len(), 4);
+
+        // The oldest signatures were evicted.
+        cache.state.lock().unwrap().hits = 0;
+        verifier
+            .verify_signatures(
+                &MetadataPath::snapshot(),
+                &signed_snapshot(1),
+                3,
+                public_keys(),
+            )
+            .unwrap();
+        assert_eq!(cache.hits(), 0);
+    }
+
+    #[test]
+    fn check_signatures_reports_every_signature() {
+        let role = MetadataPath::snapshot();
+        let raw = corrupt_signature(&signed_snapshot(1), &KEYS[2]);
+
+        // Sign the metadata twice with the first key.
+        let mut json: serde_json::Value = serde_json::from_slice(raw.as_bytes()).unwrap();
+        let signatures = json["signatures"].as_array_mut().unwrap();
+        let first = signatures
+            .iter()
+            .find(|sig| sig["keyid"] == serde_json::json!(KEYS[0].public().key_id()))
+            .unwrap()
+            .clone();
+        signatures.push(first);
+        let mut signed =
+            RawSignedMetadata::<Pouf1, SnapshotMetadata>::new(serde_json::to_vec(&json).unwrap())
+                .parse_untrusted()
+                .unwrap();
+
+        let report = Verifier::new()
+            .check_signatures(&role, &signed, 2, public_keys().into_iter().skip(1))
+            .unwrap();
+        assert_eq!(report.threshold(), 2);
+        assert!(!report.is_threshold_met());
+        assert!(!report.is_clean());
+        assert_eq!(key_ids(report.valid()), vec![KEYS[1].public().key_id()]);
+        assert_eq!(key_ids(report.invalid()), vec![KEYS[2].public().key_id()]);
+        assert_eq!(
+            key_ids(report.unknown_keys()),
+            vec![KEYS[0].public().key_id(), KEYS[0].public().key_id()]
+        );
+        assert!(report.duplicates().is_empty());
+
+        let report = Verifier::new()
+            .check_signatures(&role, &signed, 2, public_keys())
+            .unwrap();
+        assert!(report.is_threshold_met());
+        assert_eq!(
+            key_ids(report.valid()),
+            vec![KEYS[0].public().key_id(), KEYS[1].public().key_id()]
+        );
+        assert_eq!(key_ids(report.invalid()), vec![KEYS[2].public().key_id()]);
+        assert!(report.unknown_keys().is_empty());
+        assert_eq!(
+            key_ids(report.duplicates()),
+            vec![KEYS[0].public().key_id()]
+        );
+
+        // Pruning leaves exactly the valid signatures, which still meet the threshold.
+        signed.retain_valid_signatures(&report);
+        assert_eq!(signed.signatures(), report.valid());
+        let report = Verifier::new()
+            .check_signatures(&role, &signed, 2, public_keys())
+            .unwrap();
+        assert!(report.is_clean());
+        Verifier::new()
+            .verify_signatures(&role, &signed.to_raw().unwrap(), 2, public_keys())
+            .unwrap();
+
+        assert_matches!(
+            Verifier::new().check_signatures(&role, &signed, 0, public_keys()),
+            Err(Error::MetadataThresholdMustBeGreaterThanZero(_))
+        );
+    }
+
+    #[test]
+    fn verify_every_signature_of_a_key() {
+        let role = MetadataPath::snapshot();
+        let raw = signed_snapshot(1);
+
+        // Put a bad signature from the first key ahead of its good one.
+        let mut json: serde_json::Value = serde_json::from_slice(raw.as_bytes()).unwrap();
+        let bad = serde_json::to_value(KEYS[0].sign(b"not the metadata").unwrap()).unwrap();
+        json["signatures"].as_array_mut().unwrap().insert(0, bad);
+        let raw =
+            RawSignedMetadata::<Pouf1, SnapshotMetadata>::new(serde_json::to_vec(&json).unwrap());
+
+        Verifier::new()
+            .verify_signatures(&role, &raw, 1, [KEYS[0].public()])
+            .unwrap();
+        Verifier::new()
+            .verify_signatures(&role, &raw, 3, public_keys())
+            .unwrap();
+        #[cfg(feature = "batch-verify")]
+        Verifier::new()
+            .batch(true)
+            .verify_signatures(&role, &raw, 3, public_keys())
+            .unwrap();
+    }
+
+    #[test]
+    fn verify_signatures_with_key_ids() {
+        let role = MetadataPath::snapshot();
+        let key = KEYS[0].public();
+        let custom = KeyId::opaque("snapshot-key").unwrap();
+
+        // Sign the metadata with the first key, under both its canonical and its custom ID.
+        let raw = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let mut json: serde_json::Value = serde_json::from_slice(raw.as_bytes()).unwrap();
+        let signatures = json["signatures"].as_array_mut().unwrap();
+        let mut sig = signatures[0].clone();
+        sig["keyid"] = serde_json::json!(custom);
+        signatures.push(sig);
+        let raw =
+            RawSignedMetadata::<Pouf1, SnapshotMetadata>::new(serde_json::to_vec(&json).unwrap());
+
+        // By default, a key listed under a non-canonical ID is not authorized.
+        assert_matches!(
+            Verifier::new().verify_signatures_with_key_ids(&role, &raw, 1, [(&custom, key)]),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 0,
+                threshold: 1,
+                ..
+            })
+        );
+        Verifier::new()
+            .verify_signatures_with_key_ids(&role, &raw, 1, [(key.key_id(), key)])
+            .unwrap();
+
+        let verifier = Verifier::new().key_id_policy(KeyIdPolicy::new().accept(
+            KeyIdScheme::Custom(HashMap::from([(key.key_id().clone(), custom.clone())])),
+        ));
+        assert_eq!(verifier.accepted_key_ids().schemes().len(), 1);
+        verifier
+            .verify_signatures_with_key_ids(&role, &raw, 1, [(&custom, key)])
+            .unwrap();
+
+        // Signatures under both IDs are by the same key, so they count once.
+        assert_matches!(
+            verifier.verify_signatures_with_key_ids(
+                &role,
+                &raw,
+                2,
+                [(&custom, key), (key.key_id(), key)]
+            ),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 1,
+                threshold: 2,
+                ..
+            })
+        );
+
+        // Reports also count the key once, whichever ID its signatures name.
+        let mut signed = raw.parse_untrusted().unwrap();
+        let report = verifier
+            .check_signatures_with_key_ids(&role, &signed, 1, [(&custom, key)])
+            .unwrap();
+        assert!(report.is_threshold_met());
+        assert!(report.unknown_keys().is_empty());
+        assert_eq!(report.valid().len(), 1);
+        assert_eq!(report.duplicates().len(), 1);
+        signed.retain_valid_signatures(&report);
+        verifier
+            .verify_signatures_with_key_ids(&role, &signed.to_raw().unwrap(), 1, [(&custom, key)])
+            .unwrap();
+        let report = Verifier::new()
+            .check_signatures_with_key_ids(&role, &signed, 1, [(&custom, key)])
+            .unwrap();
+        assert!(!report.is_threshold_met());
+
+        // A policy for other IDs does not authorize the custom one.
+        let verifier =
+            Verifier::new().key_id_policy(KeyIdPolicy::new().accept(KeyIdScheme::Legacy));
+        assert_matches!(
+            verifier.verify_signatures_with_key_ids(&role, &raw, 1, [(&custom, key)]),
+            Err(Error::MetadataMissingSignatures { .. })
+        );
+    }
+
+    fn key_ids(signatures: &[Signature]) -> Vec<&KeyId> {
+        signatures.iter().map(|sig| sig.key_id()).collect()
+    }
+
+    /// Add a keyless signature by the certificate in `tests/sigstore/leaf.pem` to `raw`.
+    fn add_keyless_signature(
+        raw: &RawSignedMetadata<Pouf1, SnapshotMetadata>,
+        key_id: &KeyId,
+    ) -> RawSignedMetadata<Pouf1, SnapshotMetadata> {
+        use crate::crypto::SignatureBundle;
+        use data_encoding::BASE64;
+        use ring::{
+            rand::SystemRandom,
+            signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+        };
+
+        let der = |pem: &str| {
+            let base64 = pem
+                .lines()
+                .filter(|line| !line.starts_with("-----"))
+                .collect::<String>();
+            BASE64.decode(base64.as_bytes()).unwrap()
+        };
+
+        let mut json: serde_json::Value = serde_json::from_slice(raw.as_bytes()).unwrap();
+        let canonical_bytes = Pouf1::canonicalize(&json["signed"]).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P256_SHA256_ASN1_SIGNING,
+            include_bytes!("../tests/sigstore/leaf.pk8.der"),
+        )
+        .unwrap();
+        let bundle = SignatureBundle::new(
+            vec![
+                der(include_str!("../tests/sigstore/leaf.pem")),
+                der(include_str!("../tests/sigstore/intermediate.pem")),
+            ],
+            key.sign(&SystemRandom::new(), &canonical_bytes)
+                .unwrap()
+                .as_ref()
+                .to_vec(),
+        );
+
+        json["signatures"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::to_value(Signature::from_bundle(key_id.clone(), bundle)).unwrap());
+        RawSignedMetadata::new(serde_json::to_vec(&json).unwrap())
+    }
+
+    /// Sign a snapshot with `KEYS[0]` and a keyless key, returning the metadata and both keys.
+    fn keyless_signed_snapshot() -> (RawSignedMetadata<Pouf1, SnapshotMetadata>, [PublicKey; 2]) {
+        let keyless_key = PublicKey::from_keyless_identity(KeylessIdentity::new(
+            "signer@example.com",
+            "https://accounts.example.com",
+        ))
+        .unwrap();
+        let raw = add_keyless_signature(
+            &SnapshotMetadataBuilder::new()
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap()
+                .to_raw()
+                .unwrap(),
+            keyless_key.key_id(),
+        );
+        (raw, [KEYS[0].public().clone(), keyless_key])
+    }
+
+    #[test]
+    fn keyless_signatures_need_trust_roots() {
+        let (raw, keys) = keyless_signed_snapshot();
+
+        // Keyless signatures are rejected unless trust roots are configured.
+        assert_matches!(
+            Verifier::new().verify_signatures(&MetadataPath::snapshot(), &raw, 2, &keys),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 1,
+                threshold: 2,
+                ..
+            })
+        );
+    }
+
+    #[cfg(feature = "keyless")]
+    #[test]
+    fn keyless_signatures() {
+        use crate::clock::FixedClock;
+        use crate::crypto::KeylessTrustRoots;
+        use chrono::{TimeZone as _, Utc};
+
+        let (raw, keys) = keyless_signed_snapshot();
+        let role = MetadataPath::snapshot();
+        let keys = [&keys[0], &keys[1]];
+
+        let roots = KeylessTrustRoots::new()
+            .root_pem(include_str!("../tests/sigstore/root.pem"))
+            .unwrap();
+        let cache = Arc::new(SignatureCache::new(16));
+        let verifier = Verifier::new()
+            .keyless_trust_roots(roots.clone())
+            .cache(Arc::clone(&cache));
+        verifier.verify_signatures(&role, &raw, 2, keys).unwrap();
+
+        // Only the ed25519 signature is cached.
+        assert_eq!(cache.len(), 1);
+
+        // The signing certificate expired by the time of the verifier's clock.
+        let expired = FixedClock::new(Utc.with_ymd_and_hms(2125, 1, 1, 0, 0, 0).unwrap());
+        assert_matches!(
+            Verifier::new()
+                .keyless_trust_roots(roots.clone())
+                .clock(Arc::new(expired))
+                .verify_signatures(&role, &raw, 2, keys),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 1,
+                threshold: 2,
+                ..
+            })
+        );
+
+        // A key for another identity doesn't match the certificate.
+        let other_key = PublicKey::from_keyless_identity(KeylessIdentity::new(
+            "mallory@example.com",
+            "https://accounts.example.com",
+        ))
+        .unwrap();
+        let raw = add_keyless_signature(&signed_snapshot(1), other_key.key_id());
+        assert_matches!(
+            verifier.verify_signatures(&role, &raw, 1, [&other_key]),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 0,
+                threshold: 1,
+                ..
+            })
+        );
+
+        assert_ne!(verifier, Verifier::new().cache(cache));
+        assert_eq!(
+            Verifier::new().keyless_trust_roots(roots.clone()),
+            Verifier::new().keyless_trust_roots(roots)
+        );
+    }
+
+    #[cfg(feature = "batch-verify")]
+    #[test]
+    fn batch_verification() {
+        let verifier = Verifier::new().batch(true);
+        let raw = signed_snapshot(1);
+        let role = MetadataPath::snapshot();
+
+        verifier
+            .verify_signatures(&role, &raw, 3, public_keys())
+            .unwrap();
+
+        // A bad signature fails the batch, and individual verification finds the good ones.
+        let corrupted = corrupt_signature(&raw, &KEYS[1]);
+        assert_matches!(
+            verifier.verify_signatures(&role, &corrupted, 3, public_keys()),
+            Err(Error::MetadataMissingSignatures {
+                number_of_valid_signatures: 2,
+                threshold: 3,
+                ..
+            })
+        );
+        for _ in 0..8 {
+            // The signatures are checked in an arbitrary order, so repeat to cover batches with
+            // and without the bad signature.
+            verifier
+                .verify_signatures(&role, &corrupted, 2, public_keys())
+                .unwrap();
+        }
+    }
 }
@@ -1,5 +1,4 @@
 use assert_matches::assert_matches;
-use chrono::offset::Utc;
 use futures_executor::block_on;
 use tuf::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
 use tuf::metadata::{
@@ -21,8 +20,6 @@ const ED25519_6_PK8: &[u8] = include_bytes!("./ed25519/ed25519-6.pk8.der");
 #[test]
 fn simple_delegation() {
     block_on(async {
-        let now = Utc::now();
-
         let root_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
         let snapshot_key = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
         let targets_key = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
@@ -75,7 +72,6 @@ fn simple_delegation() {
         let raw_delegation = delegation.to_raw().unwrap();
 
         tuf.update_delegated_targets(
-            &now,
             &MetadataPath::targets(),
             &MetadataPath::new("delegation").unwrap(),
             &raw_delegation,
@@ -91,8 +87,6 @@ fn simple_delegation() {
 #[test]
 fn nested_delegation() {
     block_on(async {
-        let now = Utc::now();
-
         let root_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
         let snapshot_key = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
         let targets_key = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
@@ -159,7 +153,6 @@ fn nested_delegation() {
         let raw_delegation = delegation.to_raw().unwrap();
 
         tuf.update_delegated_targets(
-            &now,
             &MetadataPath::targets(),
             &MetadataPath::new("delegation-a").unwrap(),
             &raw_delegation,
@@ -182,7 +175,6 @@ fn nested_delegation() {
         let raw_delegation = delegation.to_raw().unwrap();
 
         tuf.update_delegated_targets(
-            &now,
             &MetadataPath::new("delegation-a").unwrap(),
             &MetadataPath::new("delegation-b").unwrap(),
             &raw_delegation,
@@ -198,8 +190,6 @@ fn nested_delegation() {
 #[test]
 fn rejects_bad_delegation_signatures() {
     block_on(async {
-        let now = Utc::now();
-
         let root_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
         let snapshot_key = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
         let targets_key = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
@@ -253,7 +243,6 @@ fn rejects_bad_delegation_signatures() {
 
         assert_matches!(
             tuf.update_delegated_targets(
-                &now,
                 &MetadataPath::targets(),
                 &MetadataPath::new("delegation").unwrap(),
                 &raw_delegation
@@ -277,8 +266,6 @@ fn rejects_bad_delegation_signatures() {
 #[test]
 fn diamond_delegation() {
     block_on(async {
-        let now = Utc::now();
-
         let etc_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
         let targets_key = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
         let delegation_a_key = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
@@ -432,7 +419,6 @@ fn diamond_delegation() {
         //// Verify we can trust delegation-a and delegation-b..
 
         tuf.update_delegated_targets(
-            &now,
             &MetadataPath::targets(),
             &MetadataPath::new("delegation-a").unwrap(),
             &raw_delegation_a,
@@ -440,7 +426,6 @@ fn diamond_delegation() {
         .unwrap();
 
         tuf.update_delegated_targets(
-            &now,
             &MetadataPath::targets(),
             &MetadataPath::new("delegation-b").unwrap(),
             &raw_delegation_b,
@@ -451,7 +436,6 @@ fn diamond_delegation() {
 
         assert_matches!(
             tuf.update_delegated_targets(
-                &now,
                 &MetadataPath::new("delegation-b").unwrap(),
                 &MetadataPath::new("delegation-c").unwrap(),
                 &raw_delegation_c
@@ -465,7 +449,6 @@ fn diamond_delegation() {
         );
 
         tuf.update_delegated_targets(
-            &now,
             &MetadataPath::new("delegation-a").unwrap(),
             &MetadataPath::new("delegation-c").unwrap(),
             &raw_delegation_c,
@@ -0,0 +1,77 @@
+#!/bin/sh
+# Generates the keyless signing fixtures: a local CA standing in for Fulcio, with a root, an
+# intermediate, and leaf certificates bound to an OIDC identity and issuer.
+set -eu
+cd "$(dirname "$0")"
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+cat > "$tmp/ext.cnf" <<'CNF'
+[root]
+basicConstraints = critical, CA:true
+keyUsage = critical, keyCertSign, cRLSign
+subjectKeyIdentifier = hash
+
+[intermediate]
+basicConstraints = critical, CA:true, pathlen:0
+keyUsage = critical, keyCertSign, cRLSign
+extendedKeyUsage = codeSigning
+subjectKeyIdentifier = hash
+authorityKeyIdentifier = keyid
+
+[leaf]
+basicConstraints = critical, CA:false
+keyUsage = critical, digitalSignature
+extendedKeyUsage = codeSigning
+subjectAltName = critical, email:signer@example.com
+# The OIDC issuer, as a DER encoded UTF8String.
+1.3.6.1.4.1.57264.1.8 = ASN1:UTF8String:https://accounts.example.com
+authorityKeyIdentifier = keyid
+
+[leaf-uri]
+basicConstraints = critical, CA:false
+keyUsage = critical, digitalSignature
+extendedKeyUsage = codeSigning
+subjectAltName = critical, URI:https://github.com/example/repo/.github/workflows/release.yml@refs/heads/main
+# The deprecated form of the OIDC issuer extension, holding the raw issuer.
+1.3.6.1.4.1.57264.1.1 = DER:68:74:74:70:73:3a:2f:2f:74:6f:6b:65:6e:2e:61:63:74:69:6f:6e:73:2e:67:69:74:68:75:62:75:73:65:72:63:6f:6e:74:65:6e:74:2e:63:6f:6d
+authorityKeyIdentifier = keyid
+CNF
+
+key() {
+    openssl genpkey -algorithm EC -pkeyopt "ec_paramgen_curve:$1" -out "$tmp/$2.key" 2>/dev/null
+}
+
+cert() {
+    # cert NAME SUBJECT ISSUER SECTION
+    openssl req -new -key "$tmp/$1.key" -subj "$2" -out "$tmp/$1.csr"
+    openssl x509 -req -in "$tmp/$1.csr" -CA "$3.pem" -CAkey "$tmp/$3.key" \
+        -set_serial "0x$(openssl rand -hex 8)" \
+        -not_before 20240101000000Z -not_after 21240101000000Z \
+        -extfile "$tmp/ext.cnf" -extensions "$4" -sha384 -out "$1.pem" 2>/dev/null
+}
+
+for root in root other-root; do
+    key P-384 "$root"
+    openssl req -new -x509 -key "$tmp/$root.key" -subj "/O=tuf-test/CN=$root" \
+        -not_before 20240101000000Z -not_after 21240101000000Z \
+        -config "$tmp/ext.cnf" -extensions root -sha384 -out "$root.pem"
+done
+
+key P-384 intermediate
+cert intermediate /O=tuf-test/CN=intermediate root intermediate
+
+key P-256 leaf
+cert leaf /O=tuf-test intermediate leaf
+
+key P-256 leaf-uri
+cert leaf-uri /O=tuf-test intermediate leaf-uri
+
+# A leaf issued directly by the untrusted root.
+key P-256 untrusted-leaf
+cert untrusted-leaf /O=tuf-test other-root leaf
+
+for leaf in leaf leaf-uri untrusted-leaf; do
+    openssl pkcs8 -topk8 -nocrypt -in "$tmp/$leaf.key" -outform DER -out "$leaf.pk8.der"
+done